@@ -1,11 +1,10 @@
+use heck::ToUpperCamelCase;
 use proc_macro::TokenStream;
 use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{quote, ToTokens};
 use syn::{
-    parse_macro_input, Error, Expr, FnArg, Ident, ImplItem, ItemImpl, Lit, Meta, MetaNameValue, Pat
+    parse_macro_input, Error, Expr, FnArg, Ident, ImplItem, ItemImpl, Lit, Meta, MetaNameValue, Pat,
 };
-use std::collections::HashSet;
-use heck::ToUpperCamelCase;
 
 /// # Macro for Generating `ToolBox` Implementations
 ///
@@ -73,7 +72,8 @@ use heck::ToUpperCamelCase;
 /// #### 2.2. Requirements and Limitations
 ///
 /// - **Method Receiver**: Exposed tools must be methods that take `&self` as the first argument. Static methods are not supported.
-/// - **Return Type**: The return type must be `Result<String, ToolError>`.
+/// - **Return Type**: The return type must be `Result<String, ToolError>`. Errors are passed to the
+///   agent unchanged, so variants like `ToolError::Unauthorized` can be handled by it.
 /// - **Serializable Parameters**: All method parameters must be (de)serializable by `serde`.
 ///
 /// ### 3. Advanced Configuration
@@ -81,8 +81,60 @@ use heck::ToUpperCamelCase;
 /// The `#[tool(...)]` attribute gives you broad control over the configuration of declared tools.
 /// You can change any of the options using `name=value` pairs. The following options are supported:
 /// - `name`: Overrides the default tool name. This name must be unique within the toolbox.
+/// - `capabilities`: Capabilities required by the tool, as an expression that can be converted into
+///   `Vec<agentai::tool::Capability>`, e.g. `capabilities = [Capability::Network]`. Agents expose only
+///   tools allowed by their sandbox profile, look into `agentai::tool::sandbox` for more details.
+///   Tools without this option require `Capability::Unknown` and are exposed only with full trust,
+///   tools that don't need any capabilities should declare `capabilities = []`.
+/// - `blocking`: Flag without value, marks synchronous tool that is CPU heavy or uses blocking I/O.
+///   Tool is executed on the blocking thread pool with `agentai::tool::run_blocking`, so it doesn't
+///   stall the async runtime. Toolbox has to implement `Clone`, its clone is moved to the thread
+///   executing the tool, so keep the clone cheap (e.g. with state in `Arc`).
+///
+/// ```no_run
+/// #[tool(blocking)]
+/// fn checksum(&self, path: String) -> Result<String, ToolError> {
+///     // ...
+/// }
+/// ```
+///
+/// ### 4. Tools Order
+///
+/// Tools are returned by `tools_definitions` in the order in which they are declared in the impl block.
+/// Order of tools is a part of the prompt, so it is always stable between builds, which matters for prompt
+/// caching and model behavior. Tools can be sorted alphabetically by name instead with the `order` option
+/// of the `#[toolbox]` attribute:
+///
+/// ```no_run
+/// #[toolbox(order = "alphabetical")]
+/// impl MyToolBox {
+///     // ...
+/// }
+/// ```
+///
+/// Supported values are `"declaration"` (default) and `"alphabetical"`.
+///
+/// ### 5. Schema Generation
+///
+/// Parameters schemas are generated with global options returned by `agentai::schema::default_schema_options`
+/// (JSON Schema 2020-12 by default). Toolbox can use its own options, provided as an expression evaluating
+/// to `agentai::schema::SchemaOptions` in the `schema` option of the `#[toolbox]` attribute:
+///
+/// ```no_run
+/// #[toolbox(schema = SchemaOptions::new().with_deny_additional_properties(true))]
+/// impl MyToolBox {
+///     // ...
+/// }
+/// ```
+///
+/// ### 6. Invalid Arguments
 ///
-/// ### 4. Tool Arguments
+/// When arguments provided by the model don't match the schema, the macro tries to repair them first
+/// (e.g. converting `"5"` into `5`, look into `agentai::tool::repair::coerce_arguments`). If this fails,
+/// `ToolError::InvalidArguments` is returned, containing the error, received arguments and expected schema,
+/// so the model can correct the call. Repair can be disabled with `#[toolbox(coerce_arguments = false)]`.
+///
+/// ### 7. Tool Arguments
 /// The tool's schema is generated based on the method's arguments, which is why they must be serializable.
 /// This is primarily syntactic sugar, as all arguments are copied into a new helper structure as serializable fields.
 /// This struct derives `serde::Serialize`, `serde::Deserialize`, and `schemars::JsonSchema` to handle argument
@@ -158,31 +210,103 @@ use heck::ToUpperCamelCase;
 ///         deserializes the JSON `parameters` into the corresponding parameter struct,
 ///         and invokes the actual method.
 #[proc_macro_attribute]
-pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn toolbox(attr: TokenStream, item: TokenStream) -> TokenStream {
     // Parse the original impl block
     let mut item_impl = parse_macro_input!(item as ItemImpl);
 
+    // Parse options of #[toolbox(...)] attribute
+    let mut sort_alphabetically = false;
+    let mut schema_options = quote! { ::agentai::schema::default_schema_options() };
+    let mut coerce_arguments = true;
+    let parser = syn::punctuated::Punctuated::<Meta, syn::Token![,]>::parse_terminated;
+    let toolbox_args = parse_macro_input!(attr with parser);
+    for arg_meta in toolbox_args {
+        match arg_meta {
+            Meta::NameValue(MetaNameValue {
+                ref path,
+                value: Expr::Lit(ref expr_lit),
+                ..
+            }) if path.is_ident("order") => {
+                let Lit::Str(lit_str) = &expr_lit.lit else {
+                    return Error::new_spanned(
+                        expr_lit.to_token_stream(),
+                        "Expected string literal for tools order",
+                    )
+                    .to_compile_error()
+                    .into();
+                };
+                sort_alphabetically = match lit_str.value().as_str() {
+                    "declaration" => false,
+                    "alphabetical" => true,
+                    _ => {
+                        return Error::new_spanned(
+                            lit_str.to_token_stream(),
+                            "Expected \"declaration\" or \"alphabetical\" as tools order",
+                        )
+                        .to_compile_error()
+                        .into()
+                    }
+                };
+            }
+            Meta::NameValue(MetaNameValue {
+                ref path,
+                value: Expr::Lit(ref expr_lit),
+                ..
+            }) if path.is_ident("coerce_arguments") => {
+                let Lit::Bool(lit_bool) = &expr_lit.lit else {
+                    return Error::new_spanned(
+                        expr_lit.to_token_stream(),
+                        "Expected boolean literal for coerce_arguments",
+                    )
+                    .to_compile_error()
+                    .into();
+                };
+                coerce_arguments = lit_bool.value;
+            }
+            Meta::NameValue(ref name_value) if name_value.path.is_ident("schema") => {
+                let options = &name_value.value;
+                schema_options = quote! { #options };
+            }
+            _ => {
+                return Error::new_spanned(arg_meta.to_token_stream(), "Expected order = \"...\", schema = ... or coerce_arguments = ... in toolbox attribute").to_compile_error().into();
+            }
+        }
+    }
+
     let struct_name = &item_impl.self_ty;
     let struct_ident = match &**struct_name {
-        syn::Type::Path(type_path) => {
-            type_path.path.get_ident().expect("Expected an identifier for the struct")
+        syn::Type::Path(type_path) => type_path
+            .path
+            .get_ident()
+            .expect("Expected an identifier for the struct"),
+        _ => {
+            return Error::new(
+                Span::call_site(),
+                "toolbox! macro only supports impl blocks for structs",
+            )
+            .to_compile_error()
+            .into()
         }
-        _ => return Error::new(Span::call_site(), "toolbox! macro only supports impl blocks for structs").to_compile_error().into(),
     };
 
     let mut generated_code = TokenStream2::new();
-    let mut tool_definitions = TokenStream2::new();
     let mut match_arms = TokenStream2::new();
+    let mut capabilities_arms = TokenStream2::new();
 
-    // TODO: Maybe we should use BTreeHash to preserve order of tools?
-    let mut found_tools = HashSet::new();
+    // Tool names with their definitions, in order of declaration
+    let mut tool_definitions: Vec<(String, TokenStream2)> = Vec::new();
 
     // Pass 1: Collect information for tool definitions and call dispatch
     // We iterate over a reference here because we need the original items again in Pass 2
     for item in item_impl.items.iter_mut() {
         if let ImplItem::Fn(ref mut method) = item {
             // Find the #[tool] attribute
-            if let Some(tool_attr) = method.attrs.clone().iter().find(|attr| attr.path().is_ident("tool")) {
+            if let Some(tool_attr) = method
+                .attrs
+                .clone()
+                .iter()
+                .find(|attr| attr.path().is_ident("tool"))
+            {
                 // Remove #[tool] attribute
                 // #[tool] is used only to mark functions that will be converted into tools
                 method.attrs.retain(|attr| !attr.path().is_ident("tool"));
@@ -193,56 +317,107 @@ pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
                 // Parse the #[tool] attribute for name = "..." using parse_args_with with Meta
                 let mut name_arg_found = false;
+                let mut capabilities = None;
+                let mut blocking = false;
                 let parser = syn::punctuated::Punctuated::<Meta, syn::Token![,]>::parse_terminated;
                 if let Ok(args) = tool_attr.parse_args_with(parser) {
                     // Iterate over the parsed Meta items to find 'name'. #[tool(name = "...")]
                     for arg_meta in args {
                         match arg_meta {
+                            Meta::NameValue(name_value)
+                                if name_value.path.is_ident("capabilities") =>
+                            {
+                                capabilities = Some(name_value.value);
+                            }
+                            Meta::Path(path) if path.is_ident("blocking") => {
+                                if method.sig.asyncness.is_some() {
+                                    // Error: Async functions don't block, they have to be awaited on the runtime
+                                    return Error::new_spanned(
+                                        path.to_token_stream(),
+                                        "Blocking tools have to be synchronous functions",
+                                    )
+                                    .to_compile_error()
+                                    .into();
+                                }
+                                blocking = true;
+                            }
                             Meta::NameValue(name_value) if name_value.path.is_ident("name") => {
                                 if name_arg_found {
                                     // Error: Duplicate 'name' argument
-                                    return Error::new_spanned(name_value.to_token_stream(), "Duplicate 'name' argument in tool attribute").to_compile_error().into();
+                                    return Error::new_spanned(
+                                        name_value.to_token_stream(),
+                                        "Duplicate 'name' argument in tool attribute",
+                                    )
+                                    .to_compile_error()
+                                    .into();
                                 }
                                 let Expr::Lit(expr_lit) = &name_value.value else {
                                     // Error: Expected literal value for name
-                                    return Error::new_spanned(name_value.value.to_token_stream(), "Expected literal value for tool name").to_compile_error().into();
+                                    return Error::new_spanned(
+                                        name_value.value.to_token_stream(),
+                                        "Expected literal value for tool name",
+                                    )
+                                    .to_compile_error()
+                                    .into();
                                 };
                                 let Lit::Str(lit_str) = &expr_lit.lit else {
                                     // Error: Expected string literal for name
-                                    return Error::new_spanned(expr_lit.to_token_stream(), "Expected string literal for tool name").to_compile_error().into();
+                                    return Error::new_spanned(
+                                        expr_lit.to_token_stream(),
+                                        "Expected string literal for tool name",
+                                    )
+                                    .to_compile_error()
+                                    .into();
                                 };
                                 tool_name = lit_str.value();
                                 name_arg_found = true;
-                            },
+                            }
                             _ => {
-                                // Error: If arguments are present, they must be 'name = "..."'
-                                return Error::new_spanned(arg_meta.to_token_stream(), "Expected name = \"...\" in tool attribute").to_compile_error().into();
+                                // Error: If arguments are present, they must be 'name = "..."', 'capabilities = ...' or 'blocking'
+                                return Error::new_spanned(arg_meta.to_token_stream(), "Expected name = \"...\", capabilities = [...] or blocking in tool attribute").to_compile_error().into();
                             }
                         };
                     }
                 }
 
                 // Check for duplicate tool names AFTER determining the final tool_name
-                if !found_tools.insert(tool_name.clone()) {
-                     return Error::new_spanned(tool_attr.to_token_stream(), format!("Duplicate tool name found: {}", tool_name)).to_compile_error().into();
+                if tool_definitions.iter().any(|(name, _)| *name == tool_name) {
+                    return Error::new_spanned(
+                        tool_attr.to_token_stream(),
+                        format!("Duplicate tool name found: {}", tool_name),
+                    )
+                    .to_compile_error()
+                    .into();
                 }
 
                 // Extract doc comments for description from #[doc = "..."] attributes (handles /// and /* */) from method
-                let description = method.attrs.iter()
-                    .filter_map(|attr|
-                        match attr.meta.clone() {
-                            Meta::NameValue(MetaNameValue { path, value: Expr::Lit(expr_lit), .. }) if path.is_ident("doc") => {
-                                match expr_lit.lit {
-                                    Lit::Str(lit_str) => {
-                                        // Remove leading slashes, stars, and whitespace
-                                        Some(lit_str.value().trim().trim_start_matches(|c: char| c == '/' || c == '*' || c.is_whitespace()).to_string())
-                                    }
-                                    _ => None, // Not a string literal
+                let description = method
+                    .attrs
+                    .iter()
+                    .filter_map(|attr| match attr.meta.clone() {
+                        Meta::NameValue(MetaNameValue {
+                            path,
+                            value: Expr::Lit(expr_lit),
+                            ..
+                        }) if path.is_ident("doc") => {
+                            match expr_lit.lit {
+                                Lit::Str(lit_str) => {
+                                    // Remove leading slashes, stars, and whitespace
+                                    Some(
+                                        lit_str
+                                            .value()
+                                            .trim()
+                                            .trim_start_matches(|c: char| {
+                                                c == '/' || c == '*' || c.is_whitespace()
+                                            })
+                                            .to_string(),
+                                    )
                                 }
-                            },
-                            _ => None, // Not a #[doc = ...] attribute or error
+                                _ => None, // Not a string literal
+                            }
                         }
-                    )
+                        _ => None, // Not a #[doc = ...] attribute or error
+                    })
                     .collect::<Vec<String>>()
                     .join("\n");
 
@@ -254,7 +429,10 @@ pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 };
 
                 // Generate parameter struct
-                let params_struct_name = Ident::new(&format!("{}Params", fn_name.to_upper_camel_case()), fn_name_sig.span());
+                let params_struct_name = Ident::new(
+                    &format!("{}Params", fn_name.to_upper_camel_case()),
+                    fn_name_sig.span(),
+                );
                 let mut param_fields = TokenStream2::new();
                 let mut param_assignments = TokenStream2::new();
 
@@ -274,7 +452,12 @@ pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
 
                         let Pat::Ident(ref pat_ident) = *pat_type.pat else {
                             // Handle other patterns if necessary, or return an error
-                            return Error::new_spanned(pat_type.pat.to_token_stream(), "Tool function parameters must be simple identifiers").to_compile_error().into();
+                            return Error::new_spanned(
+                                pat_type.pat.to_token_stream(),
+                                "Tool function parameters must be simple identifiers",
+                            )
+                            .to_compile_error()
+                            .into();
                         };
 
                         let arg_name = &pat_ident.ident;
@@ -284,68 +467,92 @@ pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
                         });
 
                         param_assignments.extend(quote! {
-                            params.#arg_name
+                            params.#arg_name,
                         });
                     }
                 }
 
                 if !param_fields.is_empty() {
                     generated_code.extend(quote! {
-                        // Parameters struct for #original_fn_name_str
-                        #[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
-                        #[allow(dead_code)]
-                        #[allow(clippy::all)]
-                        struct #params_struct_name {
-                            #param_fields
-                        }
-                     });
+                       // Parameters struct for #original_fn_name_str
+                       #[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+                       #[allow(dead_code)]
+                       #[allow(clippy::all)]
+                       struct #params_struct_name {
+                           #param_fields
+                       }
+                    });
                 }
 
                 // Add to tool definitions
                 let schema_token = if param_fields.is_empty() {
                     quote! { None }
                 } else {
-                    // Use the generated parameter struct name for schemars::schema_for!
-                    // quote! { Some(generate_tool_schema::<#params_struct_name>()) }
+                    // Options are evaluated on every call, so changes of global options are visible
                     quote! {
-                        Some({
-                            let generator = ::schemars::generate::SchemaSettings::draft2020_12().with(|s| {
-                                s.meta_schema = None;
-                            }).into_generator();
-                            generator.into_root_schema_for::<#params_struct_name>().into()
-                        })
+                        Some(#schema_options.schema_for::<#params_struct_name>())
                     }
                 };
 
-                tool_definitions.extend(quote! {
-                    Tool {
-                        name: #tool_name.to_string(),
-                        description: #description_token,
-                        schema: #schema_token,
+                tool_definitions.push((
+                    tool_name.clone(),
+                    quote! {
+                        Tool {
+                            name: #tool_name.to_string(),
+                            description: #description_token,
+                            schema: #schema_token,
+                        },
                     },
-                });
+                ));
 
                 // Add to match arms for call_tool
                 let mut method_call = TokenStream2::new();
 
-                if !param_fields.is_empty(){
+                if !param_fields.is_empty() {
+                    let repair_arguments = if coerce_arguments {
+                        quote! { serde_json::from_value(::agentai::tool::repair::coerce_arguments(parameters.clone(), &schema)).ok() }
+                    } else {
+                        quote! { None }
+                    };
                     method_call.extend(quote! {
-                        let params: #params_struct_name = serde_json::from_value(parameters)
-                            .map_err(|e| {
-                                eprintln!("Tool parameter deserialization error for '{}': {:?}", #tool_name, e);
-                                ToolError::ExecutionError
-                            })?;
+                        let params: #params_struct_name = match serde_json::from_value(parameters.clone()) {
+                            Ok(params) => params,
+                            Err(error) => {
+                                // Let the model correct arguments, after trying to repair them
+                                let schema = #schema_options.schema_for::<#params_struct_name>();
+                                let repaired: Option<#params_struct_name> = #repair_arguments;
+                                repaired.ok_or_else(|| ToolError::InvalidArguments {
+                                    tool: #tool_name.to_string(),
+                                    error: error.to_string(),
+                                    arguments: Box::new(parameters),
+                                    schema: Box::new(schema),
+                                })?
+                            }
+                        };
                     });
                 }
 
-                method_call.extend(quote! { self.#fn_name_sig(#param_assignments) });
+                if blocking {
+                    // Clone of the toolbox is moved to the blocking thread, as it can outlive the call
+                    method_call.extend(quote! {{
+                        let this = self.clone();
+                        ::agentai::tool::run_blocking(move || this.#fn_name_sig(#param_assignments)).await
+                    }});
+                } else {
+                    method_call.extend(quote! { self.#fn_name_sig(#param_assignments) });
+                }
                 if method.sig.asyncness.is_some() {
                     method_call.extend(quote! {.await});
                 }
 
+                let report_error = if cfg!(feature = "observability") {
+                    quote! { ::agentai::__private::tracing::error!(tool = #tool_name, error = ?e, "Tool execution error"); }
+                } else {
+                    quote! { ::agentai::__private::log::warn!("Tool execution error for '{}': {:?}", #tool_name, e); }
+                };
                 method_call.extend(quote! { .map_err(|e| {
-                    eprintln!("Tool execution error for '{}': {:?}", #tool_name, e);
-                    ToolError::ExecutionError
+                    #report_error
+                    e
                 }) });
 
                 match_arms.extend(quote! {
@@ -353,13 +560,51 @@ pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
                         #method_call
                     },
                 });
+
+                if let Some(capabilities) = capabilities {
+                    capabilities_arms.extend(quote! {
+                        #tool_name => Vec::from(#capabilities),
+                    });
+                }
             }
         }
     }
 
-    if found_tools.is_empty() {
-        return Error::new(Span::call_site(), "No #[tool] definition in impl block").to_compile_error().into()
+    if tool_definitions.is_empty() {
+        return Error::new(Span::call_site(), "No #[tool] definition in impl block")
+            .to_compile_error()
+            .into();
+    }
+
+    if sort_alphabetically {
+        tool_definitions.sort_by(|(a, _), (b, _)| a.cmp(b));
     }
+    let tool_definitions: TokenStream2 = tool_definitions
+        .into_iter()
+        .map(|(_, definition)| definition)
+        .collect();
+
+    let dispatch = quote! {
+        match tool_name.as_str() {
+            #match_arms
+            _ => {
+                Err(ToolError::NoToolFound(tool_name))
+            }
+        }
+    };
+    let call_tool_body = if cfg!(feature = "observability") {
+        quote! {
+            use ::agentai::__private::tracing::Instrument as _;
+            let span = ::agentai::__private::tracing::info_span!(
+                "tool_call",
+                tool = %tool_name,
+                arguments = %::agentai::__private::traced_arguments(&parameters),
+            );
+            async move { #dispatch }.instrument(span).await
+        }
+    } else {
+        dispatch
+    };
 
     // Generate the ToolBox implementation
     let toolbox_impl = quote! {
@@ -372,13 +617,15 @@ pub fn toolbox(_attr: TokenStream, item: TokenStream) -> TokenStream {
                 ])
             }
 
+            fn tool_capabilities(&self, tool_name: &str) -> Vec<::agentai::tool::Capability> {
+                match tool_name {
+                    #capabilities_arms
+                    _ => vec![::agentai::tool::Capability::Unknown],
+                }
+            }
+
             async fn call_tool(&self, tool_name: String, parameters: serde_json::Value) -> Result<String, ToolError> {
-                 match tool_name.as_str() {
-                     #match_arms
-                     _ => {
-                         Err(ToolError::NoToolFound(tool_name))
-                     }
-                 }
+                #call_tool_body
             }
         }
     };
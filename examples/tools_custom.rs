@@ -9,6 +9,7 @@ use agentai::Agent;
 use anyhow::Error;
 use log::{info, LevelFilter};
 use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+use std::sync::Arc;
 
 const SYSTEM: &str = "You are helpful assistant. You goal is to provide summary for provided site. Limit you answer to 3 sentences.";
 
@@ -38,7 +39,7 @@ async fn main() -> Result<(), Error> {
     let mut agent = Agent::new_with_url(&base_url, &api_key, SYSTEM);
 
     let answer: String = agent
-        .run(&model, question, Some(&toolbox), None, None)
+        .run(&model, question, Some(Arc::new(toolbox)), None, None)
         .await?;
 
     info!("Answer: {}", answer);
@@ -80,9 +81,9 @@ impl UrlFetcherToolBox {
         // The `?` operator handles potential errors from the get and text methods.
         Ok(reqwest::get(url)
             .await
-            .map_err(|e| anyhow::Error::new(e))?
+            .map_err(anyhow::Error::new)?
             .text()
             .await
-            .map_err(|e| anyhow::Error::new(e))?)
+            .map_err(anyhow::Error::new)?)
     }
 }
@@ -5,6 +5,7 @@ use log::{info, LevelFilter};
 use schemars::JsonSchema;
 use serde::Deserialize;
 use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+use std::sync::Arc;
 
 const SYSTEM: &str = "You are helpful assistant.";
 
@@ -39,7 +40,7 @@ async fn main() -> Result<()> {
     .await?;
 
     let answer: Answer = agent
-        .run(&model, question, Some(&mcp_tools), None, None)
+        .run(&model, question, Some(Arc::new(mcp_tools)), None, None)
         .await?;
 
     info!("{:#?}", answer);
@@ -6,6 +6,7 @@ use schemars::JsonSchema;
 use serde::Deserialize;
 use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
 use std::env;
+use std::sync::Arc;
 
 const SYSTEM: &str =
     "You are helpful assistant. You goal is to search for information requested by user,\
@@ -35,7 +36,13 @@ async fn main() -> Result<()> {
     let mut agent = Agent::new_with_url(&base_url, &api_key, SYSTEM);
 
     let answer: Answer = agent
-        .run(&model, question, Some(&web_search_tool), None, None)
+        .run(
+            &model,
+            question,
+            Some(Arc::new(web_search_tool)),
+            None,
+            None,
+        )
         .await?;
 
     info!("{:#?}", answer);
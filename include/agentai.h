@@ -0,0 +1,46 @@
+/*
+ * AgentAI C API
+ *
+ * Build library with:
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * All strings are NUL terminated UTF-8. Strings returned by the library must be
+ * released with agentai_string_free(). On failure functions return NULL and
+ * description of the error can be read with agentai_last_error().
+ */
+#ifndef AGENTAI_H
+#define AGENTAI_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct AgentAiAgent AgentAiAgent;
+
+/* Receives events emitted during the run, including parts of the answer
+ * ("text_delta" events), serialized as JSON. It is invoked on the thread
+ * calling agentai_agent_run(). */
+typedef void (*AgentAiEventCallback)(const char *event_json, void *user_data);
+
+AgentAiAgent *agentai_agent_new(const char *system);
+
+AgentAiAgent *agentai_agent_new_with_url(const char *base_url, const char *api_key, const char *system);
+
+/* Blocks until the answer is ready. `callback` may be NULL. */
+char *agentai_agent_run(AgentAiAgent *agent,
+                        const char *model,
+                        const char *prompt,
+                        AgentAiEventCallback callback,
+                        void *user_data);
+
+const char *agentai_last_error(void);
+
+void agentai_string_free(char *value);
+
+void agentai_agent_free(AgentAiAgent *agent);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* AGENTAI_H */
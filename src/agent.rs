@@ -7,18 +7,115 @@
 //! To read more about structured output look into [crate::structured_output]
 //!
 //! To read more about tool look into [crate::tool]
+//!
+//! To read more about events emitted during the run look into [crate::event]
+
+mod analytics;
+mod approval;
+mod builder;
+mod cache;
+mod citations;
+mod classify;
+mod compact;
+mod confidence;
+mod consistency;
+mod dedup;
+mod error;
+mod extract;
+mod format;
+mod handoff;
+mod history;
+mod instructions;
+mod language;
+mod limiter;
+mod list;
+mod metadata;
+mod middleware;
+mod output;
+mod overflow;
+mod overlay;
+mod parse;
+mod postprocess;
+mod profile;
+mod prompt;
+mod prompts;
+mod quota;
+mod refine;
+mod response;
+mod results;
+mod runner;
+mod session;
+mod stall;
+mod stream;
+mod structured;
+mod summarize;
+mod tasks;
+#[cfg(test)]
+pub(crate) mod testing;
+mod usage;
 
-use crate::tool::ToolBox;
+pub use analytics::{ToolStats, ToolUsage};
+pub use approval::{Approval, ApprovalHandler, ToolGrant};
+pub use builder::AgentBuilder;
+pub use cache::{AnswerCache, CacheStats, CachedAnswer, Embedder, ResponseCache};
+pub use citations::{CitationMode, CitationVerification};
+pub use classify::Classification;
+pub use compact::COMPACT_HISTORY_TOOL;
+pub use consistency::Aggregator;
+pub use dedup::DuplicateToolCalls;
+pub use error::AgentError;
+pub use extract::DEFAULT_EXTRACTION_CHUNK_LEN;
+pub use format::ResultFormat;
+pub use handoff::{Handoff, TRANSFER_TO_TOOL};
+pub use history::{ImageResend, ToolResultBatching};
+pub use instructions::InstructionRole;
+pub use language::ResponseLanguage;
+pub use limiter::RequestLimiter;
+pub use metadata::SessionMetadata;
+pub use middleware::{ChatMiddleware, RequestContext};
+pub use output::{AgentOutput, OutputFormat};
+pub use overflow::ContextOverflow;
+pub use parse::{OutputParsing, ParseErrorHandler};
+pub use postprocess::{Clamp, NormalizeWhitespace, PostProcessor, ResolveUrls};
+pub use profile::PromptProfile;
+pub use prompt::Prompt;
+pub use prompts::{PromptKey, Prompts};
+pub use quota::{QuotaManager, QuotaStatus, DEFAULT_TENANT_KEY};
+pub use refine::{apply_patch, PatchOperation, Refiner};
+pub use response::{AgentResponse, Citation, UnsupportedClaim};
+pub use results::{OversizedResult, ToolResultLimit, READ_ARTIFACT_TOOL};
+pub use runner::AgentRunner;
+pub use stall::StallAction;
+pub use stream::AgentChunk;
+pub use structured::StructuredOutputMode;
+pub use summarize::{Summary, SummaryStyle};
+pub use usage::{ModelDowngrade, TokenUsage, UsageThreshold};
+
+pub(crate) use crate::agent::cache::cosine_similarity;
+pub(crate) use crate::agent::compact::compact_history_tool;
+pub(crate) use crate::agent::extract::split_text;
+use crate::agent::response::ToolCallRecord;
+use crate::agent::stall::StallDetector;
+use crate::event::{AgentEvent, AgentStreamEvent, EventHandler, EventSink};
+use crate::schema::{canonicalize, sanitize, strict};
+use crate::system_prompt::SystemPrompt;
+use crate::tool::composite::SharedToolBox;
+use crate::tool::diff::ToolsDiff;
+use crate::tool::sandbox::{Capability, SandboxProfile};
+use crate::tool::{Tool, ToolBox, ToolError};
+#[cfg(feature = "trace-export")]
+use crate::trace::{TraceExporter, TraceRecorder};
 use anyhow::{anyhow, Result};
 use genai::adapter::AdapterKind;
-use genai::chat::{ChatMessage, ChatOptions, ChatRequest, JsonSpec, MessageContent, ToolResponse};
+use genai::chat::{
+    ChatMessage, ChatOptions, ChatRequest, ChatResponse, ChatResponseFormat, ChatRole, JsonSpec,
+    MessageContent, ToolCall, ToolResponse, Usage,
+};
 use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
 use genai::{Client, ClientBuilder, ModelIden, ServiceTarget};
-use log::{debug, trace};
-use schemars::{schema_for, JsonSchema};
-use serde::de::DeserializeOwned;
-use serde_json::{from_str, json, Value};
-use std::any::TypeId;
+use log::{debug, trace, warn};
+use serde_json::{json, Value};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 /// The `Agent` struct represents an agent that interacts with a chat model.
@@ -34,9 +131,226 @@ pub struct Agent {
 
     // tool_box: impl ToolBox,
     history: Vec<ChatMessage>,
+
+    /// Optional callback receiving events emitted during the run
+    event_handler: Option<EventHandler>,
+
+    /// Settings provided by [AgentBuilder]
+    options: AgentOptions,
+
+    /// Tool calls requested by the model during the last run in dry-run mode
+    planned_tool_calls: Vec<ToolCall>,
+
+    /// Oversized tool results of the current run, that model can read with `read_artifact` tool
+    artifacts: results::Artifacts,
+
+    /// Tokens used by all runs of the agent
+    usage: TokenUsage,
+
+    /// Cheaper model used after reaching usage threshold of [ModelDowngrade]
+    downgraded_model: Option<String>,
+
+    /// Tool calls made during the current run
+    run_tool_calls: Vec<ToolCallRecord>,
+
+    /// Tools called during all runs of the agent
+    tool_usage: ToolUsage,
+
+    /// Tool calls allowed without approval for the rest of the session
+    tool_grants: Vec<ToolGrant>,
+
+    /// Metadata of the conversation, like user id or tenant
+    metadata: SessionMetadata,
+
+    /// Hashes of images already sent to the model, see [ImageResend::FirstOccurrence]
+    sent_images: HashSet<u64>,
+
+    /// Tools of the toolbox seen in the last run, with address of the toolbox
+    known_tools: Option<(usize, Arc<[Tool]>)>,
+
+    /// Model called `compact_history` tool, history is compacted after results of the iteration
+    compact_requested: bool,
+
+    /// Requests are streamed and text deltas are emitted, see [Agent::run_stream]
+    streaming: bool,
 }
 
 const DEFAULT_ITERATION: u32 = 5;
+/// Seed used in deterministic mode when none was provided
+pub const DEFAULT_SEED: u64 = 42;
+
+/// Settings changing behaviour of the [Agent], configured using [AgentBuilder].
+#[derive(Clone, Default)]
+pub(crate) struct AgentOptions {
+    pub(crate) dry_run: Option<DryRun>,
+    /// Seed used in deterministic mode, `None` when the mode is disabled
+    pub(crate) deterministic_seed: Option<u64>,
+    /// Send schemas as generated, without adjusting them to the provider
+    pub(crate) raw_schemas: bool,
+    /// Limit of concurrent requests, shared with other agents
+    pub(crate) request_limiter: Option<RequestLimiter>,
+    /// Capabilities allowed for tools
+    pub(crate) sandbox: SandboxProfile,
+    /// Handling of oversized tool results
+    pub(crate) tool_result_limit: Option<ToolResultLimit>,
+    /// Cheap model used for auxiliary tasks, like summarizing the session
+    pub(crate) utility_model: Option<String>,
+    /// Switch to cheaper model after reaching usage threshold
+    pub(crate) model_downgrade: Option<ModelDowngrade>,
+    /// Collecting of sources used in the answer
+    pub(crate) citations: Option<CitationMode>,
+    /// Check of the answer against cited sources
+    pub(crate) citation_verification: Option<CitationVerification>,
+    /// Answer is wrapped together with confidence of the model
+    pub(crate) confidence: bool,
+    /// How schema of the output is provided to the model
+    pub(crate) structured_output: StructuredOutputMode,
+    /// How structured answers of the model are parsed
+    pub(crate) output_parsing: OutputParsing,
+    /// Callback recovering answers that can't be parsed
+    pub(crate) on_parse_error: Option<ParseErrorHandler>,
+    /// Cleanup of answers applied before conversion into the output type
+    pub(crate) post_processors: Vec<Arc<dyn PostProcessor>>,
+    /// Maximal length of the text extracted in a single request
+    pub(crate) extraction_chunk_len: Option<usize>,
+    /// Language in which the model answers
+    pub(crate) response_language: Option<ResponseLanguage>,
+    /// Agents that the model can hand off the task to
+    pub(crate) handoffs: Vec<Handoff>,
+    /// Conversion of tool results before sending them to the model
+    pub(crate) result_formats: format::ResultFormats,
+    /// Handling of repeated identical tool calls
+    pub(crate) duplicate_tool_calls: DuplicateToolCalls,
+    /// Handling of runs that don't make progress, disabled when `None`
+    pub(crate) stall_action: Option<StallAction>,
+    /// Behaviour in the last allowed iteration
+    pub(crate) on_iteration_exhausted: OnIterationExhausted,
+    /// Roles of messages with instructions injected by the agent
+    pub(crate) instruction_roles: instructions::InstructionRoles,
+    /// Overrides of prompts injected by the agent
+    pub(crate) prompts: Prompts,
+    /// Names of tools exposed to the model, all tools when `None`
+    pub(crate) allowed_tools: Option<Vec<String>>,
+    /// Hooks around requests sent to the model, in order of installation
+    pub(crate) middleware: middleware::MiddlewareChain,
+    /// Callback modifying requests of run iterations
+    pub(crate) iteration_hook: Option<IterationHook>,
+    /// Model is informed when tools of the toolbox changed between runs
+    pub(crate) tools_change_notes: bool,
+    /// Callback approving tool calls, all calls are executed when `None`
+    pub(crate) approval: Option<ApprovalHandler>,
+    /// Handling of toolbox failing to provide tools definitions
+    pub(crate) tool_definitions_failure: ToolDefinitionsFailure,
+    /// Recovery from requests exceeding context window
+    pub(crate) context_overflow: ContextOverflow,
+    /// Model can compact the history with `compact_history` tool
+    pub(crate) compact_tool: bool,
+    /// System prompt composed from presets, rendered with prompt profile of the model
+    pub(crate) system_prompt: Option<SystemPrompt>,
+    /// Layout of prompts chosen by provider
+    pub(crate) prompt_profiles: profile::PromptProfiles,
+    /// Handling of tools that panicked
+    pub(crate) tool_panic: ToolPanic,
+    /// Toolbox used by runs that don't provide their own one
+    pub(crate) toolbox: Option<SharedToolBox>,
+    /// Batching of tool results of a single iteration
+    pub(crate) tool_result_batching: history::ToolResultBatchings,
+    /// Handling of images already sent to the model
+    pub(crate) image_resend: ImageResend,
+    /// Cache of final answers, skipping runs in equivalent states of the conversation
+    pub(crate) answer_cache: Option<AnswerCache>,
+    /// Minimal length of repeated tool results replaced by placeholder, disabled when `None`
+    pub(crate) repeated_result_len: Option<usize>,
+    /// Recorder of runs and destination of recorded traces
+    #[cfg(feature = "trace-export")]
+    pub(crate) trace_export: Option<(TraceRecorder, Arc<dyn TraceExporter>)>,
+    /// Conversion of answers into audio, see [Agent::speak]
+    #[cfg(feature = "tts")]
+    pub(crate) speech: Option<crate::speech::TextToSpeech>,
+}
+
+/// Behaviour of the agent when the last allowed iteration is reached, see [AgentBuilder::with_on_iteration_exhausted].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnIterationExhausted {
+    /// Run fails when the model doesn't answer within allowed iterations.
+    #[default]
+    Error,
+    /// In the last iteration tools are disabled and the model is asked to provide the best
+    /// answer it can with the information gathered so far.
+    ForceAnswer,
+}
+
+/// Behaviour of the agent when toolbox fails to provide tools definitions, e.g. because MCP server
+/// disconnected, see [AgentBuilder::with_tool_definitions_failure].
+///
+/// Definitions are fetched in every iteration of the run, so failure can happen in the middle of it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolDefinitionsFailure {
+    /// Run fails with the error of the toolbox.
+    #[default]
+    Fail,
+    /// Request is sent without tools of the toolbox, definitions are fetched again in the next iteration.
+    ContinueWithoutTools,
+    /// Fetching definitions is retried up to given number of times, then the run fails.
+    Retry(u32),
+}
+
+/// Behaviour of the agent when a tool panics, see [AgentBuilder::with_tool_panic].
+///
+/// Tools are executed in separate tasks, so the panic never unwinds through the agent. It is
+/// converted into [ToolError::Panicked] and the result of the call is stored in the history either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolPanic {
+    /// Panic message is sent to the model as the error of the tool, the run continues.
+    #[default]
+    ReportToModel,
+    /// Run fails with [ToolError::Panicked].
+    Fail,
+}
+
+/// Callback modifying the request of every iteration of the run before it is sent, see [AgentBuilder::with_iteration_hook].
+///
+/// It receives number of the iteration (starting from 0), the request and its options. Changes
+/// apply only to the request of this iteration, e.g. dropping tools after a few iterations,
+/// raising temperature or appending a steering message.
+pub type IterationHook = Arc<dyn Fn(u32, &mut ChatRequest, &mut ChatOptions) + Send + Sync>;
+
+/// Callback providing simulated tool result in dry-run mode.
+pub type ToolSimulator = Arc<dyn Fn(&ToolCall) -> String + Send + Sync>;
+
+/// Behaviour of the agent in dry-run mode.
+///
+/// In dry-run mode tools are never executed. Instead, tool calls requested by the model are recorded
+/// (see [Agent::planned_tool_calls]) and emitted as [AgentEvent::ToolCall] events, so they can be
+/// inspected before running the agent for real. This is useful to preview what an agent with
+/// destructive tools would do.
+#[derive(Clone)]
+pub enum DryRun {
+    /// Tool is not executed, model is informed that the call was skipped and should continue.
+    Skip,
+    /// Tool is not executed, result sent to the model is provided by the callback.
+    Simulate(ToolSimulator),
+}
+
+impl DryRun {
+    /// Creates dry-run mode with results provided by `simulator`.
+    pub fn simulate<F>(simulator: F) -> Self
+    where
+        F: Fn(&ToolCall) -> String + Send + Sync + 'static,
+    {
+        DryRun::Simulate(Arc::new(simulator))
+    }
+
+    fn result(&self, tool_call: &ToolCall) -> String {
+        match self {
+            DryRun::Skip => format!(
+                "Tool `{}` was not executed (dry run). Assume it succeeded and continue.",
+                tool_call.fn_name
+            ),
+            DryRun::Simulate(simulator) => simulator(tool_call),
+        }
+    }
+}
 
 impl Agent {
     /// Creates a new `Agent` instance.
@@ -51,7 +365,7 @@ impl Agent {
     ///
     /// A new `Agent` instance.
     pub fn new(system: &str) -> Self {
-        let client = Client::default();
+        let client = default_client();
 
         Self::new_with_client(client, system)
     }
@@ -70,27 +384,184 @@ impl Agent {
         Self {
             client,
             history: vec![ChatMessage::system(system.trim())],
+            event_handler: None,
+            options: AgentOptions::default(),
+            planned_tool_calls: vec![],
+            artifacts: results::Artifacts::default(),
+            usage: TokenUsage::default(),
+            downgraded_model: None,
+            run_tool_calls: vec![],
+            tool_usage: ToolUsage::default(),
+            tool_grants: vec![],
+            metadata: SessionMetadata::default(),
+            sent_images: HashSet::new(),
+            known_tools: None,
+            compact_requested: false,
+            streaming: false,
         }
     }
 
     pub fn new_with_url(base_url: &str, api_key: &str, system: &str) -> Self {
-        let endpoint = Endpoint::from_owned(Arc::from(base_url));
-        let auth = AuthData::from_single(api_key);
-        let target_resolver = ServiceTargetResolver::from_resolver_fn(
-            |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
-                let ServiceTarget { model, .. } = service_target;
-                let model = ModelIden::new(AdapterKind::OpenAI, model.model_name);
-                Ok(ServiceTarget {
-                    endpoint,
-                    auth,
-                    model,
-                })
-            },
-        );
-        let client = ClientBuilder::default()
-            .with_service_target_resolver(target_resolver)
-            .build();
-        Self::new_with_client(client, system)
+        Self::new_with_client(client_with_url(base_url, api_key), system)
+    }
+
+    /// Creates [AgentBuilder] that allows to configure all aspects of the agent.
+    pub fn builder() -> AgentBuilder {
+        AgentBuilder::new()
+    }
+
+    /// Returns tool calls requested by the model during the last run in dry-run mode.
+    ///
+    /// Look into [DryRun] for more information.
+    pub fn planned_tool_calls(&self) -> &[ToolCall] {
+        &self.planned_tool_calls
+    }
+
+    /// Registers callback that will receive [AgentEvent]s emitted during the run.
+    ///
+    /// Only one handler can be registered, calling this method again replaces previous handler.
+    pub fn set_event_handler<F>(&mut self, handler: F)
+    where
+        F: Fn(&AgentEvent) + Send + Sync + 'static,
+    {
+        self.event_handler = Some(Arc::new(handler));
+    }
+
+    /// Replaces current event handler, returning the previous one.
+    ///
+    /// Passing `None` removes the handler.
+    pub fn replace_event_handler(&mut self, handler: Option<EventHandler>) -> Option<EventHandler> {
+        std::mem::replace(&mut self.event_handler, handler)
+    }
+
+    /// Returns number of tokens used by all runs of the agent.
+    pub fn usage(&self) -> &TokenUsage {
+        &self.usage
+    }
+
+    /// Returns statistics of tools called during all runs of the agent.
+    pub fn tool_usage(&self) -> &ToolUsage {
+        &self.tool_usage
+    }
+
+    /// Returns prompts injected by the agent, see [AgentBuilder::with_prompts].
+    pub fn prompts(&self) -> &Prompts {
+        &self.options.prompts
+    }
+
+    /// Resets usage statistics, including [ToolUsage], also reverting the [ModelDowngrade].
+    pub fn reset_usage(&mut self) {
+        self.usage = TokenUsage::default();
+        self.tool_usage = ToolUsage::default();
+        self.downgraded_model = None;
+    }
+
+    /// Returns metadata of the conversation, see [SessionMetadata].
+    pub fn metadata(&self) -> &SessionMetadata {
+        &self.metadata
+    }
+
+    /// Returns metadata of the conversation for modification, see [SessionMetadata].
+    pub fn metadata_mut(&mut self) -> &mut SessionMetadata {
+        &mut self.metadata
+    }
+
+    /// Returns tool calls granted for the session by [ApprovalHandler], e.g. to store them with the history.
+    pub fn tool_grants(&self) -> &[ToolGrant] {
+        &self.tool_grants
+    }
+
+    /// Replaces tool calls granted for the session, e.g. restoring the stored ones.
+    pub fn set_tool_grants(&mut self, grants: Vec<ToolGrant>) {
+        self.tool_grants = grants;
+    }
+
+    /// Returns sandbox profile used to decide which tools are available.
+    pub fn sandbox(&self) -> SandboxProfile {
+        self.options.sandbox
+    }
+
+    /// Switches sandbox profile, it takes effect from the next request to the model.
+    pub fn set_sandbox(&mut self, sandbox: SandboxProfile) {
+        self.options.sandbox = sandbox;
+    }
+
+    /// Replaces system message of the chat history, the rest of the history is kept.
+    pub fn set_system(&mut self, system: &str) {
+        let message = ChatMessage::system(system.trim());
+        match self.history.first_mut() {
+            Some(first) if matches!(first.role, ChatRole::System) => *first = message,
+            _ => self.history.insert(0, message),
+        }
+    }
+
+    /// Limits tools exposed to the model to the listed ones, `None` allows all tools.
+    /// Tools have to be allowed also by the [sandbox](Agent::set_sandbox). Handoffs to other agents
+    /// are allowed only when [TRANSFER_TO_TOOL] is listed.
+    pub fn set_allowed_tools(&mut self, tools: Option<Vec<String>>) {
+        self.options.allowed_tools = tools;
+    }
+
+    /// Sets or removes [ModelDowngrade] policy, it takes effect from the next request to the model.
+    pub fn set_model_downgrade(&mut self, downgrade: Option<ModelDowngrade>) {
+        self.options.model_downgrade = downgrade;
+    }
+
+    /// Returns toolbox used by runs that don't provide their own one.
+    pub fn toolbox(&self) -> Option<&SharedToolBox> {
+        self.options.toolbox.as_ref()
+    }
+
+    /// Sets or removes toolbox used by runs that don't provide their own one.
+    pub fn set_toolbox(&mut self, toolbox: Option<SharedToolBox>) {
+        self.options.toolbox = toolbox;
+    }
+
+    fn emit(&self, event: AgentEvent) {
+        #[cfg(feature = "trace-export")]
+        if let Some((recorder, _)) = &self.options.trace_export {
+            recorder.record_event(&event);
+        }
+        if let Some(handler) = &self.event_handler {
+            handler(&event);
+        }
+    }
+
+    /// Attaches score to the trace of the last run, e.g. rating of the answer given by the user.
+    ///
+    /// Requires exporter configured with [AgentBuilder::with_trace_exporter].
+    #[cfg(feature = "trace-export")]
+    pub async fn score_run(&self, name: &str, value: f64, comment: Option<&str>) -> Result<()> {
+        let (recorder, exporter) = self.options.trace_export.as_ref().ok_or_else(|| {
+            anyhow!("Trace exporter is not configured, use AgentBuilder::with_trace_exporter")
+        })?;
+        exporter.score(recorder, name, value, comment).await
+    }
+
+    /// Converts the answer into audio, using text to speech configured with [AgentBuilder::with_speech].
+    ///
+    /// Markdown formatting of the answer is removed, so it is not read aloud. Look into
+    /// [crate::speech] for an example.
+    #[cfg(feature = "tts")]
+    pub async fn speak(&self, answer: &str) -> Result<crate::speech::Audio> {
+        let speech = self.options.speech.as_ref().ok_or_else(|| {
+            anyhow!("Text to speech is not configured, use AgentBuilder::with_speech")
+        })?;
+        speech.synthesize(&crate::speech::speakable(answer)).await
+    }
+
+    /// Pushes trace of the finished run to the exporter, failures are only logged.
+    #[cfg(feature = "trace-export")]
+    async fn export_trace(&self, error: Option<&anyhow::Error>) {
+        let Some((recorder, exporter)) = &self.options.trace_export else {
+            return;
+        };
+        if let Some(error) = error {
+            recorder.fail_run(&error.to_string());
+        }
+        if let Err(err) = exporter.export(recorder).await {
+            warn!("Failed to export trace {}: {err:#}", recorder.trace_id());
+        }
     }
 
     /// Runs the agent with the given model and prompt.
@@ -98,7 +569,11 @@ impl Agent {
     /// # Arguments
     ///
     /// * `model` - The model to use for the chat.
-    /// * `prompt` - The prompt to send to the chat model.
+    /// * `prompt` - The prompt to send to the chat model, plain text or [Prompt] with attachments,
+    ///   developer messages and variables.
+    /// * `toolbox` - Tools available to the model, when `None` the toolbox attached with
+    ///   [AgentBuilder::with_toolbox] is used. Toolbox is shared, so the run can be moved together
+    ///   with the agent to another task, e.g. with `tokio::spawn`.
     ///
     /// # Returns
     ///
@@ -111,109 +586,445 @@ impl Agent {
     pub async fn run<D>(
         &mut self,
         model: &str,
-        prompt: &str,
-        toolbox: Option<&dyn ToolBox>,
+        prompt: impl Into<Prompt>,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+    ) -> Result<D>
+    where
+        D: AgentOutput,
+    {
+        let response = self
+            .run_detailed(model, prompt, toolbox, iteration, config)
+            .await?;
+        Ok(response.output)
+    }
+
+    /// Runs the agent like [Agent::run], returning the answer together with details of the run.
+    ///
+    /// Look into [AgentResponse] for details that are available.
+    pub async fn run_detailed<D>(
+        &mut self,
+        model: &str,
+        prompt: impl Into<Prompt>,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+    ) -> Result<AgentResponse<D>>
+    where
+        D: AgentOutput,
+    {
+        self.run_detailed_as(
+            model,
+            prompt.into(),
+            toolbox,
+            iteration,
+            config,
+            D::output_format(),
+        )
+        .await
+    }
+
+    /// Runs the agent like [Agent::run_detailed], requesting the answer in the format.
+    async fn run_detailed_as<D>(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+        output_format: OutputFormat,
+    ) -> Result<AgentResponse<D>>
+    where
+        D: AgentOutput,
+    {
+        #[cfg(feature = "observability")]
+        let span = tracing::info_span!("agent_run", model, metadata = %self.metadata);
+        // Every run is exported as a separate trace
+        #[cfg(feature = "trace-export")]
+        if let Some((recorder, _)) = &self.options.trace_export {
+            recorder.clear();
+        }
+        let run = self.run_detailed_inner(model, prompt, toolbox, iteration, config, output_format);
+        #[cfg(feature = "observability")]
+        let run = tracing::Instrument::instrument(run, span);
+        let result = run.await;
+        #[cfg(feature = "trace-export")]
+        self.export_trace(result.as_ref().err()).await;
+        result
+    }
+
+    /// Runs the agent like [Agent::run], sending events of the run to the sink.
+    ///
+    /// Every [AgentEvent] is sent as [AgentStreamEvent::Event], followed by [AgentStreamEvent::Finished]
+    /// or [AgentStreamEvent::Failed] when the run ends. Registered event handler keeps receiving
+    /// events. Look into [crate::event] for an example.
+    pub async fn run_with_sink<D>(
+        &mut self,
+        model: &str,
+        prompt: impl Into<Prompt>,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+        sink: impl EventSink + 'static,
+    ) -> Result<D>
+    where
+        D: AgentOutput,
+    {
+        let sink: Arc<dyn EventSink> = Arc::new(sink);
+        let previous = self.attach_sink(sink.clone());
+        let result = self.run(model, prompt, toolbox, iteration, config).await;
+        self.detach_sink(previous, sink, result.as_ref().err());
+        result
+    }
+
+    /// Forwards events of the agent to the sink, returns previous event handler.
+    fn attach_sink(&mut self, sink: Arc<dyn EventSink>) -> Option<EventHandler> {
+        let handler = self.event_handler.clone();
+        self.replace_event_handler(Some(Arc::new(move |event: &AgentEvent| {
+            if let Some(handler) = &handler {
+                handler(event);
+            }
+            sink.send_event(AgentStreamEvent::Event(event.clone()));
+        })))
+    }
+
+    /// Restores previous event handler and sends the last event of the run to the sink.
+    fn detach_sink(
+        &mut self,
+        previous: Option<EventHandler>,
+        sink: Arc<dyn EventSink>,
+        error: Option<&anyhow::Error>,
+    ) {
+        self.replace_event_handler(previous);
+        match error {
+            None => sink.send_event(AgentStreamEvent::Finished),
+            Some(err) => sink.send_event(AgentStreamEvent::Failed(err.to_string())),
+        }
+    }
+
+    /// Runs the agent like [Agent::run], with extra toolboxes available only during this run.
+    ///
+    /// Extra tools are merged with tools of `toolbox`, the agent is not modified. It is useful for
+    /// request-specific capabilities, e.g. a tool bound to the currently edited document. When an
+    /// extra tool has the same name as a tool of `toolbox`, the extra one is used.
+    pub async fn run_with_extra_tools<D>(
+        &mut self,
+        model: &str,
+        prompt: impl Into<Prompt>,
+        toolbox: Option<SharedToolBox>,
+        extra: &[SharedToolBox],
         iteration: Option<u32>,
         config: Option<ChatOptions>,
     ) -> Result<D>
     where
-        D: DeserializeOwned + JsonSchema + 'static,
+        D: AgentOutput,
+    {
+        let toolbox = toolbox.or_else(|| self.options.toolbox.clone());
+        let overlay = Arc::new(overlay::ToolOverlay::new(toolbox, extra));
+        self.run(model, prompt, Some(overlay), iteration, config)
+            .await
+    }
+
+    async fn run_detailed_inner<D>(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+        output_format: OutputFormat,
+    ) -> Result<AgentResponse<D>>
+    where
+        D: AgentOutput,
     {
         // TODO change returned type
         // Need to create new type that will provide not only response structure,
         // but also statistics and reasoning.
-        debug!("Agent Question: {}", prompt);
+        debug!("Agent Question: {}", prompt.text());
+        // Toolbox of the run overrides the default one
+        let toolbox = toolbox.or_else(|| self.options.toolbox.clone());
         // Add new request to history
         // TODO: Create new history trait
         // This will allow on configuring behaviour of messages. When doing multi-agent
         // approach we could decide what history is being used, should we save all messages etc.
-        // TODO: What to do when message have images? Should we send them only once?
-        self.history.push(ChatMessage::user(prompt));
+        let prompt_text = prompt.text();
+        let prompt_messages = prompt.into_messages();
+        let answer_key = match &self.options.answer_cache {
+            Some(cache) => Some(cache.key(
+                &self.current_model(model),
+                &format!("{}{output_format:?}", std::any::type_name::<D>()),
+                &self.history,
+                &prompt_messages,
+            )?),
+            None => None,
+        };
+        // Messages before it can be removed when the context window is exceeded
+        let mut run_start = self.history.len();
+        self.history.extend(prompt_messages);
+        self.planned_tool_calls.clear();
+        self.run_tool_calls.clear();
+        self.artifacts.clear();
+        self.compact_requested = false;
+        let usage_at_start = self.usage;
+        let cached_answer = self
+            .options
+            .answer_cache
+            .as_ref()
+            .zip(answer_key)
+            .and_then(|(cache, key)| cache.get(key));
+        if let Some(text) = cached_answer {
+            debug!("Agent Answer (cached): {text}");
+            self.usage.cached_requests += 1;
+            self.history.push(ChatMessage::assistant(text.clone()));
+            self.emit(AgentEvent::Answer {
+                content: text.clone(),
+            });
+            let (output, citations, confidence) = self.parse_answer(text, &output_format)?;
+            return Ok(AgentResponse {
+                output,
+                citations,
+                unsupported_claims: vec![],
+                confidence,
+                usage: self.usage.since(&usage_at_start),
+                tool_usage: ToolUsage::default(),
+                metadata: self.metadata.clone(),
+            });
+        }
 
         // Prepare chat options
         // TODO: Allow to provide chat options to GenAI
         // This should be be part
         let mut chat_opts = config.unwrap_or(ChatOptions::default().with_temperature(0.2));
+        if let Some(seed) = self.options.deterministic_seed {
+            // Seed is ignored by providers that don't support it
+            chat_opts = chat_opts.with_temperature(0.0).with_seed(seed);
+        }
 
-        let is_answer_string = TypeId::of::<String>() == TypeId::of::<D>();
-        if !is_answer_string {
-            // If answer type is more complex then add response format to request options
-            let mut response_schema = serde_json::to_value(schema_for!(D))?;
-            let obj = response_schema.as_object_mut().unwrap();
-            // Schemars attaches additional fields and not every LLM accepts them (Gemini)
-            obj.remove("$schema");
-            obj.remove("title");
-            chat_opts = chat_opts.with_response_format(JsonSpec::new("ResponseFormat", json!(obj)));
+        let is_wrapped =
+            self.options.citations == Some(CitationMode::Model) || self.options.confidence;
+        // JSON answer is requested without schema, e.g. for `serde_json::Value`
+        let is_untyped_json = !is_wrapped && output_format == OutputFormat::Json { schema: None };
+        let response_schema = if is_wrapped {
+            // Answer is wrapped in an envelope, also when answer is a plain text
+            let mut answer_schema = match output_format.clone() {
+                OutputFormat::Text => json!({"type": "string"}),
+                OutputFormat::Json { schema } => schema.unwrap_or_else(|| json!({})),
+            };
+            if self.options.confidence {
+                answer_schema = confidence::envelope_schema(answer_schema);
+            }
+            if self.options.citations == Some(CitationMode::Model) {
+                answer_schema = citations::cited_answer_schema(answer_schema);
+            }
+            Some(answer_schema)
+        } else {
+            match output_format.clone() {
+                OutputFormat::Text => None,
+                // If answer type is more complex then add response format to request options
+                OutputFormat::Json { schema } => schema,
+            }
+        };
+        // Instructions added by the agent, next to the system message from the history, with
+        // titles used by prompt profiles
+        let mut instructions = vec![];
+        if let Some(toolbox_instructions) =
+            toolbox.as_ref().and_then(|toolbox| toolbox.instructions())
+        {
+            instructions.push(("Tools", toolbox_instructions));
+        }
+        if let Some(language) = &self.options.response_language {
+            instructions.push(("Language", language.instruction(&self.options.prompts)));
+        }
+        if let Some(response_schema) = response_schema {
+            let (opts, output_instruction) =
+                self.apply_output_schema(response_schema, model, chat_opts);
+            chat_opts = opts;
+            instructions
+                .extend(output_instruction.map(|instruction| ("Output format", instruction)));
+        } else if is_untyped_json {
+            let (opts, output_instruction) = self.apply_json_mode(chat_opts);
+            chat_opts = opts;
+            instructions.push(("Output format", output_instruction));
         }
 
         // TODO move it to config structure
         let max_iterations = iteration.unwrap_or(DEFAULT_ITERATION);
 
+        if let Some(toolbox) = &toolbox {
+            self.refresh_tools_definitions(toolbox).await?;
+        }
+        // Toolbox can be shared by concurrent runs, state of this run is kept by its view
+        let toolbox = toolbox.map(|toolbox| toolbox.for_run().unwrap_or(toolbox));
+        // Definitions prepared for the model, reused in following iterations
+        let mut tools_cache: Option<(String, Arc<[Tool]>)> = None;
+
+        let mut stall_detector = StallDetector::default();
+        // When set, tools are disabled and the model is asked to answer
+        let mut force_answer: Option<String> = None;
+
         for iteration in 0..max_iterations {
             debug!("Agent iteration: {}", iteration);
-            // Create chat request
-            let mut chat_req = ChatRequest::new(self.history.clone());
-            if let Some(toolbox) = toolbox {
-                chat_req = chat_req.with_tools(toolbox.tools_definitions()?);
+            self.emit(AgentEvent::IterationStarted { iteration });
+            if iteration + 1 == max_iterations
+                && self.options.on_iteration_exhausted == OnIterationExhausted::ForceAnswer
+            {
+                force_answer = force_answer
+                    .or_else(|| Some(self.options.prompts.render(PromptKey::FinalAnswer, &[])));
+            }
+            // Model can change during the run, when usage threshold is reached
+            let model = self.current_model(model);
+            let mut overflow_attempt = 0;
+            let (chat_resp, short_circuited) = loop {
+                // Create chat request
+                let mut chat_req = ChatRequest::new(self.request_history(&model));
+                let mut parts = instructions
+                    .iter()
+                    .map(|(title, text)| (*title, text.as_str()))
+                    .collect::<Vec<_>>();
+                if let Some(force) = &force_answer {
+                    parts.push(("Final answer", force));
+                }
+                if !parts.is_empty() {
+                    let system = self.options.prompt_profiles.get(&model).render(&parts);
+                    chat_req = self
+                        .options
+                        .instruction_roles
+                        .get(&model)
+                        .apply(chat_req, system);
+                }
+                if force_answer.is_none() {
+                    let tools = match &tools_cache {
+                        Some((cached_model, tools)) if *cached_model == model => tools.clone(),
+                        _ => {
+                            let (tools, complete) = self.tools_definitions(
+                                toolbox.as_deref().map(|toolbox| toolbox as _),
+                                &model,
+                            )?;
+                            let tools = Arc::<[Tool]>::from(tools);
+                            if complete {
+                                tools_cache = Some((model.clone(), tools.clone()));
+                            }
+                            tools
+                        }
+                    };
+                    if !tools.is_empty() {
+                        // Request owns its tools, it's the only copy made in every iteration
+                        chat_req = chat_req.with_tools(tools.iter().cloned());
+                    }
+                }
+                let result = match &self.options.iteration_hook {
+                    Some(hook) => {
+                        let mut iteration_opts = chat_opts.clone();
+                        hook(iteration, &mut chat_req, &mut iteration_opts);
+                        self.exec_chat_chain(&model, chat_req, &iteration_opts)
+                            .await
+                    }
+                    None => self.exec_chat_chain(&model, chat_req, &chat_opts).await,
+                };
+                match result {
+                    Err(err)
+                        if self
+                            .recover_context_overflow(
+                                &err,
+                                &model,
+                                &mut run_start,
+                                &mut overflow_attempt,
+                            )
+                            .await =>
+                    {
+                        continue
+                    }
+                    result => break result?,
+                }
+            };
+            if short_circuited {
+                // Model was not called, e.g. response was cached
+                self.usage.cached_requests += 1;
+            } else {
+                self.record_usage(&model, &chat_resp.usage);
             }
-            let chat_resp = self
-                .client
-                .exec_chat(model, chat_req, Some(&chat_opts))
-                .await?;
 
             for content in chat_resp.content {
                 match content {
                     MessageContent::Text(text) => {
-                        let mut resp = text;
-                        debug!("Agent Answer: {resp}");
-                        self.history.push(ChatMessage::assistant(resp.clone()));
-                        if is_answer_string {
-                            // TODO: Workaround when choosing String as response type. Because we are
-                            // expecting D: DeserializeOwned then we can't return String directly.
-                            // To workaround this I escape content and later deserialize it using
-                            // serde_json::from_str to correct "struct" (String)
-                            resp = Value::String(resp).to_string();
+                        debug!("Agent Answer: {text}");
+                        self.history.push(ChatMessage::assistant(text.clone()));
+                        self.emit(AgentEvent::Answer {
+                            content: text.clone(),
+                        });
+                        let (output, mut citations, confidence) =
+                            self.parse_answer(text.clone(), &output_format)?;
+                        if let Some((cache, key)) =
+                            self.options.answer_cache.as_ref().zip(answer_key)
+                        {
+                            cache.insert(
+                                key,
+                                CachedAnswer {
+                                    prompt: prompt_text.clone(),
+                                    answer: text.clone(),
+                                    tools: self
+                                        .run_tool_calls
+                                        .iter()
+                                        .map(|record| record.name.clone())
+                                        .collect(),
+                                },
+                            );
                         }
-                        let resp = from_str(&resp)?;
-                        return Ok(resp);
+                        let unsupported_claims = match self.options.citation_verification.clone() {
+                            Some(verification) if !citations.is_empty() => {
+                                self.verify_citations(
+                                    &model,
+                                    &verification,
+                                    toolbox.as_ref(),
+                                    &text,
+                                    &mut citations,
+                                )
+                                .await
+                            }
+                            _ => vec![],
+                        };
+                        return Ok(AgentResponse {
+                            output,
+                            citations,
+                            unsupported_claims,
+                            confidence,
+                            usage: self.usage.since(&usage_at_start),
+                            tool_usage: self.run_tool_calls.iter().collect(),
+                            metadata: self.metadata.clone(),
+                        });
                     }
                     MessageContent::ToolCalls(tools_call) => {
+                        if let Some(action) = self.options.stall_action {
+                            if let Some(reason) = stall_detector.record(&tools_call) {
+                                debug!("Agent stalled, {reason}");
+                                self.emit(AgentEvent::Stalled {
+                                    reason: reason.clone(),
+                                });
+                                match action {
+                                    StallAction::Abort => {
+                                        return Err(AgentError::Stalled(reason).into())
+                                    }
+                                    StallAction::WrapUp => {
+                                        force_answer = Some(
+                                            self.options.prompts.render(PromptKey::WrapUp, &[]),
+                                        )
+                                    }
+                                }
+                            }
+                        }
                         self.history.push(ChatMessage::from(tools_call.clone()));
                         // Go through tool use
                         for tool_request in tools_call {
-                            trace!(
-                                "Tool request: {} with arguments: {}",
-                                tool_request.fn_name,
-                                tool_request.fn_arguments
-                            );
-                            if let Some(tool) = toolbox {
-                                match tool
-                                    .call_tool(tool_request.fn_name, tool_request.fn_arguments)
-                                    .await
-                                {
-                                    Ok(result) => {
-                                        trace!("Tool result: {}", result);
-                                        self.history.push(ChatMessage::from(ToolResponse::new(
-                                            tool_request.call_id.clone(),
-                                            result,
-                                        )));
-                                    }
-                                    Err(err) => {
-                                        // If MCP Server fails we need to redirect this information to model
-                                        // this will allow to react on what happens. Some MCP Servers returns
-                                        // important information as error for Agent
-                                        // TODO: Allow user to configure this behaviour. Depending on MCP
-                                        // server this may contain important information, or this may be
-                                        // indication of unrecoverable failure
-                                        trace!("Error: {}", err);
-                                        self.history.push(ChatMessage::from(ToolResponse::new(
-                                            tool_request.call_id.clone(),
-                                            err.to_string(),
-                                        )));
-                                    }
-                                };
-                            } else {
-                                todo!("No tool found for {}", tool_request.fn_name);
+                            self.handle_tool_call(&model, toolbox.as_ref(), tool_request)
+                                .await?;
+                        }
+                        if std::mem::take(&mut self.compact_requested) {
+                            // Messages of the current run are kept
+                            match self.compact_before(&model, run_start).await {
+                                Ok(0) => {}
+                                Ok(removed) => run_start = run_start - removed + 1,
+                                Err(err) => warn!("Unable to compact history: {err}"),
                             }
                         }
                     }
@@ -231,4 +1042,1279 @@ impl Agent {
             "Unable to get response in {max_iterations} tries"
         )))
     }
+
+    /// Asks the model for JSON answer without schema, using JSON mode of the provider unless
+    /// [StructuredOutputMode::Prompt] is used.
+    ///
+    /// Returns updated options and instruction that has to be added to the request, JSON mode of
+    /// some providers requires asking for JSON in the instructions.
+    fn apply_json_mode(&self, chat_opts: ChatOptions) -> (ChatOptions, String) {
+        let instruction = self.options.prompts.render(PromptKey::OutputJson, &[]);
+        match self.options.structured_output {
+            StructuredOutputMode::Prompt => (chat_opts, instruction),
+            StructuredOutputMode::Native | StructuredOutputMode::Strict => (
+                chat_opts.with_response_format(ChatResponseFormat::JsonMode),
+                instruction,
+            ),
+        }
+    }
+
+    /// Provides schema of the output to the model, according to [StructuredOutputMode].
+    ///
+    /// Returns updated options and instruction that has to be added to the request.
+    fn apply_output_schema(
+        &self,
+        schema: Value,
+        model: &str,
+        chat_opts: ChatOptions,
+    ) -> (ChatOptions, Option<String>) {
+        match self.options.structured_output {
+            StructuredOutputMode::Native => {
+                let schema = self.prepare_schema(schema, model);
+                let chat_opts =
+                    chat_opts.with_response_format(JsonSpec::new("ResponseFormat", schema));
+                (chat_opts, None)
+            }
+            StructuredOutputMode::Strict => {
+                let schema = self.prepare_schema(strict(schema), model);
+                let chat_opts =
+                    chat_opts.with_response_format(JsonSpec::new("ResponseFormat", schema));
+                (chat_opts, None)
+            }
+            StructuredOutputMode::Prompt => {
+                let schema = self.prepare_schema(schema, model);
+                (
+                    chat_opts,
+                    Some(structured::prompt_instruction(
+                        &self.options.prompts,
+                        &schema,
+                    )),
+                )
+            }
+        }
+    }
+
+    /// Deserializes answer of the model, collecting citations and confidence.
+    ///
+    /// Answers that can't be parsed are passed to [ParseErrorHandler], if it is set. When parsing
+    /// fails, [AgentError::InvalidOutput] with the raw text of the answer is returned.
+    fn parse_answer<D>(
+        &self,
+        text: String,
+        format: &OutputFormat,
+    ) -> Result<(D, Vec<Citation>, Option<f32>)>
+    where
+        D: AgentOutput,
+    {
+        let error = match self.parse_answer_text(&text, format) {
+            Ok(answer) => return Ok(answer),
+            // Other errors, e.g. abstaining from the answer, are returned unchanged
+            Err(err) => err.downcast::<serde_json::Error>()?,
+        };
+        if let Some(handler) = &self.options.on_parse_error {
+            if let Some(repaired) = handler(&text, &error) {
+                debug!("Answer replaced by parse error handler");
+                return self.parse_answer_text(&repaired, format);
+            }
+        }
+        Err(AgentError::InvalidOutput { text, error }.into())
+    }
+
+    fn parse_answer_text<D>(
+        &self,
+        text: &str,
+        format: &OutputFormat,
+    ) -> Result<(D, Vec<Citation>, Option<f32>)>
+    where
+        D: AgentOutput,
+    {
+        let (answer, citations) = match self.options.citations {
+            Some(CitationMode::Model) => {
+                let answer: citations::CitedAnswer =
+                    parse::parse_json(text, self.options.output_parsing)?;
+                let citations = citations::cited_by_model(&self.run_tool_calls, &answer.sources);
+                (answer.answer, citations)
+            }
+            mode => {
+                let citations = match mode {
+                    Some(CitationMode::Heuristic) => {
+                        citations::cited_in_answer(&self.run_tool_calls, text)
+                    }
+                    _ => vec![],
+                };
+                if self.options.confidence {
+                    (
+                        parse::parse_json(text, self.options.output_parsing)?,
+                        citations,
+                    )
+                } else {
+                    let answer = match format {
+                        OutputFormat::Text => Value::String(text.to_string()),
+                        OutputFormat::Json { .. } => {
+                            parse::parse_json(text, self.options.output_parsing)?
+                        }
+                    };
+                    return Ok((self.convert_answer(answer)?, citations, None));
+                }
+            }
+        };
+        if self.options.confidence {
+            let (answer, confidence) = confidence::unwrap_envelope(answer)?;
+            Ok((self.convert_answer(answer)?, citations, Some(confidence)))
+        } else {
+            Ok((self.convert_answer(answer)?, citations, None))
+        }
+    }
+
+    /// Applies [PostProcessor]s to the answer and converts it into the output type.
+    fn convert_answer<D>(&self, mut answer: Value) -> Result<D>
+    where
+        D: AgentOutput,
+    {
+        for processor in &self.options.post_processors {
+            processor.process(&mut answer);
+        }
+        Ok(D::from_output(answer)?)
+    }
+
+    /// Returns model used for the next request, taking into account model downgrade.
+    fn current_model(&self, model: &str) -> String {
+        self.downgraded_model.clone().unwrap_or(model.to_string())
+    }
+
+    /// Asks [ApprovalHandler] whether the tool call can be executed, unless it is already granted.
+    ///
+    /// Returns reason of denying the call.
+    fn approve_tool_call(&mut self, tool_call: &ToolCall) -> Result<(), String> {
+        let Some(approval) = &self.options.approval else {
+            return Ok(());
+        };
+        if self.tool_grants.iter().any(|grant| grant.allows(tool_call)) {
+            debug!("Tool call `{}` allowed by grant", tool_call.fn_name);
+            return Ok(());
+        }
+        match approval(tool_call) {
+            Approval::Allow => Ok(()),
+            Approval::AllowAlways(grant) => {
+                debug!("Remembering grant of `{}` tool", grant.tool);
+                self.tool_grants.push(grant);
+                Ok(())
+            }
+            Approval::Deny(reason) => Err(reason),
+        }
+    }
+
+    /// Checks that the tool call is allowed by the list of allowed tools, the sandbox and the
+    /// [ApprovalHandler], `capabilities` are required by the tool.
+    pub(crate) fn check_tool_call(
+        &mut self,
+        tool_call: &ToolCall,
+        capabilities: &[Capability],
+    ) -> Result<(), ToolError> {
+        if !self.is_tool_allowed(&tool_call.fn_name) {
+            return Err(anyhow!("Tool `{}` is not allowed", tool_call.fn_name).into());
+        }
+        if !self.options.sandbox.allows_all(capabilities) {
+            return Err(anyhow!(
+                "Tool `{}` is not allowed in {} sandbox",
+                tool_call.fn_name,
+                self.options.sandbox
+            )
+            .into());
+        }
+        self.approve_tool_call(tool_call).map_err(|reason| {
+            anyhow!("Tool call `{}` was denied: {reason}", tool_call.fn_name).into()
+        })
+    }
+
+    /// Adds usage of the request, switching to cheaper model when threshold is reached.
+    fn record_usage(&mut self, model: &str, usage: &Usage) {
+        self.usage.add(usage);
+        let Some(downgrade) = &self.options.model_downgrade else {
+            return;
+        };
+        if self.downgraded_model.is_none() && downgrade.threshold.is_reached(&self.usage) {
+            debug!("Usage threshold reached, switching to {}", downgrade.model);
+            let event = AgentEvent::ModelDowngraded {
+                from: model.to_string(),
+                to: downgrade.model.clone(),
+                total_tokens: self.usage.total_tokens,
+            };
+            self.downgraded_model = Some(downgrade.model.clone());
+            self.emit(event);
+        }
+    }
+
+    /// Sends request to the model, waiting for a free slot when requests are limited.
+    async fn exec_chat(
+        &self,
+        model: &str,
+        chat_req: ChatRequest,
+        chat_opts: &ChatOptions,
+    ) -> Result<ChatResponse> {
+        Ok(self.exec_chat_chain(model, chat_req, chat_opts).await?.0)
+    }
+
+    /// Sends request through the [ChatMiddleware] chain, see [Agent::exec_chat].
+    ///
+    /// Returns also `true` when the response was provided by a middleware, without calling the model.
+    async fn exec_chat_chain(
+        &self,
+        model: &str,
+        chat_req: ChatRequest,
+        chat_opts: &ChatOptions,
+    ) -> Result<(ChatResponse, bool)> {
+        // Request is executed as a separate task, so it owns everything it needs
+        let chain = self.options.middleware.clone();
+        let session = self.metadata.clone();
+        let client = self.client.clone();
+        let limiter = self.options.request_limiter.clone();
+        let deltas = self.event_handler.clone().filter(|_| self.streaming);
+        let model = model.to_string();
+        let chat_opts = chat_opts.clone();
+        let name = format!("chat {model}");
+        let request = async move {
+            middleware::exec_chain(
+                &chain,
+                &session,
+                &model,
+                chat_req,
+                &chat_opts,
+                |model, chat_req, chat_opts| async move {
+                    let _permit = match &limiter {
+                        Some(limiter) => limiter.acquire().await,
+                        None => None,
+                    };
+                    match &deltas {
+                        Some(handler) => {
+                            stream::exec_chat_streamed(
+                                &client, &model, chat_req, &chat_opts, handler,
+                            )
+                            .await
+                        }
+                        None => Ok(client.exec_chat(&model, chat_req, Some(&chat_opts)).await?),
+                    }
+                },
+            )
+            .await
+        };
+        tasks::run_task(&name, request)
+            .await
+            .map_err(|message| anyhow!("Request to the model panicked: {message}"))?
+    }
+
+    /// Frees context window according to [ContextOverflow], when the request was rejected as too long.
+    ///
+    /// Returns `true` when the request should be retried. `run_start` is the index of the first
+    /// message of the run, it is updated when older messages are removed.
+    async fn recover_context_overflow(
+        &mut self,
+        err: &anyhow::Error,
+        model: &str,
+        run_start: &mut usize,
+        attempt: &mut u32,
+    ) -> bool {
+        let strategy = self.options.context_overflow;
+        if *attempt >= strategy.retries() || !overflow::is_context_overflow(err) {
+            return false;
+        }
+        let Some(range) = overflow::trimmed_range(&self.history, *run_start) else {
+            return false;
+        };
+        *attempt += 1;
+        let removed = self.history.drain(range.clone()).collect::<Vec<_>>();
+        warn!(
+            "Context window exceeded, removing {} older messages ({attempt}/{})",
+            removed.len(),
+            strategy.retries()
+        );
+        let summary = match strategy {
+            ContextOverflow::Summarize(_) => match self.summarize_messages(model, &removed).await {
+                Ok(summary) => Some(summary),
+                Err(err) => {
+                    warn!("Unable to summarize removed messages: {err}");
+                    None
+                }
+            },
+            _ => None,
+        };
+        let summarized = summary.is_some();
+        if let Some(summary) = summary {
+            let summary = self
+                .options
+                .prompts
+                .render(PromptKey::ContextSummary, &[("summary", &summary)]);
+            self.history
+                .insert(range.start, ChatMessage::system(summary));
+        }
+        *run_start = *run_start - removed.len() + usize::from(summarized);
+        self.emit(AgentEvent::ContextTrimmed {
+            removed: removed.len(),
+            summarized,
+        });
+        true
+    }
+
+    /// Summarizes messages removed from the history, with the utility model or `model`.
+    async fn summarize_messages(&self, model: &str, messages: &[ChatMessage]) -> Result<String> {
+        let model = self.options.utility_model.as_deref().unwrap_or(model);
+        let chat_req = ChatRequest::new(vec![
+            ChatMessage::system(self.options.prompts.render(PromptKey::SessionSummary, &[])),
+            ChatMessage::user(session::transcript_of(messages)),
+        ]);
+        let chat_resp = self
+            .exec_chat(
+                model,
+                chat_req,
+                &ChatOptions::default().with_temperature(0.2),
+            )
+            .await?;
+        chat_resp
+            .content
+            .into_iter()
+            .find_map(|content| match content {
+                MessageContent::Text(text) => Some(text.trim().to_string()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Model didn't return text response"))
+    }
+
+    /// Handles failure of the toolbox to provide definitions according to [ToolDefinitionsFailure].
+    ///
+    /// Returns `true` when fetching should be retried, `false` when the run continues without tools.
+    fn on_definitions_failure(&self, err: ToolError, attempt: &mut u32) -> Result<bool> {
+        match self.options.tool_definitions_failure {
+            ToolDefinitionsFailure::Retry(retries) if *attempt < retries => {
+                *attempt += 1;
+                warn!("Unable to get tools definitions ({err}), retrying ({attempt}/{retries})");
+                Ok(true)
+            }
+            ToolDefinitionsFailure::ContinueWithoutTools => {
+                warn!("Unable to get tools definitions ({err}), continuing without tools");
+                Ok(false)
+            }
+            _ => Err(err.into()),
+        }
+    }
+
+    /// Returns history sent to the model, see [ImageResend] and [AgentBuilder::with_repeated_result_compaction].
+    ///
+    /// System prompt composed with [SystemPrompt] is rendered with [PromptProfile] of the model.
+    fn request_history(&mut self, model: &str) -> Vec<ChatMessage> {
+        let policy = history::HistoryPolicy {
+            images: self.options.image_resend,
+            batching: self.options.tool_result_batching.get(model),
+            repeated_result_len: self.options.repeated_result_len,
+            prompts: &self.options.prompts,
+        };
+        let mut history = history::prepare_history(&self.history, &policy, &mut self.sent_images);
+        let profile = self.options.prompt_profiles.get(model);
+        if let (Some(prompt), Some(first)) = (&self.options.system_prompt, history.first_mut()) {
+            // System message could be replaced since the agent was built
+            let is_prompt = matches!(first.role, ChatRole::System)
+                && first.content.text_as_str() == Some(prompt.to_string().as_str());
+            if is_prompt && profile != PromptProfile::Plain {
+                *first = ChatMessage::system(prompt.render(profile));
+            }
+        }
+        history
+    }
+
+    /// Refreshes definitions of dynamic toolboxes, see [ToolBox::refresh_definitions].
+    ///
+    /// Changes of tools since the previous run with the same toolbox are reported with
+    /// [AgentEvent::ToolsChanged], see [crate::tool::diff].
+    async fn refresh_tools_definitions(&mut self, toolbox: &SharedToolBox) -> Result<()> {
+        let mut attempt = 0;
+        while let Err(err) = toolbox.refresh_definitions().await {
+            if !self.on_definitions_failure(err, &mut attempt)? {
+                return Ok(());
+            }
+        }
+        let Ok(tools) = toolbox.shared_tools_definitions() else {
+            return Ok(());
+        };
+        // Tools are compared only with tools of the same toolbox
+        let id = Arc::as_ptr(toolbox) as *const () as usize;
+        let previous = self.known_tools.replace((id, tools.clone()));
+        let Some((_, previous)) = previous.filter(|(previous_id, _)| *previous_id == id) else {
+            return Ok(());
+        };
+        let diff = ToolsDiff::between(&previous, &tools);
+        if diff.is_empty() {
+            return Ok(());
+        }
+        debug!("Tools of the toolbox changed: {diff:?}");
+        if self.options.tools_change_notes {
+            let list = |names: &[String]| match names.is_empty() {
+                true => "none".to_string(),
+                false => names.join(", "),
+            };
+            let note = self.options.prompts.render(
+                PromptKey::ToolsChanged,
+                &[
+                    ("added", &list(&diff.added)),
+                    ("removed", &list(&diff.removed)),
+                    ("changed", &list(&diff.changed)),
+                ],
+            );
+            self.history.push(ChatMessage::system(note));
+        }
+        self.emit(AgentEvent::ToolsChanged {
+            added: diff.added,
+            removed: diff.removed,
+            changed: diff.changed,
+        });
+        Ok(())
+    }
+
+    /// Returns definitions of tools of the toolbox, `None` when they are not available and the
+    /// run continues without them.
+    fn fetch_tools_definitions(&self, toolbox: &dyn ToolBox) -> Result<Option<Arc<[Tool]>>> {
+        let mut attempt = 0;
+        loop {
+            match toolbox.shared_tools_definitions() {
+                Ok(tools) => return Ok(Some(tools)),
+                Err(err) => {
+                    if !self.on_definitions_failure(err, &mut attempt)? {
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns definitions of tools that will be sent to the model.
+    ///
+    /// Returns also `false` when definitions of the toolbox were not available, so they shouldn't
+    /// be reused in the next iteration.
+    fn tools_definitions(
+        &self,
+        toolbox: Option<&dyn ToolBox>,
+        model: &str,
+    ) -> Result<(Vec<Tool>, bool)> {
+        let mut tools = vec![];
+        let mut complete = true;
+        if let Some(toolbox) = toolbox {
+            match self.fetch_tools_definitions(toolbox)? {
+                Some(definitions) => {
+                    // Only allowed tools are cloned, they are prepared for the model below
+                    let sandbox = self.options.sandbox;
+                    tools = definitions
+                        .iter()
+                        .filter(|tool| {
+                            self.is_tool_allowed(&tool.name)
+                                && sandbox.allows_all(&toolbox.tool_capabilities(&tool.name))
+                        })
+                        .cloned()
+                        .collect();
+                }
+                None => complete = false,
+            }
+        }
+        if !self.options.handoffs.is_empty() && self.is_tool_allowed(TRANSFER_TO_TOOL) {
+            tools.push(handoff::transfer_to_tool(&self.options.handoffs));
+        }
+        if self.options.deterministic_seed.is_some() {
+            // Order of tools changes the prompt, keep it stable
+            tools.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        for tool in tools.iter_mut() {
+            tool.schema = tool
+                .schema
+                .take()
+                .map(|schema| self.prepare_schema(schema, model));
+        }
+        if let Some(ToolResultLimit {
+            strategy: OversizedResult::Store,
+            ..
+        }) = self.options.tool_result_limit
+        {
+            let mut tool = results::read_artifact_tool();
+            tool.schema = tool
+                .schema
+                .take()
+                .map(|schema| self.prepare_schema(schema, model));
+            tools.push(tool);
+        }
+        if self.options.compact_tool && !tools.iter().any(|t| t.name == COMPACT_HISTORY_TOOL) {
+            tools.push(compact::compact_history_tool());
+        }
+        Ok((tools, complete))
+    }
+
+    /// Checks whether the model can compact the history, with the built-in tool or the tool of
+    /// [AgentSelfToolBox](crate::tool::agent_self::AgentSelfToolBox).
+    fn is_compact_tool_exposed(&self, toolbox: Option<&SharedToolBox>) -> bool {
+        self.options.compact_tool
+            || toolbox.is_some_and(|toolbox| {
+                toolbox
+                    .shared_tools_definitions()
+                    .is_ok_and(|tools| tools.iter().any(|tool| tool.name == COMPACT_HISTORY_TOOL))
+            })
+    }
+
+    /// Checks whether the tool is on the list of allowed tools, when the list is set.
+    pub(crate) fn is_tool_allowed(&self, name: &str) -> bool {
+        self.options
+            .allowed_tools
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|tool| tool == name))
+    }
+
+    /// Adjusts schema to the provider of the model, and makes it stable in deterministic mode.
+    fn prepare_schema(&self, schema: Value, model: &str) -> Value {
+        let mut schema = schema;
+        if !self.options.raw_schemas {
+            // Models served by custom endpoints are usually available through OpenAI compatible API
+            let adapter_kind = AdapterKind::from_model(model).unwrap_or(AdapterKind::OpenAI);
+            schema = sanitize(schema, adapter_kind);
+        }
+        if self.options.deterministic_seed.is_some() {
+            schema = canonicalize(schema);
+        }
+        schema
+    }
+
+    /// Executes tool requested by the model and stores its result in history.
+    ///
+    /// Errors of the tool are sent to the model, except [ToolError::Unauthorized] that the model
+    /// can't fix and [ToolError::Panicked] when [ToolPanic::Fail] is set. They are returned after
+    /// storing the result, so the history stays consistent.
+    async fn handle_tool_call(
+        &mut self,
+        model: &str,
+        toolbox: Option<&SharedToolBox>,
+        tool_request: ToolCall,
+    ) -> Result<()> {
+        trace!(
+            "Tool request: {} with arguments: {}",
+            tool_request.fn_name,
+            tool_request.fn_arguments
+        );
+        self.emit(AgentEvent::ToolCall {
+            call_id: tool_request.call_id.clone(),
+            name: tool_request.fn_name.clone(),
+            arguments: tool_request.fn_arguments.clone(),
+        });
+
+        let store_limit = match &self.options.tool_result_limit {
+            Some(ToolResultLimit {
+                max_len,
+                strategy: OversizedResult::Store,
+            }) => Some(*max_len),
+            _ => None,
+        };
+
+        let started = std::time::Instant::now();
+        let result = if let Some(max_len) =
+            store_limit.filter(|_| tool_request.fn_name == READ_ARTIFACT_TOOL)
+        {
+            results::read_artifact(&self.artifacts, tool_request.fn_arguments.clone(), max_len)
+        } else if let Some(dry_run) = &self.options.dry_run {
+            let result = dry_run.result(&tool_request);
+            self.planned_tool_calls.push(tool_request.clone());
+            Ok(result)
+        } else if let Some(result) = dedup::reused_result(
+            self.options.duplicate_tool_calls,
+            &self.run_tool_calls,
+            &tool_request,
+            &self.options.prompts,
+        ) {
+            debug!(
+                "Tool call `{}` repeated, reusing result",
+                tool_request.fn_name
+            );
+            Ok(result)
+        } else if tool_request.fn_name == COMPACT_HISTORY_TOOL
+            && self.is_compact_tool_exposed(toolbox)
+        {
+            // History can't be changed while results of the iteration are being stored
+            self.compact_requested = true;
+            Ok("Older part of the conversation will be replaced with its summary.".to_string())
+        } else if tool_request.fn_name == TRANSFER_TO_TOOL && !self.options.handoffs.is_empty() {
+            // Target agent uses its own tools and sandbox
+            match self.check_tool_call(&tool_request, &[]) {
+                Ok(()) => {
+                    let handoffs = self.options.handoffs.clone();
+                    handoff::transfer(&handoffs, tool_request.fn_arguments.clone())
+                        .await
+                        .map(|(answer, usage)| {
+                            // Tokens used by the target agent are counted as used by this one
+                            self.usage.merge(&usage);
+                            answer
+                        })
+                }
+                Err(err) => Err(err),
+            }
+        } else if let Some(tool) = toolbox {
+            // Model can request tool that was not exposed to it
+            let capabilities = tool.tool_capabilities(&tool_request.fn_name);
+            match self.check_tool_call(&tool_request, &capabilities) {
+                Ok(()) => {
+                    let tool = tool.clone();
+                    let name = tool_request.fn_name.clone();
+                    let arguments = tool_request.fn_arguments.clone();
+                    tasks::run_task(
+                        &format!("tool {} ({})", tool_request.fn_name, tool_request.call_id),
+                        async move { tool.call_tool(name, arguments).await },
+                    )
+                    .await
+                    .unwrap_or_else(|message| Err(ToolError::Panicked { message }))
+                }
+                Err(err) => Err(err),
+            }
+        } else {
+            Err(ToolError::NoToolFound(tool_request.fn_name.clone()))
+        };
+
+        let latency = started.elapsed();
+        let mut fatal_error = None;
+        let (content, is_error) = match result {
+            Ok(result) => {
+                trace!("Tool result: {}", result);
+                let adapter_kind = AdapterKind::from_model(model).ok();
+                match self
+                    .options
+                    .result_formats
+                    .get(&tool_request.fn_name, adapter_kind)
+                {
+                    Some(format) => (format.apply(&result), false),
+                    None => (result, false),
+                }
+            }
+            Err(err) => {
+                // If MCP Server fails we need to redirect this information to model
+                // this will allow to react on what happens. Some MCP Servers returns
+                // important information as error for Agent
+                // TODO: Allow user to configure this behaviour. Depending on MCP
+                // server this may contain important information, or this may be
+                // indication of unrecoverable failure
+                trace!("Error: {}", err);
+                let content = err.to_string();
+                let fatal = match err {
+                    ToolError::Unauthorized(_) => true,
+                    ToolError::Panicked { .. } => self.options.tool_panic == ToolPanic::Fail,
+                    _ => false,
+                };
+                if fatal {
+                    fatal_error = Some(err);
+                }
+                (content, true)
+            }
+        };
+        let bytes = content.len();
+        let content = self.limit_tool_result(content).await;
+        let record = ToolCallRecord {
+            call_id: tool_request.call_id.clone(),
+            name: tool_request.fn_name.clone(),
+            arguments: tool_request.fn_arguments.clone(),
+            content: content.clone(),
+            is_error,
+            latency,
+            bytes,
+        };
+        self.tool_usage.record(&record);
+        self.run_tool_calls.push(record);
+        self.emit(AgentEvent::ToolResult {
+            call_id: tool_request.call_id.clone(),
+            name: tool_request.fn_name,
+            content: content.clone(),
+            is_error,
+        });
+        let content = match self.options.citations {
+            Some(CitationMode::Model) => citations::label_source(&tool_request.call_id, &content),
+            _ => content,
+        };
+        self.history.push(ChatMessage::from(ToolResponse::new(
+            tool_request.call_id,
+            content,
+        )));
+        match fatal_error {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies [ToolResultLimit] to the tool result.
+    async fn limit_tool_result(&mut self, content: String) -> String {
+        let Some(limit) = self.options.tool_result_limit.clone() else {
+            return content;
+        };
+        if content.chars().count() <= limit.max_len {
+            return content;
+        }
+        match limit.strategy {
+            OversizedResult::Truncate => {
+                results::truncate(&self.options.prompts, &content, limit.max_len)
+            }
+            OversizedResult::Summarize { model } => {
+                match self
+                    .summarize_tool_result(&model, &content, limit.max_len)
+                    .await
+                {
+                    Ok(summary) => {
+                        results::truncate(&self.options.prompts, &summary, limit.max_len)
+                    }
+                    Err(err) => {
+                        debug!("Unable to summarize tool result: {err}");
+                        results::truncate(&self.options.prompts, &content, limit.max_len)
+                    }
+                }
+            }
+            OversizedResult::Store => results::store(
+                &self.options.prompts,
+                &mut self.artifacts,
+                content,
+                limit.max_len,
+            ),
+        }
+    }
+
+    /// Summarizes oversized tool result using separate request to the model.
+    async fn summarize_tool_result(
+        &self,
+        model: &str,
+        content: &str,
+        max_len: usize,
+    ) -> Result<String> {
+        let task = self
+            .history
+            .iter()
+            .rev()
+            .find(|message| matches!(message.role, ChatRole::User))
+            .and_then(|message| message.content.text_as_str())
+            .unwrap_or_default();
+        let chat_req = ChatRequest::new(vec![
+            ChatMessage::system(self.options.prompts.render(
+                PromptKey::ResultSummary,
+                &[("max_len", &max_len.to_string()), ("task", task)],
+            )),
+            ChatMessage::user(content),
+        ]);
+        let chat_resp = self
+            .exec_chat(
+                model,
+                chat_req,
+                &ChatOptions::default().with_temperature(0.0),
+            )
+            .await?;
+        chat_resp
+            .content
+            .into_iter()
+            .find_map(|content| match content {
+                MessageContent::Text(text) => Some(text),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Summary was not returned"))
+    }
+}
+
+/// Creates GenAI client, that connects to OpenAI API compatible endpoint.
+fn client_with_url(base_url: &str, api_key: &str) -> Client {
+    let endpoint = Endpoint::from_owned(Arc::from(base_url));
+    let auth = AuthData::from_single(api_key);
+    let target_resolver = ServiceTargetResolver::from_resolver_fn(
+        |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
+            let ServiceTarget { model, .. } = service_target;
+            let model = ModelIden::new(AdapterKind::OpenAI, model.model_name);
+            Ok(ServiceTarget {
+                endpoint,
+                auth,
+                model,
+            })
+        },
+    );
+    ClientBuilder::default()
+        .with_service_target_resolver(target_resolver)
+        .with_reqwest(crate::http::shared_client())
+        .build()
+}
+
+/// Creates GenAI client using connections of the [shared client](crate::http::shared_client).
+pub(crate) fn default_client() -> Client {
+    ClientBuilder::default()
+        .with_reqwest(crate::http::shared_client())
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{text, tool_call, ScriptedModel};
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Toolbox failing to provide definitions the given number of times
+    struct Flaky {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl ToolBox for Flaky {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(ToolError::Other(anyhow!("disconnected")));
+            }
+            Ok(vec![Tool::new("echo")])
+        }
+
+        async fn call_tool(
+            &self,
+            _tool_name: String,
+            _arguments: Value,
+        ) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+    }
+
+    /// Toolbox panicking in every call
+    struct Panicking;
+
+    #[async_trait::async_trait]
+    impl ToolBox for Panicking {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Ok(vec![Tool::new("broken")])
+        }
+
+        async fn call_tool(
+            &self,
+            _tool_name: String,
+            _arguments: Value,
+        ) -> Result<String, ToolError> {
+            panic!("index out of bounds")
+        }
+    }
+
+    #[test]
+    fn test_tool_definitions_failure() {
+        let flaky = |failures| Flaky {
+            failures,
+            calls: AtomicU32::new(0),
+        };
+        let agent = |behaviour| {
+            Agent::builder()
+                .with_tool_definitions_failure(behaviour)
+                .build()
+        };
+
+        let tools = agent(ToolDefinitionsFailure::Retry(2))
+            .tools_definitions(Some(&flaky(2)), "gpt-4o")
+            .unwrap();
+        assert_eq!((tools.0.len(), tools.1), (1, true));
+        assert!(agent(ToolDefinitionsFailure::Retry(2))
+            .tools_definitions(Some(&flaky(3)), "gpt-4o")
+            .is_err());
+        let tools = agent(ToolDefinitionsFailure::ContinueWithoutTools)
+            .tools_definitions(Some(&flaky(1)), "gpt-4o")
+            .unwrap();
+        assert_eq!((tools.0.len(), tools.1), (0, false));
+        assert!(agent(ToolDefinitionsFailure::Fail)
+            .tools_definitions(Some(&flaky(1)), "gpt-4o")
+            .is_err());
+    }
+
+    #[test]
+    fn test_run_can_be_spawned() {
+        fn assert_spawnable<T: Send + 'static>(_: &T) {}
+        let mut agent = Agent::new("You are helpful assistant.");
+        let toolbox: SharedToolBox = Arc::new(Flaky {
+            failures: 0,
+            calls: AtomicU32::new(0),
+        });
+        let run = async move {
+            agent
+                .run::<String>("gpt-4o", "Hello", Some(toolbox), None, None)
+                .await
+        };
+        assert_spawnable(&run);
+    }
+
+    #[tokio::test]
+    async fn test_tool_panic() {
+        let toolbox: SharedToolBox = Arc::new(Panicking);
+        let tool_call = ToolCall {
+            call_id: "call_1".to_string(),
+            fn_name: "broken".to_string(),
+            fn_arguments: Value::Null,
+        };
+        let mut agent = Agent::new("You are helpful assistant.");
+        agent
+            .handle_tool_call("gpt-4o", Some(&toolbox), tool_call.clone())
+            .await
+            .unwrap();
+        assert!(matches!(
+            &agent.history().last().unwrap().content,
+            MessageContent::ToolResponses(responses)
+                if responses[0].content == "Tool panicked: index out of bounds"
+        ));
+
+        let mut agent = Agent::builder().with_tool_panic(ToolPanic::Fail).build();
+        let err = agent
+            .handle_tool_call("gpt-4o", Some(&toolbox), tool_call)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ToolError>(),
+            Some(ToolError::Panicked { .. })
+        ));
+
+        // Without toolbox the error goes back to the model
+        let tool_call = ToolCall {
+            call_id: "call_2".to_string(),
+            fn_name: "missing".to_string(),
+            fn_arguments: Value::Null,
+        };
+        agent
+            .handle_tool_call("gpt-4o", None, tool_call)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &agent.history().last().unwrap().content,
+            MessageContent::ToolResponses(responses)
+                if responses[0].content == "Tool named 'missing' not found"
+        ));
+    }
+
+    /// Middleware rejecting every request as too long
+    struct ContextFull;
+
+    #[async_trait::async_trait]
+    impl ChatMiddleware for ContextFull {
+        async fn before_request(
+            &self,
+            _context: &mut RequestContext,
+            _request: &mut ChatRequest,
+            _options: &mut ChatOptions,
+        ) -> Result<Option<ChatResponse>> {
+            Err(anyhow!(
+                "This model's maximum context length is 8192 tokens"
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow() {
+        let trimmed = Arc::new(AtomicU32::new(0));
+        let counter = trimmed.clone();
+        let mut agent = Agent::builder()
+            .with_system("You are helpful assistant.")
+            .with_middleware(ContextFull)
+            .with_context_overflow(ContextOverflow::Trim(1))
+            .with_event_handler(move |event: &AgentEvent| {
+                if matches!(event, AgentEvent::ContextTrimmed { .. }) {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build();
+        for text in ["Hi", "Hello!", "Weather?", "Sunny"] {
+            agent.push_message(ChatMessage::user(text));
+        }
+        let err = agent
+            .run::<String>("gpt-4o", "Tomorrow?", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(overflow::is_context_overflow(&err));
+        assert_eq!(trimmed.load(Ordering::SeqCst), 1);
+        let texts = agent
+            .history()
+            .iter()
+            .filter_map(|message| message.content.text_as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "You are helpful assistant.",
+                "Weather?",
+                "Sunny",
+                "Tomorrow?"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let agent = Agent::new("");
+        let err = agent
+            .parse_answer::<u32>("The answer is forty-two".to_string(), &u32::output_format())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AgentError>(),
+            Some(AgentError::InvalidOutput { text, .. }) if text == "The answer is forty-two"
+        ));
+
+        let agent = Agent::builder()
+            .with_on_parse_error(|text, _| text.contains("forty-two").then(|| "42".to_string()))
+            .build();
+        let (answer, _, _) = agent
+            .parse_answer::<u32>("The answer is forty-two".to_string(), &u32::output_format())
+            .unwrap();
+        assert_eq!(answer, 42);
+    }
+
+    /// Returns content of the tool results sent in the request
+    fn tool_results(request: &ChatRequest) -> Vec<String> {
+        request
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::ToolResponses(responses) => Some(responses),
+                _ => None,
+            })
+            .flatten()
+            .map(|response| response.content.clone())
+            .collect()
+    }
+
+    /// Returns system instruction and texts of the messages of the request
+    fn request_texts(request: &ChatRequest) -> Vec<&str> {
+        request
+            .system
+            .as_deref()
+            .into_iter()
+            .chain(
+                request
+                    .messages
+                    .iter()
+                    .filter_map(|message| message.content.text_as_str()),
+            )
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_skips_tools() {
+        let toolbox: SharedToolBox = Arc::new(Panicking);
+        for (dry_run, expected) in [
+            (
+                DryRun::Skip,
+                "Tool `broken` was not executed (dry run). Assume it succeeded and continue.",
+            ),
+            (
+                DryRun::simulate(|call| format!("Simulated {}", call.fn_arguments)),
+                "Simulated {\"path\":\"/tmp\"}",
+            ),
+        ] {
+            let model = ScriptedModel::new([
+                tool_call("call_1", "broken", json!({"path": "/tmp"})),
+                text("Deleted"),
+            ]);
+            let mut agent = Agent::builder()
+                .with_middleware(model.clone())
+                .with_dry_run(dry_run)
+                .build();
+            let answer = agent
+                .run::<String>("gpt-4o", "Clean /tmp", Some(toolbox.clone()), None, None)
+                .await
+                .unwrap();
+            assert_eq!(answer, "Deleted");
+            assert_eq!(agent.planned_tool_calls().len(), 1);
+            assert_eq!(agent.planned_tool_calls()[0].fn_name, "broken");
+            let requests = model.requests();
+            assert_eq!(requests.len(), 2);
+            assert_eq!(tool_results(&requests[1].request), [expected]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_planned_tool_calls_reset_between_runs() {
+        let model = ScriptedModel::new([
+            tool_call("call_1", "broken", json!({})),
+            text("Done"),
+            text("Nothing to do"),
+        ]);
+        let toolbox: SharedToolBox = Arc::new(Panicking);
+        let mut agent = Agent::builder()
+            .with_middleware(model)
+            .with_dry_run(DryRun::Skip)
+            .build();
+        agent
+            .run::<String>("gpt-4o", "Clean", Some(toolbox.clone()), None, None)
+            .await
+            .unwrap();
+        assert_eq!(agent.planned_tool_calls().len(), 1);
+        agent
+            .run::<String>("gpt-4o", "Again", Some(toolbox), None, None)
+            .await
+            .unwrap();
+        assert!(agent.planned_tool_calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_model_downgrade_after_threshold() {
+        let downgrades = Arc::new(AtomicU32::new(0));
+        let counter = downgrades.clone();
+        let model = ScriptedModel::new([text("Hi"), text("Hi again")]);
+        let mut agent = Agent::builder()
+            .with_middleware(model.clone())
+            .with_model_downgrade(ModelDowngrade::new(
+                UsageThreshold::Tokens(1000),
+                "gpt-4o-mini",
+            ))
+            .with_event_handler(move |event: &AgentEvent| {
+                if matches!(event, AgentEvent::ModelDowngraded { to, .. } if to == "gpt-4o-mini") {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build();
+        let usage = Usage {
+            total_tokens: Some(600),
+            ..Default::default()
+        };
+        agent.record_usage("gpt-4o", &usage);
+        agent
+            .run::<String>("gpt-4o", "Hello", None, None, None)
+            .await
+            .unwrap();
+        agent.record_usage("gpt-4o", &usage);
+        agent.record_usage("gpt-4o", &usage);
+        agent
+            .run::<String>("gpt-4o", "Hello", None, None, None)
+            .await
+            .unwrap();
+        let models = model
+            .requests()
+            .into_iter()
+            .map(|request| request.model)
+            .collect::<Vec<_>>();
+        assert_eq!(models, ["gpt-4o", "gpt-4o-mini"]);
+        // Event is emitted only when switching
+        assert_eq!(downgrades.load(Ordering::SeqCst), 1);
+
+        agent.reset_usage();
+        assert_eq!(agent.current_model("gpt-4o"), "gpt-4o");
+    }
+
+    #[tokio::test]
+    async fn test_force_answer_in_last_iteration() {
+        let toolbox: SharedToolBox = Arc::new(Flaky {
+            failures: 0,
+            calls: AtomicU32::new(0),
+        });
+        let script = || {
+            ScriptedModel::new([
+                tool_call("call_1", "echo", json!({})),
+                tool_call("call_2", "echo", json!({})),
+                text("Best guess"),
+            ])
+        };
+
+        let model = script();
+        let mut agent = Agent::builder()
+            .with_middleware(model.clone())
+            .with_on_iteration_exhausted(OnIterationExhausted::ForceAnswer)
+            .build();
+        let answer = agent
+            .run::<String>("gpt-4o", "Research", Some(toolbox.clone()), Some(3), None)
+            .await
+            .unwrap();
+        assert_eq!(answer, "Best guess");
+        let requests = model.requests();
+        assert!(requests[1].request.tools.is_some());
+        assert!(requests[2].request.tools.is_none());
+        let final_answer = Prompts::default().render(PromptKey::FinalAnswer, &[]);
+        let mentions_final_answer = |request: &ChatRequest| {
+            request_texts(request)
+                .iter()
+                .any(|text| text.contains(&final_answer))
+        };
+        assert!(!mentions_final_answer(&requests[1].request));
+        assert!(mentions_final_answer(&requests[2].request));
+
+        // By default the model is not asked to answer
+        let model = script();
+        let mut agent = Agent::builder().with_middleware(model.clone()).build();
+        let err = agent
+            .run::<String>("gpt-4o", "Research", Some(toolbox), Some(2), None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unable to get response in 2 tries");
+        assert!(model.requests()[1].request.tools.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_iteration_hook_changes_single_request() {
+        let toolbox: SharedToolBox = Arc::new(Flaky {
+            failures: 0,
+            calls: AtomicU32::new(0),
+        });
+        let model = ScriptedModel::new([tool_call("call_1", "echo", json!({})), text("Done")]);
+        let mut agent = Agent::builder()
+            .with_middleware(model.clone())
+            .with_iteration_hook(|iteration, request, options| {
+                if iteration == 0 {
+                    request
+                        .messages
+                        .push(ChatMessage::user("Use the echo tool first."));
+                } else {
+                    request.tools = None;
+                    *options = options.clone().with_temperature(0.7);
+                }
+            })
+            .build();
+        agent
+            .run::<String>("gpt-4o", "Hello", Some(toolbox), None, None)
+            .await
+            .unwrap();
+
+        let requests = model.requests();
+        let steered = |request: &ChatRequest| {
+            request
+                .messages
+                .iter()
+                .any(|message| message.content.text_as_str() == Some("Use the echo tool first."))
+        };
+        assert!(steered(&requests[0].request));
+        assert!(requests[0].request.tools.is_some());
+        assert_eq!(requests[0].options.temperature, Some(0.2));
+        assert!(!steered(&requests[1].request));
+        assert!(requests[1].request.tools.is_none());
+        assert_eq!(requests[1].options.temperature, Some(0.7));
+        // Steering message is not stored in the history
+        assert!(!agent
+            .history()
+            .iter()
+            .any(|message| message.content.text_as_str() == Some("Use the echo tool first.")));
+    }
+
+    #[tokio::test]
+    async fn test_untyped_json_uses_json_mode() {
+        let model = ScriptedModel::new([text(r#"{"city": "Paris", "days": 3}"#), text("Paris")]);
+        let mut agent = Agent::builder().with_middleware(model.clone()).build();
+        let answer = agent
+            .run::<Value>("gpt-4o", "Plan a trip", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(answer, json!({"city": "Paris", "days": 3}));
+        let err = agent
+            .run::<Value>("gpt-4o", "Plan a trip", None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AgentError>(),
+            Some(AgentError::InvalidOutput { text, .. }) if text == "Paris"
+        ));
+        let requests = model.requests();
+        assert!(matches!(
+            requests[0].options.response_format,
+            Some(ChatResponseFormat::JsonMode)
+        ));
+
+        // Without native structured output the model is only instructed to answer with JSON
+        let model = ScriptedModel::new([text(r#"["Paris"]"#)]);
+        let mut agent = Agent::builder()
+            .with_middleware(model.clone())
+            .with_structured_output(StructuredOutputMode::Prompt)
+            .build();
+        let answer = agent
+            .run::<Value>("gpt-4o", "Plan a trip", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(answer, json!(["Paris"]));
+        let request = &model.requests()[0];
+        assert!(request.options.response_format.is_none());
+        let instruction = Prompts::default().render(PromptKey::OutputJson, &[]);
+        assert!(request_texts(&request.request)
+            .iter()
+            .any(|text| text.contains(&instruction)));
+    }
 }
@@ -0,0 +1,135 @@
+use crate::agent::response::ToolCallRecord;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Statistics of calls of a single tool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ToolStats {
+    /// Number of calls requested by the model
+    pub calls: u64,
+    /// Number of calls that returned result
+    pub successes: u64,
+    /// Number of calls that returned error
+    pub failures: u64,
+    /// Time spent executing the tool
+    pub total_latency: Duration,
+    /// Size of results returned by the tool, before applying [ToolResultLimit](crate::agent::ToolResultLimit)
+    pub bytes_returned: u64,
+}
+
+impl ToolStats {
+    /// Returns average time of a single call.
+    pub fn average_latency(&self) -> Duration {
+        match u32::try_from(self.calls) {
+            Ok(calls) if calls > 0 => self.total_latency / calls,
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Returns fraction of calls that failed, between 0 and 1.
+    pub fn failure_rate(&self) -> f64 {
+        if self.calls == 0 {
+            0.0
+        } else {
+            self.failures as f64 / self.calls as f64
+        }
+    }
+}
+
+/// Usage of tools, grouped by the tool name.
+///
+/// Available for a single run in [AgentResponse::tool_usage](crate::agent::AgentResponse::tool_usage)
+/// and aggregated for all runs in [Agent::tool_usage](crate::agent::Agent::tool_usage).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ToolUsage {
+    /// Statistics of every tool called by the model
+    pub tools: BTreeMap<String, ToolStats>,
+}
+
+impl ToolUsage {
+    /// Returns statistics of the tool, `None` when it was never called.
+    pub fn get(&self, tool: &str) -> Option<&ToolStats> {
+        self.tools.get(tool)
+    }
+
+    /// Returns number of calls of all tools.
+    pub fn total_calls(&self) -> u64 {
+        self.tools.values().map(|stats| stats.calls).sum()
+    }
+
+    /// Returns number of failed calls of all tools.
+    pub fn total_failures(&self) -> u64 {
+        self.tools.values().map(|stats| stats.failures).sum()
+    }
+
+    /// Adds statistics from other usage, e.g. to aggregate usage of many agents.
+    pub fn merge(&mut self, other: &ToolUsage) {
+        for (name, other) in &other.tools {
+            let stats = self.tools.entry(name.clone()).or_default();
+            stats.calls += other.calls;
+            stats.successes += other.successes;
+            stats.failures += other.failures;
+            stats.total_latency += other.total_latency;
+            stats.bytes_returned += other.bytes_returned;
+        }
+    }
+
+    pub(crate) fn record(&mut self, record: &ToolCallRecord) {
+        let stats = self.tools.entry(record.name.clone()).or_default();
+        stats.calls += 1;
+        if record.is_error {
+            stats.failures += 1;
+        } else {
+            stats.successes += 1;
+        }
+        stats.total_latency += record.latency;
+        stats.bytes_returned += record.bytes as u64;
+    }
+}
+
+impl<'a> FromIterator<&'a ToolCallRecord> for ToolUsage {
+    fn from_iter<I: IntoIterator<Item = &'a ToolCallRecord>>(records: I) -> Self {
+        let mut usage = ToolUsage::default();
+        for record in records {
+            usage.record(record);
+        }
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, is_error: bool, millis: u64) -> ToolCallRecord {
+        ToolCallRecord {
+            call_id: "call".to_string(),
+            name: name.to_string(),
+            arguments: serde_json::Value::Null,
+            content: "result".to_string(),
+            is_error,
+            latency: Duration::from_millis(millis),
+            bytes: 6,
+        }
+    }
+
+    #[test]
+    fn test_tool_usage() {
+        let records = [
+            record("search", false, 100),
+            record("search", true, 300),
+            record("fetch", false, 50),
+        ];
+        let mut usage: ToolUsage = records.iter().collect();
+        assert_eq!(usage.total_calls(), 3);
+        assert_eq!(usage.total_failures(), 1);
+        let search = usage.get("search").unwrap();
+        assert_eq!(search.average_latency(), Duration::from_millis(200));
+        assert_eq!(search.failure_rate(), 0.5);
+        assert_eq!(search.bytes_returned, 12);
+
+        usage.merge(&records[2..].iter().collect());
+        assert_eq!(usage.get("fetch").unwrap().calls, 2);
+    }
+}
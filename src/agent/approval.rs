@@ -0,0 +1,134 @@
+use genai::chat::ToolCall;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Callback deciding whether the tool call can be executed, see [AgentBuilder::with_approval](crate::agent::AgentBuilder::with_approval).
+pub type ApprovalHandler = Arc<dyn Fn(&ToolCall) -> Approval + Send + Sync>;
+
+/// Decision of the [ApprovalHandler] about the tool call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Approval {
+    /// Tool call is executed.
+    Allow,
+    /// Tool call is executed, and the grant is remembered, so matching calls are executed without
+    /// asking again (e.g. "always allow `read_file` under `/docs`").
+    AllowAlways(ToolGrant),
+    /// Tool call is not executed, the reason is sent to the model.
+    Deny(String),
+}
+
+/// Permission to call the tool without approval, remembered by the agent for the session.
+///
+/// Grant can be limited to calls with arguments matching patterns, where `*` matches any text.
+/// Patterns are matched against text of the argument, non-string arguments are matched against
+/// their JSON representation. Wildcards never match values containing `..`, so granting `/docs/*`
+/// doesn't allow reading `/docs/../etc/passwd`.
+///
+/// Grants are serializable, so they can be stored together with the history of the session and
+/// restored with [Agent::set_tool_grants](crate::agent::Agent::set_tool_grants).
+///
+/// ```rust
+/// use agentai::{Agent, Approval, ToolGrant};
+///
+/// let agent = Agent::builder()
+///     .with_approval(|call| match call.fn_name.as_str() {
+///         "read_file" => Approval::AllowAlways(
+///             ToolGrant::new("read_file").with_argument("path", "/docs/*"),
+///         ),
+///         _ => Approval::Deny("Only reading files is allowed".to_string()),
+///     })
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolGrant {
+    /// Name of the tool
+    pub tool: String,
+    /// Patterns that arguments have to match, any arguments are allowed when empty
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub arguments: BTreeMap<String, String>,
+}
+
+impl ToolGrant {
+    /// Creates grant allowing all calls of the tool.
+    pub fn new(tool: &str) -> Self {
+        Self {
+            tool: tool.to_string(),
+            arguments: BTreeMap::new(),
+        }
+    }
+
+    /// Limits the grant to calls with argument matching the pattern.
+    pub fn with_argument(mut self, argument: &str, pattern: &str) -> Self {
+        self.arguments
+            .insert(argument.to_string(), pattern.to_string());
+        self
+    }
+
+    /// Returns `true` when the grant allows the tool call.
+    pub fn allows(&self, tool_call: &ToolCall) -> bool {
+        if tool_call.fn_name != self.tool {
+            return false;
+        }
+        self.arguments.iter().all(|(argument, pattern)| {
+            match tool_call.fn_arguments.get(argument) {
+                Some(Value::String(value)) => matches_pattern(pattern, value),
+                Some(value) => matches_pattern(pattern, &value.to_string()),
+                None => false,
+            }
+        })
+    }
+}
+
+/// Matches value against pattern, where `*` matches any text.
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern == value;
+    }
+    // Wildcard could hide path traversal
+    if value.contains("..") {
+        return false;
+    }
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_grant() {
+        let grant = ToolGrant::new("read_file").with_argument("path", "/docs/*.md");
+        let call = |name: &str, arguments: Value| ToolCall {
+            call_id: "1".to_string(),
+            fn_name: name.to_string(),
+            fn_arguments: arguments,
+        };
+        assert!(grant.allows(&call("read_file", json!({"path": "/docs/api/intro.md"}))));
+        assert!(!grant.allows(&call("read_file", json!({"path": "/etc/passwd"}))));
+        assert!(!grant.allows(&call("read_file", json!({"path": "/docs/../x.md"}))));
+        assert!(!grant.allows(&call("read_file", json!({}))));
+        assert!(!grant.allows(&call("write_file", json!({"path": "/docs/a.md"}))));
+        assert!(ToolGrant::new("search").allows(&call("search", json!({"query": "rust"}))));
+
+        assert!(matches_pattern("a*a", "aa"));
+        assert!(!matches_pattern("ab*ba", "aba"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("5", "5"));
+    }
+}
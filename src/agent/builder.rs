@@ -0,0 +1,634 @@
+use crate::agent::{
+    client_with_url, default_client, Agent, AgentOptions, AnswerCache, Approval, ChatMiddleware,
+    CitationMode, CitationVerification, ContextOverflow, DryRun, DuplicateToolCalls, Handoff,
+    ImageResend, InstructionRole, ModelDowngrade, OnIterationExhausted, OutputParsing,
+    PostProcessor, PromptProfile, Prompts, RequestLimiter, ResponseLanguage, ResultFormat,
+    SessionMetadata, StallAction, StructuredOutputMode, ToolDefinitionsFailure, ToolPanic,
+    ToolResultBatching, ToolResultLimit, DEFAULT_SEED,
+};
+use crate::event::{AgentEvent, EventHandler};
+use crate::system_prompt::SystemPrompt;
+use crate::tool::composite::SharedToolBox;
+use crate::tool::sandbox::SandboxProfile;
+#[cfg(feature = "trace-export")]
+use crate::trace::{TraceExporter, TraceRecorder};
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatOptions, ChatRequest, ToolCall};
+use genai::Client;
+use std::sync::Arc;
+
+/// Builder for [Agent].
+///
+/// Allows to configure the agent in one place, instead of providing settings to every run.
+///
+/// ```rust
+/// use agentai::{Agent, DryRun};
+///
+/// let agent = Agent::builder()
+///     .with_system("You are a useful assistant")
+///     .with_dry_run(DryRun::Skip)
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct AgentBuilder {
+    client: Option<Client>,
+    system: String,
+    event_handler: Option<EventHandler>,
+    metadata: SessionMetadata,
+    options: AgentOptions,
+}
+
+impl AgentBuilder {
+    /// Creates builder with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets GenAI client used by the agent. When not set default client is used.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Connects the agent to OpenAI API compatible endpoint.
+    pub fn with_url(mut self, base_url: &str, api_key: &str) -> Self {
+        self.client = Some(client_with_url(base_url, api_key));
+        self
+    }
+
+    /// Fills settings that were not configured in the code with defaults from [Config](crate::config::Config).
+    ///
+    /// When `base_url` is configured and no client was set, the agent connects to it with API key
+    /// from the configured environment variable.
+    #[cfg(feature = "config")]
+    pub fn with_config(mut self, config: &crate::config::Config) -> Self {
+        if let (Some(base_url), None) = (&config.base_url, &self.client) {
+            self.client = Some(client_with_url(
+                base_url,
+                &config.api_key().unwrap_or_default(),
+            ));
+        }
+        if self.options.utility_model.is_none() {
+            self.options.utility_model = config.utility_model.clone();
+        }
+        self
+    }
+
+    /// Sets system message used to initialize the chat history.
+    pub fn with_system(mut self, system: &str) -> Self {
+        self.system = system.to_string();
+        self.options.system_prompt = None;
+        self
+    }
+
+    /// Sets system message composed from presets, see [crate::system_prompt].
+    ///
+    /// Layout of the prompt is adjusted to the model, see [AgentBuilder::with_prompt_profile].
+    pub fn with_system_prompt(mut self, prompt: SystemPrompt) -> Self {
+        self.system = prompt.to_string();
+        self.options.system_prompt = Some(prompt);
+        self
+    }
+
+    /// Sets layout of prompts composed by the crate for all models, see [PromptProfile].
+    ///
+    /// By default profile is chosen by provider of the model.
+    pub fn with_prompt_profile(mut self, profile: PromptProfile) -> Self {
+        self.options.prompt_profiles.default = Some(profile);
+        self
+    }
+
+    /// Sets layout of prompts composed by the crate for models of the provider, it takes precedence
+    /// over [AgentBuilder::with_prompt_profile].
+    pub fn with_provider_prompt_profile(
+        mut self,
+        provider: AdapterKind,
+        profile: PromptProfile,
+    ) -> Self {
+        self.options
+            .prompt_profiles
+            .providers
+            .push((provider, profile));
+        self
+    }
+
+    /// Registers callback that will receive events emitted during the run.
+    pub fn with_event_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&AgentEvent) + Send + Sync + 'static,
+    {
+        self.event_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Attaches value to metadata of the conversation, see [SessionMetadata].
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key, value);
+        self
+    }
+
+    /// Attaches tag to metadata of the conversation, see [SessionMetadata].
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.metadata.add_tag(tag);
+        self
+    }
+
+    /// Enables dry-run mode, look into [DryRun] for more information.
+    pub fn with_dry_run(mut self, dry_run: DryRun) -> Self {
+        self.options.dry_run = Some(dry_run);
+        self
+    }
+
+    /// Enables deterministic mode, making runs as reproducible as possible.
+    ///
+    /// In this mode temperature is set to 0, seed is provided to the model (for providers that
+    /// support it, default seed is [DEFAULT_SEED]), tools are sorted by name and all JSON Schemas
+    /// are serialized with sorted keys. This is useful for testing and caching.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.options.deterministic_seed = deterministic.then_some(DEFAULT_SEED);
+        self
+    }
+
+    /// Enables deterministic mode with provided seed, look into [AgentBuilder::with_deterministic].
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.options.deterministic_seed = Some(seed);
+        self
+    }
+
+    /// Disables sanitization of schemas, sending them to the model exactly as generated.
+    ///
+    /// By default tools and structured output schemas are adjusted to the requirements of the
+    /// provider serving the model, look into [sanitize](crate::schema::sanitize) for details.
+    pub fn with_raw_schemas(mut self, raw_schemas: bool) -> Self {
+        self.options.raw_schemas = raw_schemas;
+        self
+    }
+
+    /// Limits number of concurrent requests sent by the agent and its clones.
+    ///
+    /// To share the limit with agents built separately use [AgentBuilder::with_request_limiter].
+    pub fn with_max_parallel_requests(mut self, max_parallel_requests: usize) -> Self {
+        self.options.request_limiter = Some(RequestLimiter::new(max_parallel_requests));
+        self
+    }
+
+    /// Sets limiter of concurrent requests, that can be shared by many agents.
+    pub fn with_request_limiter(mut self, limiter: RequestLimiter) -> Self {
+        self.options.request_limiter = Some(limiter);
+        self
+    }
+
+    /// Sets sandbox profile, only tools with capabilities allowed by it are exposed to the model.
+    pub fn with_sandbox(mut self, sandbox: SandboxProfile) -> Self {
+        self.options.sandbox = sandbox;
+        self
+    }
+
+    /// Limits the size of tool results sent to the model, see [ToolResultLimit].
+    pub fn with_tool_result_limit(mut self, limit: ToolResultLimit) -> Self {
+        self.options.tool_result_limit = Some(limit);
+        self
+    }
+
+    /// Sets cheap model used for auxiliary tasks, like [Agent::summarize_session] and [Agent::suggest_title].
+    pub fn with_utility_model(mut self, model: &str) -> Self {
+        self.options.utility_model = Some(model.to_string());
+        self
+    }
+
+    /// Switches the agent to a cheaper model after reaching usage threshold, see [ModelDowngrade].
+    pub fn with_model_downgrade(mut self, downgrade: ModelDowngrade) -> Self {
+        self.options.model_downgrade = Some(downgrade);
+        self
+    }
+
+    /// Registers callback approving tool calls before they are executed, see [Approval].
+    ///
+    /// Denied calls are not executed, the reason is sent to the model instead. Calls allowed with
+    /// [Approval::AllowAlways] are remembered, see [Agent::tool_grants].
+    pub fn with_approval<F>(mut self, approval: F) -> Self
+    where
+        F: Fn(&ToolCall) -> Approval + Send + Sync + 'static,
+    {
+        self.options.approval = Some(Arc::new(approval));
+        self
+    }
+
+    /// Limits tools exposed to the model to the listed ones, see [Agent::set_allowed_tools].
+    pub fn with_allowed_tools<S: AsRef<str>>(mut self, tools: impl IntoIterator<Item = S>) -> Self {
+        self.options.allowed_tools = Some(
+            tools
+                .into_iter()
+                .map(|tool| tool.as_ref().to_string())
+                .collect(),
+        );
+        self
+    }
+
+    /// Enables collecting sources used in the answer, available in [AgentResponse::citations](crate::agent::AgentResponse::citations).
+    pub fn with_citations(mut self, mode: CitationMode) -> Self {
+        self.options.citations = Some(mode);
+        self
+    }
+
+    /// Answers prompts from the cache in equivalent states of the conversation, see [AnswerCache].
+    ///
+    /// Cache can be cloned and shared by many agents.
+    pub fn with_answer_cache(mut self, cache: AnswerCache) -> Self {
+        self.options.answer_cache = Some(cache);
+        self
+    }
+
+    /// Checks the answer against cited sources with a cheap model, see [CitationVerification].
+    ///
+    /// Citations have to be enabled with [with_citations](AgentBuilder::with_citations).
+    pub fn with_citation_verification(mut self, verification: CitationVerification) -> Self {
+        self.options.citation_verification = Some(verification);
+        self
+    }
+
+    /// Asks the model to rate its confidence in the answer, and allows it to abstain from answering.
+    ///
+    /// Answer is wrapped in an envelope using structured output, also when plain text is requested.
+    /// Confidence is available in [AgentResponse::confidence](crate::agent::AgentResponse::confidence),
+    /// so low confidence answers can be routed to humans. When the model abstains, the run fails with
+    /// [AgentError::CannotAnswer](crate::agent::AgentError::CannotAnswer), containing its reason.
+    pub fn with_confidence(mut self, confidence: bool) -> Self {
+        self.options.confidence = confidence;
+        self
+    }
+
+    /// Sets how schema of the output type is provided to the model, see [StructuredOutputMode].
+    pub fn with_structured_output(mut self, mode: StructuredOutputMode) -> Self {
+        self.options.structured_output = mode;
+        self
+    }
+
+    /// Sets how structured answers of the model are parsed, see [OutputParsing].
+    pub fn with_output_parsing(mut self, parsing: OutputParsing) -> Self {
+        self.options.output_parsing = parsing;
+        self
+    }
+
+    /// Sets callback called when the answer of the model can't be parsed, see [ParseErrorHandler](crate::agent::ParseErrorHandler).
+    ///
+    /// It allows to log the raw answer or to recover from the error, e.g. with custom repair of JSON.
+    pub fn with_on_parse_error<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&str, &serde_json::Error) -> Option<String> + Send + Sync + 'static,
+    {
+        self.options.on_parse_error = Some(Arc::new(handler));
+        self
+    }
+
+    /// Adds post-processor cleaning up answers before they are converted into the output type, see [PostProcessor].
+    ///
+    /// Post-processors run in the order they were added, e.g. [NormalizeWhitespace](crate::agent::NormalizeWhitespace)
+    /// followed by [Clamp](crate::agent::Clamp).
+    pub fn with_post_processor(mut self, processor: impl PostProcessor + 'static) -> Self {
+        self.options.post_processors.push(Arc::new(processor));
+        self
+    }
+
+    /// Sets maximal number of characters of the text extracted in a single request by [Agent::extract].
+    ///
+    /// Longer texts are split into chunks, default length is [DEFAULT_EXTRACTION_CHUNK_LEN](crate::agent::DEFAULT_EXTRACTION_CHUNK_LEN).
+    pub fn with_extraction_chunk_len(mut self, chunk_len: usize) -> Self {
+        self.options.extraction_chunk_len = Some(chunk_len);
+        self
+    }
+
+    /// Sets language of the answers, instead of repeating it in every system prompt.
+    ///
+    /// With [ResponseLanguage::Auto] the model answers in the language used by the user.
+    pub fn with_response_language(mut self, language: ResponseLanguage) -> Self {
+        self.options.response_language = Some(language);
+        self
+    }
+
+    /// Registers agent that the model can hand off the task to, see [Handoff].
+    pub fn with_handoff(mut self, handoff: Handoff) -> Self {
+        self.options.handoffs.push(handoff);
+        self
+    }
+
+    /// Sets format of all tool results sent to the model, see [ResultFormat].
+    pub fn with_result_format(mut self, format: ResultFormat) -> Self {
+        self.options.result_formats.default = Some(format);
+        self
+    }
+
+    /// Sets format of tool results sent to models of the provider, overriding [AgentBuilder::with_result_format].
+    pub fn with_provider_result_format(
+        mut self,
+        provider: AdapterKind,
+        format: ResultFormat,
+    ) -> Self {
+        self.options
+            .result_formats
+            .providers
+            .push((provider, format));
+        self
+    }
+
+    /// Sets format of results of the tool, overriding provider and default formats.
+    pub fn with_tool_result_format(mut self, tool: &str, format: ResultFormat) -> Self {
+        self.options
+            .result_formats
+            .tools
+            .push((tool.to_string(), format));
+        self
+    }
+
+    /// Sets role of messages with instructions added by the agent, see [InstructionRole].
+    pub fn with_instruction_role(mut self, role: InstructionRole) -> Self {
+        self.options.instruction_roles.default = role;
+        self
+    }
+
+    /// Sets role of instructions sent to models of the provider, overriding [AgentBuilder::with_instruction_role].
+    pub fn with_provider_instruction_role(
+        mut self,
+        provider: AdapterKind,
+        role: InstructionRole,
+    ) -> Self {
+        self.options
+            .instruction_roles
+            .providers
+            .push((provider, role));
+        self
+    }
+
+    /// Sets how results of tools called in the same iteration are sent, see [ToolResultBatching].
+    pub fn with_tool_result_batching(mut self, batching: ToolResultBatching) -> Self {
+        self.options.tool_result_batching.default = batching;
+        self
+    }
+
+    /// Sets batching of tool results sent to models of the provider, overriding [AgentBuilder::with_tool_result_batching].
+    pub fn with_provider_tool_result_batching(
+        mut self,
+        provider: AdapterKind,
+        batching: ToolResultBatching,
+    ) -> Self {
+        self.options
+            .tool_result_batching
+            .providers
+            .push((provider, batching));
+        self
+    }
+
+    /// Sets handling of tool calls repeating calls already made in the run, see [DuplicateToolCalls].
+    pub fn with_duplicate_tool_calls(mut self, policy: DuplicateToolCalls) -> Self {
+        self.options.duplicate_tool_calls = policy;
+        self
+    }
+
+    /// Attaches toolbox used by runs that don't provide their own one, see [Agent::run].
+    ///
+    /// Toolbox is shared, so the agent can be cloned or moved to another task together with it.
+    pub fn with_toolbox(mut self, toolbox: SharedToolBox) -> Self {
+        self.options.toolbox = Some(toolbox);
+        self
+    }
+
+    /// Sets handling of images already sent to the model, see [ImageResend].
+    pub fn with_image_resend(mut self, policy: ImageResend) -> Self {
+        self.options.image_resend = policy;
+        self
+    }
+
+    /// Replaces tool results identical to an earlier result of the conversation with a short
+    /// placeholder (see [PromptKey::RepeatedResult](crate::agent::PromptKey::RepeatedResult)),
+    /// when they have at least `min_len` characters.
+    ///
+    /// History of the agent keeps full results, only requests sent to the model are compacted.
+    pub fn with_repeated_result_compaction(mut self, min_len: usize) -> Self {
+        self.options.repeated_result_len = Some(min_len);
+        self
+    }
+
+    /// Informs the model when tools of the toolbox changed since the previous run, disabled by default.
+    ///
+    /// Note listing added, removed and changed tools is added to the history as a system message
+    /// (see [PromptKey::ToolsChanged](crate::agent::PromptKey::ToolsChanged)), so the model doesn't
+    /// call tools that are no longer available. Changes are always reported with
+    /// [AgentEvent::ToolsChanged].
+    pub fn with_tools_change_notes(mut self, enabled: bool) -> Self {
+        self.options.tools_change_notes = enabled;
+        self
+    }
+
+    /// Enables detection of runs that don't make progress, see [StallAction].
+    pub fn with_stall_guard(mut self, action: StallAction) -> Self {
+        self.options.stall_action = Some(action);
+        self
+    }
+
+    /// Sets behaviour when the model doesn't answer within allowed iterations, see [OnIterationExhausted].
+    pub fn with_on_iteration_exhausted(mut self, behaviour: OnIterationExhausted) -> Self {
+        self.options.on_iteration_exhausted = behaviour;
+        self
+    }
+
+    /// Overrides prompts injected by the agent, e.g. to translate them, see [Prompts].
+    pub fn with_prompts(mut self, prompts: Prompts) -> Self {
+        self.options.prompts = prompts;
+        self
+    }
+
+    /// Installs middleware wrapping requests sent to the model, see [ChatMiddleware].
+    ///
+    /// Middlewares are called in order of installation.
+    pub fn with_middleware(mut self, middleware: impl ChatMiddleware + 'static) -> Self {
+        self.options.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Records every run of the agent and pushes its trace to the exporter, e.g. [LangfuseExporter](crate::trace::LangfuseExporter)
+    /// or [LangSmithExporter](crate::trace::LangSmithExporter). Requires `trace-export` feature.
+    ///
+    /// Trace is exported when the run finishes, also when it fails. Failure of the export doesn't
+    /// fail the run, it is only logged. Scores can be attached to the last run with [Agent::score_run].
+    ///
+    /// ```rust,no_run
+    /// use agentai::trace::LangfuseExporter;
+    /// use agentai::Agent;
+    ///
+    /// let agent = Agent::builder()
+    ///     .with_trace_exporter(LangfuseExporter::new("pk-lf-...", "sk-lf-..."))
+    ///     .build();
+    /// ```
+    #[cfg(feature = "trace-export")]
+    pub fn with_trace_exporter(mut self, exporter: impl TraceExporter + 'static) -> Self {
+        let recorder = TraceRecorder::new();
+        self.options.middleware.push(Arc::new(recorder.clone()));
+        self.options.trace_export = Some((recorder, Arc::new(exporter)));
+        self
+    }
+
+    /// Sets text to speech used by [Agent::speak], look into [crate::speech] for an example.
+    #[cfg(feature = "tts")]
+    pub fn with_speech(mut self, speech: crate::speech::TextToSpeech) -> Self {
+        self.options.speech = Some(speech);
+        self
+    }
+
+    /// Sets callback modifying the request of every iteration of runs, see [IterationHook](crate::agent::IterationHook).
+    ///
+    /// Unlike [ChatMiddleware], that sees every request of the agent, the hook is called only in
+    /// the loop of runs and knows the number of the iteration. It is called before middlewares.
+    ///
+    /// ```rust
+    /// use agentai::Agent;
+    ///
+    /// let agent = Agent::builder()
+    ///     .with_iteration_hook(|iteration, request, _options| {
+    ///         // Model had enough time to gather information, make it answer
+    ///         if iteration >= 3 {
+    ///             request.tools = None;
+    ///         }
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn with_iteration_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(u32, &mut ChatRequest, &mut ChatOptions) + Send + Sync + 'static,
+    {
+        self.options.iteration_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets behaviour when toolbox fails to provide tools definitions, see [ToolDefinitionsFailure].
+    pub fn with_tool_definitions_failure(mut self, behaviour: ToolDefinitionsFailure) -> Self {
+        self.options.tool_definitions_failure = behaviour;
+        self
+    }
+
+    /// Sets recovery from requests exceeding context window of the model, see [ContextOverflow].
+    ///
+    /// By default the run fails with the error of the provider.
+    pub fn with_context_overflow(mut self, recovery: ContextOverflow) -> Self {
+        self.options.context_overflow = recovery;
+        self
+    }
+
+    /// Exposes [COMPACT_HISTORY_TOOL](crate::agent::COMPACT_HISTORY_TOOL) to the model, so it can compact the history when the
+    /// conversation becomes long, see [Agent::compact].
+    ///
+    /// Messages of the current run are kept, older ones are summarized by the utility model (see
+    /// [AgentBuilder::with_utility_model]) or by the model of the run.
+    pub fn with_compact_tool(mut self) -> Self {
+        self.options.compact_tool = true;
+        self
+    }
+
+    /// Sets behaviour when a tool panics, see [ToolPanic].
+    pub fn with_tool_panic(mut self, behaviour: ToolPanic) -> Self {
+        self.options.tool_panic = behaviour;
+        self
+    }
+
+    /// Creates the agent.
+    pub fn build(self) -> Agent {
+        let mut agent =
+            Agent::new_with_client(self.client.unwrap_or_else(default_client), &self.system);
+        agent.event_handler = self.event_handler;
+        agent.metadata = self.metadata;
+        agent.options = self.options;
+        agent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::testing::{text, tool_call, ScriptedModel};
+    use crate::tool::{Tool, ToolBox, ToolError};
+    use anyhow::Result;
+    use genai::chat::MessageContent;
+    use serde_json::{json, Value};
+
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl ToolBox for Echo {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Ok(vec![Tool::new("echo")])
+        }
+
+        async fn call_tool(
+            &self,
+            _tool_name: String,
+            arguments: Value,
+        ) -> Result<String, ToolError> {
+            Ok(arguments.to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_system_and_metadata_applied() {
+        let model = ScriptedModel::new([text("Hi")]);
+        let mut agent = AgentBuilder::new()
+            .with_system("You are a pirate.")
+            .with_metadata("user", "alice")
+            .with_tag("beta")
+            .with_middleware(model.clone())
+            .build();
+        agent
+            .run::<String>("gpt-4o", "Hello", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(agent.metadata().get("user"), Some("alice"));
+        assert!(agent.metadata().has_tag("beta"));
+        let request = &model.requests()[0].request;
+        assert_eq!(
+            request.messages[0].content.text_as_str(),
+            Some("You are a pirate.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_toolbox_used_without_run_toolbox() {
+        let model = ScriptedModel::new([
+            tool_call("call_1", "echo", json!({"text": "ahoy"})),
+            text("Done"),
+        ]);
+        let mut agent = AgentBuilder::new()
+            .with_toolbox(Arc::new(Echo))
+            .with_middleware(model.clone())
+            .build();
+        agent
+            .run::<String>("gpt-4o", "Echo ahoy", None, None, None)
+            .await
+            .unwrap();
+        let requests = model.requests();
+        assert_eq!(requests[0].request.tools.as_ref().map(Vec::len), Some(1));
+        assert!(matches!(
+            &requests[1].request.messages.last().unwrap().content,
+            MessageContent::ToolResponses(responses) if responses[0].content == r#"{"text":"ahoy"}"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_seed_sets_sampling_options() {
+        let model = ScriptedModel::new([text("Hi"), text("Hi")]);
+        let mut agent = AgentBuilder::new().with_middleware(model.clone()).build();
+        agent
+            .run::<String>("gpt-4o", "Hello", None, None, None)
+            .await
+            .unwrap();
+        let mut agent = AgentBuilder::new()
+            .with_seed(7)
+            .with_middleware(model.clone())
+            .build();
+        agent
+            .run::<String>("gpt-4o", "Hello", None, None, None)
+            .await
+            .unwrap();
+        let requests = model.requests();
+        assert_eq!(requests[0].options.seed, None);
+        assert_eq!(requests[1].options.temperature, Some(0.0));
+        assert_eq!(requests[1].options.seed, Some(7));
+    }
+}
@@ -0,0 +1,572 @@
+use crate::agent::{ChatMiddleware, RequestContext};
+use anyhow::Result;
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, ChatResponse, ChatRole};
+use log::trace;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Metadata key under which the cache passes the key of the request to [ChatMiddleware::after_response]
+const CACHE_KEY: &str = "response_cache_key";
+/// Metadata key of the context of the prompt, used in semantic mode
+const CONTEXT_KEY: &str = "response_cache_context";
+/// Metadata key of the embedding of the prompt (JSON array), used in semantic mode
+const EMBEDDING_KEY: &str = "response_cache_embedding";
+
+/// Converts text into embedding vector, used by semantic mode of [ResponseCache].
+///
+/// Implement it with the embedding model of your choice, e.g. calling the embeddings endpoint of
+/// the provider or running a local model.
+#[async_trait::async_trait]
+pub trait Embedder: Send + Sync {
+    /// Returns embedding of the text.
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// Middleware caching complete responses of the model, keyed on the full request.
+///
+/// Request is served from the cache only when model, all messages, tools and options are exactly
+/// the same, so it is safe for idempotent pipelines (e.g. extraction of the same documents) and
+/// makes tests repeatable without calling the model. Cached responses are not counted as tokens
+/// used, they are reported in [TokenUsage::cached_requests](crate::agent::TokenUsage::cached_requests).
+///
+/// Cache can be cloned and shared by many agents, its statistics are available with [ResponseCache::stats].
+/// Entries live for the whole lifetime of the cache, unless time-to-live is set.
+///
+/// ```rust
+/// use agentai::{Agent, ResponseCache};
+/// use std::time::Duration;
+///
+/// let cache = ResponseCache::new().with_ttl(Duration::from_secs(3600));
+/// let agent = Agent::builder().with_middleware(cache.clone()).build();
+/// // ...
+/// println!("Cache hits: {}", cache.stats().hits);
+/// ```
+///
+/// # Semantic mode
+///
+/// Users ask the same question in many ways, so FAQ-style assistants rarely get exact hits. With
+/// [ResponseCache::with_semantic] the prompt of the user is embedded, and cached answer to a
+/// similar prompt is returned when cosine similarity of their embeddings reaches the threshold.
+/// Only the last user message is compared, the rest of the request (model, system message,
+/// earlier messages, tools and options) still has to be identical. Requests that don't end with
+/// a user message, e.g. ones sending tool results, use only exact matching.
+///
+/// Threshold depends on the embedding model, start high (e.g. `0.95`) and lower it carefully, as
+/// similar prompts can still require different answers.
+#[derive(Clone, Default)]
+pub struct ResponseCache {
+    ttl: Option<Duration>,
+    semantic: Option<(Arc<dyn Embedder>, f32)>,
+    state: Arc<Mutex<CacheState>>,
+}
+
+/// Statistics of the [ResponseCache].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    /// Requests answered from the cache
+    pub hits: u64,
+    /// Hits of similar prompts in semantic mode, included in `hits`
+    pub semantic_hits: u64,
+    /// Requests that had to be sent to the model
+    pub misses: u64,
+    /// Responses currently stored in the cache
+    pub entries: usize,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    /// Embeddings of prompts of cached requests, pointing to their entries
+    prompts: Vec<PromptEmbedding>,
+    hits: u64,
+    semantic_hits: u64,
+    misses: u64,
+}
+
+struct CacheEntry<T = ChatResponse> {
+    value: T,
+    created: std::time::Instant,
+}
+
+struct PromptEmbedding {
+    /// Part of the request without the prompt, that has to match exactly
+    context: String,
+    embedding: Vec<f32>,
+    /// Key of the cached entry
+    key: String,
+}
+
+impl<T> CacheEntry<T> {
+    fn new(value: T) -> Self {
+        Self {
+            value,
+            created: std::time::Instant::now(),
+        }
+    }
+
+    fn is_expired(&self, ttl: Option<Duration>) -> bool {
+        ttl.is_some_and(|ttl| self.created.elapsed() > ttl)
+    }
+}
+
+impl CacheState {
+    /// Removes expired entries and embeddings pointing to them.
+    fn remove_expired(&mut self, ttl: Option<Duration>) {
+        if ttl.is_none() {
+            return;
+        }
+        self.entries.retain(|_, entry| !entry.is_expired(ttl));
+        let entries = &self.entries;
+        self.prompts
+            .retain(|prompt| entries.contains_key(&prompt.key));
+    }
+
+    /// Returns response to the most similar prompt with the same context, when similarity reaches the threshold.
+    fn find_similar(
+        &self,
+        context: &str,
+        embedding: &[f32],
+        threshold: f32,
+    ) -> Option<ChatResponse> {
+        self.prompts
+            .iter()
+            .filter(|prompt| prompt.context == context)
+            .map(|prompt| (cosine_similarity(&prompt.embedding, embedding), prompt))
+            .filter(|(similarity, _)| *similarity >= threshold)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .and_then(|(_, prompt)| self.entries.get(&prompt.key))
+            .map(|entry| entry.value.clone())
+    }
+}
+
+impl ResponseCache {
+    /// Creates empty cache, with entries that never expire.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets time after which cached responses expire.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Enables semantic mode, returning cached answers to prompts with similarity of at least `threshold`.
+    ///
+    /// Look into [semantic mode](ResponseCache#semantic-mode) for details.
+    pub fn with_semantic(mut self, embedder: impl Embedder + 'static, threshold: f32) -> Self {
+        self.semantic = Some((Arc::new(embedder), threshold));
+        self
+    }
+
+    /// Returns statistics of the cache.
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        CacheStats {
+            hits: state.hits,
+            semantic_hits: state.semantic_hits,
+            misses: state.misses,
+            entries: state.entries.len(),
+        }
+    }
+
+    /// Removes all cached responses, statistics are kept.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.prompts.clear();
+    }
+
+    /// Looks for cached response in semantic mode, storing prompt details in the context.
+    async fn lookup_similar(
+        &self,
+        context: &mut RequestContext,
+        request: &ChatRequest,
+        options: &ChatOptions,
+    ) -> Result<Option<ChatResponse>> {
+        let Some((embedder, threshold)) = &self.semantic else {
+            return Ok(None);
+        };
+        let Some((prompt, rest)) = split_prompt(request) else {
+            return Ok(None);
+        };
+        let prompt_context = cache_key(&context.model, &rest, options)?;
+        let embedding = embedder.embed(prompt).await?;
+        let response =
+            self.state
+                .lock()
+                .unwrap()
+                .find_similar(&prompt_context, &embedding, *threshold);
+        context
+            .metadata
+            .insert(CONTEXT_KEY.to_string(), prompt_context);
+        context.metadata.insert(
+            EMBEDDING_KEY.to_string(),
+            serde_json::to_string(&embedding)?,
+        );
+        Ok(response)
+    }
+}
+
+/// Default number of last messages of the conversation included in the key of [AnswerCache]
+const DEFAULT_HISTORY_WINDOW: usize = 4;
+
+/// Cache of final answers of runs, keyed by the state of the conversation, see [AgentBuilder::with_answer_cache](crate::agent::AgentBuilder::with_answer_cache).
+///
+/// Unlike [ResponseCache], that caches every request sent to the model, it stores only final
+/// answers, and on hit the whole run is skipped, including tool calls. It is cheaper for
+/// assistants that are repeatedly asked the same questions in equivalent states of the
+/// conversation, e.g. FAQ bots.
+///
+/// The key is a hash of the model, the requested output type, the system message, text of the
+/// last user and assistant messages (see [AnswerCache::with_history_window]) and the prompt.
+/// Tool calls and their results are not part of the key, so answers that depend on data returned
+/// by tools have to be invalidated when the data changes: [AnswerCache::invalidate_tool] removes
+/// answers of runs that called the tool, [AnswerCache::invalidate_if] removes answers matching
+/// the predicate and [AnswerCache::clear] removes all of them.
+///
+/// ```rust
+/// use agentai::{Agent, AnswerCache};
+///
+/// let cache = AnswerCache::new().with_history_window(0);
+/// let agent = Agent::builder().with_answer_cache(cache.clone()).build();
+/// // ...
+/// // Prices were updated, answers based on them are no longer valid
+/// cache.invalidate_tool("get_prices");
+/// ```
+#[derive(Clone)]
+pub struct AnswerCache {
+    ttl: Option<Duration>,
+    history_window: usize,
+    state: Arc<Mutex<AnswerCacheState>>,
+}
+
+/// Answer stored in [AnswerCache].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedAnswer {
+    /// Text of the prompt that was answered
+    pub prompt: String,
+    /// Raw text of the answer
+    pub answer: String,
+    /// Names of tools called during the run
+    pub tools: Vec<String>,
+}
+
+#[derive(Default)]
+struct AnswerCacheState {
+    entries: HashMap<u64, CacheEntry<CachedAnswer>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for AnswerCache {
+    fn default() -> Self {
+        Self {
+            ttl: None,
+            history_window: DEFAULT_HISTORY_WINDOW,
+            state: Default::default(),
+        }
+    }
+}
+
+impl AnswerCache {
+    /// Creates empty cache, with entries that never expire.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets time after which cached answers expire.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets number of last user and assistant messages included in the key, 4 by default.
+    ///
+    /// With `0` only the system message and the prompt are compared, so the same question gets
+    /// the same answer regardless of the conversation.
+    pub fn with_history_window(mut self, messages: usize) -> Self {
+        self.history_window = messages;
+        self
+    }
+
+    /// Returns statistics of the cache, there are no semantic hits.
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        CacheStats {
+            hits: state.hits,
+            semantic_hits: 0,
+            misses: state.misses,
+            entries: state.entries.len(),
+        }
+    }
+
+    /// Removes all cached answers, statistics are kept.
+    pub fn clear(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .clear();
+    }
+
+    /// Removes answers of runs that called the tool, returning number of removed answers.
+    pub fn invalidate_tool(&self, tool_name: &str) -> usize {
+        self.invalidate_if(|answer| answer.tools.iter().any(|tool| tool == tool_name))
+    }
+
+    /// Removes answers matching the predicate, returning number of removed answers.
+    pub fn invalidate_if(&self, predicate: impl Fn(&CachedAnswer) -> bool) -> usize {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let before = state.entries.len();
+        state.entries.retain(|_, entry| !predicate(&entry.value));
+        before - state.entries.len()
+    }
+
+    /// Returns key of the conversation state, before the prompt is added to the history.
+    pub(crate) fn key(
+        &self,
+        model: &str,
+        output_type: &str,
+        history: &[ChatMessage],
+        prompt: &[ChatMessage],
+    ) -> Result<u64> {
+        let system = history
+            .iter()
+            .filter(|message| matches!(message.role, ChatRole::System))
+            .filter_map(|message| message.content.text_as_str())
+            .collect::<Vec<_>>();
+        let mut recent = history
+            .iter()
+            .filter(|message| matches!(message.role, ChatRole::User | ChatRole::Assistant))
+            .filter_map(|message| message.content.text_as_str())
+            .rev()
+            .take(self.history_window)
+            .collect::<Vec<_>>();
+        recent.reverse();
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_string(&(model, output_type, system, recent, prompt))?.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    /// Returns cached answer, counting hit or miss.
+    pub(crate) fn get(&self, key: u64) -> Option<String> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if self.ttl.is_some() {
+            state.entries.retain(|_, entry| !entry.is_expired(self.ttl));
+        }
+        let answer = state
+            .entries
+            .get(&key)
+            .map(|entry| entry.value.answer.clone());
+        if answer.is_some() {
+            trace!("Answer cache hit");
+            state.hits += 1;
+        } else {
+            trace!("Answer cache miss");
+            state.misses += 1;
+        }
+        answer
+    }
+
+    pub(crate) fn insert(&self, key: u64, answer: CachedAnswer) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entries
+            .insert(key, CacheEntry::new(answer));
+    }
+}
+
+/// Returns key identifying the request, all parts that change the response are included.
+fn cache_key(model: &str, request: &ChatRequest, options: &ChatOptions) -> Result<String> {
+    Ok(serde_json::to_string(&(model, request, options))?)
+}
+
+/// Splits request ending with user text message into the text and the rest of the request.
+fn split_prompt(request: &ChatRequest) -> Option<(&str, ChatRequest)> {
+    let (last, messages) = request.messages.split_last()?;
+    if !matches!(last.role, ChatRole::User) {
+        return None;
+    }
+    let prompt = last.content.text_as_str()?;
+    let mut rest = request.clone();
+    rest.messages = messages.to_vec();
+    Some((prompt, rest))
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm_a = a.iter().map(|a| a * a).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|b| b * b).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a * norm_b)
+}
+
+#[async_trait::async_trait]
+impl ChatMiddleware for ResponseCache {
+    async fn before_request(
+        &self,
+        context: &mut RequestContext,
+        request: &mut ChatRequest,
+        options: &mut ChatOptions,
+    ) -> Result<Option<ChatResponse>> {
+        let key = cache_key(&context.model, request, options)?;
+        let response = {
+            let mut state = self.state.lock().unwrap();
+            state.remove_expired(self.ttl);
+            state.entries.get(&key).map(|entry| entry.value.clone())
+        };
+        let response = match response {
+            Some(response) => Some(response),
+            None => {
+                let response = self.lookup_similar(context, request, options).await?;
+                if response.is_some() {
+                    self.state.lock().unwrap().semantic_hits += 1;
+                }
+                response
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        if response.is_some() {
+            trace!("Response cache hit");
+            state.hits += 1;
+        } else {
+            trace!("Response cache miss");
+            state.misses += 1;
+        }
+        context.metadata.insert(CACHE_KEY.to_string(), key);
+        Ok(response)
+    }
+
+    async fn after_response(
+        &self,
+        context: &RequestContext,
+        _request: &ChatRequest,
+        response: &mut ChatResponse,
+    ) -> Result<()> {
+        let Some(key) = context.metadata.get(CACHE_KEY) else {
+            return Ok(());
+        };
+        let mut state = self.state.lock().unwrap();
+        state
+            .entries
+            .insert(key.clone(), CacheEntry::new(response.clone()));
+        let prompt = context
+            .metadata
+            .get(CONTEXT_KEY)
+            .zip(context.metadata.get(EMBEDDING_KEY));
+        if let Some((prompt_context, embedding)) = prompt {
+            state.prompts.push(PromptEmbedding {
+                context: prompt_context.clone(),
+                embedding: serde_json::from_str(embedding)?,
+                key: key.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genai::chat::ChatMessage;
+
+    /// Embeds text as counts of vowels
+    struct Vowels;
+
+    #[async_trait::async_trait]
+    impl Embedder for Vowels {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok("aeiou"
+                .chars()
+                .map(|vowel| text.matches(vowel).count() as f32)
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_miss() {
+        let cache = ResponseCache::new().with_semantic(Vowels, 0.9);
+        let mut context = RequestContext::new("gpt-4o", &Default::default());
+        let mut request = ChatRequest::new(vec![
+            ChatMessage::system("You are a helpful assistant."),
+            ChatMessage::user("What is the capital of France?"),
+        ]);
+        let mut options = ChatOptions::default();
+        let response = cache
+            .before_request(&mut context, &mut request, &mut options)
+            .await
+            .unwrap();
+        assert!(response.is_none());
+        assert!(context.metadata.contains_key(CACHE_KEY));
+        assert_eq!(context.metadata[EMBEDDING_KEY], "[4.0,2.0,2.0,1.0,0.0]");
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 0,
+                semantic_hits: 0,
+                misses: 1,
+                entries: 0
+            }
+        );
+
+        // Model is part of the key
+        let other_key = cache_key("gpt-4o-mini", &request, &options).unwrap();
+        assert_ne!(context.metadata[CACHE_KEY], other_key);
+
+        // Prompt is not part of the context
+        let (prompt, rest) = split_prompt(&request).unwrap();
+        assert_eq!(prompt, "What is the capital of France?");
+        assert_eq!(rest.messages.len(), 1);
+    }
+
+    #[test]
+    fn test_answer_cache() {
+        let cache = AnswerCache::new().with_history_window(1);
+        let history = vec![
+            ChatMessage::system("You are a helpful assistant."),
+            ChatMessage::user("Hi"),
+            ChatMessage::assistant("Hello!"),
+        ];
+        let prompt = vec![ChatMessage::user("What are your prices?")];
+        let key = cache.key("gpt-4o", "String", &history, &prompt).unwrap();
+        assert_eq!(cache.get(key), None);
+        cache.insert(
+            key,
+            CachedAnswer {
+                prompt: "What are your prices?".to_string(),
+                answer: "10 EUR".to_string(),
+                tools: vec!["get_prices".to_string()],
+            },
+        );
+        assert_eq!(cache.get(key).as_deref(), Some("10 EUR"));
+
+        // Only the last message of the history is compared
+        let other = [&history[..1], &[ChatMessage::assistant("Hello!")]].concat();
+        assert_eq!(cache.key("gpt-4o", "String", &other, &prompt).unwrap(), key);
+        assert_ne!(cache.key("gpt-4o", "u32", &history, &prompt).unwrap(), key);
+
+        assert_eq!(cache.invalidate_tool("get_weather"), 0);
+        assert_eq!(cache.invalidate_tool("get_prices"), 1);
+        assert_eq!(cache.get(key), None);
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 2);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::agent::response::{Citation, ToolCallRecord, UnsupportedClaim};
+use crate::agent::{Agent, PromptKey};
+use crate::schema::default_schema_options;
+use crate::tool::composite::SharedToolBox;
+use log::debug;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// Default maximal number of characters of the source checked by [CitationVerification]
+const DEFAULT_MAX_SOURCE_LEN: usize = 20_000;
+
+/// How citations of the answer are collected, see [AgentBuilder::with_citations](crate::agent::AgentBuilder::with_citations).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CitationMode {
+    /// URLs returned by tools, that appear in the answer, are cited. This mode doesn't change
+    /// requests sent to the model, but can miss sources that were used without quoting their URLs.
+    Heuristic,
+    /// Model is asked to list identifiers of tool results used in the answer. Tool results are
+    /// labeled with identifiers of tool calls, and the answer is wrapped in an object with `answer`
+    /// and `sources` fields using structured output.
+    Model,
+}
+
+/// Check of the answer against its cited sources, see [AgentBuilder::with_citation_verification](crate::agent::AgentBuilder::with_citation_verification).
+///
+/// After the answer, every cited source is given to the utility model (see
+/// [AgentBuilder::with_utility_model](crate::agent::AgentBuilder::with_utility_model)), or the
+/// model of the run when it is not set, together with the answer. The model lists claims of the
+/// answer based on the source and confirms whether the source supports them.
+/// [Citation::supported] is set for checked citations, and claims that are not supported are
+/// listed in [AgentResponse::unsupported_claims](crate::agent::AgentResponse::unsupported_claims).
+///
+/// By default the source is the result of the cited tool call. With a reader tool (e.g. a tool
+/// fetching web pages) cited URLs are fetched again, so the full page is checked instead of e.g.
+/// a search snippet. Sources that can't be checked are left unverified, verification never fails
+/// the run.
+#[derive(Debug, Clone)]
+pub struct CitationVerification {
+    /// Name of the tool fetching sources and its argument receiving the URL
+    reader: Option<(String, String)>,
+    max_source_len: usize,
+}
+
+impl Default for CitationVerification {
+    fn default() -> Self {
+        Self {
+            reader: None,
+            max_source_len: DEFAULT_MAX_SOURCE_LEN,
+        }
+    }
+}
+
+impl CitationVerification {
+    /// Creates verification checking results of cited tool calls.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetches cited URLs again with the tool of the run toolbox, passing the URL in `argument`.
+    ///
+    /// The tool has to be allowed by the [sandbox](crate::agent::Agent::set_sandbox), result of
+    /// the cited tool call is used when it is not or when the call fails.
+    pub fn with_reader(mut self, tool: &str, argument: &str) -> Self {
+        self.reader = Some((tool.to_string(), argument.to_string()));
+        self
+    }
+
+    /// Sets maximal number of characters of the source sent to the model, 20 000 by default.
+    pub fn with_max_source_len(mut self, max_len: usize) -> Self {
+        self.max_source_len = max_len;
+        self
+    }
+}
+
+/// Claims of the answer checked against a single source
+#[derive(Deserialize, JsonSchema)]
+struct ClaimChecks {
+    /// Claims of the answer that are based on the source
+    claims: Vec<ClaimCheck>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ClaimCheck {
+    /// The claim, quoted or paraphrased from the answer
+    claim: String,
+    /// Whether the source supports the claim
+    supported: bool,
+    /// Why the claim is not supported, null when it is
+    reason: Option<String>,
+}
+
+impl Agent {
+    /// Checks cited sources of the answer, returning claims that are not supported by them.
+    pub(crate) async fn verify_citations(
+        &mut self,
+        model: &str,
+        verification: &CitationVerification,
+        toolbox: Option<&SharedToolBox>,
+        answer: &str,
+        citations: &mut [Citation],
+    ) -> Vec<UnsupportedClaim> {
+        let model = self
+            .options
+            .utility_model
+            .clone()
+            .unwrap_or_else(|| model.to_string());
+        let schema = default_schema_options().schema_for::<ClaimChecks>();
+        let instruction = self.options.prompts.render(PromptKey::CitationCheck, &[]);
+        let mut unsupported = vec![];
+        for idx in 0..citations.len() {
+            let citation = &citations[idx];
+            if let Some(checked) = citations[..idx]
+                .iter()
+                .find(|other| other.source == citation.source)
+            {
+                citations[idx].supported = checked.supported;
+                continue;
+            }
+            let Some(source) = self.citation_source(verification, toolbox, citation).await else {
+                continue;
+            };
+            let source: String = source.chars().take(verification.max_source_len).collect();
+            let input = format!("Answer:\n{answer}\n\nSource {}:\n{source}", citation.source);
+            let checks = match self
+                .extract_value(&model, &instruction, &input, &schema)
+                .await
+                .and_then(|value| Ok(serde_json::from_value::<ClaimChecks>(value)?))
+            {
+                Ok(checks) => checks,
+                Err(err) => {
+                    debug!("Unable to verify citation {}: {err}", citation.source);
+                    continue;
+                }
+            };
+            let source = citation.source.clone();
+            citations[idx].supported = Some(checks.claims.iter().all(|check| check.supported));
+            unsupported.extend(
+                checks
+                    .claims
+                    .into_iter()
+                    .filter(|check| !check.supported)
+                    .map(|check| UnsupportedClaim {
+                        claim: check.claim,
+                        source: source.clone(),
+                        reason: check.reason,
+                    }),
+            );
+        }
+        unsupported
+    }
+
+    /// Returns content of the cited source, fetched with the reader tool when possible.
+    async fn citation_source(
+        &self,
+        verification: &CitationVerification,
+        toolbox: Option<&SharedToolBox>,
+        citation: &Citation,
+    ) -> Option<String> {
+        if let (Some((tool, argument)), Some(toolbox)) = (&verification.reader, toolbox) {
+            let is_url =
+                citation.source.starts_with("https://") || citation.source.starts_with("http://");
+            if is_url
+                && self
+                    .options
+                    .sandbox
+                    .allows_all(&toolbox.tool_capabilities(tool))
+            {
+                match toolbox
+                    .call_tool(tool.clone(), json!({ argument: citation.source }))
+                    .await
+                {
+                    Ok(content) => return Some(content),
+                    Err(err) => debug!("Unable to fetch {}: {err}", citation.source),
+                }
+            }
+        }
+        self.run_tool_calls
+            .iter()
+            .find(|record| record.call_id == citation.call_id && !record.is_error)
+            .map(|record| record.content.clone())
+    }
+}
+
+/// Answer wrapped together with its sources in [CitationMode::Model]
+#[derive(Deserialize)]
+pub(crate) struct CitedAnswer {
+    pub(crate) answer: Value,
+    #[serde(default)]
+    pub(crate) sources: Vec<String>,
+}
+
+/// Returns schema of the answer wrapped together with its sources.
+pub(crate) fn cited_answer_schema(answer_schema: Value) -> Value {
+    let (answer_schema, definitions) = embeddable_schema(answer_schema);
+    let mut schema = json!({
+        "type": "object",
+        "properties": {
+            "answer": answer_schema,
+            "sources": {
+                "type": "array",
+                "items": {"type": "string"},
+                "description": "Source ids of tool results used in the answer"
+            }
+        },
+        "required": ["answer", "sources"]
+    });
+    if !definitions.is_empty() {
+        schema["$defs"] = Value::Object(definitions);
+    }
+    schema
+}
+
+/// Prepares schema to be embedded in other schema, returning it together with its definitions.
+///
+/// References are resolved from the root of the schema, so definitions have to be moved there.
+pub(crate) fn embeddable_schema(schema: Value) -> (Value, Map<String, Value>) {
+    let mut schema = schema;
+    let mut definitions = Map::new();
+    if let Value::Object(object) = &mut schema {
+        for key in ["$defs", "definitions"] {
+            if let Some(Value::Object(defs)) = object.remove(key) {
+                definitions.extend(defs);
+            }
+        }
+        object.remove("$schema");
+        object.remove("title");
+    }
+    (schema, definitions)
+}
+
+/// Label of tool result, allowing the model to refer to it.
+pub(crate) fn label_source(call_id: &str, content: &str) -> String {
+    format!("[source id: {call_id}]\n{content}")
+}
+
+/// Returns citations for sources listed by the model.
+pub(crate) fn cited_by_model(records: &[ToolCallRecord], source_ids: &[String]) -> Vec<Citation> {
+    source_ids
+        .iter()
+        .filter_map(|id| records.iter().find(|record| record.call_id == *id))
+        .map(|record| Citation {
+            call_id: record.call_id.clone(),
+            tool: record.name.clone(),
+            source: extract_urls(&record.content)
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| record.call_id.clone()),
+            supported: None,
+        })
+        .collect()
+}
+
+/// Returns citations for URLs from tool results that appear in the answer.
+pub(crate) fn cited_in_answer(records: &[ToolCallRecord], answer: &str) -> Vec<Citation> {
+    let mut citations: Vec<Citation> = vec![];
+    for record in records.iter().filter(|record| !record.is_error) {
+        for url in extract_urls(&record.content) {
+            let is_cited = answer.contains(&url);
+            if is_cited && !citations.iter().any(|citation| citation.source == url) {
+                citations.push(Citation {
+                    call_id: record.call_id.clone(),
+                    tool: record.name.clone(),
+                    source: url,
+                    supported: None,
+                });
+            }
+        }
+    }
+    citations
+}
+
+/// Returns HTTP(S) URLs found in the text, in order of appearance.
+pub(crate) fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = vec![];
+    for (start, _) in text.match_indices("http") {
+        let candidate = &text[start..];
+        if !(candidate.starts_with("https://") || candidate.starts_with("http://")) {
+            continue;
+        }
+        let end = candidate
+            .find(|c: char| {
+                c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | '`' | ']' | ')')
+            })
+            .unwrap_or(candidate.len());
+        let url = candidate[..end].trim_end_matches(['.', ',', ';', ':', '\\']);
+        if !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cited_in_answer() {
+        let records = vec![ToolCallRecord {
+            call_id: "call_1".to_string(),
+            name: "web_search".to_string(),
+            arguments: Value::Null,
+            content: r#"[{"url":"https://www.rust-lang.org/"},{"url":"https://docs.rs"}]"#
+                .to_string(),
+            is_error: false,
+            latency: std::time::Duration::ZERO,
+            bytes: 0,
+        }];
+        let citations = cited_in_answer(&records, "Rust (see https://www.rust-lang.org/).");
+        assert_eq!(
+            citations,
+            vec![Citation {
+                call_id: "call_1".to_string(),
+                tool: "web_search".to_string(),
+                source: "https://www.rust-lang.org/".to_string(),
+                supported: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::agent::{Agent, PromptKey};
+use crate::schema::default_schema_options;
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::from_value;
+
+/// Result of the [Agent::classify].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Classification<L> {
+    /// Label assigned to the text
+    pub label: L,
+    /// Confidence of the classification, between 0 and 1
+    #[schemars(range(min = 0.0, max = 1.0))]
+    pub confidence: f32,
+}
+
+impl Agent {
+    /// Classifies the text, returning one of the labels together with the confidence.
+    ///
+    /// Labels are provided as Rust enum, its schema constraints the model to return one of the
+    /// variants. Like [Agent::extract] this is a single request, history of the agent is neither
+    /// used nor modified. Confidence is reported by the model itself, so it should be treated as
+    /// a hint, e.g. for sending uncertain cases to review.
+    ///
+    /// ```rust,no_run
+    /// # use agentai::Agent;
+    /// # use schemars::JsonSchema;
+    /// # use serde::Deserialize;
+    /// #[derive(Debug, Deserialize, JsonSchema)]
+    /// enum Sentiment {
+    ///     Positive,
+    ///     Neutral,
+    ///     Negative,
+    /// }
+    ///
+    /// # async fn example() -> anyhow::Result<()> {
+    /// let mut agent = Agent::new("");
+    /// let result = agent
+    ///     .classify::<Sentiment>("gpt-4o-mini", "I love this library!")
+    ///     .await?;
+    /// println!("{:?} ({})", result.label, result.confidence);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn classify<L>(&mut self, model: &str, text: &str) -> Result<Classification<L>>
+    where
+        L: for<'de> Deserialize<'de> + JsonSchema,
+    {
+        let schema = default_schema_options().schema_for::<Classification<L>>();
+        let instruction = self.options.prompts.render(PromptKey::Classify, &[]);
+        let value = self
+            .extract_value(model, &instruction, text, &schema)
+            .await?;
+        let mut classification: Classification<L> =
+            from_value(value).context("Model returned unknown label")?;
+        classification.confidence = classification.confidence.clamp(0.0, 1.0);
+        Ok(classification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    enum Label {
+        Spam,
+        Ham,
+    }
+
+    #[test]
+    fn test_classification_schema() {
+        let schema = default_schema_options().schema_for::<Classification<Label>>();
+        let label = &schema["properties"]["label"];
+        let label = match label.get("$ref").and_then(|r| r.as_str()) {
+            Some(reference) => &schema["$defs"][reference.trim_start_matches("#/$defs/")],
+            None => label,
+        };
+        assert_eq!(label["enum"], serde_json::json!(["Spam", "Ham"]));
+        assert_eq!(schema["properties"]["confidence"]["maximum"], 1.0);
+    }
+}
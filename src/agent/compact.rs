@@ -0,0 +1,104 @@
+use crate::agent::{Agent, PromptKey};
+use crate::event::AgentEvent;
+use crate::tool::Tool;
+use anyhow::{anyhow, Result};
+use genai::chat::{ChatMessage, ChatRole};
+use serde_json::json;
+
+/// Name of the tool, exposed to the model, that compacts the history, see [AgentBuilder::with_compact_tool](crate::agent::AgentBuilder::with_compact_tool)
+/// and [AgentSelfToolBox](crate::tool::agent_self::AgentSelfToolBox).
+pub const COMPACT_HISTORY_TOOL: &str = "compact_history";
+
+impl Agent {
+    /// Replaces older part of the conversation with its summary, freeing the context window.
+    ///
+    /// Works like `/compact` command of chat applications: messages before the last turn of the
+    /// user are summarized by the utility model (see [AgentBuilder::with_utility_model](crate::agent::AgentBuilder::with_utility_model))
+    /// and replaced with a single system message ([PromptKey::ContextSummary]). System messages
+    /// at the beginning of the history and the last turn are kept. History is not modified when
+    /// summarization fails.
+    ///
+    /// Returns number of removed messages, `0` when there is nothing to compact.
+    pub async fn compact(&mut self) -> Result<usize> {
+        let model = self.options.utility_model.clone().ok_or_else(|| {
+            anyhow!("Utility model is not configured, use AgentBuilder::with_utility_model")
+        })?;
+        let end = self
+            .history
+            .iter()
+            .rposition(|message| matches!(message.role, ChatRole::User))
+            .unwrap_or(self.history.len());
+        self.compact_before(&model, end).await
+    }
+
+    /// Replaces messages before `end` with their summary, generated by the utility model or `model`.
+    ///
+    /// Returns number of removed messages.
+    pub(crate) async fn compact_before(&mut self, model: &str, end: usize) -> Result<usize> {
+        let Some(range) = compacted_range(&self.history, end) else {
+            return Ok(0);
+        };
+        let summary = self
+            .summarize_messages(model, &self.history[range.clone()])
+            .await?;
+        let summary = self
+            .options
+            .prompts
+            .render(PromptKey::ContextSummary, &[("summary", &summary)]);
+        self.history
+            .splice(range.clone(), [ChatMessage::system(summary)]);
+        self.emit(AgentEvent::HistoryCompacted {
+            removed: range.len(),
+        });
+        Ok(range.len())
+    }
+}
+
+/// Returns range of messages replaced by the summary, all messages before `end` except system
+/// messages at the beginning of the history. Summaries of earlier compactions are kept this way.
+///
+/// Returns `None` when there is nothing to compact.
+pub(crate) fn compacted_range(
+    history: &[ChatMessage],
+    end: usize,
+) -> Option<std::ops::Range<usize>> {
+    let start = history
+        .iter()
+        .position(|message| !matches!(message.role, ChatRole::System))?;
+    (start < end).then_some(start..end)
+}
+
+pub(crate) fn compact_history_tool() -> Tool {
+    Tool {
+        name: COMPACT_HISTORY_TOOL.to_string(),
+        description: Some(
+            "Replaces older part of the conversation with its summary. Use it when the \
+             conversation becomes long and earlier details are no longer needed."
+                .to_string(),
+        ),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {}
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compacted_range() {
+        let history = vec![
+            ChatMessage::system("You are helpful assistant."),
+            ChatMessage::system("Summary of the removed part: greetings"),
+            ChatMessage::user("Weather?"),
+            ChatMessage::assistant("Sunny"),
+            ChatMessage::user("Tomorrow?"),
+            ChatMessage::assistant("Rainy"),
+        ];
+        assert_eq!(compacted_range(&history, 4), Some(2..4));
+        assert_eq!(compacted_range(&history, 2), None);
+        assert_eq!(compacted_range(&history[..2], 2), None);
+    }
+}
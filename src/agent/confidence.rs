@@ -0,0 +1,86 @@
+use crate::agent::citations::embeddable_schema;
+use crate::agent::AgentError;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Answer wrapped together with its confidence, see [AgentBuilder::with_confidence](crate::agent::AgentBuilder::with_confidence)
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    answer: Value,
+    confidence: f32,
+    #[serde(default)]
+    cannot_answer: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Returns schema of the answer wrapped together with its confidence and abstention flag.
+pub(crate) fn envelope_schema(answer_schema: Value) -> Value {
+    let (answer_schema, definitions) = embeddable_schema(answer_schema);
+    let mut schema = json!({
+        "type": "object",
+        "properties": {
+            "answer": {
+                "anyOf": [answer_schema, {"type": "null"}],
+                "description": "The answer, null when the question can't be answered"
+            },
+            "confidence": {
+                "type": "number",
+                "minimum": 0.0,
+                "maximum": 1.0,
+                "description": "Probability that the answer is correct, from 0 to 1"
+            },
+            "cannot_answer": {
+                "type": "boolean",
+                "description": "True when the question can't be answered reliably with available information"
+            },
+            "reason": {
+                "type": ["string", "null"],
+                "description": "Why the question can't be answered, null when it was answered"
+            }
+        },
+        "required": ["answer", "confidence", "cannot_answer", "reason"]
+    });
+    if !definitions.is_empty() {
+        schema["$defs"] = Value::Object(definitions);
+    }
+    schema
+}
+
+/// Unwraps the answer from the envelope, returning it with its confidence.
+///
+/// Fails with [AgentError::CannotAnswer] when the model abstained from answering.
+pub(crate) fn unwrap_envelope(envelope: Value) -> Result<(Value, f32)> {
+    let envelope: Envelope = serde_json::from_value(envelope)?;
+    if envelope.cannot_answer || envelope.answer.is_null() {
+        let reason = envelope
+            .reason
+            .unwrap_or_else(|| "no reason provided".to_string());
+        return Err(AgentError::CannotAnswer(reason).into());
+    }
+    Ok((envelope.answer, envelope.confidence.clamp(0.0, 1.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unwrap_envelope() {
+        let envelope =
+            json!({"answer": "Paris", "confidence": 1.2, "cannot_answer": false, "reason": null});
+        let (answer, confidence) = unwrap_envelope(envelope).unwrap();
+        assert_eq!(answer, json!("Paris"));
+        assert_eq!(confidence, 1.0);
+
+        let envelope =
+            json!({"answer": null, "confidence": 0.1, "cannot_answer": true, "reason": "No data"});
+        let err = unwrap_envelope(envelope).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AgentError>(),
+            Some(AgentError::CannotAnswer(reason)) if reason == "No data"
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::agent::{Agent, AgentOutput, AgentResponse, PromptKey};
+use crate::schema::canonicalize;
+use crate::tool::composite::SharedToolBox;
+use anyhow::{anyhow, Result};
+use futures::future::try_join_all;
+use genai::chat::ChatOptions;
+use log::debug;
+use serde::Serialize;
+use serde_json::{json, to_value};
+
+/// Temperature used to sample answers, it has to be high enough for samples to differ
+const SAMPLING_TEMPERATURE: f64 = 0.8;
+
+/// How the answer is chosen from samples in [Agent::run_self_consistent].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Aggregator {
+    /// The most frequent answer is chosen, ties are resolved in favour of the earlier sample.
+    /// Answers are compared after serialization, so it works best with structured output, e.g.
+    /// enums or numbers.
+    MajorityVote,
+    /// Model chooses the best answer, useful for free text answers that rarely are identical.
+    Judge {
+        /// Model judging the answers
+        model: String,
+    },
+}
+
+impl Agent {
+    /// Runs the agent `n` times independently and chooses the answer using the [Aggregator].
+    ///
+    /// Samples are run concurrently from the current state of the agent, with temperature high
+    /// enough to get different reasoning paths (deterministic mode is disabled for them). Use
+    /// [AgentBuilder::with_max_parallel_requests](crate::agent::AgentBuilder::with_max_parallel_requests)
+    /// to limit number of concurrent requests. After the run the agent continues from the state of
+    /// the chosen sample, and usage of all samples is included in [AgentResponse::usage].
+    pub async fn run_self_consistent<D>(
+        &mut self,
+        model: &str,
+        prompt: &str,
+        toolbox: Option<SharedToolBox>,
+        n: usize,
+        aggregator: Aggregator,
+    ) -> Result<AgentResponse<D>>
+    where
+        D: AgentOutput + Serialize,
+    {
+        if n == 0 {
+            return Err(anyhow!("At least one sample is required"));
+        }
+        let usage_at_start = self.usage;
+        let mut samples: Vec<Agent> = (0..n)
+            .map(|_| {
+                let mut sample = self.clone();
+                sample.options.deterministic_seed = None;
+                sample.usage = Default::default();
+                sample
+            })
+            .collect();
+        let config = ChatOptions::default().with_temperature(SAMPLING_TEMPERATURE);
+        let mut responses = try_join_all(samples.iter_mut().map(|sample| {
+            sample.run_detailed::<D>(model, prompt, toolbox.clone(), None, Some(config.clone()))
+        }))
+        .await?;
+
+        let chosen = match aggregator {
+            Aggregator::MajorityVote => majority_vote(&responses)?,
+            Aggregator::Judge { model } => self.judge_samples(&model, prompt, &responses).await?,
+        };
+        debug!("Chosen sample {chosen} of {n}");
+
+        // Usage of the judge was already recorded by the agent
+        for response in &responses {
+            self.usage.merge(&response.usage);
+        }
+        let chosen_sample = samples.swap_remove(chosen);
+        // Options of samples were adjusted for sampling
+        *self = Agent {
+            usage: self.usage,
+            options: self.options.clone(),
+            ..chosen_sample
+        };
+
+        let mut response = responses.swap_remove(chosen);
+        response.usage = self.usage.since(&usage_at_start);
+        Ok(response)
+    }
+
+    /// Asks the model to choose the best answer, returning its index.
+    async fn judge_samples<D: Serialize>(
+        &mut self,
+        model: &str,
+        prompt: &str,
+        responses: &[AgentResponse<D>],
+    ) -> Result<usize> {
+        let mut input = format!("# Question\n{prompt}");
+        for (idx, response) in responses.iter().enumerate() {
+            let answer = match to_value(&response.output)? {
+                serde_json::Value::String(answer) => answer,
+                answer => answer.to_string(),
+            };
+            input.push_str(&format!("\n\n# Answer {}\n{answer}", idx + 1));
+        }
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "answer": {
+                    "type": "integer",
+                    "description": "Number of the chosen answer"
+                }
+            },
+            "required": ["answer"]
+        });
+        let instruction = self
+            .options
+            .prompts
+            .render(PromptKey::SelfConsistencyJudge, &[]);
+        let choice = self
+            .extract_value(model, &instruction, &input, &schema)
+            .await?;
+        choice["answer"]
+            .as_u64()
+            .and_then(|answer| (answer as usize).checked_sub(1))
+            .filter(|idx| *idx < responses.len())
+            .ok_or_else(|| anyhow!("Judge chose unknown answer: {choice}"))
+    }
+}
+
+/// Returns index of the most frequent answer.
+fn majority_vote<D: Serialize>(responses: &[AgentResponse<D>]) -> Result<usize> {
+    let answers = responses
+        .iter()
+        .map(|response| Ok(canonicalize(to_value(&response.output)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let votes = |answer| answers.iter().filter(|other| *other == answer).count();
+    let mut chosen = 0;
+    for (idx, answer) in answers.iter().enumerate() {
+        if votes(answer) > votes(&answers[chosen]) {
+            chosen = idx;
+        }
+    }
+    Ok(chosen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(output: u32) -> AgentResponse<u32> {
+        AgentResponse {
+            output,
+            citations: vec![],
+            unsupported_claims: vec![],
+            confidence: None,
+            usage: Default::default(),
+            tool_usage: Default::default(),
+            metadata: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_majority_vote() {
+        let responses = [3, 7, 7, 3, 7].map(response);
+        assert_eq!(majority_vote(&responses).unwrap(), 1);
+        let responses = [1, 2].map(response);
+        assert_eq!(majority_vote(&responses).unwrap(), 0);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::agent::response::ToolCallRecord;
+use crate::agent::{PromptKey, Prompts};
+use genai::chat::ToolCall;
+
+/// Handling of tool calls identical to calls already made in the run, see [AgentBuilder::with_duplicate_tool_calls](crate::agent::AgentBuilder::with_duplicate_tool_calls).
+///
+/// Calls are identical when they have the same tool name and arguments. Models prone to loops tend
+/// to repeat the same call (e.g. the same search), wasting iterations and money.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateToolCalls {
+    /// Tool is executed again.
+    #[default]
+    Execute,
+    /// Tool is not executed, result of the previous call is returned.
+    ReuseResult,
+    /// Tool is not executed, model receives result of the previous call together with a reminder
+    /// to use it instead of repeating the call.
+    Nudge,
+}
+
+/// Returns result of the previous identical call, when it has to be reused instead of executing the tool.
+pub(crate) fn reused_result(
+    policy: DuplicateToolCalls,
+    records: &[ToolCallRecord],
+    tool_call: &ToolCall,
+    prompts: &Prompts,
+) -> Option<String> {
+    if policy == DuplicateToolCalls::Execute {
+        return None;
+    }
+    let previous = records.iter().find(|record| {
+        record.name == tool_call.fn_name && record.arguments == tool_call.fn_arguments
+    })?;
+    match policy {
+        DuplicateToolCalls::Execute => None,
+        DuplicateToolCalls::ReuseResult => Some(previous.content.clone()),
+        DuplicateToolCalls::Nudge => Some(prompts.render(
+            PromptKey::DuplicateToolCall,
+            &[("tool", &previous.name), ("result", &previous.content)],
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reused_result() {
+        let records = vec![ToolCallRecord {
+            call_id: "call_1".to_string(),
+            name: "search".to_string(),
+            arguments: json!({"query": "rust", "limit": 5}),
+            content: "results".to_string(),
+            is_error: false,
+            latency: Duration::ZERO,
+            bytes: 7,
+        }];
+        let repeated = ToolCall {
+            call_id: "call_2".to_string(),
+            fn_name: "search".to_string(),
+            fn_arguments: json!({"limit": 5, "query": "rust"}),
+        };
+        let prompts = Prompts::default();
+        let policy = DuplicateToolCalls::ReuseResult;
+        assert_eq!(
+            reused_result(policy, &records, &repeated, &prompts).as_deref(),
+            Some("results")
+        );
+        assert_eq!(
+            reused_result(DuplicateToolCalls::Execute, &records, &repeated, &prompts),
+            None
+        );
+
+        let different = ToolCall {
+            fn_arguments: json!({"query": "rust", "limit": 10}),
+            ..repeated
+        };
+        assert_eq!(reused_result(policy, &records, &different, &prompts), None);
+    }
+}
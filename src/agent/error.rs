@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+/// Errors returned by [Agent](crate::agent::Agent) runs, that may require special handling.
+///
+/// Runs return [anyhow::Error], use [anyhow::Error::downcast_ref] to check for these errors.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum AgentError {
+    /// Run was not making progress and was aborted, see [StallAction::Abort](crate::agent::StallAction::Abort).
+    #[error("Agent stalled: {0}")]
+    Stalled(String),
+    /// Model abstained from answering, with the reason provided by it, see [AgentBuilder::with_confidence](crate::agent::AgentBuilder::with_confidence).
+    #[error("Model cannot answer: {0}")]
+    CannotAnswer(String),
+    /// Daily usage limit of the tenant was reached, see [QuotaManager](crate::agent::QuotaManager).
+    #[error("Daily quota of tenant `{0}` exceeded")]
+    QuotaExceeded(String),
+    /// Answer of the model doesn't match the output type, it contains the whole text of the
+    /// answer, so it can be logged or recovered from, see also [AgentBuilder::with_on_parse_error](crate::agent::AgentBuilder::with_on_parse_error).
+    #[error("Unable to parse answer of the model: {error}")]
+    InvalidOutput {
+        /// Raw text of the answer
+        text: String,
+        /// Error of the deserialization
+        #[source]
+        error: serde_json::Error,
+    },
+}
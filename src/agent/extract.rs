@@ -0,0 +1,161 @@
+use crate::agent::parse::parse_json;
+use crate::agent::{Agent, AgentError, PromptKey};
+use crate::schema::default_schema_options;
+use anyhow::{anyhow, Context, Result};
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, MessageContent};
+use log::debug;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{from_value, Value};
+
+/// Default maximal number of characters of the text extracted in a single request
+pub const DEFAULT_EXTRACTION_CHUNK_LEN: usize = 20_000;
+
+impl Agent {
+    /// Extracts structured information of type `T` from the text.
+    ///
+    /// Unlike [Agent::run], extraction is a single-shot request optimized for this task: history of
+    /// the agent is neither used nor modified, tools are not available and output is always forced
+    /// to match the schema of `T` (according to [StructuredOutputMode](crate::agent::StructuredOutputMode)).
+    ///
+    /// Long texts are split into chunks (see [AgentBuilder::with_extraction_chunk_len](crate::agent::AgentBuilder::with_extraction_chunk_len)),
+    /// information is extracted from every chunk separately and then partial extractions are
+    /// merged by the model into the final result.
+    ///
+    /// ```rust,no_run
+    /// # use agentai::Agent;
+    /// # use schemars::JsonSchema;
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize, JsonSchema)]
+    /// struct Invoice {
+    ///     number: String,
+    ///     total: f64,
+    /// }
+    ///
+    /// # async fn example(text: &str) -> anyhow::Result<()> {
+    /// let mut agent = Agent::new("");
+    /// let invoice: Invoice = agent.extract("gpt-4o-mini", text).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn extract<T>(&mut self, model: &str, text: &str) -> Result<T>
+    where
+        T: DeserializeOwned + JsonSchema,
+    {
+        let schema = default_schema_options().schema_for::<T>();
+        let chunk_len = self
+            .options
+            .extraction_chunk_len
+            .unwrap_or(DEFAULT_EXTRACTION_CHUNK_LEN);
+        let chunks = split_text(text, chunk_len);
+        let instruction = self.options.prompts.render(PromptKey::Extract, &[]);
+
+        let mut partials = Vec::with_capacity(chunks.len());
+        for (idx, chunk) in chunks.iter().enumerate() {
+            debug!("Extracting chunk {} of {}", idx + 1, chunks.len());
+            partials.push(
+                self.extract_value(model, &instruction, chunk, &schema)
+                    .await?,
+            );
+        }
+
+        let value = if partials.len() == 1 {
+            partials.remove(0)
+        } else {
+            debug!("Merging {} partial extractions", partials.len());
+            let partials = serde_json::to_string(&partials)?;
+            let instruction = self.options.prompts.render(PromptKey::ExtractMerge, &[]);
+            self.extract_value(model, &instruction, &partials, &schema)
+                .await?
+        };
+        from_value(value).context("Extracted data doesn't match the requested type")
+    }
+
+    /// Sends single extraction request, returning JSON answer of the model.
+    pub(super) async fn extract_value(
+        &mut self,
+        model: &str,
+        instruction: &str,
+        input: &str,
+        schema: &Value,
+    ) -> Result<Value> {
+        let model = self.current_model(model);
+        let mut chat_opts = ChatOptions::default().with_temperature(0.0);
+        if let Some(seed) = self.options.deterministic_seed {
+            chat_opts = chat_opts.with_seed(seed);
+        }
+        let (chat_opts, output_instruction) =
+            self.apply_output_schema(schema.clone(), &model, chat_opts);
+
+        let mut chat_req = ChatRequest::new(vec![
+            ChatMessage::system(instruction),
+            ChatMessage::user(input),
+        ]);
+        if let Some(output_instruction) = output_instruction {
+            chat_req = chat_req.with_system(output_instruction);
+        }
+        let chat_resp = self.exec_chat(&model, chat_req, &chat_opts).await?;
+        self.record_usage(&model, &chat_resp.usage);
+
+        let text = chat_resp
+            .content
+            .into_iter()
+            .find_map(|content| match content {
+                MessageContent::Text(text) => Some(text),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Model didn't return text response"))?;
+        parse_json(&text, self.options.output_parsing)
+            .map_err(|error| AgentError::InvalidOutput { text, error }.into())
+    }
+}
+
+/// Splits text into chunks of at most `max_len` characters.
+///
+/// Chunks are split on paragraph boundaries when possible, then on lines and words.
+pub(crate) fn split_text(text: &str, max_len: usize) -> Vec<&str> {
+    let max_len = max_len.max(1);
+    let mut chunks = vec![];
+    let mut rest = text;
+    while rest.chars().count() > max_len {
+        // Byte offset of the character that doesn't fit into the chunk
+        let limit = rest
+            .char_indices()
+            .nth(max_len)
+            .map(|(idx, _)| idx)
+            .unwrap_or(rest.len());
+        let head = &rest[..limit];
+        let split = ["\n\n", "\n", " "]
+            .iter()
+            .find_map(|separator| {
+                head.rfind(separator)
+                    .map(|idx| idx + separator.len())
+                    .filter(|&idx| idx > 0)
+            })
+            .unwrap_or(limit);
+        chunks.push(&rest[..split]);
+        rest = &rest[split..];
+    }
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_text() {
+        assert_eq!(split_text("short", 10), vec!["short"]);
+        assert_eq!(split_text("", 10), vec![""]);
+        assert_eq!(
+            split_text("first\n\nsecond line\nthird", 16),
+            vec!["first\n\n", "second line\n", "third"]
+        );
+        assert_eq!(split_text("one two three", 8), vec!["one two ", "three"]);
+        assert_eq!(split_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(split_text("żółw żółw", 5), vec!["żółw ", "żółw"]);
+    }
+}
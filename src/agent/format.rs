@@ -0,0 +1,168 @@
+use genai::adapter::AdapterKind;
+use serde_json::{from_str, Value};
+
+/// Format of tool results sent to the model, see [AgentBuilder::with_result_format](crate::agent::AgentBuilder::with_result_format).
+///
+/// Tools return results as strings, often containing JSON (sometimes encoded twice, as JSON string
+/// containing JSON). Some models handle better plain text, other JSON, so results can be converted
+/// before sending them to the model. Conversion is applied before [ToolResultLimit](crate::agent::ToolResultLimit),
+/// so notes about truncation are added to the converted result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFormat {
+    /// JSON is rendered as lines of text, e.g. `key: value`, texts are sent unchanged.
+    PlainText,
+    /// Result is always valid JSON, texts are sent as JSON strings. JSON encoded twice is decoded.
+    Json,
+    /// JSON is sent as markdown code block, texts are sent unchanged.
+    Markdown,
+}
+
+impl ResultFormat {
+    /// Converts tool result to the format.
+    pub fn apply(&self, content: &str) -> String {
+        let value = decode(content);
+        match (self, value) {
+            (ResultFormat::Json, Some(value)) => value.to_string(),
+            (ResultFormat::Json, None) => Value::String(content.to_string()).to_string(),
+            (_, Some(Value::String(text))) => text,
+            (_, None) => content.to_string(),
+            (ResultFormat::PlainText, Some(value)) => {
+                text_contents(&value).unwrap_or_else(|| render_plain(&value, 0))
+            }
+            (ResultFormat::Markdown, Some(value)) => match text_contents(&value) {
+                Some(text) => text,
+                None => format!(
+                    "```json\n{}\n```",
+                    serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string())
+                ),
+            },
+        }
+    }
+}
+
+/// Formats of tool results chosen by tool and provider.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ResultFormats {
+    pub(crate) default: Option<ResultFormat>,
+    pub(crate) providers: Vec<(AdapterKind, ResultFormat)>,
+    pub(crate) tools: Vec<(String, ResultFormat)>,
+}
+
+impl ResultFormats {
+    /// Returns format of the result, tool settings take precedence over provider settings.
+    pub(crate) fn get(
+        &self,
+        tool: &str,
+        adapter_kind: Option<AdapterKind>,
+    ) -> Option<ResultFormat> {
+        self.tools
+            .iter()
+            .find(|(name, _)| name == tool)
+            .map(|(_, format)| *format)
+            .or_else(|| {
+                self.providers
+                    .iter()
+                    .find(|(kind, _)| Some(*kind) == adapter_kind)
+                    .map(|(_, format)| *format)
+            })
+            .or(self.default)
+    }
+}
+
+/// Parses JSON result, decoding JSON encoded as JSON string. Returns `None` for texts.
+fn decode(content: &str) -> Option<Value> {
+    match from_str::<Value>(content.trim()).ok()? {
+        Value::String(inner) => match from_str::<Value>(inner.trim()) {
+            Ok(value @ (Value::Object(_) | Value::Array(_))) => Some(value),
+            _ => Some(Value::String(inner)),
+        },
+        value => Some(value),
+    }
+}
+
+/// Returns texts of MCP like content list, e.g. `[{"type": "text", "text": "..."}]`.
+fn text_contents(value: &Value) -> Option<String> {
+    let texts = value
+        .as_array()?
+        .iter()
+        .map(|item| match item.get("type")?.as_str()? {
+            "text" => item.get("text")?.as_str().map(str::to_string),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!texts.is_empty()).then(|| texts.join("\n"))
+}
+
+/// Renders JSON as indented lines of text.
+fn render_plain(value: &Value, indent: usize) -> String {
+    let prefix = "  ".repeat(indent);
+    match value {
+        Value::Object(object) => object
+            .iter()
+            .map(|(key, value)| match value {
+                Value::Object(_) | Value::Array(_) if !is_empty(value) => {
+                    format!("{prefix}{key}:\n{}", render_plain(value, indent + 1))
+                }
+                _ => format!("{prefix}{key}: {}", render_scalar(value)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Array(array) => array
+            .iter()
+            .map(|value| match value {
+                Value::Object(_) | Value::Array(_) if !is_empty(value) => {
+                    format!("{prefix}-\n{}", render_plain(value, indent + 1))
+                }
+                _ => format!("{prefix}- {}", render_scalar(value)),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => format!("{prefix}{}", render_scalar(value)),
+    }
+}
+
+fn render_scalar(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        _ => value.to_string(),
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Object(object) => object.is_empty(),
+        Value::Array(array) => array.is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_format() {
+        let double_encoded = r#""{\"city\":\"Warsaw\",\"temp\":21}""#;
+        assert_eq!(
+            ResultFormat::Json.apply(double_encoded),
+            r#"{"city":"Warsaw","temp":21}"#
+        );
+        assert_eq!(
+            ResultFormat::PlainText.apply(double_encoded),
+            "city: Warsaw\ntemp: 21"
+        );
+        assert_eq!(
+            ResultFormat::Markdown.apply(double_encoded),
+            "```json\n{\n  \"city\": \"Warsaw\",\n  \"temp\": 21\n}\n```"
+        );
+
+        let mcp = r#"[{"type":"text","text":"first"},{"type":"text","text":"second"}]"#;
+        assert_eq!(ResultFormat::PlainText.apply(mcp), "first\nsecond");
+        assert_eq!(ResultFormat::Json.apply("plain text"), r#""plain text""#);
+        assert_eq!(ResultFormat::PlainText.apply("plain text"), "plain text");
+        assert_eq!(
+            ResultFormat::PlainText.apply(r#"{"meta":{},"tags":["a","b"]}"#),
+            "meta: {}\ntags:\n  - a\n  - b"
+        );
+    }
+}
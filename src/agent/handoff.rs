@@ -0,0 +1,177 @@
+use crate::agent::{Agent, TokenUsage};
+use crate::tool::composite::SharedToolBox;
+use crate::tool::{Tool, ToolError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Name of the tool used by the model to hand off the task to other agent
+pub const TRANSFER_TO_TOOL: &str = "transfer_to";
+
+/// Agent that the coordinator can hand off the task to, see [AgentBuilder::with_handoff](crate::agent::AgentBuilder::with_handoff).
+///
+/// The coordinator calls [TRANSFER_TO_TOOL] tool, providing the name of the agent and the context of
+/// the task. The target agent doesn't see history of the coordinator, it receives only the context
+/// written by the coordinator. Every handoff starts from the state the agent had when registered, so
+/// handoffs don't influence each other. When the target agent answers, control returns to the
+/// coordinator with the answer as the tool result.
+///
+/// ```rust,no_run
+/// # use agentai::{Agent, Handoff};
+/// let billing = Agent::new("You are responsible for billing questions of our customers.");
+/// let coordinator = Agent::builder()
+///     .with_system("You are a customer support agent.")
+///     .with_handoff(Handoff::new("billing", "Answers questions about invoices", billing, "gpt-4o-mini"))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct Handoff {
+    name: String,
+    description: String,
+    agent: Agent,
+    model: String,
+    toolbox: Option<SharedToolBox>,
+}
+
+impl Handoff {
+    /// Creates handoff target, `description` is shown to the coordinator to decide when to use it.
+    pub fn new(name: &str, description: &str, agent: Agent, model: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            agent,
+            model: model.to_string(),
+            toolbox: None,
+        }
+    }
+
+    /// Sets toolbox available to the target agent.
+    pub fn with_toolbox(mut self, toolbox: SharedToolBox) -> Self {
+        self.toolbox = Some(toolbox);
+        self
+    }
+
+    /// Returns name of the target agent.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[derive(Deserialize)]
+struct TransferArguments {
+    agent_name: String,
+    context: String,
+}
+
+/// Returns definition of the tool handing off the task to one of the agents.
+pub(crate) fn transfer_to_tool(handoffs: &[Handoff]) -> Tool {
+    let agents = handoffs
+        .iter()
+        .map(|handoff| format!("- {}: {}", handoff.name, handoff.description))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Tool {
+        name: TRANSFER_TO_TOOL.to_string(),
+        description: Some(format!(
+            "Hands off the task to other agent and returns its answer. The agent doesn't see this \
+             conversation, provide all information it needs in the context. Available agents:\n{agents}"
+        )),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "agent_name": {
+                    "type": "string",
+                    "enum": handoffs.iter().map(|handoff| handoff.name.as_str()).collect::<Vec<_>>(),
+                    "description": "Name of the agent"
+                },
+                "context": {
+                    "type": "string",
+                    "description": "Task for the agent, together with relevant facts from the conversation"
+                }
+            },
+            "required": ["agent_name", "context"]
+        })),
+    }
+}
+
+/// Runs the target agent, returning its answer and tokens used by it.
+pub(crate) async fn transfer(
+    handoffs: &[Handoff],
+    arguments: Value,
+) -> Result<(String, TokenUsage), ToolError> {
+    let args: TransferArguments =
+        serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+            tool: TRANSFER_TO_TOOL.to_string(),
+            error: e.to_string(),
+            arguments: Box::new(arguments),
+            schema: Box::new(transfer_to_tool(handoffs).schema.unwrap_or_default()),
+        })?;
+    let handoff = handoffs
+        .iter()
+        .find(|handoff| handoff.name == args.agent_name)
+        .ok_or_else(|| ToolError::NoToolFound(args.agent_name.clone()))?;
+
+    let mut agent = handoff.agent.clone();
+    let toolbox = handoff.toolbox.clone();
+    // Target agent can hand off further, so the future has to be boxed
+    let response =
+        Box::pin(agent.run_detailed::<String>(&handoff.model, &args.context, toolbox, None, None))
+            .await?;
+    Ok((response.output, response.usage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{AgentBuilder, Approval};
+    use genai::chat::{MessageContent, ToolCall};
+
+    fn coordinator() -> AgentBuilder {
+        let billing = Agent::new("You are responsible for billing questions.");
+        Agent::builder().with_handoff(Handoff::new(
+            "billing",
+            "Answers questions about invoices",
+            billing,
+            "gpt-4o-mini",
+        ))
+    }
+
+    /// Hands off the task and returns the result stored for the model.
+    async fn transfer_result(agent: &mut Agent) -> String {
+        let tool_call = ToolCall {
+            call_id: "call_1".to_string(),
+            fn_name: TRANSFER_TO_TOOL.to_string(),
+            fn_arguments: json!({"agent_name": "billing", "context": "Refund invoice 42"}),
+        };
+        agent
+            .handle_tool_call("gpt-4o", None, tool_call)
+            .await
+            .unwrap();
+        match &agent.history().last().unwrap().content {
+            MessageContent::ToolResponses(responses) => responses[0].content.clone(),
+            content => panic!("Unexpected content {content:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_checks() {
+        let mut agent = coordinator()
+            .with_approval(|_: &ToolCall| Approval::Deny("handoffs need review".to_string()))
+            .build();
+        assert_eq!(
+            transfer_result(&mut agent).await,
+            "Tool call `transfer_to` was denied: handoffs need review"
+        );
+
+        let mut agent = coordinator().with_allowed_tools(["web_search"]).build();
+        let (tools, _) = agent.tools_definitions(None, "gpt-4o").unwrap();
+        assert!(tools.is_empty());
+        assert_eq!(
+            transfer_result(&mut agent).await,
+            "Tool `transfer_to` is not allowed"
+        );
+
+        let agent = coordinator().with_allowed_tools([TRANSFER_TO_TOOL]).build();
+        let (tools, _) = agent.tools_definitions(None, "gpt-4o").unwrap();
+        assert_eq!(tools[0].name, TRANSFER_TO_TOOL);
+    }
+}
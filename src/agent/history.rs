@@ -0,0 +1,284 @@
+use crate::agent::{PromptKey, Prompts};
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatMessage, ContentPart, MessageContent, ToolResponse};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+/// Handling of images attached to messages of the history, see [AgentBuilder::with_image_resend](crate::agent::AgentBuilder::with_image_resend).
+///
+/// History is sent to the model in every iteration of every run, so large images are paid for
+/// again and again. Models usually describe what they saw in their answers, which is often
+/// enough for following requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageResend {
+    /// Images are sent in every request.
+    #[default]
+    Always,
+    /// Image is sent only in the first request containing it. In following requests, including
+    /// following iterations of the same run, it is replaced with [PromptKey::ImageOmitted].
+    /// Identical images attached more than once are sent only once.
+    FirstOccurrence,
+}
+
+/// How results of tools called in the same iteration are sent to the model, see [AgentBuilder::with_tool_result_batching](crate::agent::AgentBuilder::with_tool_result_batching).
+///
+/// Providers differ in requirements on tool results: some expect a separate message for every
+/// result, following the assistant message with tool calls, others accept all results in a single
+/// message, which saves tokens of message overhead when many tools are called at once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ToolResultBatching {
+    /// Every tool result is sent as a separate message.
+    #[default]
+    Separate,
+    /// Consecutive tool results are sent as a single message containing all of them.
+    Batched,
+}
+
+/// Batching of tool results chosen by provider.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ToolResultBatchings {
+    pub(crate) default: ToolResultBatching,
+    pub(crate) providers: Vec<(AdapterKind, ToolResultBatching)>,
+}
+
+impl ToolResultBatchings {
+    /// Returns batching of results sent to the model, provider settings take precedence.
+    pub(crate) fn get(&self, model: &str) -> ToolResultBatching {
+        let adapter_kind = AdapterKind::from_model(model).ok();
+        self.providers
+            .iter()
+            .find(|(kind, _)| Some(*kind) == adapter_kind)
+            .map(|(_, batching)| *batching)
+            .unwrap_or(self.default)
+    }
+}
+
+/// Settings of [prepare_history], taken from options of the agent
+pub(crate) struct HistoryPolicy<'a> {
+    pub(crate) images: ImageResend,
+    pub(crate) batching: ToolResultBatching,
+    /// Minimal length of tool results that are compacted when repeated, disabled when `None`
+    pub(crate) repeated_result_len: Option<usize>,
+    pub(crate) prompts: &'a Prompts,
+}
+
+/// Returns history that is sent to the model, with repeated content replaced by placeholders.
+///
+/// `sent_images` keeps hashes of images already sent to the model, it is updated with images of
+/// the returned history. History of the agent is never modified, so the policy can be changed later.
+pub(crate) fn prepare_history(
+    history: &[ChatMessage],
+    policy: &HistoryPolicy,
+    sent_images: &mut HashSet<u64>,
+) -> Vec<ChatMessage> {
+    if policy.images == ImageResend::Always
+        && policy.repeated_result_len.is_none()
+        && policy.batching == ToolResultBatching::Separate
+    {
+        return history.to_vec();
+    }
+    let mut sent_now = HashSet::new();
+    // Call ids of large tool results already included in the request, by hash of their content
+    let mut results = HashMap::new();
+    let history = history
+        .iter()
+        .map(|message| {
+            let content = match &message.content {
+                MessageContent::Parts(parts) if policy.images == ImageResend::FirstOccurrence => {
+                    MessageContent::Parts(
+                        parts
+                            .iter()
+                            .map(|part| {
+                                image_part(part, policy.prompts, sent_images, &mut sent_now)
+                            })
+                            .collect(),
+                    )
+                }
+                MessageContent::ToolResponses(responses) => match policy.repeated_result_len {
+                    Some(min_len) => MessageContent::ToolResponses(
+                        responses
+                            .iter()
+                            .map(|response| {
+                                tool_response(response, min_len, policy.prompts, &mut results)
+                            })
+                            .collect(),
+                    ),
+                    None => message.content.clone(),
+                },
+                content => content.clone(),
+            };
+            ChatMessage {
+                content,
+                ..message.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    sent_images.extend(sent_now);
+    match policy.batching {
+        ToolResultBatching::Separate => history,
+        ToolResultBatching::Batched => batch_tool_responses(history),
+    }
+}
+
+/// Merges consecutive messages with tool responses into a single message.
+fn batch_tool_responses(history: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let mut batched: Vec<ChatMessage> = Vec::with_capacity(history.len());
+    for message in history {
+        if let (Some(last), MessageContent::ToolResponses(responses)) =
+            (batched.last_mut(), &message.content)
+        {
+            if let MessageContent::ToolResponses(last_responses) = &mut last.content {
+                last_responses.extend(responses.iter().cloned());
+                continue;
+            }
+        }
+        batched.push(message);
+    }
+    batched
+}
+
+/// Returns the part, or placeholder when it is an image that was already sent.
+fn image_part(
+    part: &ContentPart,
+    prompts: &Prompts,
+    sent_images: &HashSet<u64>,
+    sent_now: &mut HashSet<u64>,
+) -> ContentPart {
+    if !matches!(part, ContentPart::Image { .. }) {
+        return part.clone();
+    }
+    // Serialized part includes content type and source of the image
+    let hash = hash(&serde_json::to_string(part).unwrap_or_default());
+    if sent_images.contains(&hash) || !sent_now.insert(hash) {
+        return ContentPart::Text(prompts.render(PromptKey::ImageOmitted, &[]));
+    }
+    part.clone()
+}
+
+/// Returns the response, or placeholder when it is a large result identical to an earlier one.
+fn tool_response(
+    response: &ToolResponse,
+    min_len: usize,
+    prompts: &Prompts,
+    results: &mut HashMap<u64, String>,
+) -> ToolResponse {
+    if response.content.len() < min_len {
+        return response.clone();
+    }
+    match results.get(&hash(&response.content)) {
+        Some(call_id) => ToolResponse::new(
+            response.call_id.clone(),
+            prompts.render(PromptKey::RepeatedResult, &[("call_id", call_id)]),
+        ),
+        None => {
+            results.insert(hash(&response.content), response.call_id.clone());
+            response.clone()
+        }
+    }
+}
+
+fn hash(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_history() {
+        let image = ContentPart::from_image_url("image/png", "https://example.com/cat.png");
+        let history = vec![
+            ChatMessage::user(vec![ContentPart::from_text("What is it?"), image.clone()]),
+            ChatMessage::from(ToolResponse::new("call_1", "x".repeat(100))),
+            ChatMessage::from(ToolResponse::new("call_2", "x".repeat(100))),
+            ChatMessage::from(ToolResponse::new("call_3", "short")),
+            ChatMessage::from(ToolResponse::new("call_4", "short")),
+        ];
+        let prompts = Prompts::default();
+        let policy = HistoryPolicy {
+            images: ImageResend::FirstOccurrence,
+            batching: ToolResultBatching::Separate,
+            repeated_result_len: Some(50),
+            prompts: &prompts,
+        };
+        let mut sent_images = HashSet::new();
+        let first = prepare_history(&history, &policy, &mut sent_images);
+        assert!(matches!(
+            &first[0].content,
+            MessageContent::Parts(parts) if matches!(parts[1], ContentPart::Image { .. })
+        ));
+        let result = |message: &ChatMessage| match &message.content {
+            MessageContent::ToolResponses(responses) => responses[0].content.clone(),
+            _ => String::new(),
+        };
+        assert_eq!(result(&first[1]), "x".repeat(100));
+        assert_eq!(
+            result(&first[2]),
+            prompts.render(PromptKey::RepeatedResult, &[("call_id", "call_1")])
+        );
+        assert_eq!(result(&first[4]), "short");
+
+        let second = prepare_history(&history, &policy, &mut sent_images);
+        assert!(matches!(
+            &second[0].content,
+            MessageContent::Parts(parts) if matches!(parts[1], ContentPart::Text(_))
+        ));
+
+        let policy = HistoryPolicy {
+            batching: ToolResultBatching::Batched,
+            ..policy
+        };
+        let batched = prepare_history(&history, &policy, &mut sent_images);
+        assert_eq!(batched.len(), 2);
+        assert!(matches!(
+            &batched[1].content,
+            MessageContent::ToolResponses(responses) if responses.len() == 4
+        ));
+    }
+
+    #[test]
+    fn test_batching_by_provider() {
+        let batchings = ToolResultBatchings {
+            default: ToolResultBatching::Batched,
+            providers: vec![(AdapterKind::Anthropic, ToolResultBatching::Separate)],
+        };
+        assert_eq!(batchings.get("gpt-4o"), ToolResultBatching::Batched);
+        assert_eq!(
+            batchings.get("claude-3-5-sonnet-latest"),
+            ToolResultBatching::Separate
+        );
+        assert_eq!(
+            ToolResultBatchings::default().get("gpt-4o"),
+            ToolResultBatching::Separate
+        );
+    }
+
+    #[test]
+    fn test_batching_keeps_iterations_apart() {
+        let response = |id: &str| ChatMessage::from(ToolResponse::new(id, "ok"));
+        let history = vec![
+            ChatMessage::user("Check both"),
+            response("call_1"),
+            response("call_2"),
+            ChatMessage::assistant("Checking again"),
+            response("call_3"),
+        ];
+        let batched = batch_tool_responses(history);
+        let ids = batched
+            .iter()
+            .map(|message| match &message.content {
+                MessageContent::ToolResponses(responses) => responses
+                    .iter()
+                    .map(|response| response.call_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                _ => String::new(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["", "call_1,call_2", "", "call_3"]);
+    }
+}
@@ -0,0 +1,106 @@
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatMessage, ChatRequest, ChatRole};
+
+/// Role of messages with instructions added by the agent, see [AgentBuilder::with_instruction_role](crate::agent::AgentBuilder::with_instruction_role).
+///
+/// The agent injects instructions about tools, output schema, response language or the final
+/// answer. Providers treat roles differently, e.g. OpenAI reasoning models give developer
+/// messages priority over the conversation, while some local models accept only a single system
+/// message at the beginning of the conversation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InstructionRole {
+    /// Instructions are sent as the system prompt of the request, together with the system message
+    /// of the conversation.
+    #[default]
+    System,
+    /// Instructions are sent as a separate message right after the system message of the
+    /// conversation. GenAI doesn't have a dedicated developer role, the message is sent with the
+    /// system role, which OpenAI treats as a developer message for reasoning models.
+    Developer,
+    /// Instructions are sent as a user message before the last prompt of the user, for models
+    /// that ignore or reject additional system messages.
+    User,
+}
+
+impl InstructionRole {
+    /// Adds instructions to the request, as a message with this role.
+    pub(crate) fn apply(&self, request: ChatRequest, instructions: String) -> ChatRequest {
+        match self {
+            InstructionRole::System => request.with_system(instructions),
+            InstructionRole::Developer => {
+                let mut request = request;
+                let position = request
+                    .messages
+                    .iter()
+                    .position(|message| !matches!(message.role, ChatRole::System))
+                    .unwrap_or(request.messages.len());
+                request
+                    .messages
+                    .insert(position, ChatMessage::system(instructions));
+                request
+            }
+            InstructionRole::User => {
+                let mut request = request;
+                let position = request
+                    .messages
+                    .iter()
+                    .rposition(|message| matches!(message.role, ChatRole::User))
+                    .unwrap_or(request.messages.len());
+                request
+                    .messages
+                    .insert(position, ChatMessage::user(instructions));
+                request
+            }
+        }
+    }
+}
+
+/// Roles of instructions chosen by provider.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct InstructionRoles {
+    pub(crate) default: InstructionRole,
+    pub(crate) providers: Vec<(AdapterKind, InstructionRole)>,
+}
+
+impl InstructionRoles {
+    /// Returns role of instructions sent to the model, provider settings take precedence.
+    pub(crate) fn get(&self, model: &str) -> InstructionRole {
+        let adapter_kind = AdapterKind::from_model(model).ok();
+        self.providers
+            .iter()
+            .find(|(kind, _)| Some(*kind) == adapter_kind)
+            .map(|(_, role)| *role)
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction_roles() {
+        let request = ChatRequest::new(vec![
+            ChatMessage::system("You are helpful assistant."),
+            ChatMessage::user("Hi"),
+            ChatMessage::assistant("Hello!"),
+            ChatMessage::user("Weather?"),
+        ]);
+        let system = InstructionRole::System.apply(request.clone(), "Be brief.".to_string());
+        assert_eq!(system.system.as_deref(), Some("Be brief."));
+        assert_eq!(system.messages.len(), 4);
+
+        let developer = InstructionRole::Developer.apply(request.clone(), "Be brief.".to_string());
+        assert!(developer.system.is_none());
+        assert!(matches!(developer.messages[1].role, ChatRole::System));
+        assert_eq!(
+            developer.messages[1].content.text_as_str(),
+            Some("Be brief.")
+        );
+
+        let user = InstructionRole::User.apply(request, "Be brief.".to_string());
+        assert_eq!(user.messages[3].content.text_as_str(), Some("Be brief."));
+        assert!(matches!(user.messages[3].role, ChatRole::User));
+        assert_eq!(user.messages[4].content.text_as_str(), Some("Weather?"));
+    }
+}
@@ -0,0 +1,37 @@
+use crate::agent::{PromptKey, Prompts};
+
+/// Language of the answers given by the agent, see [AgentBuilder::with_response_language](crate::agent::AgentBuilder::with_response_language).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseLanguage {
+    /// Model detects language of the last user message and answers in it.
+    Auto,
+    /// Model always answers in the given language, e.g. `"German"` or `"pl-PL"`.
+    Fixed(String),
+}
+
+impl ResponseLanguage {
+    /// Returns instruction added to the request, next to the system message.
+    pub(crate) fn instruction(&self, prompts: &Prompts) -> String {
+        match self {
+            ResponseLanguage::Auto => prompts.render(PromptKey::LanguageAuto, &[]),
+            ResponseLanguage::Fixed(language) => {
+                prompts.render(PromptKey::LanguageFixed, &[("language", language)])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instruction() {
+        assert!(ResponseLanguage::Fixed("German".to_string())
+            .instruction(&Prompts::default())
+            .starts_with("Always answer in German,"));
+        assert!(ResponseLanguage::Auto
+            .instruction(&Prompts::default())
+            .contains("last message"));
+    }
+}
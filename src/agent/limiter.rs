@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Limits number of concurrent requests to LLM providers.
+///
+/// Providers often limit number of concurrent requests per API key, so running many agents at once
+/// (e.g. in batch jobs) results in rate limit errors. Limiter can be cloned and shared by many agents,
+/// all of them will wait until there is a free slot before sending a request to the model.
+/// Agents cloned from the same [Agent](crate::agent::Agent) share its limiter.
+///
+/// ```rust
+/// use agentai::{Agent, RequestLimiter};
+///
+/// // At most 4 requests will be in flight, regardless of the number of agents
+/// let limiter = RequestLimiter::new(4);
+/// let summarizer = Agent::builder()
+///     .with_request_limiter(limiter.clone())
+///     .build();
+/// let translator = Agent::builder()
+///     .with_request_limiter(limiter)
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct RequestLimiter {
+    semaphore: Arc<Semaphore>,
+}
+
+impl RequestLimiter {
+    /// Creates limiter allowing at most `max_parallel_requests` concurrent requests, at least one
+    /// request is always allowed.
+    pub fn new(max_parallel_requests: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_parallel_requests.max(1))),
+        }
+    }
+
+    /// Returns number of requests that can be sent without waiting.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    /// Waits for a free slot, request can be sent while returned permit is alive.
+    pub(crate) async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        // Semaphore is never closed, so acquiring can't fail
+        self.semaphore.acquire().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_request_limit() {
+        let limiter = RequestLimiter::new(2);
+        let shared = limiter.clone();
+        let first = limiter.acquire().await.unwrap();
+        let second = shared.acquire().await.unwrap();
+        assert_eq!(limiter.available(), 0);
+        let third = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(third.is_err(), "third request must wait");
+
+        drop(first);
+        assert_eq!(shared.available(), 1);
+        let third = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(third.unwrap().is_some());
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn test_permit_released_after_failed_request() {
+        let limiter = RequestLimiter::new(1);
+        // Nothing listens on the port, so the request fails
+        let mut agent = crate::agent::Agent::builder()
+            .with_url("http://127.0.0.1:9/v1/", "key")
+            .with_request_limiter(limiter.clone())
+            .build();
+        let answer = agent.run::<String>("gpt-4o", "Hello", None, None, None);
+        assert!(answer.await.is_err());
+        assert_eq!(limiter.available(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_zero_limit() {
+        let limiter = RequestLimiter::new(0);
+        assert_eq!(limiter.available(), 1);
+        let permit = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(permit.unwrap().is_some());
+    }
+}
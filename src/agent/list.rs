@@ -0,0 +1,87 @@
+use crate::agent::{Agent, Prompt};
+use crate::tool::composite::SharedToolBox;
+use anyhow::Result;
+use genai::chat::ChatOptions;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+
+/// Answer of [Agent::run_list], many providers reject schemas with array at the top level.
+#[derive(JsonSchema)]
+struct ItemList<T> {
+    items: Vec<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ItemList<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Models not constrained by the schema sometimes answer with the bare array
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Items<T> {
+            Wrapped { items: Vec<T> },
+            Bare(Vec<T>),
+        }
+        match Items::deserialize(deserializer)? {
+            Items::Wrapped { items } | Items::Bare(items) => Ok(Self { items }),
+        }
+    }
+}
+
+impl Agent {
+    /// Runs the agent like [Agent::run], returning a list of items of type `T`.
+    ///
+    /// Many providers don't accept structured output with array at the top level, so the model is
+    /// asked for an object with `items` field, that is unwrapped from the answer. It removes the
+    /// need of wrapper structures in the code of the application.
+    ///
+    /// ```rust,no_run
+    /// # use agentai::Agent;
+    /// # use schemars::JsonSchema;
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize, JsonSchema)]
+    /// struct Task {
+    ///     title: String,
+    ///     priority: u8,
+    /// }
+    ///
+    /// # async fn example() -> anyhow::Result<()> {
+    /// let mut agent = Agent::new("You are project manager.");
+    /// let tasks: Vec<Task> = agent
+    ///     .run_list("gpt-4o", "Plan the release of version 2.0", None, None, None)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_list<T>(
+        &mut self,
+        model: &str,
+        prompt: impl Into<Prompt>,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+    ) -> Result<Vec<T>>
+    where
+        T: DeserializeOwned + JsonSchema + 'static,
+    {
+        let list: ItemList<T> = self.run(model, prompt, toolbox, iteration, config).await?;
+        Ok(list.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::default_schema_options;
+
+    #[test]
+    fn test_item_list() {
+        let schema = default_schema_options().schema_for::<ItemList<u32>>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["items"]["type"], "array");
+
+        let list: ItemList<u32> = serde_json::from_str(r#"{"items": [1, 2]}"#).unwrap();
+        assert_eq!(list.items, [1, 2]);
+        let list: ItemList<u32> = serde_json::from_str("[3]").unwrap();
+        assert_eq!(list.items, [3]);
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// Metadata of the conversation, like user id, tenant or tags.
+///
+/// Metadata is not sent to the model. It is attached to everything produced by the agent, so
+/// usage and costs can be attributed in multi-tenant services:
+/// - [AgentResponse::metadata](crate::agent::AgentResponse::metadata) of every run,
+/// - [RequestContext::session](crate::agent::RequestContext::session) of every request passed to [ChatMiddleware](crate::agent::ChatMiddleware),
+/// - `agent_run` span recorded with `observability` feature.
+///
+/// ```rust
+/// use agentai::Agent;
+///
+/// let mut agent = Agent::builder()
+///     .with_metadata("tenant", "acme")
+///     .with_tag("support")
+///     .build();
+/// agent.metadata_mut().insert("user_id", "42");
+/// assert_eq!(agent.metadata().to_string(), "tenant=acme user_id=42 #support");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    /// Named values, e.g. user id
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+    /// Labels of the conversation
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+impl SessionMetadata {
+    /// Creates empty metadata.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value, replacing the previous one.
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.values.insert(key.to_string(), value.to_string());
+    }
+
+    /// Returns the value.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Adds the tag.
+    pub fn add_tag(&mut self, tag: &str) {
+        self.tags.insert(tag.to_string());
+    }
+
+    /// Returns `true` when the conversation has the tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    /// Returns `true` when there are no values and tags.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.tags.is_empty()
+    }
+}
+
+impl Display for SessionMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parts = self
+            .values
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .chain(self.tags.iter().map(|tag| format!("#{tag}")))
+            .collect::<Vec<_>>();
+        write!(f, "{}", parts.join(" "))
+    }
+}
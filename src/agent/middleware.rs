@@ -0,0 +1,186 @@
+use crate::agent::SessionMetadata;
+use anyhow::Result;
+use genai::chat::{ChatOptions, ChatRequest, ChatResponse};
+use log::debug;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+/// Hook around every request sent by the agent to the model, see [AgentBuilder::with_middleware](crate::agent::AgentBuilder::with_middleware).
+///
+/// Middlewares are the extension point for caching, guardrails or custom routing, without
+/// modifying the agent loop. They are installed as an ordered chain: [ChatMiddleware::before_request]
+/// hooks are called in order of installation, then the request is sent, then
+/// [ChatMiddleware::after_response] hooks are called in reverse order.
+///
+/// Middleware can short-circuit the request by returning a response from
+/// [ChatMiddleware::before_request], e.g. from a cache. In that case the model is not called, and
+/// only middlewares installed before it receive the response. Returning an error fails the run.
+///
+/// ```rust
+/// use agentai::{Agent, ChatMiddleware, RequestContext};
+/// use anyhow::Result;
+/// use genai::chat::{ChatOptions, ChatRequest, ChatResponse};
+///
+/// struct Router;
+///
+/// #[async_trait::async_trait]
+/// impl ChatMiddleware for Router {
+///     async fn before_request(
+///         &self,
+///         context: &mut RequestContext,
+///         request: &mut ChatRequest,
+///         _options: &mut ChatOptions,
+///     ) -> Result<Option<ChatResponse>> {
+///         // Short conversations don't need the large model
+///         if request.messages.len() < 4 {
+///             context.model = "gpt-4o-mini".to_string();
+///         }
+///         Ok(None)
+///     }
+/// }
+///
+/// let agent = Agent::builder().with_middleware(Router).build();
+/// ```
+#[async_trait::async_trait]
+pub trait ChatMiddleware: Send + Sync {
+    /// Called before the request is sent, can modify the request, its options and the model.
+    ///
+    /// Returning `Some` skips the model and the rest of the chain, the response is used instead.
+    async fn before_request(
+        &self,
+        _context: &mut RequestContext,
+        _request: &mut ChatRequest,
+        _options: &mut ChatOptions,
+    ) -> Result<Option<ChatResponse>> {
+        Ok(None)
+    }
+
+    /// Called after the response was received, can modify it before the agent processes it.
+    async fn after_response(
+        &self,
+        _context: &RequestContext,
+        _request: &ChatRequest,
+        _response: &mut ChatResponse,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Information about the request, shared by all middlewares of the chain.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RequestContext {
+    /// Model that will receive the request, middleware can change it to route the request
+    pub model: String,
+    /// Metadata that middlewares can attach to the request, e.g. to pass information from
+    /// [ChatMiddleware::before_request] to [ChatMiddleware::after_response]
+    pub metadata: HashMap<String, String>,
+    /// Metadata of the conversation, see [Agent::metadata](crate::agent::Agent::metadata)
+    pub session: SessionMetadata,
+}
+
+impl RequestContext {
+    pub(crate) fn new(model: &str, session: &SessionMetadata) -> Self {
+        Self {
+            model: model.to_string(),
+            metadata: HashMap::new(),
+            session: session.clone(),
+        }
+    }
+}
+
+/// Ordered chain of middlewares installed on the agent
+pub(crate) type MiddlewareChain = Vec<Arc<dyn ChatMiddleware>>;
+
+/// Passes the request through the chain, using `send` to get the response from the model.
+///
+/// Returns also `true` when the request was short-circuited by a middleware.
+pub(crate) async fn exec_chain<F, Fut>(
+    chain: &[Arc<dyn ChatMiddleware>],
+    session: &SessionMetadata,
+    model: &str,
+    mut request: ChatRequest,
+    options: &ChatOptions,
+    send: F,
+) -> Result<(ChatResponse, bool)>
+where
+    F: FnOnce(String, ChatRequest, ChatOptions) -> Fut,
+    Fut: Future<Output = Result<ChatResponse>>,
+{
+    if chain.is_empty() {
+        let response = send(model.to_string(), request, options.clone()).await?;
+        return Ok((response, false));
+    }
+    let mut context = RequestContext::new(model, session);
+    let mut options = options.clone();
+    let mut called = chain.len();
+    let mut response = None;
+    for (index, middleware) in chain.iter().enumerate() {
+        response = middleware
+            .before_request(&mut context, &mut request, &mut options)
+            .await?;
+        if response.is_some() {
+            debug!("Request to {} short-circuited by middleware", context.model);
+            called = index;
+            break;
+        }
+    }
+    let short_circuited = response.is_some();
+    let mut response = match response {
+        Some(response) => response,
+        None => send(context.model.clone(), request.clone(), options).await?,
+    };
+    for middleware in chain[..called].iter().rev() {
+        middleware
+            .after_response(&context, &request, &mut response)
+            .await?;
+    }
+    Ok((response, short_circuited))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use genai::chat::ChatMessage;
+
+    struct Tag(&'static str);
+
+    #[async_trait::async_trait]
+    impl ChatMiddleware for Tag {
+        async fn before_request(
+            &self,
+            context: &mut RequestContext,
+            request: &mut ChatRequest,
+            _options: &mut ChatOptions,
+        ) -> Result<Option<ChatResponse>> {
+            request.messages.push(ChatMessage::user(self.0));
+            context.model.push_str(self.0);
+            Ok(None)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chain_order() {
+        let chain: MiddlewareChain = vec![Arc::new(Tag("a")), Arc::new(Tag("b"))];
+        let err = exec_chain(
+            &chain,
+            &SessionMetadata::default(),
+            "model-",
+            ChatRequest::default(),
+            &ChatOptions::default(),
+            |model, request, _| async move {
+                let texts = request
+                    .messages
+                    .iter()
+                    .filter_map(|message| message.content.text_as_str())
+                    .collect::<Vec<_>>();
+                Err(anyhow!("{model} {}", texts.join(",")))
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "model-ab a,b");
+    }
+}
@@ -0,0 +1,108 @@
+use crate::schema::default_schema_options;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How the answer of the model is requested and converted into the output type.
+///
+/// JSON representation contains `type` field, e.g. `{"type": "json", "schema": {...}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Answer is free text, it is not parsed.
+    Text,
+    /// Answer is JSON, matching the schema when it is provided.
+    Json {
+        /// Schema of the answer
+        schema: Option<Value>,
+    },
+}
+
+/// Type of the answer returned by [Agent::run](crate::agent::Agent::run).
+///
+/// It is implemented for every type implementing `DeserializeOwned` and `JsonSchema`. Types with
+/// schema of a plain string (e.g. `String` or newtypes around it) are answered with free text,
+/// types accepting any JSON (e.g. `serde_json::Value`) are requested as JSON without schema,
+/// other types are requested as JSON matching their schema. Look into [crate::structured_output]
+/// for details.
+pub trait AgentOutput: Sized + 'static {
+    /// Returns format of the answer requested from the model.
+    fn output_format() -> OutputFormat;
+
+    /// Converts the answer, JSON value of the answer or text of the answer as `Value::String`
+    /// for [OutputFormat::Text].
+    fn from_output(value: Value) -> serde_json::Result<Self>;
+}
+
+impl<T> AgentOutput for T
+where
+    T: DeserializeOwned + JsonSchema + 'static,
+{
+    fn output_format() -> OutputFormat {
+        let schema = default_schema_options().schema_for::<T>();
+        if is_text_schema(&schema) {
+            OutputFormat::Text
+        } else if is_any_schema(&schema) {
+            // Providers reject schemas accepting any value
+            OutputFormat::Json { schema: None }
+        } else {
+            OutputFormat::Json {
+                schema: Some(schema),
+            }
+        }
+    }
+
+    fn from_output(value: Value) -> serde_json::Result<Self> {
+        serde_json::from_value(value)
+    }
+}
+
+/// Returns `true` for schema accepting any string, without restrictions like `enum` or `format`.
+fn is_text_schema(schema: &Value) -> bool {
+    let Value::Object(object) = schema else {
+        return false;
+    };
+    object.get("type") == Some(&Value::String("string".to_string()))
+        && object
+            .keys()
+            .all(|key| matches!(key.as_str(), "type" | "$schema" | "title" | "description"))
+}
+
+/// Returns `true` for schema accepting any value, e.g. schema of `serde_json::Value`.
+fn is_any_schema(schema: &Value) -> bool {
+    match schema {
+        Value::Bool(accepts) => *accepts,
+        Value::Object(object) => object
+            .keys()
+            .all(|key| matches!(key.as_str(), "$schema" | "title" | "description")),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Name(String);
+
+    #[derive(Deserialize, JsonSchema)]
+    enum Answer {
+        Yes,
+        No,
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert_eq!(String::output_format(), OutputFormat::Text);
+        assert_eq!(Name::output_format(), OutputFormat::Text);
+        assert!(matches!(Answer::output_format(), OutputFormat::Json { .. }));
+        assert!(matches!(u32::output_format(), OutputFormat::Json { .. }));
+        assert_eq!(Value::output_format(), OutputFormat::Json { schema: None });
+
+        let name = Name::from_output(Value::String("Bob \"the\" builder".to_string())).unwrap();
+        assert_eq!(name.0, "Bob \"the\" builder");
+    }
+}
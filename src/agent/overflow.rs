@@ -0,0 +1,105 @@
+use genai::chat::{ChatMessage, ChatRole};
+
+/// Behaviour of the agent when the request exceeds context window of the model, see [AgentBuilder::with_context_overflow](crate::agent::AgentBuilder::with_context_overflow).
+///
+/// Long conversations eventually don't fit into the context window, and counting tokens up front
+/// is not reliable for all tokenizers. When the provider rejects the request as too long, older
+/// part of the conversation is removed from the history and the request is retried. Messages of
+/// the current run and system messages at the beginning of the history are never removed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextOverflow {
+    /// Run fails with the error of the provider.
+    #[default]
+    Fail,
+    /// Older half of the conversation is dropped, up to given number of times per request.
+    Trim(u32),
+    /// Older half of the conversation is replaced with its summary, up to given number of times
+    /// per request. Summary is generated by the utility model (see [AgentBuilder::with_utility_model](crate::agent::AgentBuilder::with_utility_model)),
+    /// or by the model of the run. When summarization fails, messages are dropped.
+    Summarize(u32),
+}
+
+impl ContextOverflow {
+    /// Returns maximal number of retries of a single request.
+    pub(crate) fn retries(&self) -> u32 {
+        match self {
+            ContextOverflow::Fail => 0,
+            ContextOverflow::Trim(retries) | ContextOverflow::Summarize(retries) => *retries,
+        }
+    }
+}
+
+/// Fragments of error messages of providers rejecting requests longer than the context window
+const OVERFLOW_MARKERS: &[&str] = &[
+    "context_length_exceeded",
+    "maximum context length",
+    "context length",
+    "context window",
+    "prompt is too long",
+    "input is too long",
+    "too many tokens",
+    "exceeds the maximum number of tokens",
+];
+
+/// Returns `true` when the error of the request means that the context window was exceeded.
+///
+/// Providers don't report it with a dedicated error type, so the message is inspected.
+pub(crate) fn is_context_overflow(err: &anyhow::Error) -> bool {
+    let message = format!("{err:#}").to_lowercase();
+    OVERFLOW_MARKERS
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+/// Returns range of messages removed to free the context, older half of the conversation before
+/// the current run, ending at the beginning of a turn of the user.
+///
+/// `run_start` is the index of the first message of the current run. Returns `None` when there
+/// is nothing to remove.
+pub(crate) fn trimmed_range(
+    history: &[ChatMessage],
+    run_start: usize,
+) -> Option<std::ops::Range<usize>> {
+    let start = history
+        .iter()
+        .position(|message| !matches!(message.role, ChatRole::System))?;
+    if start >= run_start {
+        return None;
+    }
+    let half = start + (run_start - start).div_ceil(2);
+    // Messages are removed up to the next turn of the user, so tool results are never orphaned
+    let end = (half..run_start)
+        .find(|idx| matches!(history[*idx].role, ChatRole::User))
+        .unwrap_or(run_start);
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_context_overflow() {
+        assert!(is_context_overflow(&anyhow!(
+            "Web call failed: 400 {{\"error\": {{\"code\": \"context_length_exceeded\"}}}}"
+        )));
+        assert!(is_context_overflow(
+            &anyhow!("prompt is too long: 210000 tokens > 200000 maximum")
+                .context("Request failed")
+        ));
+        assert!(!is_context_overflow(&anyhow!("rate limit exceeded")));
+
+        let history = vec![
+            ChatMessage::system("You are helpful assistant."),
+            ChatMessage::user("Hi"),
+            ChatMessage::assistant("Hello!"),
+            ChatMessage::user("Weather?"),
+            ChatMessage::assistant("Sunny"),
+            ChatMessage::user("Tomorrow?"),
+        ];
+        assert_eq!(trimmed_range(&history, 5), Some(1..3));
+        assert_eq!(trimmed_range(&history, 3), Some(1..3));
+        assert_eq!(trimmed_range(&history, 1), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::tool::composite::{join_instructions, SharedToolBox};
+use crate::tool::{Capability, Tool, ToolBox, ToolError, ToolFuture};
+use futures::future::join_all;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Toolboxes added for a single run on top of the toolbox of the run, see [Agent::run_with_extra_tools](crate::agent::Agent::run_with_extra_tools).
+///
+/// Unlike [CompositeToolBox](crate::tool::composite::CompositeToolBox) names of tools are not
+/// prefixed. When more toolboxes provide tool with the same name, the one added first is used, so
+/// extra tools shadow tools of the base toolbox.
+pub(crate) struct ToolOverlay {
+    /// Extra toolboxes followed by the base toolbox
+    toolboxes: Vec<SharedToolBox>,
+}
+
+impl ToolOverlay {
+    pub(crate) fn new(base: Option<SharedToolBox>, extra: &[SharedToolBox]) -> Self {
+        Self {
+            toolboxes: extra.iter().cloned().chain(base).collect(),
+        }
+    }
+
+    /// Finds toolbox serving the tool.
+    fn route(&self, tool_name: &str) -> Option<&SharedToolBox> {
+        self.toolboxes.iter().find(|toolbox| {
+            toolbox
+                .shared_tools_definitions()
+                .is_ok_and(|tools| tools.iter().any(|tool| tool.name == tool_name))
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ToolBox for ToolOverlay {
+    fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+        let mut names = HashSet::new();
+        let mut tools = vec![];
+        for toolbox in &self.toolboxes {
+            for tool in toolbox.shared_tools_definitions()?.iter() {
+                if names.insert(tool.name.clone()) {
+                    tools.push(tool.clone());
+                }
+            }
+        }
+        Ok(tools)
+    }
+
+    async fn call_tool(&self, tool_name: String, arguments: Value) -> Result<String, ToolError> {
+        let toolbox = self
+            .route(&tool_name)
+            .ok_or_else(|| ToolError::NoToolFound(tool_name.clone()))?;
+        toolbox.call_tool(tool_name, arguments).await
+    }
+
+    fn instructions(&self) -> Option<String> {
+        join_instructions(self.toolboxes.iter())
+    }
+
+    fn refresh_definitions(&self) -> ToolFuture<'_, Result<(), ToolError>> {
+        Box::pin(async move {
+            join_all(
+                self.toolboxes
+                    .iter()
+                    .map(|toolbox| toolbox.refresh_definitions()),
+            )
+            .await
+            .into_iter()
+            .collect()
+        })
+    }
+
+    fn for_run(&self) -> Option<SharedToolBox> {
+        let views: Vec<_> = self
+            .toolboxes
+            .iter()
+            .map(|toolbox| toolbox.for_run())
+            .collect();
+        if views.iter().all(Option::is_none) {
+            return None;
+        }
+        let toolboxes = self
+            .toolboxes
+            .iter()
+            .zip(views)
+            .map(|(toolbox, view)| view.unwrap_or(toolbox.clone()))
+            .collect();
+        Some(Arc::new(Self { toolboxes }))
+    }
+
+    fn tool_capabilities(&self, tool_name: &str) -> Vec<Capability> {
+        self.route(tool_name)
+            .map(|toolbox| toolbox.tool_capabilities(tool_name))
+            .unwrap_or_else(|| vec![Capability::Unknown])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    struct Echo(&'static str);
+
+    #[async_trait::async_trait]
+    impl ToolBox for Echo {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Ok(vec![Tool::new("echo"), Tool::new(self.0)])
+        }
+
+        async fn call_tool(&self, tool_name: String, _: Value) -> Result<String, ToolError> {
+            Ok(format!("{} {tool_name}", self.0))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_overlay() {
+        let base: SharedToolBox = Arc::new(Echo("base"));
+        let document: SharedToolBox = Arc::new(Echo("document"));
+        let overlay = ToolOverlay::new(Some(base), &[document]);
+        let names = overlay
+            .tools_definitions()
+            .unwrap()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["echo", "document", "base"]);
+        let result = overlay.call_tool("echo".to_string(), Value::Null).await;
+        assert_eq!(result.unwrap(), "document echo");
+        let result = overlay.call_tool("base".to_string(), Value::Null).await;
+        assert_eq!(result.unwrap(), "base base");
+        assert!(overlay
+            .call_tool("missing".to_string(), Value::Null)
+            .await
+            .is_err());
+    }
+}
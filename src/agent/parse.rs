@@ -0,0 +1,205 @@
+use serde::de::DeserializeOwned;
+use serde_json::from_str;
+use std::sync::Arc;
+
+/// Callback called when the answer of the model can't be parsed, see [AgentBuilder::with_on_parse_error](crate::agent::AgentBuilder::with_on_parse_error).
+///
+/// It receives the raw text of the answer and the error. Returned text replaces the answer and is
+/// parsed again, returning `None` fails the run with [AgentError::InvalidOutput](crate::agent::AgentError::InvalidOutput).
+pub type ParseErrorHandler = Arc<dyn Fn(&str, &serde_json::Error) -> Option<String> + Send + Sync>;
+
+/// How structured answers of the model are parsed, see [AgentBuilder::with_output_parsing](crate::agent::AgentBuilder::with_output_parsing).
+///
+/// Weak models often wrap JSON in markdown fences or add a sentence before or after it, even when
+/// asked for JSON only. Answer is always parsed as is first, cleanup is applied only when it fails.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputParsing {
+    /// Answer has to be valid JSON.
+    Strict,
+    /// Markdown fences (e.g. ```` ```json ````), leading prose and trailing commentary around
+    /// JSON are removed.
+    #[default]
+    StripFences,
+    /// Like [OutputParsing::StripFences], additionally JSON5 style syntax is accepted: comments,
+    /// trailing commas, single quoted strings and unquoted keys.
+    Lenient,
+}
+
+/// Deserializes answer of the model according to [OutputParsing].
+///
+/// When the answer can't be parsed, error of parsing the original answer is returned.
+pub(crate) fn parse_json<T: DeserializeOwned>(
+    text: &str,
+    parsing: OutputParsing,
+) -> serde_json::Result<T> {
+    let err = match from_str(text) {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+    let json = match parsing {
+        OutputParsing::Strict => return Err(err),
+        OutputParsing::StripFences => extract_json(text).to_string(),
+        OutputParsing::Lenient => lenient_to_json(extract_json(text)),
+    };
+    from_str(&json).map_err(|_| err)
+}
+
+/// Returns JSON found in the text, inside of markdown fences or surrounded by prose.
+fn extract_json(text: &str) -> &str {
+    let mut text = text.trim();
+    if let Some(start) = text.find("```") {
+        let fenced = &text[start + 3..];
+        // Language of the block, e.g. `json`, is followed by new line
+        let fenced = match fenced.find('\n') {
+            Some(end) if fenced[..end].trim().chars().all(char::is_alphanumeric) => {
+                &fenced[end + 1..]
+            }
+            _ => fenced,
+        };
+        text = fenced
+            .find("```")
+            .map_or(fenced, |end| &fenced[..end])
+            .trim();
+    }
+    let Some(start) = text.find(['{', '[']) else {
+        return text;
+    };
+    match closing_bracket(&text[start..]) {
+        Some(end) => &text[start..start + end + 1],
+        None => &text[start..],
+    }
+}
+
+/// Returns position of the bracket closing the one the text starts with, brackets in strings are skipped.
+fn closing_bracket(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '{' | '[' => depth += 1,
+                '}' | ']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(idx);
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    None
+}
+
+/// Converts JSON5 style syntax into JSON, it doesn't validate the text.
+fn lenient_to_json(text: &str) -> String {
+    let mut json = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                let quote = c;
+                json.push('"');
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => match chars.next() {
+                            // Single quote doesn't need escaping in JSON
+                            Some('\'') => json.push('\''),
+                            Some(escaped) => {
+                                json.push('\\');
+                                json.push(escaped);
+                            }
+                            None => {}
+                        },
+                        c if c == quote => break,
+                        // Only in single quoted strings
+                        '"' => json.push_str("\\\""),
+                        c => json.push(c),
+                    }
+                }
+                json.push('"');
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '}' | ']' => {
+                // Trailing comma
+                let end = json.trim_end().len();
+                json.truncate(end);
+                if json.ends_with(',') {
+                    json.pop();
+                }
+                json.push(c);
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_' || c == '$') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                if chars.peek() == Some(&':') {
+                    json.push('"');
+                    json.push_str(&word);
+                    json.push('"');
+                } else {
+                    json.push_str(&word);
+                    json.push(' ');
+                }
+            }
+            c => json.push(c),
+        }
+    }
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_parse_json() {
+        let fenced = "Here is the answer:\n```json\n{\"city\": \"Paris\"}\n```\nHope it helps!";
+        let value: Value = parse_json(fenced, OutputParsing::StripFences).unwrap();
+        assert_eq!(value, json!({"city": "Paris"}));
+        assert!(parse_json::<Value>(fenced, OutputParsing::Strict).is_err());
+
+        let prose = "The list is [1, 2] (in {order}).";
+        let value: Value = parse_json(prose, OutputParsing::StripFences).unwrap();
+        assert_eq!(value, json!([1, 2]));
+
+        let json5 = "{\n  // Capital\n  city: 'Paris \"Lutetia\"',\n  tags: ['it\\'s', 1e3,],\n}";
+        assert!(parse_json::<Value>(json5, OutputParsing::StripFences).is_err());
+        let value: Value = parse_json(json5, OutputParsing::Lenient).unwrap();
+        assert_eq!(
+            value,
+            json!({"city": "Paris \"Lutetia\"", "tags": ["it's", 1000.0]})
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use serde_json::Value;
+
+/// Cleanup of the answer applied before it is converted into the output type, see [AgentBuilder::with_post_processor](crate::agent::AgentBuilder::with_post_processor).
+///
+/// Processors receive JSON of the answer after it was parsed (text answers as `Value::String`),
+/// and can modify it in place, so cleanup logic is kept out of call sites. Conversion into the
+/// output type happens after all processors, so they can also fix values that would be rejected
+/// by it. Processors run in order of registration.
+///
+/// Any `Fn(&mut Value)` closure is a processor:
+///
+/// ```rust
+/// use agentai::{Agent, NormalizeWhitespace};
+/// use serde_json::Value;
+///
+/// let agent = Agent::builder()
+///     .with_post_processor(NormalizeWhitespace)
+///     .with_post_processor(|answer: &mut Value| {
+///         if let Some(tags) = answer.get_mut("tags").and_then(Value::as_array_mut) {
+///             tags.dedup();
+///         }
+///     })
+///     .build();
+/// ```
+pub trait PostProcessor: Send + Sync {
+    /// Modifies the answer.
+    fn process(&self, answer: &mut Value);
+}
+
+impl<F> PostProcessor for F
+where
+    F: Fn(&mut Value) + Send + Sync,
+{
+    fn process(&self, answer: &mut Value) {
+        self(answer)
+    }
+}
+
+/// Trims all strings of the answer and collapses runs of whitespace inside them into a single space.
+///
+/// Line breaks are kept, only spaces and tabs around them are removed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NormalizeWhitespace;
+
+impl PostProcessor for NormalizeWhitespace {
+    fn process(&self, answer: &mut Value) {
+        visit_strings(answer, None, &mut |_, text| {
+            *text = text
+                .lines()
+                .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string();
+        });
+    }
+}
+
+/// Resolves relative URLs against the base URL, e.g. links extracted from a scraped page.
+///
+/// By default values of fields with names ending with `url`, `href` or `link` (case insensitive)
+/// are resolved, other fields can be chosen with [ResolveUrls::with_fields].
+#[derive(Debug, Clone)]
+pub struct ResolveUrls {
+    base: String,
+    fields: Option<Vec<String>>,
+}
+
+impl ResolveUrls {
+    /// Creates processor resolving URLs against the base, e.g. `https://example.com/blog/`.
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: base.to_string(),
+            fields: None,
+        }
+    }
+
+    /// Sets names of fields containing URLs.
+    pub fn with_fields(mut self, fields: &[&str]) -> Self {
+        self.fields = Some(fields.iter().map(|field| field.to_string()).collect());
+        self
+    }
+
+    fn is_url_field(&self, field: &str) -> bool {
+        match &self.fields {
+            Some(fields) => fields.iter().any(|name| name == field),
+            None => {
+                let field = field.to_lowercase();
+                ["url", "href", "link"]
+                    .iter()
+                    .any(|suffix| field.ends_with(suffix))
+            }
+        }
+    }
+}
+
+impl PostProcessor for ResolveUrls {
+    fn process(&self, answer: &mut Value) {
+        visit_strings(answer, None, &mut |field, text| {
+            if field.is_some_and(|field| self.is_url_field(field)) {
+                *text = resolve_url(&self.base, text.trim());
+            }
+        });
+    }
+}
+
+/// Clamps numbers of the field into the range, e.g. scores that have to be between 0 and 1.
+///
+/// Fields with the name are clamped at any depth of the answer.
+#[derive(Debug, Clone)]
+pub struct Clamp {
+    field: String,
+    min: f64,
+    max: f64,
+}
+
+impl Clamp {
+    /// Creates processor clamping values of the field between `min` and `max`.
+    pub fn new(field: &str, min: f64, max: f64) -> Self {
+        Self {
+            field: field.to_string(),
+            min,
+            max,
+        }
+    }
+}
+
+impl PostProcessor for Clamp {
+    fn process(&self, answer: &mut Value) {
+        visit_values(answer, None, &mut |field, value| {
+            if field != Some(self.field.as_str()) {
+                return;
+            }
+            // Integers stay integers, when the bounds allow it
+            if let Some(number) = value.as_i64() {
+                let clamped = (number as f64).clamp(self.min, self.max);
+                if clamped.fract() == 0.0 {
+                    *value = Value::from(clamped as i64);
+                    return;
+                }
+            }
+            if let Some(number) = value.as_f64() {
+                *value = Value::from(number.clamp(self.min, self.max));
+            }
+        });
+    }
+}
+
+/// Calls `f` with every value of the answer, together with the name of the field containing it.
+fn visit_values(
+    value: &mut Value,
+    field: Option<&str>,
+    f: &mut impl FnMut(Option<&str>, &mut Value),
+) {
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                visit_values(value, Some(key), f);
+            }
+        }
+        // Items of arrays belong to the field of the array
+        Value::Array(items) => {
+            for item in items {
+                visit_values(item, field, f);
+            }
+        }
+        value => f(field, value),
+    }
+}
+
+fn visit_strings(
+    value: &mut Value,
+    field: Option<&str>,
+    f: &mut impl FnMut(Option<&str>, &mut String),
+) {
+    visit_values(value, field, &mut |field, value| {
+        if let Value::String(text) = value {
+            f(field, text);
+        }
+    });
+}
+
+/// Resolves reference against the base URL, like links in HTML documents.
+pub(crate) fn resolve_url(base: &str, reference: &str) -> String {
+    if reference.is_empty() || reference.contains("://") || reference.starts_with("mailto:") {
+        return reference.to_string();
+    }
+    let Some((scheme, rest)) = base.split_once("://") else {
+        return reference.to_string();
+    };
+    if let Some(reference) = reference.strip_prefix("//") {
+        return format!("{scheme}://{reference}");
+    }
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    let path = if reference.starts_with('/') {
+        reference.to_string()
+    } else if reference.starts_with(['?', '#']) {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        format!("{path}{reference}")
+    } else {
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        let directory = &path[..path.rfind('/').map(|idx| idx + 1).unwrap_or(0)];
+        format!(
+            "{}{reference}",
+            if directory.is_empty() { "/" } else { directory }
+        )
+    };
+    format!("{scheme}://{authority}{}", remove_dot_segments(&path))
+}
+
+/// Removes `.` and `..` segments of the path, keeping query and fragment unchanged.
+fn remove_dot_segments(path: &str) -> String {
+    let end = path.find(['?', '#']).unwrap_or(path.len());
+    let (path, suffix) = path.split_at(end);
+    let mut segments: Vec<&str> = vec![];
+    let parts: Vec<&str> = path.split('/').collect();
+    for (idx, segment) in parts.iter().enumerate() {
+        let is_last = idx + 1 == parts.len();
+        match *segment {
+            "." if is_last => segments.push(""),
+            "." => {}
+            ".." => {
+                if segments.len() > 1 {
+                    segments.pop();
+                }
+                if is_last {
+                    segments.push("");
+                }
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("{}{suffix}", segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_post_processors() {
+        let mut answer = json!({
+            "title": "  Rust \t 1.80   released \n",
+            "links": [{"url": "../news/rust.html", "label": "/about"}, {"href": "/about?x=1"}],
+            "score": 1.7,
+            "items": [{"score": -3}]
+        });
+        NormalizeWhitespace.process(&mut answer);
+        ResolveUrls::new("https://example.com/blog/posts/index.html").process(&mut answer);
+        Clamp::new("score", 0.0, 1.0).process(&mut answer);
+        assert_eq!(
+            answer,
+            json!({
+                "title": "Rust 1.80 released",
+                "links": [
+                    {"url": "https://example.com/blog/news/rust.html", "label": "/about"},
+                    {"href": "https://example.com/about?x=1"}
+                ],
+                "score": 1.0,
+                "items": [{"score": 0}]
+            })
+        );
+        assert_eq!(
+            resolve_url("https://example.com", "docs/./intro"),
+            "https://example.com/docs/intro"
+        );
+    }
+}
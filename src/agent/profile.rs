@@ -0,0 +1,127 @@
+use genai::adapter::AdapterKind;
+
+/// Layout of prompts composed by the crate, see [AgentBuilder::with_prompt_profile](crate::agent::AgentBuilder::with_prompt_profile).
+///
+/// Models follow instructions better when they are structured the way the model was trained
+/// on. Profile changes how parts of the [SystemPrompt](crate::system_prompt::SystemPrompt) and
+/// instructions added by the agent (tools, response language, output format, ...) are put
+/// together, the text of the parts stays the same. Plain system messages set with
+/// [AgentBuilder::with_system](crate::agent::AgentBuilder::with_system) are never changed.
+///
+/// Unless overridden, profile is chosen by provider of the model: [PromptProfile::Xml] for
+/// Anthropic, [PromptProfile::Markdown] for OpenAI, [PromptProfile::Terse] for Ollama and
+/// [PromptProfile::Plain] for others.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptProfile {
+    /// Parts are separated with empty lines.
+    #[default]
+    Plain,
+    /// Every part is wrapped in XML tag named after it, e.g. `<output_format>`.
+    Xml,
+    /// Every part starts with markdown header, e.g. `## Output format`.
+    Markdown,
+    /// Parts are compacted for small models with short context: whitespace is collapsed, built-in
+    /// presets of the system prompt are shortened to their first sentence.
+    Terse,
+}
+
+impl PromptProfile {
+    /// Returns profile used for models of the provider when it is not overridden.
+    pub fn for_adapter(kind: AdapterKind) -> Self {
+        match kind {
+            AdapterKind::Anthropic => PromptProfile::Xml,
+            AdapterKind::OpenAI => PromptProfile::Markdown,
+            AdapterKind::Ollama => PromptProfile::Terse,
+            _ => PromptProfile::Plain,
+        }
+    }
+
+    /// Puts together titled parts of the prompt.
+    pub(crate) fn render(&self, parts: &[(&str, &str)]) -> String {
+        match self {
+            PromptProfile::Plain => parts
+                .iter()
+                .map(|(_, text)| *text)
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            PromptProfile::Xml => parts
+                .iter()
+                .map(|(title, text)| {
+                    let tag = title.to_lowercase().replace(' ', "_");
+                    format!("<{tag}>\n{text}\n</{tag}>")
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            PromptProfile::Markdown => parts
+                .iter()
+                .map(|(title, text)| format!("## {title}\n\n{text}"))
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+            PromptProfile::Terse => parts
+                .iter()
+                .map(|(_, text)| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Prompt profiles chosen by provider, with overrides of the user.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PromptProfiles {
+    /// Profile of all models, automatic selection when `None`
+    pub(crate) default: Option<PromptProfile>,
+    pub(crate) providers: Vec<(AdapterKind, PromptProfile)>,
+}
+
+impl PromptProfiles {
+    /// Returns profile of prompts sent to the model, provider settings take precedence.
+    pub(crate) fn get(&self, model: &str) -> PromptProfile {
+        let adapter_kind = AdapterKind::from_model(model).ok();
+        self.providers
+            .iter()
+            .find(|(kind, _)| Some(*kind) == adapter_kind)
+            .map(|(_, profile)| *profile)
+            .or(self.default)
+            .or_else(|| adapter_kind.map(PromptProfile::for_adapter))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt_profiles() {
+        let parts = [
+            ("Tools", "Use tools."),
+            ("Output format", "Respond  with\nJSON."),
+        ];
+        assert_eq!(
+            PromptProfile::Plain.render(&parts),
+            "Use tools.\n\nRespond  with\nJSON."
+        );
+        assert_eq!(
+            PromptProfile::Xml.render(&parts),
+            "<tools>\nUse tools.\n</tools>\n<output_format>\nRespond  with\nJSON.\n</output_format>"
+        );
+        assert_eq!(
+            PromptProfile::Markdown.render(&parts),
+            "## Tools\n\nUse tools.\n\n## Output format\n\nRespond  with\nJSON."
+        );
+        assert_eq!(
+            PromptProfile::Terse.render(&parts),
+            "Use tools.\nRespond with JSON."
+        );
+
+        let mut profiles = PromptProfiles::default();
+        let automatic = PromptProfile::for_adapter(AdapterKind::from_model("gpt-4o").unwrap());
+        assert_eq!(profiles.get("gpt-4o"), automatic);
+        profiles.default = Some(PromptProfile::Plain);
+        assert_eq!(profiles.get("gpt-4o"), PromptProfile::Plain);
+        let kind = AdapterKind::from_model("gpt-4o").unwrap();
+        profiles.providers.push((kind, PromptProfile::Terse));
+        assert_eq!(profiles.get("gpt-4o"), PromptProfile::Terse);
+    }
+}
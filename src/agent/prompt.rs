@@ -0,0 +1,148 @@
+use crate::agent::prompts::fill;
+use genai::chat::{ChatMessage, ContentPart};
+use serde::{Deserialize, Serialize};
+
+/// Input of a run, see [Agent::run](crate::agent::Agent::run).
+///
+/// Runs accept anything that converts into a prompt, so plain `&str` and `String` keep working.
+/// Prompt extends the text of the user with:
+/// - attachments, e.g. images, sent together with the text in a single user message,
+/// - developer messages, instructions for this request sent as system messages before the user message,
+/// - variables, replacing `{name}` placeholders of the text, unknown placeholders are left unchanged.
+///
+/// All messages of the prompt are stored in the history, like the plain text.
+///
+/// ```rust
+/// use agentai::Prompt;
+/// use genai::chat::ContentPart;
+///
+/// let prompt = Prompt::new("Describe the product {product} in {words} words.")
+///     .with_variable("product", "Aurora lamp")
+///     .with_variable("words", "50")
+///     .with_attachment(ContentPart::from_image_url("image/png", "https://example.com/lamp.png"))
+///     .with_developer_message("The description is published in the web shop, avoid superlatives.");
+/// assert_eq!(prompt.text(), "Describe the product Aurora lamp in 50 words.");
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Prompt {
+    text: String,
+    attachments: Vec<ContentPart>,
+    developer_messages: Vec<String>,
+    variables: Vec<(String, String)>,
+}
+
+impl Prompt {
+    /// Creates prompt with the text of the user.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Attaches content, e.g. an image, to the user message.
+    pub fn with_attachment(mut self, attachment: ContentPart) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Adds developer message, sent before the user message. Messages are sent in order of adding.
+    pub fn with_developer_message(mut self, message: impl Into<String>) -> Self {
+        self.developer_messages.push(message.into());
+        self
+    }
+
+    /// Sets value of `{name}` placeholder of the text, replacing previous value of the variable.
+    pub fn with_variable(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.variables.retain(|(variable, _)| variable != name);
+        self.variables.push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Returns text of the user, with placeholders replaced by values of variables.
+    pub fn text(&self) -> String {
+        let values = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        fill(&self.text, &values)
+    }
+
+    /// Returns developer messages, in order of adding.
+    pub fn developer_messages(&self) -> &[String] {
+        &self.developer_messages
+    }
+
+    /// Returns attachments of the user message.
+    pub fn attachments(&self) -> &[ContentPart] {
+        &self.attachments
+    }
+
+    /// Returns messages of the prompt, in order they are added to the history.
+    ///
+    /// It is useful for custom implementations of [AgentRunner](crate::agent::AgentRunner).
+    pub fn into_messages(self) -> Vec<ChatMessage> {
+        let text = self.text();
+        let mut messages = self
+            .developer_messages
+            .into_iter()
+            .map(ChatMessage::system)
+            .collect::<Vec<_>>();
+        if self.attachments.is_empty() {
+            messages.push(ChatMessage::user(text));
+        } else {
+            let mut parts = vec![ContentPart::from_text(text)];
+            parts.extend(self.attachments);
+            messages.push(ChatMessage::user(parts));
+        }
+        messages
+    }
+}
+
+impl From<&str> for Prompt {
+    fn from(text: &str) -> Self {
+        Prompt::new(text)
+    }
+}
+
+impl From<String> for Prompt {
+    fn from(text: String) -> Self {
+        Prompt::new(text)
+    }
+}
+
+impl From<&String> for Prompt {
+    fn from(text: &String) -> Self {
+        Prompt::new(text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genai::chat::{ChatRole, MessageContent};
+
+    #[test]
+    fn test_prompt_messages() {
+        let messages = Prompt::new("Hello {name}, {unknown}")
+            .with_variable("name", "{name}")
+            .with_developer_message("Be brief")
+            .into_messages();
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0].role, ChatRole::System));
+        assert_eq!(
+            messages[1].content.text_as_str(),
+            Some("Hello {name}, {unknown}")
+        );
+
+        let image = ContentPart::from_image_url("image/png", "https://example.com/cat.png");
+        let messages = Prompt::from("What is it?")
+            .with_attachment(image)
+            .into_messages();
+        assert!(matches!(
+            &messages[0].content,
+            MessageContent::Parts(parts) if parts.len() == 2
+        ));
+    }
+}
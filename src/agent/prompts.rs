@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+/// Text injected by the crate into requests, that can be overridden with [Prompts].
+///
+/// Some prompts are templates, placeholders listed in the documentation of the variant (e.g.
+/// `{schema}`) are replaced with values when the prompt is used. Unknown placeholders are left
+/// unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PromptKey {
+    /// Instruction to answer with JSON, used with [StructuredOutputMode::Prompt](crate::agent::StructuredOutputMode::Prompt).
+    /// Placeholders: `{schema}`.
+    OutputSchema,
+    /// Instruction to answer with JSON without schema, used for untyped outputs like `serde_json::Value`.
+    OutputJson,
+    /// Instruction to answer in the language of the user, used with [ResponseLanguage::Auto](crate::agent::ResponseLanguage::Auto).
+    LanguageAuto,
+    /// Instruction to answer in the given language, used with [ResponseLanguage::Fixed](crate::agent::ResponseLanguage::Fixed).
+    /// Placeholders: `{language}`.
+    LanguageFixed,
+    /// Request for the final answer in the last iteration, used with [OnIterationExhausted::ForceAnswer](crate::agent::OnIterationExhausted::ForceAnswer).
+    FinalAnswer,
+    /// Request to stop and answer when the run stalled, used with [StallAction::WrapUp](crate::agent::StallAction::WrapUp).
+    WrapUp,
+    /// Reminder sent instead of executing repeated tool call, used with [DuplicateToolCalls::Nudge](crate::agent::DuplicateToolCalls::Nudge).
+    /// Placeholders: `{tool}`, `{result}`.
+    DuplicateToolCall,
+    /// Truncated tool result, used with [OversizedResult::Truncate](crate::agent::OversizedResult::Truncate).
+    /// Placeholders: `{content}`, `{max_len}`, `{total}`.
+    ResultTruncated,
+    /// Instruction summarizing tool result, used with [OversizedResult::Summarize](crate::agent::OversizedResult::Summarize).
+    /// Placeholders: `{max_len}`, `{task}`.
+    ResultSummary,
+    /// First page of stored tool result, used with [OversizedResult::Store](crate::agent::OversizedResult::Store).
+    /// Placeholders: `{content}`, `{max_len}`, `{total}`, `{artifact_id}`, `{tool}`.
+    ResultStored,
+    /// Instruction of [Agent::classify](crate::agent::Agent::classify).
+    Classify,
+    /// Instruction of [Agent::extract](crate::agent::Agent::extract).
+    Extract,
+    /// Instruction merging extractions of chunks in [Agent::extract](crate::agent::Agent::extract).
+    ExtractMerge,
+    /// Instruction summarizing chunks in [Agent::summarize](crate::agent::Agent::summarize).
+    SummarizeChunk,
+    /// Instruction of the final summary in [Agent::summarize](crate::agent::Agent::summarize).
+    /// Placeholders: `{style}`.
+    Summarize,
+    /// Description of [SummaryStyle::Brief](crate::agent::SummaryStyle::Brief).
+    SummaryBrief,
+    /// Description of [SummaryStyle::Detailed](crate::agent::SummaryStyle::Detailed).
+    SummaryDetailed,
+    /// Description of [SummaryStyle::Bullets](crate::agent::SummaryStyle::Bullets).
+    SummaryBullets,
+    /// Instruction of the judge in [Aggregator::Judge](crate::agent::Aggregator::Judge).
+    SelfConsistencyJudge,
+    /// Instruction of [Agent::summarize_session](crate::agent::Agent::summarize_session).
+    SessionSummary,
+    /// Instruction of [Agent::suggest_title](crate::agent::Agent::suggest_title).
+    SessionTitle,
+    /// First request of [Refiner](crate::agent::Refiner).
+    /// Placeholders: `{schema}`, `{document}`, `{instruction}`.
+    RefineStart,
+    /// Following requests of [Refiner](crate::agent::Refiner).
+    /// Placeholders: `{instruction}`.
+    RefineChange,
+    /// Request to correct rejected patch in [Refiner](crate::agent::Refiner).
+    /// Placeholders: `{error}`.
+    RefineRetry,
+    /// Request to critique other answers in [Ensemble](crate::ensemble::Ensemble).
+    /// Placeholders: `{round}`, `{answers}`.
+    DebateCritique,
+    /// Request to choose the final answer in [Ensemble](crate::ensemble::Ensemble).
+    /// Placeholders: `{question}`, `{answers}`.
+    DebateJudge,
+    /// Placeholder of image that was already sent, used with [ImageResend::FirstOccurrence](crate::agent::ImageResend::FirstOccurrence).
+    ImageOmitted,
+    /// Placeholder of tool result identical to an earlier one, used with [AgentBuilder::with_repeated_result_compaction](crate::agent::AgentBuilder::with_repeated_result_compaction).
+    /// Placeholders: `{call_id}`.
+    RepeatedResult,
+    /// Note about changed tools, used with [AgentBuilder::with_tools_change_notes](crate::agent::AgentBuilder::with_tools_change_notes).
+    /// Placeholders: `{added}`, `{removed}`, `{changed}`.
+    ToolsChanged,
+    /// Instruction checking the answer against its source, used with [CitationVerification](crate::agent::CitationVerification).
+    CitationCheck,
+    /// Summary of messages removed from the history, used with [ContextOverflow::Summarize](crate::agent::ContextOverflow::Summarize)
+    /// and by [Agent::compact](crate::agent::Agent::compact).
+    /// Placeholders: `{summary}`.
+    ContextSummary,
+}
+
+impl PromptKey {
+    /// Returns built-in (English) text of the prompt.
+    pub fn default_text(&self) -> &'static str {
+        match self {
+            PromptKey::OutputSchema => {
+                "Respond only with a JSON value matching the following JSON Schema, \
+                 without any additional text or markdown code fences:\n{schema}"
+            }
+            PromptKey::OutputJson => {
+                "Respond only with a valid JSON value, without any additional text or markdown \
+                 code fences."
+            }
+            PromptKey::LanguageAuto => {
+                "Detect the language of the last message from the user and always answer in \
+                 that language, even when instructions, tool results or documents are written in \
+                 a different one."
+            }
+            PromptKey::LanguageFixed => {
+                "Always answer in {language}, regardless of the language used by the user, \
+                 instructions, tool results or documents."
+            }
+            PromptKey::FinalAnswer => {
+                "This is the last step, tools are no longer available. \
+                 Provide the best final answer you can with the information gathered so far."
+            }
+            PromptKey::WrapUp => {
+                "You are not making progress, tools are no longer available. Stop and provide \
+                 the best final answer you can with the information gathered so far."
+            }
+            PromptKey::DuplicateToolCall => {
+                "You already called `{tool}` with the same arguments, the tool was not executed \
+                 again. Result of the previous call:\n{result}\n\n\
+                 Use this result, call the tool with different arguments or provide the answer."
+            }
+            PromptKey::ResultTruncated => {
+                "{content}\n\n[Result truncated: showing {max_len} of {total} characters]"
+            }
+            PromptKey::ResultSummary => {
+                "Summarize the tool result provided by the user in at most {max_len} characters. \
+                 Keep all facts, numbers and links relevant to the task: {task}"
+            }
+            PromptKey::ResultStored => {
+                "{content}\n\n[Result has {total} characters, only first {max_len} are shown. \
+                 Full result was stored as artifact {artifact_id}, use `{tool}` tool \
+                 with `artifact_id` {artifact_id} and `offset` to read the rest.]"
+            }
+            PromptKey::Classify => {
+                "Classify the text provided by the user, choosing exactly one of the allowed \
+                 labels. Provide confidence of the classification as a number between 0 and 1."
+            }
+            PromptKey::Extract => {
+                "Extract information from the text provided by the user. \
+                 Use only facts stated in the text, leave fields empty when information is \
+                 missing. Respond only with the extracted data."
+            }
+            PromptKey::ExtractMerge => {
+                "The user provides a JSON array of partial extractions, each made from a \
+                 consecutive fragment of the same document. Merge them into a single extraction: \
+                 combine lists, remove duplicates and prefer the most complete values. \
+                 Respond only with the merged data."
+            }
+            PromptKey::SummarizeChunk => {
+                "The user provides a fragment of a longer document. Summarize it, keeping all \
+                 facts, names and numbers that may be important for the whole document. \
+                 Respond only with the summary."
+            }
+            PromptKey::Summarize => {
+                "Summarize the document provided by the user. {style} Respond only with the summary."
+            }
+            PromptKey::SummaryBrief => {
+                "Write a brief summary in a few sentences, describing the main points."
+            }
+            PromptKey::SummaryDetailed => {
+                "Write a detailed summary in several paragraphs, covering all important details."
+            }
+            PromptKey::SummaryBullets => "Write a summary as a bulleted list of key points.",
+            PromptKey::SelfConsistencyJudge => {
+                "The user provides a question and several candidate answers. Carefully verify \
+                 the candidates and choose the one that is correct and most complete. \
+                 Respond with the number of the chosen answer."
+            }
+            PromptKey::SessionSummary => {
+                "Summarize the conversation provided by the user in a few sentences. \
+                 Include the main topics, decisions and open questions. Respond only with the summary."
+            }
+            PromptKey::SessionTitle => {
+                "Suggest a short title (at most 6 words) for the conversation provided by the user. \
+                 Respond only with the title, without quotes or punctuation at the end."
+            }
+            PromptKey::RefineStart => {
+                "You are editing a JSON document matching the following JSON Schema:\n{schema}\n\n\
+                 Current document:\n{document}\n\n\
+                 Answer with JSON Patch (RFC 6902) operations changing the document, \
+                 never repeat the whole document. Change the document: {instruction}"
+            }
+            PromptKey::RefineChange => "Change the document: {instruction}",
+            PromptKey::RefineRetry => {
+                "The patch was rejected and the document was not changed: {error}\n\
+                 Provide corrected patch."
+            }
+            PromptKey::DebateCritique => {
+                "Debate round {round}. Other agents answered the same question:\n\n{answers}\n\n\
+                 Critically review their answers, point out mistakes and consider arguments you \
+                 missed. Then provide your revised answer to the question."
+            }
+            PromptKey::DebateJudge => {
+                "Several agents debated the following question:\n\n{question}\n\n\
+                 Their final answers are:\n\n{answers}\n\n\
+                 Select the best answer or merge them into a single answer that is correct and \
+                 complete. Respond only with the final answer."
+            }
+            PromptKey::ImageOmitted => "[Image omitted, it was already sent earlier in the conversation]",
+            PromptKey::RepeatedResult => {
+                "[Same result as tool call {call_id}, see the earlier result]"
+            }
+            PromptKey::ToolsChanged => {
+                "Available tools have changed since the previous request. Added tools: {added}. \
+                 Removed tools: {removed}. Tools with changed parameters: {changed}. \
+                 Use only the tools that are currently available."
+            }
+            PromptKey::CitationCheck => {
+                "The user provides an answer and one of the sources cited in it. List the claims \
+                 of the answer that are based on this source, and for each of them decide whether \
+                 the source supports it. A claim is supported only when the source states it \
+                 explicitly or it directly follows from the source."
+            }
+            PromptKey::ContextSummary => {
+                "Earlier part of the conversation was removed to fit into the context window. \
+                 Summary of the removed part:\n{summary}"
+            }
+        }
+    }
+}
+
+/// Overrides of prompts injected by the crate, see [AgentBuilder::with_prompts](crate::agent::AgentBuilder::with_prompts).
+///
+/// Instructions, repair prompts and notes added by the agent are written in English. Deployments
+/// in other languages can translate them, so the model doesn't receive mixed-language prompts.
+/// Prompts that are not overridden use [PromptKey::default_text].
+///
+/// ```rust
+/// # use agentai::{Agent, PromptKey, Prompts};
+/// let prompts = Prompts::new()
+///     .with_prompt(PromptKey::FinalAnswer, "To ostatni krok, narzędzia nie są już dostępne. \
+///         Udziel najlepszej odpowiedzi na podstawie zebranych informacji.")
+///     .with_prompt(PromptKey::LanguageFixed, "Zawsze odpowiadaj w języku: {language}.");
+/// let agent = Agent::builder().with_prompts(prompts).build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Prompts {
+    overrides: HashMap<PromptKey, String>,
+}
+
+impl Prompts {
+    /// Creates table without overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the prompt, templates should contain the same placeholders as the default text.
+    pub fn with_prompt(mut self, key: PromptKey, text: &str) -> Self {
+        self.overrides.insert(key, text.to_string());
+        self
+    }
+
+    /// Returns text of the prompt, before replacing placeholders.
+    pub fn get(&self, key: PromptKey) -> &str {
+        self.overrides
+            .get(&key)
+            .map(String::as_str)
+            .unwrap_or_else(|| key.default_text())
+    }
+
+    /// Returns the prompt with placeholders replaced by values.
+    pub(crate) fn render(&self, key: PromptKey, values: &[(&str, &str)]) -> String {
+        fill(self.get(key), values)
+    }
+}
+
+/// Replaces `{name}` placeholders of the template with values, unknown placeholders are left unchanged.
+pub(crate) fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rest = template;
+    let mut rendered = String::with_capacity(rest.len());
+    // Single pass, so placeholders inside of values are not replaced
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            values
+                .iter()
+                .find(|(name, _)| *name == &rest[1..end])
+                .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                rendered.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let prompts = Prompts::new().with_prompt(PromptKey::RefineRetry, "Błąd {error} {unknown}");
+        assert_eq!(
+            prompts.render(PromptKey::RefineRetry, &[("error", "{error}")]),
+            "Błąd {error} {unknown}"
+        );
+        assert_eq!(
+            prompts.render(PromptKey::LanguageFixed, &[("language", "German")]),
+            "Always answer in German, regardless of the language used by the user, \
+             instructions, tool results or documents."
+        );
+    }
+}
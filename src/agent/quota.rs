@@ -0,0 +1,218 @@
+use crate::agent::{AgentError, ChatMiddleware, RequestContext, TokenUsage, UsageThreshold};
+use anyhow::Result;
+use genai::chat::{ChatOptions, ChatRequest, ChatResponse};
+use log::debug;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Key of [SessionMetadata](crate::agent::SessionMetadata) value identifying the tenant, used by default
+pub const DEFAULT_TENANT_KEY: &str = "tenant";
+
+/// Middleware enforcing daily usage limits of tenants, see [AgentBuilder::with_middleware](crate::agent::AgentBuilder::with_middleware).
+///
+/// Tenant of the request is taken from [metadata](crate::agent::Agent::metadata) of the agent
+/// (`tenant` value by default, see [QuotaManager::with_tenant_key]). Requests without the tenant
+/// are counted as anonymous tenant with empty name. Manager can be cloned and shared by all agents
+/// serving the tenants, so usage of all their sessions is summed up.
+///
+/// When the limit is reached, following requests fail with [AgentError::QuotaExceeded]. Usage is
+/// recorded after the response is received, so requests running in parallel can exceed the limit
+/// slightly. Usage is reset at midnight UTC.
+///
+/// ```rust
+/// use agentai::{Agent, AgentError, QuotaManager, UsageThreshold};
+///
+/// let quota = QuotaManager::new(UsageThreshold::Tokens(100_000))
+///     .with_tenant_limit("acme", UsageThreshold::Tokens(1_000_000));
+/// let agent = Agent::builder()
+///     .with_middleware(quota.clone())
+///     .with_metadata("tenant", "acme")
+///     .build();
+///
+/// // Show the user how much they can still use today
+/// let status = quota.status("acme");
+/// println!("Remaining tokens: {}", status.remaining());
+/// ```
+#[derive(Clone)]
+pub struct QuotaManager {
+    default_limit: UsageThreshold,
+    limits: HashMap<String, UsageThreshold>,
+    tenant_key: String,
+    state: Arc<Mutex<QuotaState>>,
+}
+
+/// Usage of the tenant compared with its limit, returned by [QuotaManager::status].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaStatus {
+    /// Usage of the current day
+    pub usage: TokenUsage,
+    /// Daily limit of the tenant
+    pub limit: UsageThreshold,
+}
+
+impl QuotaStatus {
+    /// Returns remaining quota, in tokens or cost depending on the limit.
+    pub fn remaining(&self) -> f64 {
+        match self.limit {
+            UsageThreshold::Tokens(tokens) => tokens.saturating_sub(self.usage.total_tokens) as f64,
+            UsageThreshold::Cost {
+                limit,
+                input_price,
+                output_price,
+            } => (limit - self.usage.cost(input_price, output_price)).max(0.0),
+        }
+    }
+
+    /// Returns `true` when the limit was reached.
+    pub fn is_exceeded(&self) -> bool {
+        self.limit.is_reached(&self.usage)
+    }
+}
+
+#[derive(Default)]
+struct QuotaState {
+    /// Day to which usage applies, as number of days since Unix epoch
+    day: u64,
+    usage: HashMap<String, TokenUsage>,
+}
+
+impl QuotaState {
+    /// Returns usage of the tenant, resetting usage of all tenants when the day changed.
+    fn usage(&mut self, tenant: &str) -> &mut TokenUsage {
+        let day = today();
+        if self.day != day {
+            self.day = day;
+            self.usage.clear();
+        }
+        self.usage.entry(tenant.to_string()).or_default()
+    }
+}
+
+/// Returns number of days since Unix epoch.
+fn today() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / 86_400)
+        .unwrap_or_default()
+}
+
+impl QuotaManager {
+    /// Creates manager with the same daily limit for all tenants.
+    pub fn new(default_limit: UsageThreshold) -> Self {
+        Self {
+            default_limit,
+            limits: HashMap::new(),
+            tenant_key: DEFAULT_TENANT_KEY.to_string(),
+            state: Arc::new(Mutex::new(QuotaState::default())),
+        }
+    }
+
+    /// Sets daily limit of the tenant, overriding the default one.
+    pub fn with_tenant_limit(mut self, tenant: &str, limit: UsageThreshold) -> Self {
+        self.limits.insert(tenant.to_string(), limit);
+        self
+    }
+
+    /// Sets key of metadata value identifying the tenant, default is [DEFAULT_TENANT_KEY].
+    pub fn with_tenant_key(mut self, key: &str) -> Self {
+        self.tenant_key = key.to_string();
+        self
+    }
+
+    /// Returns daily limit of the tenant.
+    pub fn limit(&self, tenant: &str) -> UsageThreshold {
+        self.limits
+            .get(tenant)
+            .copied()
+            .unwrap_or(self.default_limit)
+    }
+
+    /// Returns usage and limit of the tenant for the current day.
+    pub fn status(&self, tenant: &str) -> QuotaStatus {
+        QuotaStatus {
+            usage: *self.state.lock().unwrap().usage(tenant),
+            limit: self.limit(tenant),
+        }
+    }
+
+    /// Adds usage of the tenant, e.g. of requests made outside of agents.
+    pub fn record(&self, tenant: &str, usage: &TokenUsage) {
+        self.state.lock().unwrap().usage(tenant).merge(usage);
+    }
+
+    fn tenant<'a>(&self, context: &'a RequestContext) -> &'a str {
+        context.session.get(&self.tenant_key).unwrap_or_default()
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatMiddleware for QuotaManager {
+    async fn before_request(
+        &self,
+        context: &mut RequestContext,
+        _request: &mut ChatRequest,
+        _options: &mut ChatOptions,
+    ) -> Result<Option<ChatResponse>> {
+        let tenant = self.tenant(context);
+        if self.status(tenant).is_exceeded() {
+            debug!("Daily quota of tenant `{tenant}` exceeded");
+            return Err(AgentError::QuotaExceeded(tenant.to_string()).into());
+        }
+        Ok(None)
+    }
+
+    async fn after_response(
+        &self,
+        context: &RequestContext,
+        _request: &ChatRequest,
+        response: &mut ChatResponse,
+    ) -> Result<()> {
+        let tenant = self.tenant(context);
+        self.state
+            .lock()
+            .unwrap()
+            .usage(tenant)
+            .add(&response.usage);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::SessionMetadata;
+
+    #[tokio::test]
+    async fn test_quota() {
+        let quota = QuotaManager::new(UsageThreshold::Tokens(100))
+            .with_tenant_limit("acme", UsageThreshold::Tokens(1000));
+        let usage = TokenUsage {
+            total_tokens: 500,
+            ..Default::default()
+        };
+        quota.record("acme", &usage);
+        quota.record("", &usage);
+        assert_eq!(quota.status("acme").remaining(), 500.0);
+        assert!(quota.status("").is_exceeded());
+
+        let mut session = SessionMetadata::new();
+        session.insert("tenant", "acme");
+        let mut context = RequestContext::new("gpt-4o", &session);
+        let mut request = ChatRequest::default();
+        let mut options = ChatOptions::default();
+        assert!(quota
+            .before_request(&mut context, &mut request, &mut options)
+            .await
+            .is_ok());
+
+        let mut context = RequestContext::new("gpt-4o", &SessionMetadata::new());
+        let err = quota
+            .before_request(&mut context, &mut request, &mut options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AgentError>(),
+            Some(AgentError::QuotaExceeded(tenant)) if tenant.is_empty()
+        ));
+    }
+}
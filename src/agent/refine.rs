@@ -0,0 +1,275 @@
+use crate::agent::{Agent, PromptKey, TokenUsage};
+use crate::schema::default_schema_options;
+use crate::tool::composite::SharedToolBox;
+use anyhow::{anyhow, Result};
+use log::debug;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, to_value, Value};
+use std::marker::PhantomData;
+
+/// Number of times the model can correct patch that failed to apply
+const MAX_PATCH_ATTEMPTS: u32 = 3;
+
+/// Single operation of JSON Patch ([RFC 6902](https://datatracker.ietf.org/doc/html/rfc6902)).
+///
+/// Paths are JSON Pointers ([RFC 6901](https://datatracker.ietf.org/doc/html/rfc6901)), `-` as the
+/// last segment refers to the end of an array.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    /// Adds value to an object or inserts it into an array
+    Add { path: String, value: Value },
+    /// Removes value
+    Remove { path: String },
+    /// Replaces existing value
+    Replace { path: String, value: Value },
+    /// Moves value to other location
+    Move { from: String, path: String },
+    /// Copies value to other location
+    Copy { from: String, path: String },
+    /// Checks that value is equal to the expected one
+    Test { path: String, value: Value },
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct Patch {
+    /// Operations applied to the document in order
+    operations: Vec<PatchOperation>,
+}
+
+/// Structured document refined by the agent over many turns, using JSON Patch edits.
+///
+/// Instead of generating the whole document in every turn, the model receives it once and then
+/// answers with [PatchOperation]s, that are applied to the document. Patched document is validated
+/// by deserializing it into `T`, when it fails the error is sent back to the model to correct the
+/// patch. This reduces tokens used to refine large documents.
+///
+/// The conversation is kept in the history of the agent, so the same agent should be used for all
+/// turns of the refinement.
+///
+/// ```rust,no_run
+/// # use agentai::{Agent, Refiner};
+/// # use schemars::JsonSchema;
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize, JsonSchema)]
+/// struct Plan {
+///     steps: Vec<String>,
+/// }
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let mut agent = Agent::new("You are a project manager.");
+/// let mut refiner = Refiner::new(&Plan { steps: vec!["Design".to_string()] })?;
+/// refiner.refine(&mut agent, "gpt-4o", "Add implementation and testing steps", None).await?;
+/// refiner.refine(&mut agent, "gpt-4o", "Split testing into unit and integration tests", None).await?;
+/// let plan: Plan = refiner.document()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Refiner<T> {
+    document: Value,
+    /// Document was already sent to the model
+    sent: bool,
+    usage: TokenUsage,
+    _document_type: PhantomData<T>,
+}
+
+impl<T> Refiner<T>
+where
+    T: Serialize + DeserializeOwned + JsonSchema,
+{
+    /// Starts refinement of the document.
+    pub fn new(document: &T) -> Result<Self> {
+        Ok(Self {
+            document: to_value(document)?,
+            sent: false,
+            usage: TokenUsage::default(),
+            _document_type: PhantomData,
+        })
+    }
+
+    /// Returns current version of the document.
+    pub fn document(&self) -> Result<T> {
+        Ok(from_value(self.document.clone())?)
+    }
+
+    /// Returns tokens used by all turns of the refinement.
+    pub fn usage(&self) -> &TokenUsage {
+        &self.usage
+    }
+
+    /// Asks the agent to change the document according to the instruction, returning applied patch.
+    ///
+    /// Document is not changed when the model fails to provide valid patch.
+    pub async fn refine(
+        &mut self,
+        agent: &mut Agent,
+        model: &str,
+        instruction: &str,
+        toolbox: Option<SharedToolBox>,
+    ) -> Result<Vec<PatchOperation>> {
+        let prompts = agent.options.prompts.clone();
+        let mut prompt = if self.sent {
+            prompts.render(PromptKey::RefineChange, &[("instruction", instruction)])
+        } else {
+            prompts.render(
+                PromptKey::RefineStart,
+                &[
+                    (
+                        "schema",
+                        &default_schema_options().schema_for::<T>().to_string(),
+                    ),
+                    ("document", &self.document.to_string()),
+                    ("instruction", instruction),
+                ],
+            )
+        };
+
+        for attempt in 1..=MAX_PATCH_ATTEMPTS {
+            let response = agent
+                .run_detailed::<Patch>(model, &prompt, toolbox.clone(), None, None)
+                .await?;
+            self.usage.merge(&response.usage);
+            self.sent = true;
+            let operations = response.output.operations;
+            let result = apply_patch(&self.document, &operations).and_then(|document| {
+                // Patched document has to match the type of the document
+                from_value::<T>(document.clone())?;
+                Ok(document)
+            });
+            match result {
+                Ok(document) => {
+                    self.document = document;
+                    return Ok(operations);
+                }
+                Err(err) => {
+                    debug!("Patch attempt {attempt} failed: {err}");
+                    prompt = prompts.render(PromptKey::RefineRetry, &[("error", &err.to_string())]);
+                }
+            }
+        }
+        Err(anyhow!(
+            "Unable to get valid patch in {MAX_PATCH_ATTEMPTS} tries"
+        ))
+    }
+}
+
+/// Applies operations to the copy of the document, either all of them or none.
+pub fn apply_patch(document: &Value, operations: &[PatchOperation]) -> Result<Value> {
+    let mut document = document.clone();
+    for operation in operations {
+        match operation {
+            PatchOperation::Add { path, value } => add(&mut document, path, value.clone())?,
+            PatchOperation::Remove { path } => {
+                remove(&mut document, path)?;
+            }
+            PatchOperation::Replace { path, value } => {
+                *get_mut(&mut document, path)? = value.clone();
+            }
+            PatchOperation::Move { from, path } => {
+                let value = remove(&mut document, from)?;
+                add(&mut document, path, value)?;
+            }
+            PatchOperation::Copy { from, path } => {
+                let value = get_mut(&mut document, from)?.clone();
+                add(&mut document, path, value)?;
+            }
+            PatchOperation::Test { path, value } => {
+                if get_mut(&mut document, path)? != value {
+                    return Err(anyhow!("Test of `{path}` failed"));
+                }
+            }
+        }
+    }
+    Ok(document)
+}
+
+fn get_mut<'a>(document: &'a mut Value, path: &str) -> Result<&'a mut Value> {
+    document
+        .pointer_mut(path)
+        .ok_or_else(|| anyhow!("Path `{path}` doesn't exist"))
+}
+
+/// Splits path into pointer of the parent and unescaped last segment.
+fn split_path(path: &str) -> Result<(&str, String)> {
+    let (parent, key) = path
+        .rsplit_once('/')
+        .ok_or_else(|| anyhow!("Invalid path `{path}`"))?;
+    Ok((parent, key.replace("~1", "/").replace("~0", "~")))
+}
+
+fn add(document: &mut Value, path: &str, value: Value) -> Result<()> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+    let (parent, key) = split_path(path)?;
+    match get_mut(document, parent)? {
+        Value::Object(object) => {
+            object.insert(key, value);
+        }
+        Value::Array(array) => {
+            let index = if key == "-" {
+                array.len()
+            } else {
+                key.parse()?
+            };
+            if index > array.len() {
+                return Err(anyhow!("Index of `{path}` is out of bounds"));
+            }
+            array.insert(index, value);
+        }
+        _ => return Err(anyhow!("Parent of `{path}` is not a container")),
+    }
+    Ok(())
+}
+
+fn remove(document: &mut Value, path: &str) -> Result<Value> {
+    let (parent, key) = split_path(path)?;
+    let removed = match get_mut(document, parent)? {
+        Value::Object(object) => object.remove(&key),
+        Value::Array(array) => key
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < array.len())
+            .map(|index| array.remove(index)),
+        _ => None,
+    };
+    removed.ok_or_else(|| anyhow!("Path `{path}` doesn't exist"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_patch() {
+        let document = json!({"title": "Plan", "steps": ["design", "test"], "a/b": 1});
+        let operations: Vec<PatchOperation> = serde_json::from_value(json!([
+            {"op": "add", "path": "/steps/1", "value": "implement"},
+            {"op": "add", "path": "/steps/-", "value": "release"},
+            {"op": "replace", "path": "/title", "value": "Project plan"},
+            {"op": "move", "from": "/a~1b", "path": "/version"},
+            {"op": "copy", "from": "/steps/0", "path": "/first"},
+            {"op": "test", "path": "/version", "value": 1},
+            {"op": "remove", "path": "/steps/0"}
+        ]))
+        .unwrap();
+        assert_eq!(
+            apply_patch(&document, &operations).unwrap(),
+            json!({
+                "title": "Project plan",
+                "steps": ["implement", "test", "release"],
+                "version": 1,
+                "first": "design"
+            })
+        );
+
+        let failing = vec![PatchOperation::Remove {
+            path: "/missing".to_string(),
+        }];
+        assert!(apply_patch(&document, &failing).is_err());
+    }
+}
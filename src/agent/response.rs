@@ -0,0 +1,64 @@
+use crate::agent::{SessionMetadata, TokenUsage, ToolUsage};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Detailed result of the run, returned by [Agent::run_detailed](crate::agent::Agent::run_detailed).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct AgentResponse<D> {
+    /// Answer of the model, deserialized into requested type
+    pub output: D,
+    /// Sources used in the answer, empty when citations are disabled (see [CitationMode](crate::agent::CitationMode))
+    pub citations: Vec<Citation>,
+    /// Claims of the answer not supported by their sources, empty when citations are not verified
+    /// (see [CitationVerification](crate::agent::CitationVerification))
+    pub unsupported_claims: Vec<UnsupportedClaim>,
+    /// Confidence of the model in the answer, from 0 to 1. Available only when enabled with
+    /// [AgentBuilder::with_confidence](crate::agent::AgentBuilder::with_confidence)
+    pub confidence: Option<f32>,
+    /// Tokens used during this run
+    pub usage: TokenUsage,
+    /// Tools called during this run
+    pub tool_usage: ToolUsage,
+    /// Metadata of the conversation at the time of the run, e.g. to attribute usage to the tenant
+    pub metadata: SessionMetadata,
+}
+
+/// Source of information used in the answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Citation {
+    /// Identifier of the tool call that returned the source
+    pub call_id: String,
+    /// Name of the tool that returned the source
+    pub tool: String,
+    /// URL of the source, or identifier of the tool call when result doesn't contain any URL
+    pub source: String,
+    /// Whether the source supports the claims based on it, `None` when it was not verified (see
+    /// [CitationVerification](crate::agent::CitationVerification))
+    pub supported: Option<bool>,
+}
+
+/// Claim of the answer that is not supported by the cited source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnsupportedClaim {
+    /// The claim, quoted or paraphrased from the answer
+    pub claim: String,
+    /// URL or identifier of the source, as in [Citation::source]
+    pub source: String,
+    /// Why the source doesn't support the claim, as explained by the model
+    pub reason: Option<String>,
+}
+
+/// Tool call made during the run, together with its result.
+#[derive(Debug, Clone)]
+pub(crate) struct ToolCallRecord {
+    pub(crate) call_id: String,
+    pub(crate) name: String,
+    pub(crate) arguments: Value,
+    pub(crate) content: String,
+    pub(crate) is_error: bool,
+    pub(crate) latency: Duration,
+    /// Size of the result before applying the limit
+    pub(crate) bytes: usize,
+}
@@ -0,0 +1,219 @@
+use crate::agent::{PromptKey, Prompts};
+use crate::tool::{Tool, ToolError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Name of the tool, exposed to the model, that reads stored oversized results.
+pub const READ_ARTIFACT_TOOL: &str = "read_artifact";
+
+/// What to do with tool results exceeding [ToolResultLimit::max_len].
+#[derive(Clone, Debug)]
+pub enum OversizedResult {
+    /// Result is truncated, note about truncation is appended.
+    Truncate,
+    /// Result is summarized by provided (preferably cheap) model. If summarization fails result is truncated.
+    Summarize {
+        /// Model used to summarize results
+        model: String,
+    },
+    /// Full result is stored as an artifact, model receives first page and can read
+    /// the rest with `read_artifact` tool. Artifacts are kept until the next run starts.
+    Store,
+}
+
+/// Limit of the size of tool results sent to the model.
+///
+/// Single large result (e.g. web page fetched by a tool) can fill the whole context window.
+/// With this limit results longer than `max_len` characters are handled according to `strategy`.
+///
+/// ```rust
+/// use agentai::{Agent, ToolResultLimit};
+///
+/// let agent = Agent::builder()
+///     .with_tool_result_limit(ToolResultLimit::summarize(20_000, "gpt-4o-mini"))
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ToolResultLimit {
+    /// Maximal number of characters of the result
+    pub max_len: usize,
+    /// Handling of results exceeding the limit
+    pub strategy: OversizedResult,
+}
+
+impl ToolResultLimit {
+    /// Truncates results longer than `max_len` characters.
+    pub fn truncate(max_len: usize) -> Self {
+        Self {
+            max_len,
+            strategy: OversizedResult::Truncate,
+        }
+    }
+
+    /// Summarizes results longer than `max_len` characters using `model`.
+    pub fn summarize(max_len: usize, model: &str) -> Self {
+        Self {
+            max_len,
+            strategy: OversizedResult::Summarize {
+                model: model.to_string(),
+            },
+        }
+    }
+
+    /// Stores results longer than `max_len` characters, allowing the model to read them page by page.
+    pub fn store(max_len: usize) -> Self {
+        Self {
+            max_len,
+            strategy: OversizedResult::Store,
+        }
+    }
+}
+
+/// Returns first `max_len` characters of the content, with a note about truncation.
+pub(crate) fn truncate(prompts: &Prompts, content: &str, max_len: usize) -> String {
+    let total = content.chars().count();
+    if total <= max_len {
+        return content.to_string();
+    }
+    let truncated: String = content.chars().take(max_len).collect();
+    prompts.render(
+        PromptKey::ResultTruncated,
+        &[
+            ("content", &truncated),
+            ("max_len", &max_len.to_string()),
+            ("total", &total.to_string()),
+        ],
+    )
+}
+
+/// Oversized results stored during the current run.
+///
+/// Artifacts are dropped when the next run starts, but identifiers keep increasing, so identifiers
+/// of earlier runs, still present in the history, never point to new artifacts.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Artifacts {
+    /// Identifier of the first artifact of the run
+    first_id: usize,
+    contents: Vec<String>,
+}
+
+impl Artifacts {
+    /// Drops artifacts of the previous run.
+    pub(crate) fn clear(&mut self) {
+        self.first_id += self.contents.len();
+        self.contents.clear();
+    }
+
+    fn push(&mut self, content: String) -> usize {
+        self.contents.push(content);
+        self.first_id + self.contents.len() - 1
+    }
+
+    fn get(&self, artifact_id: usize) -> Option<&String> {
+        self.contents.get(artifact_id.checked_sub(self.first_id)?)
+    }
+}
+
+/// Stores content and returns its first page, with instructions how to read the rest.
+pub(crate) fn store(
+    prompts: &Prompts,
+    artifacts: &mut Artifacts,
+    content: String,
+    max_len: usize,
+) -> String {
+    let total = content.chars().count();
+    let page: String = content.chars().take(max_len).collect();
+    let artifact_id = artifacts.push(content);
+    prompts.render(
+        PromptKey::ResultStored,
+        &[
+            ("content", &page),
+            ("max_len", &max_len.to_string()),
+            ("total", &total.to_string()),
+            ("artifact_id", &artifact_id.to_string()),
+            ("tool", READ_ARTIFACT_TOOL),
+        ],
+    )
+}
+
+/// Definition of the tool reading stored artifacts.
+pub(crate) fn read_artifact_tool() -> Tool {
+    Tool {
+        name: READ_ARTIFACT_TOOL.to_string(),
+        description: Some(
+            "Reads part of a tool result that was too long to be returned at once.".to_string(),
+        ),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "artifact_id": {
+                    "type": "integer",
+                    "description": "Identifier of the artifact"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "Number of characters to skip"
+                }
+            },
+            "required": ["artifact_id", "offset"]
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+struct ReadArtifactArguments {
+    artifact_id: usize,
+    #[serde(default)]
+    offset: usize,
+}
+
+/// Returns page of the stored artifact, starting at requested offset.
+pub(crate) fn read_artifact(
+    artifacts: &Artifacts,
+    arguments: Value,
+    max_len: usize,
+) -> Result<String, ToolError> {
+    let args: ReadArtifactArguments =
+        serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+            tool: READ_ARTIFACT_TOOL.to_string(),
+            error: e.to_string(),
+            arguments: Box::new(arguments),
+            schema: Box::new(read_artifact_tool().schema.unwrap_or_default()),
+        })?;
+    let artifact = artifacts.get(args.artifact_id).ok_or_else(|| {
+        ToolError::Other(anyhow::anyhow!(
+            "Artifact {} not found, artifacts can be read only during the run that stored them",
+            args.artifact_id
+        ))
+    })?;
+    let total = artifact.chars().count();
+    let page: String = artifact.chars().skip(args.offset).take(max_len).collect();
+    let end = (args.offset + max_len).min(total);
+    Ok(format!(
+        "{page}\n\n[Characters {}..{end} of {total}]",
+        args.offset.min(total)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_read_artifact() {
+        let mut artifacts = Artifacts::default();
+        let prompts = Prompts::default();
+        let first = store(&prompts, &mut artifacts, "abcdefghij".to_string(), 4);
+        assert!(first.starts_with("abcd\n"));
+        let page = read_artifact(&artifacts, json!({"artifact_id": 0, "offset": 8}), 4).unwrap();
+        assert_eq!(page, "ij\n\n[Characters 8..10 of 10]");
+        artifacts.clear();
+        assert!(read_artifact(&artifacts, json!({"artifact_id": 0, "offset": 0}), 4).is_err());
+        let second = store(&prompts, &mut artifacts, "klmnopqrst".to_string(), 4);
+        assert!(second.contains("artifact 1"));
+        assert_eq!(
+            truncate(&prompts, "ąęść", 2),
+            "ąę\n\n[Result truncated: showing 2 of 4 characters]"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::agent::{Agent, AgentOutput, OutputFormat, Prompt};
+use crate::event::EventSink;
+use anyhow::Result;
+use genai::chat::ChatMessage;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Public surface of [Agent], for code that runs agents without depending on how they are
+/// implemented.
+///
+/// Orchestration components can accept any implementation, e.g. a local [Agent], an agent running
+/// in another service or an agent replaying recorded answers, and applications can replace agents
+/// with mocks in their tests. Implementations provide runs returning JSON ([AgentRunner::run_output]
+/// and [AgentRunner::run_output_stream]), the trait converts it into the output type with
+/// [AgentRunner::run] and [AgentRunner::run_stream]. The trait is object safe, typed runs are
+/// available also for `Box<dyn AgentRunner>`.
+///
+/// Runs use the toolbox and options configured for the agent. [Agent] has inherent methods with
+/// the same names, call them as `AgentRunner::run(&mut agent, ...)` to use the trait.
+///
+/// ```rust
+/// use agentai::{AgentRunner, OutputFormat, Prompt};
+/// use agentai::event::EventSink;
+/// use anyhow::Result;
+/// use genai::chat::ChatMessage;
+/// use serde_json::Value;
+/// use std::sync::Arc;
+///
+/// /// Agent answering every prompt with the same text
+/// struct Parrot(Vec<ChatMessage>);
+///
+/// #[async_trait::async_trait]
+/// impl AgentRunner for Parrot {
+///     async fn run_output(&mut self, _model: &str, prompt: Prompt, _format: OutputFormat) -> Result<Value> {
+///         self.0.extend(prompt.into_messages());
+///         Ok(Value::String("Polly wants a cracker".to_string()))
+///     }
+///
+///     async fn run_output_stream(
+///         &mut self,
+///         model: &str,
+///         prompt: Prompt,
+///         format: OutputFormat,
+///         _sink: Arc<dyn EventSink>,
+///     ) -> Result<Value> {
+///         self.run_output(model, prompt, format).await
+///     }
+///
+///     fn history(&self) -> &[ChatMessage] {
+///         &self.0
+///     }
+///
+///     fn push_message(&mut self, message: ChatMessage) {
+///         self.0.push(message);
+///     }
+/// }
+///
+/// # async fn run() -> Result<()> {
+/// let mut agent: Box<dyn AgentRunner> = Box::new(Parrot(vec![]));
+/// let answer: String = agent.run("gpt-4o-mini", "Hello!").await?;
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait::async_trait]
+pub trait AgentRunner: Send {
+    /// Runs the agent with the prompt, requesting the answer in the format.
+    ///
+    /// Answer is returned as JSON, text answers as `Value::String`.
+    async fn run_output(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        format: OutputFormat,
+    ) -> Result<Value>;
+
+    /// Runs the agent like [AgentRunner::run_output], sending events of the run to the sink.
+    ///
+    /// Look into [Agent::run_with_sink] for events that are sent.
+    async fn run_output_stream(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        format: OutputFormat,
+        sink: Arc<dyn EventSink>,
+    ) -> Result<Value>;
+
+    /// Returns history of the conversation, starting with the system message.
+    fn history(&self) -> &[ChatMessage];
+
+    /// Appends the message to the history, it is sent to the model with the next run.
+    fn push_message(&mut self, message: ChatMessage);
+
+    /// Runs the agent with the prompt, returning the answer converted into the output type.
+    async fn run<D>(&mut self, model: &str, prompt: impl Into<Prompt> + Send) -> Result<D>
+    where
+        Self: Sized,
+        D: AgentOutput,
+    {
+        let value = self
+            .run_output(model, prompt.into(), D::output_format())
+            .await?;
+        Ok(D::from_output(value)?)
+    }
+
+    /// Runs the agent like [AgentRunner::run], sending events of the run to the sink.
+    async fn run_stream<D>(
+        &mut self,
+        model: &str,
+        prompt: impl Into<Prompt> + Send,
+        sink: impl EventSink + 'static,
+    ) -> Result<D>
+    where
+        Self: Sized,
+        D: AgentOutput,
+    {
+        let value = self
+            .run_output_stream(model, prompt.into(), D::output_format(), Arc::new(sink))
+            .await?;
+        Ok(D::from_output(value)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl AgentRunner for Agent {
+    async fn run_output(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        format: OutputFormat,
+    ) -> Result<Value> {
+        let response = self
+            .run_detailed_as::<Value>(model, prompt, None, None, None, format)
+            .await?;
+        Ok(response.output)
+    }
+
+    async fn run_output_stream(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        format: OutputFormat,
+        sink: Arc<dyn EventSink>,
+    ) -> Result<Value> {
+        let previous = self.attach_sink(sink.clone());
+        let result = AgentRunner::run_output(self, model, prompt, format).await;
+        self.detach_sink(previous, sink, result.as_ref().err());
+        result
+    }
+
+    fn history(&self) -> &[ChatMessage] {
+        Agent::history(self)
+    }
+
+    fn push_message(&mut self, message: ChatMessage) {
+        Agent::push_message(self, message)
+    }
+}
+
+#[async_trait::async_trait]
+impl<R> AgentRunner for Box<R>
+where
+    R: AgentRunner + ?Sized,
+{
+    async fn run_output(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        format: OutputFormat,
+    ) -> Result<Value> {
+        (**self).run_output(model, prompt, format).await
+    }
+
+    async fn run_output_stream(
+        &mut self,
+        model: &str,
+        prompt: Prompt,
+        format: OutputFormat,
+        sink: Arc<dyn EventSink>,
+    ) -> Result<Value> {
+        (**self)
+            .run_output_stream(model, prompt, format, sink)
+            .await
+    }
+
+    fn history(&self) -> &[ChatMessage] {
+        (**self).history()
+    }
+
+    fn push_message(&mut self, message: ChatMessage) {
+        (**self).push_message(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::AgentStreamEvent;
+    use tokio::sync::mpsc;
+
+    /// Runner answering with number of messages in the history, as text or JSON
+    #[derive(Default)]
+    struct Counter(Vec<ChatMessage>);
+
+    #[async_trait::async_trait]
+    impl AgentRunner for Counter {
+        async fn run_output(
+            &mut self,
+            _model: &str,
+            prompt: Prompt,
+            format: OutputFormat,
+        ) -> Result<Value> {
+            self.0.extend(prompt.into_messages());
+            Ok(match format {
+                OutputFormat::Text => Value::String(self.0.len().to_string()),
+                OutputFormat::Json { .. } => Value::from(self.0.len()),
+            })
+        }
+
+        async fn run_output_stream(
+            &mut self,
+            model: &str,
+            prompt: Prompt,
+            format: OutputFormat,
+            sink: Arc<dyn EventSink>,
+        ) -> Result<Value> {
+            let result = self.run_output(model, prompt, format).await;
+            sink.send_event(AgentStreamEvent::Finished);
+            result
+        }
+
+        fn history(&self) -> &[ChatMessage] {
+            &self.0
+        }
+
+        fn push_message(&mut self, message: ChatMessage) {
+            self.0.push(message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_runner() {
+        let mut runner: Box<dyn AgentRunner> = Box::new(Counter::default());
+        runner.push_message(ChatMessage::system("Count messages."));
+        let count: u32 = runner.run("gpt-4o-mini", "Hi").await.unwrap();
+        assert_eq!(count, 2);
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let count: String = runner
+            .run_stream("gpt-4o-mini", "Hi", sender)
+            .await
+            .unwrap();
+        assert_eq!(count, "3");
+        assert_eq!(runner.history().len(), 3);
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(AgentStreamEvent::Finished)
+        ));
+    }
+}
@@ -0,0 +1,210 @@
+use crate::agent::{Agent, PromptKey};
+use anyhow::{anyhow, Result};
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, ChatRole, ContentPart, MessageContent};
+
+/// Maximal number of characters of a single tool result included in the transcript
+const MAX_TOOL_RESULT_LEN: usize = 500;
+
+impl Agent {
+    /// Returns history of the conversation, starting with the system message.
+    pub fn history(&self) -> &[ChatMessage] {
+        &self.history
+    }
+
+    /// Appends the message to the history, it is sent to the model with the next run.
+    ///
+    /// It allows e.g. to restore the conversation, attachments of a single run are better passed
+    /// with [Prompt](crate::agent::Prompt).
+    pub fn push_message(&mut self, message: ChatMessage) {
+        self.history.push(message);
+    }
+
+    /// Replaces history of the conversation, e.g. with history kept by a remote client.
+    #[cfg(feature = "remote")]
+    pub(crate) fn replace_history(&mut self, history: Vec<ChatMessage>) {
+        self.history = history;
+    }
+
+    /// Summarizes the conversation stored in the history.
+    ///
+    /// Summary is generated by the utility model (see [AgentBuilder::with_utility_model](crate::agent::AgentBuilder::with_utility_model)),
+    /// history of the agent is not modified.
+    pub async fn summarize_session(&self) -> Result<String> {
+        self.ask_about_session(PromptKey::SessionSummary).await
+    }
+
+    /// Suggests short title of the conversation stored in the history, e.g. for listing chats in UI.
+    ///
+    /// Title is generated by the utility model (see [AgentBuilder::with_utility_model](crate::agent::AgentBuilder::with_utility_model)),
+    /// history of the agent is not modified.
+    pub async fn suggest_title(&self) -> Result<String> {
+        let title = self.ask_about_session(PromptKey::SessionTitle).await?;
+        Ok(title
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'')
+            .to_string())
+    }
+
+    async fn ask_about_session(&self, prompt: PromptKey) -> Result<String> {
+        let model = self.options.utility_model.as_deref().ok_or_else(|| {
+            anyhow!("Utility model is not configured, use AgentBuilder::with_utility_model")
+        })?;
+        let transcript = self.transcript();
+        if transcript.is_empty() {
+            return Err(anyhow!("Conversation is empty"));
+        }
+        let chat_req = ChatRequest::new(vec![
+            ChatMessage::system(self.options.prompts.render(prompt, &[])),
+            ChatMessage::user(transcript),
+        ]);
+        let chat_resp = self
+            .exec_chat(
+                model,
+                chat_req,
+                &ChatOptions::default().with_temperature(0.2),
+            )
+            .await?;
+        chat_resp
+            .content
+            .into_iter()
+            .find_map(|content| match content {
+                MessageContent::Text(text) => Some(text.trim().to_string()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Model didn't return text response"))
+    }
+
+    /// Renders history as plain text, without system message.
+    pub(crate) fn transcript(&self) -> String {
+        transcript_of(&self.history)
+    }
+}
+
+/// Renders messages as plain text, without system messages.
+pub(crate) fn transcript_of(messages: &[ChatMessage]) -> String {
+    let mut lines = vec![];
+    for message in messages {
+        let role = match message.role {
+            ChatRole::System => continue,
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+            ChatRole::Tool => "Tool",
+        };
+        match &message.content {
+            MessageContent::Text(text) => lines.push(format!("{role}: {text}")),
+            MessageContent::Parts(parts) => {
+                for part in parts {
+                    match part {
+                        ContentPart::Text(text) => lines.push(format!("{role}: {text}")),
+                        _ => lines.push(format!("{role}: [attachment]")),
+                    }
+                }
+            }
+            MessageContent::ToolCalls(tool_calls) => {
+                for tool_call in tool_calls {
+                    lines.push(format!(
+                        "{role} called tool {}({})",
+                        tool_call.fn_name, tool_call.fn_arguments
+                    ));
+                }
+            }
+            MessageContent::ToolResponses(tool_responses) => {
+                for tool_response in tool_responses {
+                    let mut content: String = tool_response
+                        .content
+                        .chars()
+                        .take(MAX_TOOL_RESULT_LEN)
+                        .collect();
+                    if content.len() < tool_response.content.len() {
+                        content.push('…');
+                    }
+                    lines.push(format!("{role} result: {content}"));
+                }
+            }
+            #[allow(unreachable_patterns)]
+            _ => {}
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::testing::{text, ScriptedModel};
+    use genai::chat::{ToolCall, ToolResponse};
+    use serde_json::json;
+
+    fn conversation(model: &ScriptedModel) -> Agent {
+        let mut agent = Agent::builder()
+            .with_system("You are helpful assistant.")
+            .with_utility_model("gpt-4o-mini")
+            .with_middleware(model.clone())
+            .build();
+        agent.push_message(ChatMessage::user("How do I boil an egg?"));
+        agent.push_message(ChatMessage::assistant("Boil it for 8 minutes."));
+        agent
+    }
+
+    #[tokio::test]
+    async fn test_title_uses_utility_model() {
+        let model = ScriptedModel::new([text(" \"Boiling eggs\"\n")]);
+        let agent = conversation(&model);
+        assert_eq!(agent.suggest_title().await.unwrap(), "Boiling eggs");
+        let requests = model.requests();
+        assert_eq!(requests[0].model, "gpt-4o-mini");
+        assert_eq!(
+            requests[0].request.messages[1].content.text_as_str(),
+            Some("User: How do I boil an egg?\nAssistant: Boil it for 8 minutes.")
+        );
+        // History is not modified
+        assert_eq!(agent.history().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_summary_requires_utility_model_and_history() {
+        let model = ScriptedModel::default();
+        let mut agent = Agent::builder().with_middleware(model.clone()).build();
+        agent.push_message(ChatMessage::user("Hello"));
+        let err = agent.summarize_session().await.unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("Utility model is not configured"));
+
+        let agent = Agent::builder()
+            .with_system("You are helpful assistant.")
+            .with_utility_model("gpt-4o-mini")
+            .with_middleware(model.clone())
+            .build();
+        let err = agent.summarize_session().await.unwrap_err();
+        assert_eq!(err.to_string(), "Conversation is empty");
+        assert!(model.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summary_without_text_fails() {
+        let model = ScriptedModel::new([MessageContent::ToolCalls(vec![])]);
+        let err = conversation(&model).summarize_session().await.unwrap_err();
+        assert_eq!(err.to_string(), "Model didn't return text response");
+    }
+
+    #[test]
+    fn test_transcript_truncates_tool_results() {
+        let messages = vec![
+            ChatMessage::system("You are helpful assistant."),
+            ChatMessage::from(vec![ToolCall {
+                call_id: "call_1".to_string(),
+                fn_name: "read".to_string(),
+                fn_arguments: json!({"path": "a.txt"}),
+            }]),
+            ChatMessage::from(ToolResponse::new("call_1", "é".repeat(600))),
+        ];
+        let transcript = transcript_of(&messages);
+        let lines = transcript.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], r#"Assistant called tool read({"path":"a.txt"})"#);
+        assert_eq!(
+            lines[1],
+            format!("Tool result: {}…", "é".repeat(MAX_TOOL_RESULT_LEN))
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::schema::canonicalize;
+use genai::chat::ToolCall;
+
+/// What to do when the run is not making progress, see [AgentBuilder::with_stall_guard](crate::agent::AgentBuilder::with_stall_guard).
+///
+/// Run is considered stalled when the model repeats exactly the same tool calls in consecutive
+/// iterations, or oscillates between two sets of tool calls (A, B, A, B).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallAction {
+    /// Tools are disabled and the model is asked to provide the final answer.
+    WrapUp,
+    /// Run is aborted with [AgentError::Stalled](crate::agent::AgentError::Stalled).
+    Abort,
+}
+
+/// Detects runs that don't make progress, based on tool calls made in every iteration.
+#[derive(Default)]
+pub(crate) struct StallDetector {
+    iterations: Vec<String>,
+}
+
+impl StallDetector {
+    /// Records tool calls of the iteration, returning reason when the run is stalled.
+    pub(crate) fn record(&mut self, tool_calls: &[ToolCall]) -> Option<String> {
+        let mut calls: Vec<String> = tool_calls
+            .iter()
+            .map(|call| {
+                format!(
+                    "{}({})",
+                    call.fn_name,
+                    canonicalize(call.fn_arguments.clone())
+                )
+            })
+            .collect();
+        calls.sort();
+        self.iterations.push(calls.join(", "));
+
+        match self.iterations.as_slice() {
+            [.., a, b] if a == b => Some(format!("tool calls repeated: {b}")),
+            [.., a, b, c, d] if a == c && b == d => {
+                Some(format!("tool calls oscillate between {c} and {d}"))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call(name: &str) -> ToolCall {
+        ToolCall {
+            call_id: "call".to_string(),
+            fn_name: name.to_string(),
+            fn_arguments: json!({}),
+        }
+    }
+
+    #[test]
+    fn test_stall_detector() {
+        let mut detector = StallDetector::default();
+        assert_eq!(detector.record(&[call("a")]), None);
+        assert_eq!(detector.record(&[call("b")]), None);
+        assert_eq!(detector.record(&[call("a")]), None);
+        assert_eq!(
+            detector.record(&[call("b")]).as_deref(),
+            Some("tool calls oscillate between a({}) and b({})")
+        );
+
+        let mut detector = StallDetector::default();
+        assert_eq!(detector.record(&[call("a"), call("b")]), None);
+        assert!(detector.record(&[call("b"), call("a")]).is_some());
+    }
+}
@@ -0,0 +1,291 @@
+use crate::agent::{Agent, AgentOutput, Prompt};
+use crate::event::{AgentEvent, EventHandler};
+use crate::tool::composite::SharedToolBox;
+use anyhow::Result;
+use futures::future::{self, Either};
+use futures::{Stream, StreamExt};
+use genai::chat::{
+    ChatOptions, ChatRequest, ChatResponse, ChatStreamEvent, MessageContent, ToolCall, Usage,
+};
+use genai::Client;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Chunk of the response returned by [Agent::run_stream].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum AgentChunk<D> {
+    /// Part of the text generated by the model
+    TextDelta(String),
+    /// Event emitted during the run, like tool calls and their results
+    Event(AgentEvent),
+    /// Final answer deserialized into the output type, this is the last chunk of the run
+    Output(D),
+}
+
+impl Agent {
+    /// Runs the agent like [Agent::run], returning a stream of the response chunks.
+    ///
+    /// Requests are sent to the model with streaming enabled, so text appears in
+    /// [AgentChunk::TextDelta] chunks while it is generated, instead of waiting for the whole
+    /// completion. Other [AgentEvent]s of the run are returned as [AgentChunk::Event] and the run
+    /// ends with [AgentChunk::Output], or with an error when the run fails. Registered event
+    /// handler keeps receiving events.
+    ///
+    /// Deltas are sent for every response of the model, also for text generated before tool calls.
+    /// Answers provided without calling the model, e.g. by [ResponseCache](crate::agent::ResponseCache),
+    /// have no deltas. The run is driven by polling the stream, dropping it cancels the run.
+    ///
+    /// ```rust,no_run
+    /// use agentai::agent::AgentChunk;
+    /// use agentai::Agent;
+    /// use futures::StreamExt;
+    /// use std::io::Write;
+    ///
+    /// # async fn example() -> anyhow::Result<()> {
+    /// let mut agent = Agent::new("You are a useful assistant");
+    /// let mut stream = agent.run_stream::<String>("gpt-4o", "Why is the sky blue?", None, None, None);
+    /// while let Some(chunk) = stream.next().await {
+    ///     match chunk? {
+    ///         AgentChunk::TextDelta(text) => {
+    ///             print!("{text}");
+    ///             std::io::stdout().flush()?;
+    ///         }
+    ///         AgentChunk::Output(_) => println!(),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn run_stream<'a, D>(
+        &'a mut self,
+        model: &'a str,
+        prompt: impl Into<Prompt>,
+        toolbox: Option<SharedToolBox>,
+        iteration: Option<u32>,
+        config: Option<ChatOptions>,
+    ) -> impl Stream<Item = Result<AgentChunk<D>>> + Unpin + 'a
+    where
+        D: AgentOutput + 'a,
+    {
+        let prompt = prompt.into();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let run = Box::pin(async move {
+            let guard = StreamingGuard::new(self, sender);
+            guard
+                .agent
+                .run(model, prompt, toolbox, iteration, config)
+                .await
+        });
+        chunks(run, receiver)
+    }
+}
+
+/// Switches the agent to streaming for the duration of the run.
+///
+/// Previous state is restored on drop, so the agent is usable also when the stream is dropped
+/// before the run finished.
+struct StreamingGuard<'a> {
+    agent: &'a mut Agent,
+    previous: Option<EventHandler>,
+}
+
+impl<'a> StreamingGuard<'a> {
+    fn new(agent: &'a mut Agent, sender: mpsc::UnboundedSender<AgentEvent>) -> Self {
+        let handler = agent.event_handler.clone();
+        let previous = agent.replace_event_handler(Some(Arc::new(move |event: &AgentEvent| {
+            if let Some(handler) = &handler {
+                handler(event);
+            }
+            let _ = sender.send(event.clone());
+        })));
+        agent.streaming = true;
+        Self { agent, previous }
+    }
+}
+
+impl Drop for StreamingGuard<'_> {
+    fn drop(&mut self) {
+        self.agent.streaming = false;
+        self.agent.replace_event_handler(self.previous.take());
+    }
+}
+
+/// Yields events received while the run is in progress, followed by its result.
+fn chunks<D>(
+    run: impl Future<Output = Result<D>> + Unpin,
+    receiver: mpsc::UnboundedReceiver<AgentEvent>,
+) -> impl Stream<Item = Result<AgentChunk<D>>> + Unpin {
+    let state = (Some(run), receiver, None);
+    Box::pin(futures::stream::unfold(
+        state,
+        |(mut run, mut receiver, mut result)| async move {
+            let mut event = None;
+            if let Some(running) = run.take() {
+                match future::select(Box::pin(receiver.recv()), running).await {
+                    Either::Left((Some(received), running)) => {
+                        run = Some(running);
+                        event = Some(received);
+                    }
+                    // Channel is closed, there will be no more events
+                    Either::Left((None, running)) => result = Some(running.await),
+                    Either::Right((finished, _)) => result = Some(finished),
+                }
+            }
+            // Events emitted before the run finished are in the channel
+            if run.is_none() && event.is_none() {
+                event = receiver.try_recv().ok();
+            }
+            let chunk = match event {
+                Some(AgentEvent::TextDelta { content }) => Ok(AgentChunk::TextDelta(content)),
+                Some(event) => Ok(AgentChunk::Event(event)),
+                None => result.take()?.map(AgentChunk::Output),
+            };
+            Some((chunk, (run, receiver, result)))
+        },
+    ))
+}
+
+/// Sends the request with streaming enabled, passing text deltas to the handler.
+///
+/// Response is assembled from the stream, so the agent loop handles it like the response of
+/// [Client::exec_chat].
+pub(crate) async fn exec_chat_streamed(
+    client: &Client,
+    model: &str,
+    chat_req: ChatRequest,
+    chat_opts: &ChatOptions,
+    handler: &EventHandler,
+) -> Result<ChatResponse> {
+    let chat_opts = chat_opts
+        .clone()
+        .with_capture_usage(true)
+        .with_capture_tool_calls(true);
+    let response = client
+        .exec_chat_stream(model, chat_req, Some(&chat_opts))
+        .await?;
+    let mut stream = response.stream;
+    let mut text = String::new();
+    let mut reasoning_content: Option<String> = None;
+    let mut tool_calls: Vec<ToolCall> = vec![];
+    let mut usage = Usage::default();
+    while let Some(event) = stream.next().await {
+        match event? {
+            ChatStreamEvent::Chunk(chunk) => {
+                text.push_str(&chunk.content);
+                handler(&AgentEvent::TextDelta {
+                    content: chunk.content,
+                });
+            }
+            ChatStreamEvent::ReasoningChunk(chunk) => reasoning_content
+                .get_or_insert_with(String::new)
+                .push_str(&chunk.content),
+            // Chunks carry the call accumulated so far
+            ChatStreamEvent::ToolCallChunk(chunk) => {
+                let call = chunk.tool_call;
+                match tool_calls.iter_mut().find(|c| c.call_id == call.call_id) {
+                    Some(existing) => *existing = call,
+                    None => tool_calls.push(call),
+                }
+            }
+            ChatStreamEvent::End(end) => {
+                usage = end.captured_usage.unwrap_or_default();
+                if text.is_empty() {
+                    if let Some(content) = end.captured_content.as_ref() {
+                        text = content.text_as_str().unwrap_or_default().to_string();
+                    }
+                }
+            }
+            ChatStreamEvent::Start => {}
+        }
+    }
+    // Text generated before tool calls is not the answer
+    let content = if !tool_calls.is_empty() {
+        vec![MessageContent::ToolCalls(tool_calls)]
+    } else {
+        vec![MessageContent::Text(text)]
+    };
+    Ok(ChatResponse {
+        content,
+        reasoning_content,
+        model_iden: response.model_iden.clone(),
+        provider_model_iden: response.model_iden,
+        usage,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{ChatMiddleware, RequestContext};
+    use anyhow::anyhow;
+
+    #[tokio::test]
+    async fn test_chunks() {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let run = Box::pin(async move {
+            sender
+                .send(AgentEvent::IterationStarted { iteration: 0 })
+                .unwrap();
+            tokio::task::yield_now().await;
+            for content in ["The sky ", "is blue"] {
+                sender
+                    .send(AgentEvent::TextDelta {
+                        content: content.to_string(),
+                    })
+                    .unwrap();
+            }
+            Ok("The sky is blue".to_string())
+        });
+        let streamed = chunks(run, receiver).collect::<Vec<_>>().await;
+        assert_eq!(streamed.len(), 4);
+        assert!(matches!(
+            streamed[0],
+            Ok(AgentChunk::Event(AgentEvent::IterationStarted {
+                iteration: 0
+            }))
+        ));
+        assert!(matches!(&streamed[1], Ok(AgentChunk::TextDelta(text)) if text == "The sky "));
+        assert!(matches!(&streamed[2], Ok(AgentChunk::TextDelta(text)) if text == "is blue"));
+        assert!(
+            matches!(&streamed[3], Ok(AgentChunk::Output(answer)) if answer == "The sky is blue")
+        );
+
+        let (_sender, receiver) = mpsc::unbounded_channel();
+        let run = Box::pin(async { Err::<String, _>(anyhow!("Model is not available")) });
+        let streamed = chunks(run, receiver).collect::<Vec<_>>().await;
+        assert_eq!(streamed.len(), 1);
+        assert!(streamed[0].is_err());
+    }
+
+    /// Middleware waiting forever for the model
+    struct Pending;
+
+    #[async_trait::async_trait]
+    impl ChatMiddleware for Pending {
+        async fn before_request(
+            &self,
+            _context: &mut RequestContext,
+            _request: &mut ChatRequest,
+            _options: &mut ChatOptions,
+        ) -> Result<Option<ChatResponse>> {
+            future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream() {
+        let mut agent = Agent::builder()
+            .with_middleware(Pending)
+            .with_event_handler(|_: &AgentEvent| {})
+            .build();
+        let handler = agent.event_handler.clone().unwrap();
+        let mut stream = agent.run_stream::<String>("gpt-4o", "Hello", None, None, None);
+        assert!(futures::poll!(stream.next()).is_pending());
+        drop(stream);
+        assert!(!agent.streaming);
+        assert!(Arc::ptr_eq(agent.event_handler.as_ref().unwrap(), &handler));
+    }
+}
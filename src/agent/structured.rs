@@ -0,0 +1,24 @@
+use crate::agent::{PromptKey, Prompts};
+use serde_json::Value;
+
+/// How the agent makes the model return answer matching the schema of the output type.
+///
+/// Look into [crate::structured_output] for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StructuredOutputMode {
+    /// Schema is sent as the response format, using native support of the provider.
+    #[default]
+    Native,
+    /// Schema is converted to satisfy requirements of strict structured outputs (see [strict](crate::schema::strict))
+    /// and sent as the response format. Providers supporting it (OpenAI, llama.cpp server) constrain
+    /// decoding, so the answer always matches the schema.
+    Strict,
+    /// Schema is included in the instructions, for providers without support of structured output.
+    /// The answer is not guaranteed to match the schema.
+    Prompt,
+}
+
+/// Returns instruction asking the model to answer with JSON matching the schema.
+pub(crate) fn prompt_instruction(prompts: &Prompts, schema: &Value) -> String {
+    prompts.render(PromptKey::OutputSchema, &[("schema", &schema.to_string())])
+}
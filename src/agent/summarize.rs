@@ -0,0 +1,236 @@
+use crate::agent::extract::{split_text, DEFAULT_EXTRACTION_CHUNK_LEN};
+use crate::agent::{Agent, PromptKey, Prompts, TokenUsage};
+use anyhow::{anyhow, Result};
+use futures::future::try_join_all;
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, MessageContent, Usage};
+use log::debug;
+
+/// Style of the summary generated by [Agent::summarize].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SummaryStyle {
+    /// Few sentences describing the main points
+    #[default]
+    Brief,
+    /// Several paragraphs covering all important details
+    Detailed,
+    /// List of key points
+    Bullets,
+    /// Custom instruction describing the summary
+    Custom(String),
+}
+
+impl SummaryStyle {
+    fn instruction(&self, prompts: &Prompts) -> String {
+        let style = match self {
+            SummaryStyle::Brief => prompts.get(PromptKey::SummaryBrief),
+            SummaryStyle::Detailed => prompts.get(PromptKey::SummaryDetailed),
+            SummaryStyle::Bullets => prompts.get(PromptKey::SummaryBullets),
+            SummaryStyle::Custom(instruction) => instruction,
+        };
+        prompts.render(PromptKey::Summarize, &[("style", style)])
+    }
+}
+
+/// Result of the [Agent::summarize].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Summary {
+    /// Summary of the whole document
+    pub text: String,
+    /// Tokens used to summarize every chunk of the document, in order of chunks
+    pub chunks_usage: Vec<TokenUsage>,
+    /// Tokens used in total, including reducing summaries of chunks
+    pub usage: TokenUsage,
+}
+
+impl Agent {
+    /// Summarizes the document, also when it doesn't fit into the context window of the model.
+    ///
+    /// Long documents are split into chunks (see [AgentBuilder::with_extraction_chunk_len](crate::agent::AgentBuilder::with_extraction_chunk_len)),
+    /// which are summarized concurrently. Summaries of chunks are then reduced into the final
+    /// summary of requested style, when they are still too long they are summarized again.
+    /// Requests respect limit of parallel requests (see [RequestLimiter](crate::agent::RequestLimiter))
+    /// and switch to cheaper model after reaching the usage threshold (see [ModelDowngrade](crate::agent::ModelDowngrade)).
+    ///
+    /// Like [Agent::extract] history of the agent is neither used nor modified.
+    pub async fn summarize(
+        &mut self,
+        model: &str,
+        document: &str,
+        style: SummaryStyle,
+    ) -> Result<Summary> {
+        let usage_at_start = self.usage;
+        let chunk_len = self
+            .options
+            .extraction_chunk_len
+            .unwrap_or(DEFAULT_EXTRACTION_CHUNK_LEN);
+
+        let mut chunks: Vec<String> = split_text(document, chunk_len)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let mut chunks_usage = None;
+        while chunks.len() > 1 {
+            debug!("Summarizing {} chunks", chunks.len());
+            let model = self.current_model(model);
+            let instruction = self.options.prompts.render(PromptKey::SummarizeChunk, &[]);
+            let results = try_join_all(
+                chunks
+                    .iter()
+                    .map(|chunk| self.summarize_text(&model, &instruction, chunk)),
+            )
+            .await?;
+
+            let mut usages = Vec::with_capacity(results.len());
+            let mut summaries = Vec::with_capacity(results.len());
+            for (summary, usage) in results {
+                self.record_usage(&model, &usage);
+                let mut chunk_usage = TokenUsage::default();
+                chunk_usage.add(&usage);
+                usages.push(chunk_usage);
+                summaries.push(summary);
+            }
+            // Usage of the chunks of original document is reported
+            chunks_usage.get_or_insert(usages);
+
+            let combined = summaries.join("\n\n");
+            let next_chunks: Vec<String> = split_text(&combined, chunk_len)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            if next_chunks.len() >= chunks.len() {
+                // Summaries are not getting shorter, reduce them all at once
+                chunks = vec![combined];
+            } else {
+                chunks = next_chunks;
+            }
+        }
+
+        let model = self.current_model(model);
+        let text = chunks.pop().unwrap_or_default();
+        let instruction = style.instruction(&self.options.prompts);
+        let (text, usage) = self.summarize_text(&model, &instruction, &text).await?;
+        self.record_usage(&model, &usage);
+        let chunks_usage = chunks_usage.unwrap_or_else(|| {
+            let mut chunk_usage = TokenUsage::default();
+            chunk_usage.add(&usage);
+            vec![chunk_usage]
+        });
+
+        Ok(Summary {
+            text,
+            chunks_usage,
+            usage: self.usage.since(&usage_at_start),
+        })
+    }
+
+    async fn summarize_text(
+        &self,
+        model: &str,
+        instruction: &str,
+        text: &str,
+    ) -> Result<(String, Usage)> {
+        let chat_req = ChatRequest::new(vec![
+            ChatMessage::system(instruction),
+            ChatMessage::user(text),
+        ]);
+        let mut chat_opts = ChatOptions::default().with_temperature(0.2);
+        if let Some(seed) = self.options.deterministic_seed {
+            chat_opts = chat_opts.with_temperature(0.0).with_seed(seed);
+        }
+        let chat_resp = self.exec_chat(model, chat_req, &chat_opts).await?;
+        let summary = chat_resp
+            .content
+            .into_iter()
+            .find_map(|content| match content {
+                MessageContent::Text(text) => Some(text.trim().to_string()),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("Model didn't return text response"))?;
+        Ok((summary, chat_resp.usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::testing::{text, ScriptedModel};
+
+    fn agent(model: &ScriptedModel) -> Agent {
+        Agent::builder()
+            .with_middleware(model.clone())
+            .with_extraction_chunk_len(20)
+            .build()
+    }
+
+    fn usage(total_tokens: i32) -> Usage {
+        Usage {
+            total_tokens: Some(total_tokens),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_long_document_reduced() {
+        let model = ScriptedModel::new([text("A"), text("B"), text("C"), text(" Summary ")])
+            .with_usage(usage(10));
+        let mut agent = agent(&model);
+        let document = "First paragraph.\n\nSecond paragraph.\n\nThird paragraph.";
+        let summary = agent
+            .summarize("gpt-4o", document, SummaryStyle::Bullets)
+            .await
+            .unwrap();
+        assert_eq!(summary.text, "Summary");
+        assert_eq!(summary.chunks_usage.len(), 3);
+        assert!(summary
+            .chunks_usage
+            .iter()
+            .all(|usage| usage.total_tokens == 10 && usage.requests == 1));
+        assert_eq!(
+            (summary.usage.total_tokens, summary.usage.requests),
+            (40, 4)
+        );
+        assert_eq!(agent.usage().total_tokens, 40);
+
+        let requests = model.requests();
+        assert_eq!(requests.len(), 4);
+        // Final request reduces summaries of the chunks, history is not used
+        let last = &requests[3].request.messages;
+        assert_eq!(last.len(), 2);
+        assert_eq!(last[1].content.text_as_str(), Some("A\n\nB\n\nC"));
+    }
+
+    #[tokio::test]
+    async fn test_short_document_single_request() {
+        let model = ScriptedModel::new([text("Short")]).with_usage(usage(5));
+        let summary = agent(&model)
+            .summarize("gpt-4o", "Tiny text.", SummaryStyle::default())
+            .await
+            .unwrap();
+        assert_eq!(summary.text, "Short");
+        assert_eq!(summary.chunks_usage.len(), 1);
+        assert_eq!(summary.usage.requests, 1);
+        assert_eq!(model.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_chunk_fails_summary() {
+        let model = ScriptedModel::new([text("A")]);
+        let err = agent(&model)
+            .summarize(
+                "gpt-4o",
+                "First paragraph.\n\nSecond paragraph.",
+                SummaryStyle::Brief,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Model has no more scripted responses");
+
+        let model = ScriptedModel::new([MessageContent::ToolCalls(vec![])]);
+        let err = agent(&model)
+            .summarize("gpt-4o", "Tiny text.", SummaryStyle::Brief)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Model didn't return text response");
+    }
+}
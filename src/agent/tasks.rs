@@ -0,0 +1,74 @@
+use log::debug;
+use std::future::Future;
+
+/// Executes part of the run, a request to the model or a tool call, as a separate task.
+///
+/// Tasks are named after the work they do and, with `observability` feature, instrumented with
+/// `tracing` span nested in the span of the run. They are scoped to the run: the run waits for the
+/// task, and the task is aborted when the run is dropped, so cancellation and timeouts of runs stop
+/// also their requests and tools. Panic of the task is caught and returned as error with the panic
+/// message, instead of unwinding through the agent loop.
+///
+/// Tasks are spawned on the current Tokio runtime. Without the runtime the future is executed in
+/// place.
+pub(crate) async fn run_task<F>(name: &str, future: F) -> Result<F::Output, String>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    use crate::tool::panic_message;
+    use futures::FutureExt;
+    use std::panic::AssertUnwindSafe;
+
+    // Nested toolboxes track delegation in task local storage
+    let future = crate::tool::composite::with_call_path(future);
+    #[cfg(feature = "observability")]
+    let future = tracing::Instrument::instrument(future, tracing::info_span!("agent_task", name));
+    debug!("Task `{name}` started");
+    let result = match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            let mut task = AbortOnDrop(runtime.spawn(future));
+            (&mut task.0)
+                .await
+                .map_err(|err| match err.try_into_panic() {
+                    Ok(payload) => panic_message(payload),
+                    Err(err) => err.to_string(),
+                })
+        }
+        Err(_) => AssertUnwindSafe(future)
+            .catch_unwind()
+            .await
+            .map_err(panic_message),
+    };
+    match &result {
+        Ok(_) => debug!("Task `{name}` finished"),
+        Err(message) => debug!("Task `{name}` panicked: {message}"),
+    }
+    result
+}
+
+/// Aborts the task when the run waiting for it is dropped.
+struct AbortOnDrop<T>(tokio::task::JoinHandle<T>);
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_task() {
+        assert_eq!(run_task("answer", async { 42 }).await, Ok(42));
+        let result = run_task("panicking", async {
+            if true {
+                panic!("tool failed");
+            }
+        })
+        .await;
+        assert_eq!(result, Err("tool failed".to_string()));
+    }
+}
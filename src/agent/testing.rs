@@ -0,0 +1,92 @@
+use crate::agent::{ChatMiddleware, RequestContext};
+use anyhow::{anyhow, Result};
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatOptions, ChatRequest, ChatResponse, MessageContent, ToolCall, Usage};
+use genai::ModelIden;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Request received by [ScriptedModel].
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedRequest {
+    pub(crate) model: String,
+    pub(crate) request: ChatRequest,
+    pub(crate) options: ChatOptions,
+}
+
+/// Middleware answering requests with scripted responses instead of the model.
+///
+/// Clones share the script and requests, so a clone kept by the test can inspect requests sent by
+/// the agent. Requests after the end of the script fail.
+#[derive(Clone, Default)]
+pub(crate) struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<MessageContent>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    usage: Usage,
+}
+
+impl ScriptedModel {
+    pub(crate) fn new(responses: impl IntoIterator<Item = MessageContent>) -> Self {
+        Self {
+            responses: Arc::new(Mutex::new(responses.into_iter().collect())),
+            requests: Arc::default(),
+            usage: Usage::default(),
+        }
+    }
+
+    /// Reports `usage` in every response.
+    pub(crate) fn with_usage(mut self, usage: Usage) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// Returns requests received so far.
+    pub(crate) fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Response of the model with a text.
+pub(crate) fn text(text: &str) -> MessageContent {
+    MessageContent::Text(text.to_string())
+}
+
+/// Response of the model calling a tool.
+pub(crate) fn tool_call(call_id: &str, name: &str, arguments: Value) -> MessageContent {
+    MessageContent::ToolCalls(vec![ToolCall {
+        call_id: call_id.to_string(),
+        fn_name: name.to_string(),
+        fn_arguments: arguments,
+    }])
+}
+
+#[async_trait::async_trait]
+impl ChatMiddleware for ScriptedModel {
+    async fn before_request(
+        &self,
+        context: &mut RequestContext,
+        request: &mut ChatRequest,
+        options: &mut ChatOptions,
+    ) -> Result<Option<ChatResponse>> {
+        self.requests.lock().unwrap().push(RecordedRequest {
+            model: context.model.clone(),
+            request: request.clone(),
+            options: options.clone(),
+        });
+        let content = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| anyhow!("Model has no more scripted responses"))?;
+        let model_iden = ModelIden::new(AdapterKind::OpenAI, context.model.as_str());
+        Ok(Some(ChatResponse {
+            content: vec![content],
+            reasoning_content: None,
+            model_iden: model_iden.clone(),
+            provider_model_iden: model_iden,
+            usage: self.usage.clone(),
+        }))
+    }
+}
@@ -0,0 +1,190 @@
+use genai::chat::Usage;
+use serde::{Deserialize, Serialize};
+
+/// Number of tokens used by the agent, accumulated over all requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    /// Tokens sent to the model (prompt, history and tools definitions)
+    pub prompt_tokens: u64,
+    /// Tokens generated by the model
+    pub completion_tokens: u64,
+    /// Sum of prompt and completion tokens
+    pub total_tokens: u64,
+    /// Number of requests sent to the model
+    pub requests: u64,
+    /// Number of requests answered by [ChatMiddleware](crate::agent::ChatMiddleware) without calling
+    /// the model, e.g. by [ResponseCache](crate::agent::ResponseCache), their tokens are not counted
+    pub cached_requests: u64,
+}
+
+impl TokenUsage {
+    /// Adds usage reported by the provider for single request.
+    pub fn add(&mut self, usage: &Usage) {
+        let prompt_tokens = usage.prompt_tokens.unwrap_or_default().max(0) as u64;
+        let completion_tokens = usage.completion_tokens.unwrap_or_default().max(0) as u64;
+        self.prompt_tokens += prompt_tokens;
+        self.completion_tokens += completion_tokens;
+        // Not every provider reports total
+        self.total_tokens += usage
+            .total_tokens
+            .map(|total| total.max(0) as u64)
+            .unwrap_or(prompt_tokens + completion_tokens);
+        self.requests += 1;
+    }
+
+    /// Adds usage of other agent, e.g. the one that the task was handed off to.
+    pub(crate) fn merge(&mut self, other: &TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.requests += other.requests;
+        self.cached_requests += other.cached_requests;
+    }
+
+    /// Returns usage since `earlier` snapshot of the statistics.
+    pub(crate) fn since(&self, earlier: &TokenUsage) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens: self.prompt_tokens.saturating_sub(earlier.prompt_tokens),
+            completion_tokens: self
+                .completion_tokens
+                .saturating_sub(earlier.completion_tokens),
+            total_tokens: self.total_tokens.saturating_sub(earlier.total_tokens),
+            requests: self.requests.saturating_sub(earlier.requests),
+            cached_requests: self.cached_requests.saturating_sub(earlier.cached_requests),
+        }
+    }
+
+    /// Returns cost of used tokens, given prices per million of tokens.
+    pub fn cost(&self, input_price: f64, output_price: f64) -> f64 {
+        (self.prompt_tokens as f64 * input_price + self.completion_tokens as f64 * output_price)
+            / 1_000_000.0
+    }
+}
+
+/// Threshold of the usage, after which [ModelDowngrade] takes effect.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageThreshold {
+    /// Total number of tokens
+    Tokens(u64),
+    /// Cost of used tokens, with prices per million of tokens
+    Cost {
+        /// Maximal cost, in the same currency as prices
+        limit: f64,
+        /// Price of million prompt tokens
+        input_price: f64,
+        /// Price of million completion tokens
+        output_price: f64,
+    },
+}
+
+impl UsageThreshold {
+    /// Returns `true` when usage reached the threshold.
+    pub fn is_reached(&self, usage: &TokenUsage) -> bool {
+        match *self {
+            UsageThreshold::Tokens(tokens) => usage.total_tokens >= tokens,
+            UsageThreshold::Cost {
+                limit,
+                input_price,
+                output_price,
+            } => usage.cost(input_price, output_price) >= limit,
+        }
+    }
+}
+
+/// Policy switching the agent to a cheaper model after reaching usage threshold.
+///
+/// Long-lived assistants can accumulate significant costs. With this policy, once [Agent::usage](crate::agent::Agent::usage)
+/// reaches the threshold, all following requests (including the rest of the current run) are sent to
+/// the cheaper model, and [AgentEvent::ModelDowngraded](crate::event::AgentEvent::ModelDowngraded) is emitted.
+/// The downgrade lasts until [Agent::reset_usage](crate::agent::Agent::reset_usage) is called.
+///
+/// ```rust
+/// use agentai::{Agent, ModelDowngrade, UsageThreshold};
+///
+/// let agent = Agent::builder()
+///     .with_model_downgrade(ModelDowngrade::new(UsageThreshold::Tokens(100_000), "gpt-4o-mini"))
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModelDowngrade {
+    /// Usage after which model is switched
+    pub threshold: UsageThreshold,
+    /// Cheaper model used after reaching the threshold
+    pub model: String,
+}
+
+impl ModelDowngrade {
+    /// Creates policy switching to `model` after reaching `threshold`.
+    pub fn new(threshold: UsageThreshold, model: &str) -> Self {
+        Self {
+            threshold,
+            model: model.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(prompt: i32, completion: i32, total: Option<i32>) -> Usage {
+        Usage {
+            prompt_tokens: Some(prompt),
+            completion_tokens: Some(completion),
+            total_tokens: total,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_add_computes_missing_total() {
+        let mut tokens = TokenUsage::default();
+        tokens.add(&usage(100, 20, Some(130)));
+        tokens.add(&usage(50, 10, None));
+        // Negative counts reported by broken providers are ignored
+        tokens.add(&usage(-5, -1, Some(-6)));
+        assert_eq!(
+            tokens,
+            TokenUsage {
+                prompt_tokens: 150,
+                completion_tokens: 30,
+                total_tokens: 190,
+                requests: 3,
+                cached_requests: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_since_snapshot() {
+        let mut tokens = TokenUsage::default();
+        tokens.add(&usage(100, 20, None));
+        let snapshot = tokens;
+        tokens.add(&usage(10, 5, None));
+        tokens.cached_requests += 1;
+        let delta = tokens.since(&snapshot);
+        assert_eq!((delta.total_tokens, delta.requests), (15, 1));
+        assert_eq!(delta.cached_requests, 1);
+        // Snapshot taken after reset doesn't underflow
+        assert_eq!(TokenUsage::default().since(&tokens), TokenUsage::default());
+    }
+
+    #[test]
+    fn test_threshold_reached() {
+        let mut tokens = TokenUsage::default();
+        tokens.add(&usage(800_000, 100_000, None));
+        assert!(UsageThreshold::Tokens(900_000).is_reached(&tokens));
+        assert!(!UsageThreshold::Tokens(900_001).is_reached(&tokens));
+
+        // 0.8 * 2.5 + 0.1 * 10.0
+        assert_eq!(tokens.cost(2.5, 10.0), 3.0);
+        let cost = |limit| UsageThreshold::Cost {
+            limit,
+            input_price: 2.5,
+            output_price: 10.0,
+        };
+        assert!(cost(3.0).is_reached(&tokens));
+        assert!(!cost(3.5).is_reached(&tokens));
+    }
+}
@@ -0,0 +1,168 @@
+//! # Configuration Defaults
+//!
+//! Programs using agents repeat the same setup: which model to use, where the OpenAI API
+//! compatible endpoint is and which environment variable holds the API key. [Config] reads these
+//! defaults once per machine from `~/.config/agentai/config.toml`, and from the environment, so
+//! examples and CLIs work everywhere without environment variables set for every program. It is
+//! enabled with `config` feature.
+//!
+//! ```toml
+//! model = "openai/gpt-4.1-mini"
+//! utility_model = "openai/gpt-4.1-nano"
+//! base_url = "https://openrouter.ai/api/v1"
+//! api_key_env = "OPENROUTER_API_KEY"
+//! telemetry = false
+//! ```
+//!
+//! Environment variables take precedence over the file:
+//!
+//! - `AGENTAI_CONFIG` -- path of the configuration file,
+//! - `AGENTAI_MODEL`, `AGENTAI_UTILITY_MODEL`, `AGENTAI_BASE_URL`, `AGENTAI_API_KEY_ENV` and
+//!   `AGENTAI_TELEMETRY` -- override the settings of the file.
+//!
+//! Settings configured in the code take precedence over the configuration, [AgentBuilder::with_config](crate::agent::AgentBuilder::with_config)
+//! fills only settings that were not set.
+//!
+//! ```rust,no_run
+//! use agentai::config::Config;
+//! use agentai::Agent;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let config = Config::load()?;
+//! let mut agent = Agent::builder()
+//!     .with_system("You are a helpful assistant.")
+//!     .with_config(&config)
+//!     .build();
+//! let model = config.model_or("openai/gpt-4.1-mini");
+//! let answer: String = agent.run(&model, "Hello!", None, None, None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Environment variable with path of the configuration file.
+pub const CONFIG_PATH_ENV: &str = "AGENTAI_CONFIG";
+/// Environment variable with API key, used when the configuration doesn't name other one.
+pub const DEFAULT_API_KEY_ENV: &str = "AGENTAI_API_KEY";
+
+/// Defaults loaded from the configuration file and the environment.
+///
+/// Look into [module documentation](crate::config) for more details.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[non_exhaustive]
+pub struct Config {
+    /// Default model of runs, the agent doesn't store it so it has to be passed to the run by caller
+    pub model: Option<String>,
+    /// Cheap model used for auxiliary tasks, see [AgentBuilder::with_utility_model](crate::agent::AgentBuilder::with_utility_model)
+    pub utility_model: Option<String>,
+    /// URL of OpenAI API compatible endpoint, default endpoints of providers are used when not set
+    pub base_url: Option<String>,
+    /// Name of the environment variable with API key for `base_url`, [DEFAULT_API_KEY_ENV] when not set
+    pub api_key_env: Option<String>,
+    /// Whether the user opted in to telemetry. The crate doesn't send anything by itself,
+    /// applications should check it before enabling exporters, e.g. [crate::trace].
+    pub telemetry: bool,
+}
+
+impl Config {
+    /// Loads configuration from the default file (if it exists) merged with environment variables.
+    pub fn load() -> Result<Self> {
+        let path = std::env::var_os(CONFIG_PATH_ENV)
+            .map(PathBuf::from)
+            .or_else(Self::default_path);
+        let config = match path {
+            Some(path) if path.exists() => Self::from_file(&path)?,
+            _ => Self::default(),
+        };
+        Ok(config.merge_env(|name| std::env::var(name).ok()))
+    }
+
+    /// Loads configuration from the TOML file, without looking into environment variables.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid configuration {}", path.display()))
+    }
+
+    /// Parses configuration in TOML format.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Returns default path of the configuration file, `agentai/config.toml` in the configuration
+    /// directory of the user (`$XDG_CONFIG_HOME`, `~/.config` or `%APPDATA%` on Windows).
+    pub fn default_path() -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        let directory = var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| var("APPDATA").map(PathBuf::from))?;
+        Some(directory.join("agentai").join("config.toml"))
+    }
+
+    /// Returns configured model, or the default one.
+    pub fn model_or(&self, default: &str) -> String {
+        self.model.clone().unwrap_or_else(|| default.to_string())
+    }
+
+    /// Returns API key from the configured environment variable.
+    pub fn api_key(&self) -> Option<String> {
+        let name = self.api_key_env.as_deref().unwrap_or(DEFAULT_API_KEY_ENV);
+        std::env::var(name).ok()
+    }
+
+    /// Overrides settings with environment variables returned by `var`.
+    fn merge_env(mut self, var: impl Fn(&str) -> Option<String>) -> Self {
+        let fields = [
+            ("AGENTAI_MODEL", &mut self.model),
+            ("AGENTAI_UTILITY_MODEL", &mut self.utility_model),
+            ("AGENTAI_BASE_URL", &mut self.base_url),
+            ("AGENTAI_API_KEY_ENV", &mut self.api_key_env),
+        ];
+        for (name, field) in fields {
+            if let Some(value) = var(name).filter(|value| !value.is_empty()) {
+                *field = Some(value);
+            }
+        }
+        if let Some(telemetry) = var("AGENTAI_TELEMETRY") {
+            self.telemetry = matches!(
+                telemetry.to_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            );
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::from_toml(
+            r#"
+            model = "gpt-4o"
+            base_url = "http://localhost:8080/v1"
+            telemetry = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.model_or("gpt-4o-mini"), "gpt-4o");
+        assert!(Config::from_toml("modle = \"gpt-4o\"").is_err());
+
+        let config = config.merge_env(|name| match name {
+            "AGENTAI_MODEL" => Some("gpt-4.1".to_string()),
+            "AGENTAI_BASE_URL" => Some(String::new()),
+            "AGENTAI_TELEMETRY" => Some("0".to_string()),
+            _ => None,
+        });
+        assert_eq!(config.model.as_deref(), Some("gpt-4.1"));
+        assert_eq!(config.base_url.as_deref(), Some("http://localhost:8080/v1"));
+        assert!(!config.telemetry);
+    }
+}
@@ -0,0 +1,287 @@
+//! # Discord Connector
+//!
+//! Connects [SessionRouter] with Discord using its REST API. The bot periodically polls configured
+//! channels for new messages, so no gateway connection or public endpoint is required. Each channel
+//! is a separate session.
+//!
+//! The bot needs `Message Content Intent` enabled in Discord Developer Portal, and permissions to
+//! read and send messages in configured channels. Slash commands are recognized from message text
+//! (e.g. `/tools`), they are not registered as Discord application commands.
+//!
+//! ```rust,no_run
+//! use agentai::Agent;
+//! use agentai::connectors::{discord::DiscordConnector, SessionRouter};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let router = SessionRouter::new(Agent::new("You are helpful assistant"), "gpt-4.1-mini");
+//! DiscordConnector::new("<BOT TOKEN>", vec!["<CHANNEL ID>".to_string()], router)
+//!     .run()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::connectors::{show_progress, split_message, SessionRouter};
+use anyhow::{anyhow, Result};
+use log::{debug, error, warn};
+use reqwest::{Client, Method, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const DISCORD_API_URL: &str = "https://discord.com/api/v10";
+// Maximal length of message accepted by Discord
+const MESSAGE_LIMIT: usize = 2000;
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+    #[serde(default)]
+    content: String,
+    author: Author,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    #[serde(default)]
+    bot: bool,
+}
+
+impl Message {
+    /// Returns `false` for messages of bots (including this one) and messages without text.
+    fn needs_answer(&self) -> bool {
+        !self.author.bot && !self.content.trim().is_empty()
+    }
+}
+
+/// Discord bot serving agent sessions in selected channels.
+pub struct DiscordConnector {
+    client: Client,
+    token: String,
+    channels: Vec<String>,
+    poll_interval: Duration,
+    router: SessionRouter,
+}
+
+impl DiscordConnector {
+    /// Creates connector for the bot identified by `token`, answering in provided `channels` (by id).
+    pub fn new(token: &str, channels: Vec<String>, router: SessionRouter) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            token: token.to_string(),
+            channels,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            router,
+        }
+    }
+
+    /// Changes how often channels are checked for new messages.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Polls channels and answers messages until an unrecoverable error occurs. Messages of
+    /// different channels are answered concurrently.
+    pub async fn run(self) -> Result<()> {
+        let token: Arc<str> = self.token.into();
+        let router = Arc::new(self.router);
+        // Messages sent before the bot was started are ignored
+        let mut last_seen = HashMap::new();
+        for channel in &self.channels {
+            let messages: Vec<Message> = request(
+                &self.client,
+                &token,
+                Method::GET,
+                &format!("/channels/{channel}/messages?limit=1"),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+            last_seen.insert(channel.clone(), messages.first().map(|m| m.id.clone()));
+        }
+
+        loop {
+            for channel in self.channels.clone() {
+                let path = match last_seen.get(&channel).cloned().flatten() {
+                    Some(after) => format!("/channels/{channel}/messages?after={after}&limit=50"),
+                    None => format!("/channels/{channel}/messages?limit=50"),
+                };
+                let messages: Vec<Message> = match fetch(&self.client, &token, &path).await {
+                    Ok(messages) => messages,
+                    Err(err) => {
+                        warn!("Unable to receive Discord messages from {channel}: {err}");
+                        continue;
+                    }
+                };
+
+                let mut messages = messages;
+                sort_chronologically(&mut messages);
+                for message in messages {
+                    last_seen.insert(channel.clone(), Some(message.id.clone()));
+                    if !message.needs_answer() {
+                        continue;
+                    }
+                    let (client, token, router, channel) = (
+                        self.client.clone(),
+                        token.clone(),
+                        router.clone(),
+                        channel.clone(),
+                    );
+                    tokio::spawn(async move {
+                        let result =
+                            handle_message(&client, &token, &router, &channel, &message.content)
+                                .await;
+                        if let Err(err) = result {
+                            error!("Unable to answer Discord message: {err}");
+                        }
+                    });
+                }
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+async fn handle_message(
+    client: &Client,
+    token: &str,
+    router: &SessionRouter,
+    channel: &str,
+    text: &str,
+) -> Result<()> {
+    debug!("Discord message in channel {channel}: {text}");
+    request(
+        client,
+        token,
+        Method::POST,
+        &format!("/channels/{channel}/typing"),
+    )
+    .send()
+    .await?;
+    let reply = send_message(client, token, channel, "…").await?;
+
+    let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+    let reply_id = reply.id.as_str();
+    let progress = show_progress(progress_rx, move |text| async move {
+        let text = split_message(&text, MESSAGE_LIMIT).remove(0);
+        let _ = edit_message(client, token, channel, reply_id, &text).await;
+    });
+    let (answer, _) = tokio::join!(
+        router.handle_message(channel, text, Some(progress_tx)),
+        progress
+    );
+
+    let answer = answer.unwrap_or_else(|err| format!("Error: {err}"));
+    let mut parts = split_message(&answer, MESSAGE_LIMIT).into_iter();
+    if let Some(first) = parts.next() {
+        edit_message(client, token, channel, &reply.id, &first).await?;
+    }
+    for part in parts {
+        send_message(client, token, channel, &part).await?;
+    }
+    Ok(())
+}
+
+/// Sorts messages from the oldest, Discord returns the newest first.
+fn sort_chronologically(messages: &mut [Message]) {
+    // Ids are snowflakes, growing with time of creation
+    messages.sort_by_key(|message| message.id.parse::<u64>().unwrap_or_default());
+}
+
+fn request(client: &Client, token: &str, method: Method, path: &str) -> RequestBuilder {
+    client
+        .request(method, format!("{DISCORD_API_URL}{path}"))
+        .header("Authorization", format!("Bot {token}"))
+}
+
+async fn fetch(client: &Client, token: &str, path: &str) -> Result<Vec<Message>> {
+    let response = request(client, token, Method::GET, path).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Discord API returned {}", response.status()));
+    }
+    Ok(response.json().await?)
+}
+
+async fn send_message(client: &Client, token: &str, channel: &str, text: &str) -> Result<Message> {
+    Ok(request(
+        client,
+        token,
+        Method::POST,
+        &format!("/channels/{channel}/messages"),
+    )
+    .json(&json!({ "content": text }))
+    .send()
+    .await?
+    .error_for_status()?
+    .json()
+    .await?)
+}
+
+async fn edit_message(
+    client: &Client,
+    token: &str,
+    channel: &str,
+    message_id: &str,
+    text: &str,
+) -> Result<()> {
+    request(
+        client,
+        token,
+        Method::PATCH,
+        &format!("/channels/{channel}/messages/{message_id}"),
+    )
+    .json(&json!({ "content": text }))
+    .send()
+    .await?
+    .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_to_answer() {
+        let mut messages: Vec<Message> = serde_json::from_value(json!([
+            {"id": "1000", "content": "Thanks!", "author": {"id": "7"}},
+            {"id": "999", "content": "Answer", "author": {"id": "1", "bot": true}},
+            {"id": "998", "author": {"id": "7"}},
+            {"id": "997", "content": "Question", "author": {"id": "7"}}
+        ]))
+        .unwrap();
+        sort_chronologically(&mut messages);
+        let answered = messages
+            .iter()
+            .filter(|message| message.needs_answer())
+            .map(|message| message.content.as_str())
+            .collect::<Vec<_>>();
+        // Ids are compared as numbers, not as text
+        assert_eq!(messages[3].id, "1000");
+        assert_eq!(answered, ["Question", "Thanks!"]);
+    }
+
+    #[test]
+    fn test_request_authorized_as_bot() {
+        let request = request(
+            &Client::new(),
+            "SECRET",
+            Method::GET,
+            "/channels/1/messages",
+        )
+        .build()
+        .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://discord.com/api/v10/channels/1/messages"
+        );
+        assert_eq!(request.headers()["Authorization"], "Bot SECRET");
+    }
+}
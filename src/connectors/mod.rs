@@ -0,0 +1,521 @@
+//! # Connectors
+//!
+//! Ready-made adapters exposing an [Agent] through chat platforms. This module is enabled with
+//! `connectors` feature.
+//!
+//! Every connector behaves in the same way:
+//! - each chat (or channel) is mapped to a separate session, with its own copy of the agent and
+//!   separate history,
+//! - while the agent is working, reply message is updated with progress (called tools and partial
+//!   answer),
+//! - slash commands are routed directly to tools, without asking the model.
+//!
+//! Supported platforms:
+//! - [telegram]: Telegram Bot API
+//! - [discord]: Discord REST API
+//! - [slack]: Slack Events API (requires the `slack` feature)
+//!
+//! ## Commands
+//! - `/reset` -- clears history of the current session
+//! - `/tools` -- lists tools available for the agent
+//! - `/<tool_name> <arguments>` -- calls the tool directly. Arguments can be provided as JSON object,
+//!   or as plain text when tool has exactly one required argument.
+//!
+//! Tools called with commands are checked like tool calls of the model: they have to be allowed by
+//! [allowed tools](crate::agent::AgentBuilder::with_allowed_tools) and the
+//! [sandbox](crate::agent::AgentBuilder::with_sandbox) of the agent, and approved by its
+//! [approval handler](crate::agent::AgentBuilder::with_approval). `/tools` lists only allowed tools.
+
+pub mod discord;
+#[cfg(feature = "slack")]
+pub mod slack;
+pub mod telegram;
+
+use crate::agent::{Agent, AgentChunk};
+use crate::event::AgentEvent;
+use crate::tool::{Tool, ToolBox};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use genai::chat::ToolCall;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+/// Minimal interval between updates of the reply with progress, platforms limit rate of edits
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// Default number of sessions kept by [SessionRouter]
+pub const DEFAULT_MAX_SESSIONS: usize = 1000;
+
+/// Maps chat sessions to agents and routes messages to them.
+///
+/// Every new session starts from the copy of the template agent, so its history contains only
+/// the system prompt. Messages of different sessions are handled concurrently, messages of the
+/// same session one after another. When there are more sessions than the limit (see
+/// [SessionRouter::with_max_sessions]), the least recently used session is dropped.
+pub struct SessionRouter {
+    template: Agent,
+    model: String,
+    toolbox: Option<Arc<dyn ToolBox + Send + Sync>>,
+    max_sessions: usize,
+    sessions: Mutex<Sessions>,
+}
+
+/// Agents of sessions, with the order of their last use
+#[derive(Default)]
+struct Sessions {
+    agents: HashMap<String, (u64, Arc<tokio::sync::Mutex<Agent>>)>,
+    last_use: u64,
+}
+
+impl SessionRouter {
+    /// Creates a new router, where each session is based on the `template` agent and uses `model`.
+    pub fn new(template: Agent, model: &str) -> Self {
+        Self {
+            template,
+            model: model.to_string(),
+            toolbox: None,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Sets toolbox available for agents and slash commands.
+    pub fn with_toolbox(mut self, toolbox: Arc<dyn ToolBox + Send + Sync>) -> Self {
+        self.toolbox = Some(toolbox);
+        self
+    }
+
+    /// Sets maximal number of sessions kept, default is [DEFAULT_MAX_SESSIONS]. History of the
+    /// least recently used session is dropped when a new session exceeds the limit.
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = max_sessions.max(1);
+        self
+    }
+
+    /// Returns agent of the session, creating it from the template when needed.
+    fn session(&self, session_id: &str) -> Arc<tokio::sync::Mutex<Agent>> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.last_use += 1;
+        let last_use = sessions.last_use;
+        if let Some((used, agent)) = sessions.agents.get_mut(session_id) {
+            *used = last_use;
+            return agent.clone();
+        }
+        if sessions.agents.len() >= self.max_sessions {
+            let oldest = sessions
+                .agents
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(id, _)| id.clone());
+            // Run in progress keeps its agent, only the session is forgotten
+            if let Some(oldest) = oldest {
+                sessions.agents.remove(&oldest);
+            }
+        }
+        let agent = Arc::new(tokio::sync::Mutex::new(self.template.clone()));
+        sessions
+            .agents
+            .insert(session_id.to_string(), (last_use, agent.clone()));
+        agent
+    }
+
+    /// Handles message received in the session and returns reply.
+    ///
+    /// When `progress` is provided, the answer is streamed and the reply shown while the agent is
+    /// working (called tools followed by the partial answer) is sent to it after every change.
+    pub async fn handle_message(
+        &self,
+        session_id: &str,
+        text: &str,
+        progress: Option<UnboundedSender<String>>,
+    ) -> Result<String> {
+        if let Some(command) = text.trim().strip_prefix('/') {
+            return self.handle_command(session_id, command).await;
+        }
+
+        let agent = self.session(session_id);
+        let mut agent = agent.lock().await;
+
+        let toolbox = self.toolbox.clone();
+        let Some(progress) = progress else {
+            return agent
+                .run::<String>(&self.model, text, toolbox, None, None)
+                .await;
+        };
+        // Event handler of the agent is kept, stream receives events of this run only
+        let mut stream = agent.run_stream::<String>(&self.model, text, toolbox, None, None);
+        let mut reply = PartialReply::default();
+        while let Some(chunk) = stream.next().await {
+            match chunk? {
+                AgentChunk::Output(answer) => return Ok(answer),
+                chunk => {
+                    if reply.apply(chunk) {
+                        let _ = progress.send(reply.text());
+                    }
+                }
+            }
+        }
+        Err(anyhow!("Run of the agent ended without answer"))
+    }
+
+    async fn handle_command(&self, session_id: &str, command: &str) -> Result<String> {
+        let (name, arguments) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        // Telegram appends bot name to commands in group chats, e.g. `/reset@my_bot`
+        let name = name.split('@').next().unwrap_or(name);
+
+        match name {
+            "reset" => {
+                self.sessions.lock().unwrap().agents.remove(session_id);
+                Ok("Session history cleared".to_string())
+            }
+            "tools" => {
+                let tools = self.tools()?;
+                let toolbox = self.toolbox.as_ref();
+                let tools: Vec<_> = tools
+                    .into_iter()
+                    .filter(|tool| {
+                        toolbox.is_some_and(|toolbox| {
+                            self.template.is_tool_allowed(&tool.name)
+                                && self
+                                    .template
+                                    .sandbox()
+                                    .allows_all(&toolbox.tool_capabilities(&tool.name))
+                        })
+                    })
+                    .collect();
+                if tools.is_empty() {
+                    return Ok("No tools available".to_string());
+                }
+                Ok(tools
+                    .iter()
+                    .map(|tool| match &tool.description {
+                        Some(description) => format!("/{} - {}", tool.name, description),
+                        None => format!("/{}", tool.name),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            _ => {
+                let tool = self
+                    .tools()?
+                    .into_iter()
+                    .find(|tool| tool.name == name)
+                    .ok_or_else(|| anyhow!("Unknown command /{name}"))?;
+                let arguments = command_arguments(&tool, arguments);
+                let toolbox = self.toolbox.as_ref().expect("tool found without toolbox");
+                let tool_call = ToolCall {
+                    call_id: format!("command_{session_id}"),
+                    fn_name: tool.name.clone(),
+                    fn_arguments: arguments.clone(),
+                };
+                // Grants remembered by the approval handler belong to the session
+                self.session(session_id)
+                    .lock()
+                    .await
+                    .check_tool_call(&tool_call, &toolbox.tool_capabilities(&tool.name))?;
+                Ok(toolbox.call_tool(tool.name, arguments).await?)
+            }
+        }
+    }
+
+    fn tools(&self) -> Result<Vec<Tool>> {
+        match &self.toolbox {
+            Some(toolbox) => Ok(toolbox.tools_definitions()?),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Reply shown while the agent is working, tools called so far followed by the partial answer.
+#[derive(Default)]
+struct PartialReply {
+    tools: Vec<String>,
+    answer: String,
+}
+
+impl PartialReply {
+    /// Applies the chunk of the run, returns `true` when the reply changed.
+    fn apply(&mut self, chunk: AgentChunk<String>) -> bool {
+        match chunk {
+            AgentChunk::TextDelta(text) => {
+                self.answer.push_str(&text);
+                true
+            }
+            AgentChunk::Event(AgentEvent::ToolCall { name, .. }) => {
+                self.tools.push(format!("Using tool: {name}"));
+                true
+            }
+            // Text generated before tool calls is not the answer
+            AgentChunk::Event(AgentEvent::IterationStarted { .. }) => {
+                self.answer.clear();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn text(&self) -> String {
+        let mut parts = self.tools.clone();
+        if !self.answer.is_empty() {
+            parts.push(self.answer.clone());
+        }
+        parts.join("\n")
+    }
+}
+
+/// Shows progress received from [SessionRouter::handle_message] with `update`, at most once per
+/// [PROGRESS_INTERVAL]. Only the latest progress is shown, older ones are skipped.
+async fn show_progress<F, Fut>(mut progress: UnboundedReceiver<String>, mut update: F)
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut latest = None;
+    let mut next_update = Instant::now();
+    loop {
+        tokio::select! {
+            received = progress.recv() => match received {
+                Some(text) => latest = Some(text),
+                None => break,
+            },
+            _ = tokio::time::sleep_until(next_update), if latest.is_some() => {
+                if let Some(text) = latest.take() {
+                    update(text).await;
+                }
+                next_update = Instant::now() + PROGRESS_INTERVAL;
+            }
+        }
+    }
+}
+
+/// Converts text provided after slash command into tool arguments.
+fn command_arguments(tool: &Tool, arguments: &str) -> Value {
+    let arguments = arguments.trim();
+    if let Ok(value @ Value::Object(_)) = serde_json::from_str(arguments) {
+        return value;
+    }
+
+    // When tool has only one required argument, whole text is used as its value
+    let required = tool
+        .schema
+        .as_ref()
+        .and_then(|schema| schema.get("required"))
+        .and_then(Value::as_array);
+    let mut object = Map::new();
+    if let Some([Value::String(field)]) = required.map(Vec::as_slice) {
+        object.insert(field.clone(), Value::String(arguments.to_string()));
+    }
+    Value::Object(object)
+}
+
+/// Splits text into parts that do not exceed `limit` characters, preferring line boundaries.
+fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    for line in text.split_inclusive('\n') {
+        if current.chars().count() + line.chars().count() > limit && !current.is_empty() {
+            parts.push(std::mem::take(&mut current));
+        }
+        if line.chars().count() > limit {
+            let chars: Vec<char> = line.chars().collect();
+            for chunk in chars.chunks(limit) {
+                parts.push(chunk.iter().collect());
+            }
+        } else {
+            current.push_str(line);
+        }
+    }
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::{Approval, ChatMiddleware, RequestContext};
+    use crate::tool::sandbox::SandboxProfile;
+    use genai::chat::{ChatOptions, ChatRequest, ChatResponse};
+    use serde_json::json;
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(split_message("short", 10), vec!["short"]);
+        assert_eq!(split_message("", 10), vec![""]);
+        assert_eq!(
+            split_message("line one\nline two", 10),
+            vec!["line one\n", "line two"]
+        );
+        assert_eq!(split_message("abcdefgh", 3), vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn test_partial_reply() {
+        let mut reply = PartialReply::default();
+        let delta = |text: &str| AgentChunk::TextDelta(text.to_string());
+        assert!(reply.apply(delta("Let me check")));
+        assert!(
+            !reply.apply(AgentChunk::Event(AgentEvent::IterationStarted {
+                iteration: 1
+            }))
+        );
+        assert!(reply.apply(AgentChunk::Event(AgentEvent::ToolCall {
+            call_id: "call_1".to_string(),
+            name: "web_search".to_string(),
+            arguments: json!({}),
+        })));
+        assert!(reply.apply(delta("It is ")));
+        assert!(reply.apply(delta("sunny")));
+        assert_eq!(reply.text(), "Using tool: web_search\nIt is sunny");
+    }
+
+    /// Middleware failing every request
+    struct Unavailable;
+
+    #[async_trait::async_trait]
+    impl ChatMiddleware for Unavailable {
+        async fn before_request(
+            &self,
+            _context: &mut RequestContext,
+            _request: &mut ChatRequest,
+            _options: &mut ChatOptions,
+        ) -> Result<Option<ChatResponse>> {
+            Err(anyhow!("Model is not available"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_handler_is_kept() {
+        let template = Agent::builder()
+            .with_middleware(Unavailable)
+            .with_event_handler(|_: &AgentEvent| {})
+            .build();
+        let router = SessionRouter::new(template, "gpt-4o");
+        let (progress, _receiver) = tokio::sync::mpsc::unbounded_channel();
+        assert!(router
+            .handle_message("chat", "Hello", Some(progress))
+            .await
+            .is_err());
+        let agent = router.session("chat");
+        assert!(agent.lock().await.replace_event_handler(None).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_max_sessions() {
+        let template = Agent::builder().with_middleware(Unavailable).build();
+        let router = SessionRouter::new(template, "gpt-4o").with_max_sessions(2);
+        for session_id in ["first", "second", "first", "third"] {
+            let _ = router.handle_message(session_id, "Hello", None).await;
+        }
+        let sessions = router.sessions.lock().unwrap();
+        let mut ids: Vec<_> = sessions.agents.keys().map(String::as_str).collect();
+        ids.sort();
+        assert_eq!(ids, ["first", "third"]);
+    }
+
+    /// Toolbox with `echo` tool, its capabilities are not declared
+    struct Echo;
+
+    #[async_trait::async_trait]
+    impl ToolBox for Echo {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, crate::tool::ToolError> {
+            Ok(vec![Tool {
+                name: "echo".to_string(),
+                description: None,
+                schema: Some(json!({
+                    "type": "object",
+                    "properties": {"text": {"type": "string"}},
+                    "required": ["text"]
+                })),
+            }])
+        }
+
+        async fn call_tool(
+            &self,
+            _tool_name: String,
+            arguments: Value,
+        ) -> Result<String, crate::tool::ToolError> {
+            Ok(arguments["text"].as_str().unwrap_or_default().to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_checks() {
+        let router = |template: crate::agent::AgentBuilder| {
+            SessionRouter::new(template.build(), "gpt-4o").with_toolbox(Arc::new(Echo))
+        };
+        let command = |router: SessionRouter, text: &'static str| async move {
+            router
+                .handle_message("chat", text, None)
+                .await
+                .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(
+            command(router(Agent::builder()), "/echo hi").await,
+            Ok("hi".to_string())
+        );
+        assert_eq!(
+            command(router(Agent::builder()), "/tools").await,
+            Ok("/echo".to_string())
+        );
+
+        let read_only = || Agent::builder().with_sandbox(SandboxProfile::ReadOnlyFs);
+        assert_eq!(
+            command(router(read_only()), "/echo hi").await,
+            Err("Tool `echo` is not allowed in read-only file system sandbox".to_string())
+        );
+        assert_eq!(
+            command(router(read_only()), "/tools").await,
+            Ok("No tools available".to_string())
+        );
+
+        let allowed = Agent::builder().with_allowed_tools(["web_search"]);
+        assert_eq!(
+            command(router(allowed), "/echo hi").await,
+            Err("Tool `echo` is not allowed".to_string())
+        );
+
+        let denied = Agent::builder()
+            .with_approval(|_: &ToolCall| Approval::Deny("commands are disabled".to_string()));
+        assert_eq!(
+            command(router(denied), "/echo hi").await,
+            Err("Tool call `echo` was denied: commands are disabled".to_string())
+        );
+    }
+
+    #[test]
+    fn test_command_arguments() {
+        let tool = Tool {
+            name: "web_search".to_string(),
+            description: None,
+            schema: Some(json!({
+                "type": "object",
+                "properties": {"query": {"type": "string"}},
+                "required": ["query"]
+            })),
+        };
+        assert_eq!(
+            command_arguments(&tool, "rust agents"),
+            json!({"query": "rust agents"})
+        );
+        assert_eq!(
+            command_arguments(&tool, r#"{"query": "json"}"#),
+            json!({"query": "json"})
+        );
+
+        let tool = Tool {
+            name: "no_args".to_string(),
+            description: None,
+            schema: None,
+        };
+        assert_eq!(command_arguments(&tool, "ignored"), json!({}));
+    }
+}
@@ -0,0 +1,371 @@
+//! # Slack Connector
+//!
+//! Connects [SessionRouter] with Slack using its Events API. Slack sends events to the endpoint
+//! served by [SlackConnector::serve] (`POST /slack/events`), so the server has to be reachable
+//! from the internet, with the URL configured as `Request URL` in `Event Subscriptions` of the app.
+//!
+//! The bot answers mentions (`app_mention` event) in the thread of the message, each thread is a
+//! separate session. Direct messages (`message.im` event) are answered in the conversation, each
+//! conversation with the bot is a separate session. While the agent is working, the reply shows
+//! `…` and is updated with progress, Slack has no typing indicator for bots. Messages of different
+//! sessions are answered concurrently.
+//!
+//! The bot token needs `app_mentions:read`, `im:history` and `chat:write` scopes. Requests are
+//! verified with the signing secret of the app, requests with invalid signature or older than
+//! five minutes are rejected.
+//!
+//! To let the agent post to other channels and read them, use [SlackToolBox](crate::tool::slack::SlackToolBox).
+//!
+//! ```rust,no_run
+//! use agentai::Agent;
+//! use agentai::connectors::{slack::SlackConnector, SessionRouter};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let router = SessionRouter::new(Agent::new("You are helpful assistant"), "gpt-4.1-mini");
+//! SlackConnector::new("<BOT TOKEN>", "<SIGNING SECRET>", router)
+//!     .serve("0.0.0.0:3000")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::connectors::{show_progress, split_message, SessionRouter};
+use crate::tool::slack::{call_api, PostMessage};
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use hmac::{Hmac, Mac};
+use log::{debug, error, warn};
+use reqwest::Client;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+// Maximal length of message recommended by Slack
+const MESSAGE_LIMIT: usize = 4000;
+// Requests older than this (in seconds) are rejected, to prevent replay attacks
+const MAX_REQUEST_AGE: u64 = 5 * 60;
+
+/// Message addressed to the bot.
+#[derive(Debug, PartialEq)]
+struct Incoming {
+    channel: String,
+    /// Thread where the bot replies, `None` to reply in the conversation
+    thread_ts: Option<String>,
+    session_id: String,
+    text: String,
+}
+
+struct ServerState {
+    signing_secret: Vec<u8>,
+    queue: UnboundedSender<Incoming>,
+}
+
+/// Slack bot serving agent sessions in threads where it is mentioned and in direct messages.
+pub struct SlackConnector {
+    client: Client,
+    token: String,
+    signing_secret: String,
+    router: SessionRouter,
+}
+
+impl SlackConnector {
+    /// Creates connector for the bot identified by `token`, verifying requests with `signing_secret` of the app.
+    pub fn new(token: &str, signing_secret: &str, router: SessionRouter) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            token: token.to_string(),
+            signing_secret: signing_secret.to_string(),
+            router,
+        }
+    }
+
+    /// Starts listening on `addr` and answers messages until an error occurs.
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        let (queue, messages_rx) = mpsc::unbounded_channel();
+        let state = Arc::new(ServerState {
+            signing_secret: self.signing_secret.as_bytes().to_vec(),
+            queue,
+        });
+        let app = Router::new()
+            .route("/slack/events", post(receive_event))
+            .with_state(state);
+
+        // Slack expects response within 3 seconds, messages are answered by the worker
+        tokio::select! {
+            result = async { axum::serve(listener, app).await } => result?,
+            _ = worker(self.client, self.token, self.router, messages_rx) => {}
+        }
+        Ok(())
+    }
+}
+
+async fn receive_event(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    if !verify_signature(&state.signing_secret, &headers, &body, now) {
+        return (StatusCode::UNAUTHORIZED, "Invalid signature").into_response();
+    }
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    if payload["type"] == "url_verification" {
+        return Json(json!({ "challenge": payload["challenge"] })).into_response();
+    }
+    // Slack retries events not acknowledged in time, they are already being answered
+    if headers.contains_key("X-Slack-Retry-Num") {
+        return StatusCode::OK.into_response();
+    }
+    if let Some(message) = incoming_message(&payload) {
+        debug!("Slack message in {}: {}", message.session_id, message.text);
+        if state.queue.send(message).is_err() {
+            return (StatusCode::SERVICE_UNAVAILABLE, "Worker stopped").into_response();
+        }
+    }
+    StatusCode::OK.into_response()
+}
+
+/// Verifies `X-Slack-Signature` header, HMAC-SHA256 of `v0:{timestamp}:{body}` signed with the signing secret.
+fn verify_signature(secret: &[u8], headers: &HeaderMap, body: &[u8], now: u64) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let Some(timestamp) = header("X-Slack-Request-Timestamp") else {
+        return false;
+    };
+    if timestamp
+        .parse::<u64>()
+        .map_or(true, |timestamp| now.abs_diff(timestamp) > MAX_REQUEST_AGE)
+    {
+        return false;
+    }
+    let Some(signature) = header("X-Slack-Signature")
+        .and_then(|value| value.strip_prefix("v0="))
+        .and_then(|value| hex::decode(value.trim()).ok())
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret) else {
+        return false;
+    };
+    mac.update(format!("v0:{timestamp}:").as_bytes());
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Returns message addressed to the bot from `event_callback` payload, other events are ignored.
+fn incoming_message(payload: &Value) -> Option<Incoming> {
+    if payload["type"] != "event_callback" {
+        return None;
+    }
+    let event = &payload["event"];
+    // Messages of bots (including own replies) and edits, joins, etc. are not answered
+    if event.get("bot_id").is_some() || event.get("subtype").is_some() {
+        return None;
+    }
+    let channel = event["channel"].as_str()?.to_string();
+    let text = strip_mentions(event["text"].as_str()?);
+    if text.is_empty() {
+        return None;
+    }
+    match event["type"].as_str()? {
+        "app_mention" => {
+            let thread_ts = event["thread_ts"].as_str().or(event["ts"].as_str())?;
+            Some(Incoming {
+                session_id: format!("{channel}:{thread_ts}"),
+                thread_ts: Some(thread_ts.to_string()),
+                channel,
+                text,
+            })
+        }
+        "message" if event["channel_type"] == "im" => Some(Incoming {
+            session_id: channel.clone(),
+            thread_ts: None,
+            channel,
+            text,
+        }),
+        _ => None,
+    }
+}
+
+/// Removes mentions of users (`<@U0123>`) from the text.
+fn strip_mentions(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<@") {
+        result.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    result.push_str(rest);
+    result.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Answers messages concurrently, messages of the same session are answered in order by the router.
+async fn worker(
+    client: Client,
+    token: String,
+    router: SessionRouter,
+    mut messages_rx: UnboundedReceiver<Incoming>,
+) {
+    let token: Arc<str> = token.into();
+    let router = Arc::new(router);
+    while let Some(message) = messages_rx.recv().await {
+        let (client, token, router) = (client.clone(), token.clone(), router.clone());
+        tokio::spawn(async move {
+            if let Err(err) = handle_message(&client, &token, &router, &message).await {
+                error!("Unable to answer Slack message: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_message(
+    client: &Client,
+    token: &str,
+    router: &SessionRouter,
+    message: &Incoming,
+) -> Result<()> {
+    let reply = post_message(client, token, message, "…").await?;
+
+    let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+    let reply_ts = reply.as_str();
+    let progress = show_progress(progress_rx, move |text| async move {
+        let text = split_message(&text, MESSAGE_LIMIT).remove(0);
+        if let Err(err) = update_message(client, token, &message.channel, reply_ts, &text).await {
+            warn!("Unable to show progress in Slack: {err}");
+        }
+    });
+    let (answer, _) = tokio::join!(
+        router.handle_message(&message.session_id, &message.text, Some(progress_tx)),
+        progress
+    );
+
+    let answer = answer.unwrap_or_else(|err| format!("Error: {err}"));
+    let mut parts = split_message(&answer, MESSAGE_LIMIT).into_iter();
+    if let Some(first) = parts.next() {
+        update_message(client, token, &message.channel, &reply, &first).await?;
+    }
+    for part in parts {
+        post_message(client, token, message, &part).await?;
+    }
+    Ok(())
+}
+
+/// Posts reply to the message, returns timestamp of the reply.
+async fn post_message(
+    client: &Client,
+    token: &str,
+    message: &Incoming,
+    text: &str,
+) -> Result<String> {
+    let response = call_api(
+        client,
+        token,
+        "chat.postMessage",
+        &PostMessage {
+            channel: &message.channel,
+            text,
+            thread_ts: message.thread_ts.as_deref(),
+        },
+    )
+    .await?;
+    Ok(response["ts"].as_str().unwrap_or_default().to_string())
+}
+
+async fn update_message(
+    client: &Client,
+    token: &str,
+    channel: &str,
+    ts: &str,
+    text: &str,
+) -> Result<()> {
+    call_api(
+        client,
+        token,
+        "chat.update",
+        &[("channel", channel), ("ts", ts), ("text", text)],
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_signature() {
+        // Example from Slack documentation
+        let secret = b"8f742231b10e8888abcd99yyyzzz85a5";
+        let body = b"token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Slack-Request-Timestamp", "1531420618".parse().unwrap());
+        headers.insert(
+            "X-Slack-Signature",
+            "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503"
+                .parse()
+                .unwrap(),
+        );
+        assert!(verify_signature(secret, &headers, body, 1531420618));
+        assert!(!verify_signature(secret, &headers, body, 1531420618 + 3600));
+        assert!(!verify_signature(
+            b"other secret",
+            &headers,
+            body,
+            1531420618
+        ));
+    }
+
+    #[test]
+    fn test_incoming_message() {
+        let mention = json!({
+            "type": "event_callback",
+            "event": {
+                "type": "app_mention",
+                "channel": "C1",
+                "ts": "1700000000.000100",
+                "text": "<@U0BOT> what is   the weather?"
+            }
+        });
+        assert_eq!(
+            incoming_message(&mention),
+            Some(Incoming {
+                channel: "C1".to_string(),
+                thread_ts: Some("1700000000.000100".to_string()),
+                session_id: "C1:1700000000.000100".to_string(),
+                text: "what is the weather?".to_string(),
+            })
+        );
+
+        let direct = json!({
+            "type": "event_callback",
+            "event": {"type": "message", "channel_type": "im", "channel": "D1", "ts": "1", "text": "/tools"}
+        });
+        assert_eq!(incoming_message(&direct).unwrap().session_id, "D1");
+
+        let own_reply = json!({
+            "type": "event_callback",
+            "event": {"type": "message", "channel_type": "im", "channel": "D1", "ts": "2", "text": "Hi", "bot_id": "B1"}
+        });
+        assert_eq!(incoming_message(&own_reply), None);
+    }
+}
@@ -0,0 +1,238 @@
+//! # Telegram Connector
+//!
+//! Connects [SessionRouter] with Telegram Bot API. Updates are received using long polling, so no
+//! public endpoint is required. Each Telegram chat is a separate session.
+//!
+//! To create a bot and receive its token talk to [@BotFather](https://t.me/botfather).
+//!
+//! ```rust,no_run
+//! use agentai::Agent;
+//! use agentai::connectors::{telegram::TelegramConnector, SessionRouter};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let router = SessionRouter::new(Agent::new("You are helpful assistant"), "gpt-4.1-mini");
+//! TelegramConnector::new("<BOT TOKEN>", router).run().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::connectors::{show_progress, split_message, SessionRouter};
+use anyhow::{anyhow, Result};
+use log::{debug, error, warn};
+use reqwest::Client;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+// Maximal length of message accepted by Telegram
+const MESSAGE_LIMIT: usize = 4096;
+const POLL_TIMEOUT: u64 = 30;
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    message_id: i64,
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Telegram bot serving agent sessions.
+pub struct TelegramConnector {
+    client: Client,
+    api_url: String,
+    router: SessionRouter,
+}
+
+impl TelegramConnector {
+    /// Creates connector for the bot identified by `token`.
+    pub fn new(token: &str, router: SessionRouter) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            api_url: format!("{TELEGRAM_API_URL}/bot{token}"),
+            router,
+        }
+    }
+
+    /// Receives updates and answers messages until an unrecoverable error occurs. Messages of
+    /// different chats are answered concurrently.
+    pub async fn run(self) -> Result<()> {
+        let api_url: Arc<str> = self.api_url.into();
+        let router = Arc::new(self.router);
+        let mut offset = 0;
+        loop {
+            let updates: Vec<Update> = match call(
+                &self.client,
+                &api_url,
+                "getUpdates",
+                json!({
+                    "offset": offset,
+                    "timeout": POLL_TIMEOUT,
+                    "allowed_updates": ["message"],
+                }),
+            )
+            .await
+            {
+                Ok(updates) => updates,
+                Err(err) => {
+                    warn!("Unable to receive Telegram updates: {err}");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = update.update_id + 1;
+                let Some(Message {
+                    chat,
+                    text: Some(text),
+                    ..
+                }) = update.message
+                else {
+                    continue;
+                };
+                let (client, api_url, router) =
+                    (self.client.clone(), api_url.clone(), router.clone());
+                tokio::spawn(async move {
+                    if let Err(err) =
+                        handle_message(&client, &api_url, &router, chat.id, &text).await
+                    {
+                        error!("Unable to answer Telegram message: {err}");
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn handle_message(
+    client: &Client,
+    api_url: &str,
+    router: &SessionRouter,
+    chat_id: i64,
+    text: &str,
+) -> Result<()> {
+    debug!("Telegram message from chat {chat_id}: {text}");
+    call::<Value>(
+        client,
+        api_url,
+        "sendChatAction",
+        json!({"chat_id": chat_id, "action": "typing"}),
+    )
+    .await?;
+    let reply: Message = call(
+        client,
+        api_url,
+        "sendMessage",
+        json!({"chat_id": chat_id, "text": "…"}),
+    )
+    .await?;
+
+    let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+    let progress = show_progress(progress_rx, move |text| async move {
+        let text = split_message(&text, MESSAGE_LIMIT).remove(0);
+        let _ = edit_message(client, api_url, chat_id, reply.message_id, &text).await;
+    });
+    let session_id = chat_id.to_string();
+    let (answer, _) = tokio::join!(
+        router.handle_message(&session_id, text, Some(progress_tx)),
+        progress
+    );
+
+    let answer = answer.unwrap_or_else(|err| format!("Error: {err}"));
+    let mut parts = split_message(&answer, MESSAGE_LIMIT).into_iter();
+    if let Some(first) = parts.next() {
+        edit_message(client, api_url, chat_id, reply.message_id, &first).await?;
+    }
+    for part in parts {
+        call::<Message>(
+            client,
+            api_url,
+            "sendMessage",
+            json!({"chat_id": chat_id, "text": part}),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn edit_message(
+    client: &Client,
+    api_url: &str,
+    chat_id: i64,
+    message_id: i64,
+    text: &str,
+) -> Result<()> {
+    call::<Value>(
+        client,
+        api_url,
+        "editMessageText",
+        json!({"chat_id": chat_id, "message_id": message_id, "text": text}),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn call<T: DeserializeOwned>(
+    client: &Client,
+    api_url: &str,
+    method: &str,
+    body: Value,
+) -> Result<T> {
+    // URL contains token of the bot, it must not be part of logged errors
+    let response: ApiResponse<T> = client
+        .post(format!("{api_url}/{method}"))
+        .json(&body)
+        .send()
+        .await
+        .map_err(reqwest::Error::without_url)?
+        .json()
+        .await
+        .map_err(reqwest::Error::without_url)?;
+    match response {
+        ApiResponse {
+            ok: true,
+            result: Some(result),
+            ..
+        } => Ok(result),
+        ApiResponse { description, .. } => Err(anyhow!(
+            "Telegram method {method} failed: {}",
+            description.unwrap_or_default()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_error_hides_token() {
+        // Nothing listens on the port, so the request fails
+        let api_url = "http://127.0.0.1:9/bot123:SECRET";
+        let err = call::<Value>(&Client::new(), api_url, "getMe", json!({}))
+            .await
+            .unwrap_err();
+        assert!(!format!("{err:?}").contains("SECRET"));
+    }
+}
@@ -0,0 +1,212 @@
+//! # Ensembles of Agents
+//!
+//! Ensembles run many agents on the same task, to get answers that are more reliable than the
+//! answer of a single model.
+//!
+//! In the debate mode ([Ensemble::debate]) every agent answers the question, then in every round
+//! agents read answers of the others, critique them and revise their own answers. After the last
+//! round a judge selects the best answer or merges them into the final one.
+//!
+//! ```rust,no_run
+//! # use agentai::Agent;
+//! # use agentai::ensemble::Ensemble;
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut ensemble = Ensemble::debate(
+//!     [
+//!         (Agent::new("You are a careful physician."), "gpt-4o"),
+//!         (Agent::new("You are a careful physician."), "claude-3-5-sonnet-latest"),
+//!     ],
+//!     2,
+//! )
+//! .with_judge(Agent::new("You are a chief physician."), "gpt-4o");
+//! let outcome = ensemble
+//!     .run::<String>("Can ibuprofen be taken together with paracetamol?", None)
+//!     .await?;
+//! println!("{}", outcome.answer);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agent::{Agent, AgentOutput, PromptKey, Prompts, TokenUsage};
+use crate::tool::composite::SharedToolBox;
+use anyhow::{anyhow, Result};
+use futures::future::try_join_all;
+
+/// Agent taking part in the ensemble, together with the model it uses.
+struct Member {
+    agent: Agent,
+    model: String,
+}
+
+/// Group of agents working on the same task, look into [module documentation](crate::ensemble) for details.
+pub struct Ensemble {
+    members: Vec<Member>,
+    judge: Option<Member>,
+    rounds: u32,
+}
+
+/// Result of the [Ensemble::run].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct EnsembleOutcome<D> {
+    /// Final answer provided by the judge
+    pub answer: D,
+    /// Answers of members, first element contains initial answers and next ones answers revised in
+    /// following rounds. Answers are in order of members.
+    pub rounds: Vec<Vec<String>>,
+    /// Tokens used by all members and the judge
+    pub usage: TokenUsage,
+}
+
+impl Ensemble {
+    /// Creates ensemble where agents debate for given number of rounds, revising their answers.
+    ///
+    /// With 0 rounds agents only answer independently, and the judge chooses the best answer.
+    pub fn debate<'a>(agents: impl IntoIterator<Item = (Agent, &'a str)>, rounds: u32) -> Self {
+        Self {
+            members: agents
+                .into_iter()
+                .map(|(agent, model)| Member {
+                    agent,
+                    model: model.to_string(),
+                })
+                .collect(),
+            judge: None,
+            rounds,
+        }
+    }
+
+    /// Sets judge choosing or merging the final answer. When not set, the first agent is the judge.
+    pub fn with_judge(mut self, agent: Agent, model: &str) -> Self {
+        self.judge = Some(Member {
+            agent,
+            model: model.to_string(),
+        });
+        self
+    }
+
+    /// Runs the debate on the prompt, members answer concurrently.
+    ///
+    /// Members remember the debate in their history, judge receives only the question and final
+    /// answers of members.
+    pub async fn run<D>(
+        &mut self,
+        prompt: &str,
+        toolbox: Option<SharedToolBox>,
+    ) -> Result<EnsembleOutcome<D>>
+    where
+        D: AgentOutput,
+    {
+        if self.members.is_empty() {
+            return Err(anyhow!("Ensemble has no members"));
+        }
+        let mut usage = TokenUsage::default();
+        let mut rounds = vec![];
+
+        let mut answers = self
+            .ask_members(&mut usage, toolbox.clone(), |_, _| prompt.to_string())
+            .await?;
+        for round in 0..self.rounds {
+            let previous = answers.clone();
+            answers = self
+                .ask_members(&mut usage, toolbox.clone(), |prompts, idx| {
+                    critique_prompt(prompts, &previous, idx, round)
+                })
+                .await?;
+            rounds.push(previous);
+        }
+        rounds.push(answers);
+
+        let judge = match &mut self.judge {
+            Some(judge) => judge,
+            None => &mut self.members[0],
+        };
+        let judge_prompt = judge_prompt(
+            judge.agent.prompts(),
+            prompt,
+            rounds.last().unwrap_or(&vec![]),
+        );
+        let response = judge
+            .agent
+            .run_detailed::<D>(&judge.model, &judge_prompt, None, None, None)
+            .await?;
+        usage.merge(&response.usage);
+
+        Ok(EnsembleOutcome {
+            answer: response.output,
+            rounds,
+            usage,
+        })
+    }
+
+    /// Runs all members concurrently, `prompt` provides message for member with given index, using
+    /// prompts overridden for its agent.
+    async fn ask_members(
+        &mut self,
+        usage: &mut TokenUsage,
+        toolbox: Option<SharedToolBox>,
+        prompt: impl Fn(&Prompts, usize) -> String,
+    ) -> Result<Vec<String>> {
+        let responses = try_join_all(self.members.iter_mut().enumerate().map(|(idx, member)| {
+            let prompt = prompt(member.agent.prompts(), idx);
+            let toolbox = toolbox.clone();
+            async move {
+                member
+                    .agent
+                    .run_detailed::<String>(&member.model, &prompt, toolbox, None, None)
+                    .await
+            }
+        }))
+        .await?;
+        Ok(responses
+            .into_iter()
+            .map(|response| {
+                usage.merge(&response.usage);
+                response.output
+            })
+            .collect())
+    }
+}
+
+/// Returns message asking member to review answers of others and revise its own.
+fn critique_prompt(prompts: &Prompts, answers: &[String], member: usize, round: u32) -> String {
+    let others = answers
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != member)
+        .map(|(idx, answer)| format!("## Agent {}\n{answer}", idx + 1))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    prompts.render(
+        PromptKey::DebateCritique,
+        &[("round", &(round + 1).to_string()), ("answers", &others)],
+    )
+}
+
+/// Returns message asking the judge to choose or merge final answers.
+fn judge_prompt(prompts: &Prompts, question: &str, answers: &[String]) -> String {
+    let answers = answers
+        .iter()
+        .enumerate()
+        .map(|(idx, answer)| format!("## Answer {}\n{answer}", idx + 1))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    prompts.render(
+        PromptKey::DebateJudge,
+        &[("question", question), ("answers", &answers)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_critique_prompt() {
+        let answers = vec!["yes".to_string(), "no".to_string(), "maybe".to_string()];
+        let prompt = critique_prompt(&Prompts::default(), &answers, 1, 0);
+        assert!(prompt.starts_with("Debate round 1."));
+        assert!(prompt.contains("## Agent 1\nyes\n\n## Agent 3\nmaybe"));
+        assert!(!prompt.contains("## Agent 2"));
+    }
+}
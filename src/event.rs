@@ -0,0 +1,191 @@
+//! # Agent Events
+//!
+//! While running, [Agent](crate::agent::Agent) reports what is happening inside its loop using
+//! [AgentEvent]. This allows to follow progress of the run (e.g. display tool calls in UI)
+//! without waiting for the final answer.
+//!
+//! To receive events register handler using [Agent::set_event_handler](crate::agent::Agent::set_event_handler).
+//! Handler is called synchronously from the agent loop, so it should return quickly.
+//!
+//! Events can be also sent to a channel with [Agent::run_with_sink](crate::agent::Agent::run_with_sink),
+//! using [tokio::sync::broadcast] channel many consumers (e.g. UI, logger and metrics) can
+//! subscribe to events of the same run:
+//!
+//! ```rust,no_run
+//! # use agentai::Agent;
+//! # use agentai::event::AgentStreamEvent;
+//! # async fn run() -> anyhow::Result<()> {
+//! let (sender, mut ui) = tokio::sync::broadcast::channel(64);
+//! let mut logger = sender.subscribe();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = logger.recv().await {
+//!         log::info!("{event:?}");
+//!     }
+//! });
+//! let mut agent = Agent::new("You are a useful assistant");
+//! let answer: String = agent
+//!     .run_with_sink("gpt-4o", "Why is the sky blue?", None, None, None, sender)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+
+/// Event emitted by [Agent](crate::agent::Agent) during the run.
+///
+/// Events are serializable, JSON representation contains `type` field with snake case
+/// name of the variant, e.g. `{"type": "tool_call", "call_id": "...", ...}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum AgentEvent {
+    /// New iteration of the agent loop has started
+    IterationStarted {
+        /// Iteration number, starting from 0
+        iteration: u32,
+    },
+    /// Model requested to call a tool
+    ToolCall {
+        /// Identifier of the call, the same value is used in [AgentEvent::ToolResult]
+        call_id: String,
+        /// Name of the requested tool
+        name: String,
+        /// Arguments provided by the model
+        arguments: Value,
+    },
+    /// Tool call has finished and the result will be sent back to the model
+    ToolResult {
+        /// Identifier of the call
+        call_id: String,
+        /// Name of the called tool
+        name: String,
+        /// Content returned by the tool (or error description)
+        content: String,
+        /// Indicates that the tool call failed
+        is_error: bool,
+    },
+    /// Usage threshold was reached and the agent switched to a cheaper model
+    ModelDowngraded {
+        /// Model used so far
+        from: String,
+        /// Model used from now on
+        to: String,
+        /// Total number of tokens used when the threshold was reached
+        total_tokens: u64,
+    },
+    /// Run is not making progress, see [StallAction](crate::agent::StallAction)
+    Stalled {
+        /// Description of the detected problem
+        reason: String,
+    },
+    /// Tools of the toolbox changed since the previous run, e.g. MCP server was restarted with
+    /// different tools, see [crate::tool::diff]
+    ToolsChanged {
+        /// Tools that were not available before
+        added: Vec<String>,
+        /// Tools that are no longer available
+        removed: Vec<String>,
+        /// Tools with changed description or schema
+        changed: Vec<String>,
+    },
+    /// Request exceeded context window of the model, older messages were removed from the
+    /// history and the request is retried, see [ContextOverflow](crate::agent::ContextOverflow)
+    ContextTrimmed {
+        /// Number of removed messages
+        removed: usize,
+        /// Removed messages were replaced with their summary
+        summarized: bool,
+    },
+    /// Older part of the conversation was replaced with its summary, by [Agent::compact](crate::agent::Agent::compact)
+    /// or by the model calling [COMPACT_HISTORY_TOOL](crate::agent::COMPACT_HISTORY_TOOL)
+    HistoryCompacted {
+        /// Number of removed messages
+        removed: usize,
+    },
+    /// Part of the text generated by the model, emitted only by streamed runs, see
+    /// [Agent::run_stream](crate::agent::Agent::run_stream)
+    TextDelta {
+        /// Generated text
+        content: String,
+    },
+    /// Model provided the final answer
+    Answer {
+        /// Raw text of the answer, before deserialization into output type
+        content: String,
+    },
+}
+
+/// Callback receiving [AgentEvent]s.
+pub type EventHandler = Arc<dyn Fn(&AgentEvent) + Send + Sync>;
+
+/// Event sent to [EventSink] by [Agent::run_with_sink](crate::agent::Agent::run_with_sink).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum AgentStreamEvent {
+    /// Event emitted during the run
+    Event(AgentEvent),
+    /// Run has finished successfully, this is the last event of the run
+    Finished,
+    /// Run has failed with the error, this is the last event of the run
+    Failed(String),
+}
+
+/// Destination of [AgentStreamEvent]s, implemented for senders of tokio channels.
+///
+/// Events are sent from the agent loop without waiting, so when bounded channel is full the
+/// event is dropped. Events are also dropped when all receivers are gone.
+pub trait EventSink: Send + Sync {
+    /// Sends the event.
+    fn send_event(&self, event: AgentStreamEvent);
+}
+
+impl EventSink for mpsc::Sender<AgentStreamEvent> {
+    fn send_event(&self, event: AgentStreamEvent) {
+        if let Err(mpsc::error::TrySendError::Full(event)) = self.try_send(event) {
+            warn!("Event sink is full, dropping event {event:?}");
+        }
+    }
+}
+
+impl EventSink for mpsc::UnboundedSender<AgentStreamEvent> {
+    fn send_event(&self, event: AgentStreamEvent) {
+        let _ = self.send(event);
+    }
+}
+
+impl EventSink for broadcast::Sender<AgentStreamEvent> {
+    fn send_event(&self, event: AgentStreamEvent) {
+        // Error means that there are no subscribers
+        let _ = self.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_sinks() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        sender.send_event(AgentStreamEvent::Finished);
+        sender.send_event(AgentStreamEvent::Failed("dropped".to_string()));
+        assert!(matches!(
+            receiver.try_recv(),
+            Ok(AgentStreamEvent::Finished)
+        ));
+        assert!(receiver.try_recv().is_err());
+
+        let (sender, mut first) = broadcast::channel(4);
+        let mut second = sender.subscribe();
+        sender.send_event(AgentStreamEvent::Event(AgentEvent::IterationStarted {
+            iteration: 0,
+        }));
+        assert!(matches!(first.try_recv(), Ok(AgentStreamEvent::Event(_))));
+        assert!(matches!(second.try_recv(), Ok(AgentStreamEvent::Event(_))));
+    }
+}
@@ -0,0 +1,449 @@
+//! # Experiments
+//!
+//! [Experiment] compares variants of the agent (models, system prompts or whole configurations)
+//! on the same set of test cases. Every answer is rated by [Scorer]s, and for every variant
+//! accuracy, latency and cost are collected into [ExperimentReport], so the model can be selected
+//! based on data instead of impressions.
+//!
+//! ```rust,no_run
+//! use agentai::experiment::{Contains, ExactMatch, Experiment, TestCase, Variant};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let report = Experiment::new([
+//!     TestCase::new("What is the capital of France? Answer with one word.").with_expected("Paris"),
+//!     TestCase::new("How many legs does a spider have? Answer with a number.").with_expected("8"),
+//! ])
+//! .with_variant(Variant::new("gpt-4o", "gpt-4o").with_prices(2.5, 10.0))
+//! .with_variant(Variant::new("gpt-4o-mini", "gpt-4o-mini").with_prices(0.15, 0.6))
+//! .with_variant(
+//!     Variant::new("gpt-4o-mini (terse)", "gpt-4o-mini")
+//!         .with_system_prompt("Answer with a single word.")
+//!         .with_prices(0.15, 0.6),
+//! )
+//! .with_scorer(ExactMatch)
+//! .with_scorer(Contains)
+//! .run()
+//! .await?;
+//! println!("{}", report.to_markdown());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every test case is run by a fresh agent, created for the variant, so cases don't influence each
+//! other. Cases are run one after another, so latency is not affected by concurrent requests.
+//! Failed runs are scored 0 and counted in [VariantReport::errors].
+
+use crate::agent::{Agent, TokenUsage};
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Prompt of the experiment, with optional expected answer used by scorers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TestCase {
+    /// Prompt sent to the agent
+    pub prompt: String,
+    /// Reference answer, `None` when scorers don't need it
+    pub expected: Option<String>,
+}
+
+impl TestCase {
+    /// Creates test case without expected answer.
+    pub fn new(prompt: &str) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            expected: None,
+        }
+    }
+
+    /// Sets reference answer of the case.
+    pub fn with_expected(mut self, expected: &str) -> Self {
+        self.expected = Some(expected.to_string());
+        self
+    }
+}
+
+/// Rates answers of the agent, see [Experiment::with_scorer].
+#[async_trait::async_trait]
+pub trait Scorer: Send + Sync {
+    /// Name of the score in the report.
+    fn name(&self) -> &str;
+
+    /// Returns score of the answer, from 0 (wrong) to 1 (correct). Scorers using models, e.g.
+    /// an agent judging the answer, can be implemented thanks to asynchronous interface.
+    async fn score(&self, case: &TestCase, answer: &str) -> Result<f64>;
+}
+
+/// Scores 1 when the answer is equal to the expected one, ignoring case and surrounding whitespace
+/// and punctuation.
+pub struct ExactMatch;
+
+#[async_trait::async_trait]
+impl Scorer for ExactMatch {
+    fn name(&self) -> &str {
+        "exact_match"
+    }
+
+    async fn score(&self, case: &TestCase, answer: &str) -> Result<f64> {
+        let expected = expected(case)?;
+        let normalize = |text: &str| {
+            text.trim()
+                .trim_matches(|c: char| c.is_ascii_punctuation())
+                .to_lowercase()
+        };
+        Ok(f64::from(u8::from(
+            normalize(answer) == normalize(expected),
+        )))
+    }
+}
+
+/// Scores 1 when the answer contains the expected one, ignoring case.
+pub struct Contains;
+
+#[async_trait::async_trait]
+impl Scorer for Contains {
+    fn name(&self) -> &str {
+        "contains"
+    }
+
+    async fn score(&self, case: &TestCase, answer: &str) -> Result<f64> {
+        let expected = expected(case)?.trim().to_lowercase();
+        Ok(f64::from(u8::from(
+            answer.to_lowercase().contains(&expected),
+        )))
+    }
+}
+
+fn expected(case: &TestCase) -> Result<&str> {
+    case.expected
+        .as_deref()
+        .ok_or_else(|| anyhow!("Test case '{}' has no expected answer", case.prompt))
+}
+
+/// Creates agent used by the variant.
+type AgentFactory = Arc<dyn Fn() -> Agent + Send + Sync>;
+
+/// Configuration of the agent compared in the [Experiment].
+#[derive(Clone)]
+pub struct Variant {
+    name: String,
+    model: String,
+    agent: AgentFactory,
+    /// Prices of input and output tokens per million of tokens
+    prices: Option<(f64, f64)>,
+}
+
+impl Variant {
+    /// Creates variant using the model, with agent without system prompt.
+    pub fn new(name: &str, model: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            model: model.to_string(),
+            agent: Arc::new(|| Agent::new("")),
+            prices: None,
+        }
+    }
+
+    /// Uses agent with the system prompt, to compare prompts.
+    pub fn with_system_prompt(mut self, system_prompt: &str) -> Self {
+        let system_prompt = system_prompt.to_string();
+        self.agent = Arc::new(move || Agent::new(&system_prompt));
+        self
+    }
+
+    /// Uses agents created by the factory, e.g. with tools or options set with [AgentBuilder](crate::agent::AgentBuilder).
+    pub fn with_agent<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Agent + Send + Sync + 'static,
+    {
+        self.agent = Arc::new(factory);
+        self
+    }
+
+    /// Sets prices of input and output tokens of the model, per million of tokens. Cost is reported
+    /// only for variants with prices.
+    pub fn with_prices(mut self, input_price: f64, output_price: f64) -> Self {
+        self.prices = Some((input_price, output_price));
+        self
+    }
+}
+
+/// Comparison of variants on a set of test cases, look into [module documentation](crate::experiment).
+#[derive(Default)]
+pub struct Experiment {
+    cases: Vec<TestCase>,
+    variants: Vec<Variant>,
+    scorers: Vec<Arc<dyn Scorer>>,
+}
+
+impl Experiment {
+    /// Creates experiment with test cases, variants and scorers are added with builder methods.
+    pub fn new(cases: impl IntoIterator<Item = TestCase>) -> Self {
+        Self {
+            cases: cases.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Adds compared variant.
+    pub fn with_variant(mut self, variant: Variant) -> Self {
+        self.variants.push(variant);
+        self
+    }
+
+    /// Adds scorer rating answers, e.g. [ExactMatch] or [Contains].
+    pub fn with_scorer(mut self, scorer: impl Scorer + 'static) -> Self {
+        self.scorers.push(Arc::new(scorer));
+        self
+    }
+
+    /// Runs all test cases with every variant, returning the comparison.
+    pub async fn run(&self) -> Result<ExperimentReport> {
+        if self.cases.is_empty() || self.variants.is_empty() {
+            return Err(anyhow!("Experiment requires test cases and variants"));
+        }
+        if self.scorers.is_empty() {
+            return Err(anyhow!("Experiment requires at least one scorer"));
+        }
+        let mut variants = Vec::with_capacity(self.variants.len());
+        for variant in &self.variants {
+            let mut results = Vec::with_capacity(self.cases.len());
+            for (idx, case) in self.cases.iter().enumerate() {
+                debug!("Experiment variant '{}', case {}", variant.name, idx + 1);
+                results.push(self.run_case(variant, case).await?);
+            }
+            variants.push(VariantReport::new(variant, results));
+        }
+        Ok(ExperimentReport { variants })
+    }
+
+    async fn run_case(&self, variant: &Variant, case: &TestCase) -> Result<CaseResult> {
+        let mut agent = (variant.agent)();
+        let started = std::time::Instant::now();
+        let response = agent
+            .run_detailed::<String>(&variant.model, case.prompt.as_str(), None, None, None)
+            .await;
+        let latency = started.elapsed();
+        let (answer, usage) = match response {
+            Ok(response) => (Ok(response.output), response.usage),
+            Err(err) => {
+                warn!("Experiment variant '{}' failed: {err:#}", variant.name);
+                (Err(format!("{err:#}")), *agent.usage())
+            }
+        };
+        let mut scores = BTreeMap::new();
+        for scorer in &self.scorers {
+            let score = match &answer {
+                Ok(answer) => scorer.score(case, answer).await?.clamp(0.0, 1.0),
+                Err(_) => 0.0,
+            };
+            scores.insert(scorer.name().to_string(), score);
+        }
+        Ok(CaseResult {
+            prompt: case.prompt.clone(),
+            answer,
+            scores,
+            latency,
+            usage,
+        })
+    }
+}
+
+/// Result of a single test case run by a variant.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct CaseResult {
+    /// Prompt of the test case
+    pub prompt: String,
+    /// Answer of the agent, or description of the error
+    pub answer: Result<String, String>,
+    /// Scores of the answer by the name of the scorer
+    pub scores: BTreeMap<String, f64>,
+    /// Duration of the run
+    pub latency: Duration,
+    /// Tokens used by the run
+    pub usage: TokenUsage,
+}
+
+/// Aggregated results of a single variant.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct VariantReport {
+    /// Name of the variant
+    pub name: String,
+    /// Model used by the variant
+    pub model: String,
+    /// Average scores by the name of the scorer
+    pub scores: BTreeMap<String, f64>,
+    /// Average of all scores, from 0 to 1
+    pub accuracy: f64,
+    /// Number of failed runs
+    pub errors: usize,
+    /// Average duration of the run
+    pub average_latency: Duration,
+    /// Duration of the slowest run
+    pub max_latency: Duration,
+    /// Tokens used by all runs
+    pub usage: TokenUsage,
+    /// Cost of all runs, `None` when prices of the variant are not set
+    pub cost: Option<f64>,
+    /// Results of every test case, in order of cases
+    pub results: Vec<CaseResult>,
+}
+
+impl VariantReport {
+    fn new(variant: &Variant, results: Vec<CaseResult>) -> Self {
+        let mut scores = BTreeMap::new();
+        let mut usage = TokenUsage::default();
+        let mut total_latency = Duration::ZERO;
+        for result in &results {
+            for (name, score) in &result.scores {
+                *scores.entry(name.clone()).or_insert(0.0) += score / results.len() as f64;
+            }
+            usage.merge(&result.usage);
+            total_latency += result.latency;
+        }
+        let accuracy = scores.values().sum::<f64>() / scores.len().max(1) as f64;
+        Self {
+            name: variant.name.clone(),
+            model: variant.model.clone(),
+            scores,
+            accuracy,
+            errors: results
+                .iter()
+                .filter(|result| result.answer.is_err())
+                .count(),
+            average_latency: total_latency / results.len().max(1) as u32,
+            max_latency: results
+                .iter()
+                .map(|result| result.latency)
+                .max()
+                .unwrap_or_default(),
+            usage,
+            cost: variant
+                .prices
+                .map(|(input, output)| usage.cost(input, output)),
+            results,
+        }
+    }
+}
+
+/// Comparison of variants returned by [Experiment::run].
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ExperimentReport {
+    /// Results of variants, in order of adding them to the experiment
+    pub variants: Vec<VariantReport>,
+}
+
+impl ExperimentReport {
+    /// Returns variant with the highest accuracy, the cheaper one when accuracy is equal.
+    pub fn best(&self) -> Option<&VariantReport> {
+        self.variants.iter().max_by(|a, b| {
+            a.accuracy.total_cmp(&b.accuracy).then_with(|| {
+                let cost = |variant: &VariantReport| variant.cost.unwrap_or(f64::MAX);
+                cost(b).total_cmp(&cost(a))
+            })
+        })
+    }
+
+    /// Renders comparison as markdown table, with a column for every scorer.
+    pub fn to_markdown(&self) -> String {
+        let scorers: Vec<&String> = self
+            .variants
+            .first()
+            .map(|variant| variant.scores.keys().collect())
+            .unwrap_or_default();
+        let mut table = String::from("| Variant | Model | Accuracy |");
+        for scorer in &scorers {
+            table.push_str(&format!(" {scorer} |"));
+        }
+        table.push_str(" Errors | Avg latency | Max latency | Tokens | Cost |\n|---|---|---|");
+        table.push_str(&"---|".repeat(scorers.len() + 5));
+        for variant in &self.variants {
+            table.push_str(&format!(
+                "\n| {} | {} | {:.1}% |",
+                variant.name,
+                variant.model,
+                variant.accuracy * 100.0
+            ));
+            for scorer in &scorers {
+                let score = variant.scores.get(*scorer).copied().unwrap_or_default();
+                table.push_str(&format!(" {:.1}% |", score * 100.0));
+            }
+            table.push_str(&format!(
+                " {} | {:.2}s | {:.2}s | {} | {} |",
+                variant.errors,
+                variant.average_latency.as_secs_f64(),
+                variant.max_latency.as_secs_f64(),
+                variant.usage.total_tokens,
+                variant
+                    .cost
+                    .map_or_else(|| "-".to_string(), |cost| format!("{cost:.4}"))
+            ));
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(answer: Result<&str, &str>, score: f64, millis: u64, tokens: u64) -> CaseResult {
+        CaseResult {
+            prompt: "prompt".to_string(),
+            answer: answer.map(str::to_string).map_err(str::to_string),
+            scores: BTreeMap::from([("exact_match".to_string(), score)]),
+            latency: Duration::from_millis(millis),
+            usage: TokenUsage {
+                prompt_tokens: tokens,
+                total_tokens: tokens,
+                requests: 1,
+                ..TokenUsage::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_experiment_report() {
+        let case = TestCase::new("Capital of France?").with_expected("Paris");
+        assert_eq!(ExactMatch.score(&case, " paris.\n").await.unwrap(), 1.0);
+        assert_eq!(ExactMatch.score(&case, "It is Paris").await.unwrap(), 0.0);
+        assert_eq!(Contains.score(&case, "It is Paris").await.unwrap(), 1.0);
+        assert!(Contains.score(&TestCase::new("Hi"), "Hello").await.is_err());
+
+        let large = VariantReport::new(
+            &Variant::new("large", "gpt-4o").with_prices(2.0, 8.0),
+            vec![
+                result(Ok("Paris"), 1.0, 1000, 500_000),
+                result(Err("timeout"), 0.0, 3000, 0),
+            ],
+        );
+        assert_eq!(large.accuracy, 0.5);
+        assert_eq!(large.errors, 1);
+        assert_eq!(large.average_latency, Duration::from_secs(2));
+        assert_eq!(large.cost, Some(1.0));
+        let small = VariantReport::new(
+            &Variant::new("small", "gpt-4o-mini"),
+            vec![
+                result(Ok("Paris"), 1.0, 500, 100),
+                result(Ok("Rome"), 0.0, 500, 100),
+            ],
+        );
+        let report = ExperimentReport {
+            variants: vec![large, small],
+        };
+        assert_eq!(report.best().unwrap().name, "large");
+        assert_eq!(
+            report.to_markdown(),
+            "| Variant | Model | Accuracy | exact_match | Errors | Avg latency | Max latency | Tokens | Cost |\n\
+             |---|---|---|---|---|---|---|---|---|\n\
+             | large | gpt-4o | 50.0% | 50.0% | 1 | 2.00s | 3.00s | 500000 | 1.0000 |\n\
+             | small | gpt-4o-mini | 50.0% | 50.0% | 0 | 0.50s | 0.50s | 200 | - |"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! # C Foreign Function Interface
+//!
+//! This module exposes C compatible API, that allows to embed AgentAI in applications written in
+//! other languages (C, C++, Swift, ...). It is enabled with `ffi` feature.
+//!
+//! To build dynamic (or static) library, enter this command:
+//! ```bash
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+//!
+//! Declarations for all exported functions are available in `include/agentai.h` header file.
+//!
+//! ## Conventions
+//! - All strings are NUL terminated UTF-8 strings.
+//! - Strings returned by this API are owned by the caller and must be released with [agentai_string_free].
+//! - When function fails it returns `NULL`, description of the error can be retrieved with [agentai_last_error].
+//! - Agent handle is not thread safe, it must not be used by two threads at the same time.
+//! - Callback passed to [agentai_agent_run] is invoked only on the thread that called it, before
+//!   the function returns.
+//!
+//! ## Example
+//! ```c
+//! void on_event(const char *event_json, void *user_data) {
+//!     // e.g. {"type": "text_delta", "content": "The sky"}
+//!     printf("event: %s\n", event_json);
+//! }
+//!
+//! AgentAiAgent *agent = agentai_agent_new("You are a useful assistant");
+//! char *answer = agentai_agent_run(agent, "gpt-4o", "Why sky is blue?", on_event, NULL);
+//! if (answer) {
+//!     printf("%s\n", answer);
+//!     agentai_string_free(answer);
+//! } else {
+//!     printf("error: %s\n", agentai_last_error());
+//! }
+//! agentai_agent_free(agent);
+//! ```
+
+use crate::agent::{Agent, AgentChunk};
+use crate::event::AgentEvent;
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use tokio::runtime::Runtime;
+
+/// Opaque handle to the agent, created with [agentai_agent_new] and released with [agentai_agent_free].
+pub struct AgentAiAgent {
+    agent: Agent,
+    runtime: Runtime,
+}
+
+/// Callback receiving events emitted during the run, serialized as JSON.
+///
+/// Event string is valid only during the callback execution.
+pub type AgentAiEventCallback =
+    Option<extern "C" fn(event_json: *const c_char, user_data: *mut c_void)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: impl ToString) {
+    let message = CString::new(err.to_string().replace('\0', ""))
+        .unwrap_or_else(|_| CString::from(c"unknown error"));
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Converts C string into `&str`, returns error when pointer is NULL or string is not valid UTF-8.
+unsafe fn str_from_ptr<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(anyhow!("argument `{name}` is NULL"));
+    }
+    Ok(CStr::from_ptr(ptr).to_str()?)
+}
+
+/// Runs function, converting errors and panics into NULL pointer with last error set.
+fn guard<T>(f: impl FnOnce() -> Result<*mut T>) -> *mut T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_last_error(err);
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error("panic inside agentai library");
+            ptr::null_mut()
+        }
+    }
+}
+
+fn new_handle(agent: Agent) -> Result<*mut AgentAiAgent> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(Box::into_raw(Box::new(AgentAiAgent { agent, runtime })))
+}
+
+/// Creates a new agent with default GenAI client.
+///
+/// Returns NULL on failure.
+///
+/// # Safety
+/// `system` must be a valid pointer to NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn agentai_agent_new(system: *const c_char) -> *mut AgentAiAgent {
+    guard(|| {
+        let system = str_from_ptr(system, "system")?;
+        new_handle(Agent::new(system))
+    })
+}
+
+/// Creates a new agent connected to OpenAI API compatible endpoint.
+///
+/// Returns NULL on failure.
+///
+/// # Safety
+/// All arguments must be valid pointers to NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn agentai_agent_new_with_url(
+    base_url: *const c_char,
+    api_key: *const c_char,
+    system: *const c_char,
+) -> *mut AgentAiAgent {
+    guard(|| {
+        let base_url = str_from_ptr(base_url, "base_url")?;
+        let api_key = str_from_ptr(api_key, "api_key")?;
+        let system = str_from_ptr(system, "system")?;
+        new_handle(Agent::new_with_url(base_url, api_key, system))
+    })
+}
+
+/// Passes the event serialized as JSON to the callback.
+fn call_back(callback: AgentAiEventCallback, user_data: *mut c_void, event: &AgentEvent) {
+    let Some(callback) = callback else {
+        return;
+    };
+    let Ok(json) = serde_json::to_string(event) else {
+        return;
+    };
+    if let Ok(json) = CString::new(json) {
+        callback(json.as_ptr(), user_data);
+    }
+}
+
+/// Runs the agent with the given model and prompt, blocking until the answer is ready.
+///
+/// The answer is streamed, parts of the text (`text_delta` events) and other events emitted during
+/// the run are passed to `callback` (if not NULL) as JSON strings, together with `user_data`
+/// pointer. Callback is invoked on the calling thread. Returns the answer, that must be released
+/// with [agentai_string_free], or NULL on failure.
+///
+/// # Safety
+/// `agent` must be a pointer returned by agent creation function, that was not released yet.
+/// `model` and `prompt` must be valid pointers to NUL terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn agentai_agent_run(
+    agent: *mut AgentAiAgent,
+    model: *const c_char,
+    prompt: *const c_char,
+    callback: AgentAiEventCallback,
+    user_data: *mut c_void,
+) -> *mut c_char {
+    guard(|| {
+        let Some(handle) = agent.as_mut() else {
+            return Err(anyhow!("argument `agent` is NULL"));
+        };
+        let model = str_from_ptr(model, "model")?;
+        let prompt = str_from_ptr(prompt, "prompt")?;
+
+        let AgentAiAgent { agent, runtime } = handle;
+        // Chunks are received by this thread, so the callback is not invoked from other threads.
+        // Event handler of the agent is restored when the stream is dropped, also on panic.
+        let answer = runtime.block_on(async {
+            let mut stream = agent.run_stream::<String>(model, prompt, None, None, None);
+            while let Some(chunk) = stream.next().await {
+                let event = match chunk? {
+                    AgentChunk::Output(answer) => return Ok(answer),
+                    AgentChunk::TextDelta(content) => AgentEvent::TextDelta { content },
+                    AgentChunk::Event(event) => event,
+                };
+                call_back(callback, user_data, &event);
+            }
+            Err(anyhow!("Run of the agent ended without answer"))
+        });
+        Ok(CString::new(answer?)?.into_raw())
+    })
+}
+
+/// Returns description of the last error that occurred in the calling thread, or NULL.
+///
+/// Returned string is owned by the library and is valid until next call to this API.
+#[no_mangle]
+pub extern "C" fn agentai_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Releases string returned by this API.
+///
+/// # Safety
+/// `value` must be a pointer returned by this API or NULL. It must not be released twice.
+#[no_mangle]
+pub unsafe extern "C" fn agentai_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// Releases the agent.
+///
+/// # Safety
+/// `agent` must be a pointer returned by agent creation function or NULL. It must not be released twice.
+#[no_mangle]
+pub unsafe extern "C" fn agentai_agent_free(agent: *mut AgentAiAgent) {
+    if !agent.is_null() {
+        drop(Box::from_raw(agent));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        let error = agentai_last_error();
+        assert!(!error.is_null());
+        unsafe { CStr::from_ptr(error) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    extern "C" fn count_events(_event_json: *const c_char, user_data: *mut c_void) {
+        unsafe { *(user_data as *mut usize) += 1 };
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        unsafe {
+            assert!(agentai_agent_new(ptr::null()).is_null());
+            assert_eq!(last_error(), "argument `system` is NULL");
+            assert!(agentai_agent_new(c"\xff".as_ptr()).is_null());
+            assert!(last_error().contains("utf-8"));
+
+            let answer = agentai_agent_run(
+                ptr::null_mut(),
+                c"gpt-4o".as_ptr(),
+                c"Hello".as_ptr(),
+                None,
+                ptr::null_mut(),
+            );
+            assert!(answer.is_null());
+            assert_eq!(last_error(), "argument `agent` is NULL");
+            agentai_string_free(ptr::null_mut());
+            agentai_agent_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_failed_run() {
+        unsafe {
+            // Nothing listens on the port, so the run fails
+            let agent = agentai_agent_new_with_url(
+                c"http://127.0.0.1:9/v1/".as_ptr(),
+                c"key".as_ptr(),
+                c"You are a useful assistant".as_ptr(),
+            );
+            assert!(!agent.is_null());
+            let run = |prompt: *const c_char, events: &mut usize| {
+                agentai_agent_run(
+                    agent,
+                    c"gpt-4o".as_ptr(),
+                    prompt,
+                    Some(count_events),
+                    events as *mut usize as *mut c_void,
+                )
+            };
+            let mut events = 0;
+            assert!(run(ptr::null(), &mut events).is_null());
+            assert_eq!(last_error(), "argument `prompt` is NULL");
+            assert_eq!(events, 0);
+
+            assert!(run(c"Hello".as_ptr(), &mut events).is_null());
+            assert!(!last_error().is_empty());
+            // Iteration started before the request failed
+            assert!(events > 0);
+            // Event handler of the agent is restored after the run
+            assert!((*agent).agent.replace_event_handler(None).is_none());
+            agentai_agent_free(agent);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! # HTTP Connections
+//!
+//! Built-in components talking over HTTP share a single [reqwest::Client], so they reuse pooled
+//! connections instead of opening new ones for every request:
+//! - requests of [Agent](crate::agent::Agent) to LLM providers, unless the agent was created with its own client,
+//! - [WebSearchToolBox](crate::tool::websearch::WebSearchToolBox),
+//! - MCP servers connected with Streamable HTTP transport (see [crate::tool::mcp]),
+//! - bot adapters of `connectors` module.
+//!
+//! The pool is configured with [set_http_pool_config]. Configuration applies to components created
+//! after the call, so it should be set at the start of the application.
+//!
+//! ```rust
+//! use agentai::http::{set_http_pool_config, HttpPoolConfig};
+//! use std::time::Duration;
+//!
+//! set_http_pool_config(
+//!     HttpPoolConfig::default()
+//!         .with_tcp_keep_alive(Duration::from_secs(30))
+//!         .with_idle_timeout(Duration::from_secs(60))
+//!         .with_max_idle_per_host(8),
+//! )
+//! .expect("Invalid configuration of HTTP pool");
+//! ```
+
+use reqwest::Client;
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Client shared by built-in components, created on first use
+static SHARED_CLIENT: RwLock<Option<Client>> = RwLock::new(None);
+
+/// Configuration of the pool of HTTP connections, see [set_http_pool_config].
+///
+/// Defaults are the defaults of `reqwest`: idle connections are closed after 90 seconds, number of
+/// idle connections is not limited and TCP keep-alive is disabled.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpPoolConfig {
+    /// Interval of TCP keep-alive probes, disabled when `None`
+    pub tcp_keep_alive: Option<Duration>,
+    /// Time after which idle connections are closed, never when `None`
+    pub idle_timeout: Option<Duration>,
+    /// Maximal number of idle connections kept per host
+    pub max_idle_per_host: usize,
+    /// Timeout of establishing new connection, unlimited when `None`
+    pub connect_timeout: Option<Duration>,
+}
+
+impl Default for HttpPoolConfig {
+    fn default() -> Self {
+        Self {
+            tcp_keep_alive: None,
+            idle_timeout: Some(Duration::from_secs(90)),
+            max_idle_per_host: usize::MAX,
+            connect_timeout: None,
+        }
+    }
+}
+
+impl HttpPoolConfig {
+    /// Enables TCP keep-alive probes, keeping connections through proxies and NATs dropping idle ones.
+    pub fn with_tcp_keep_alive(mut self, interval: Duration) -> Self {
+        self.tcp_keep_alive = Some(interval);
+        self
+    }
+
+    /// Sets time after which idle connections are closed.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits number of idle connections kept per host, `0` disables pooling.
+    pub fn with_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.max_idle_per_host = max_idle;
+        self
+    }
+
+    /// Limits time of establishing new connection.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Creates client with this configuration, e.g. for components that shouldn't share connections.
+    pub fn build_client(&self) -> reqwest::Result<Client> {
+        let mut builder = Client::builder()
+            .tcp_keepalive(self.tcp_keep_alive)
+            .pool_idle_timeout(self.idle_timeout)
+            .pool_max_idle_per_host(self.max_idle_per_host);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder.build()
+    }
+}
+
+/// Returns client shared by built-in components. Client is cheap to clone, clones share the pool.
+pub fn shared_client() -> Client {
+    if let Some(client) = SHARED_CLIENT
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+    {
+        return client.clone();
+    }
+    SHARED_CLIENT
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Client::default)
+        .clone()
+}
+
+/// Sets configuration of the pool of the shared client, look into [module documentation](crate::http).
+///
+/// Components created before keep using connections of the previous client.
+pub fn set_http_pool_config(config: HttpPoolConfig) -> reqwest::Result<()> {
+    let client = config.build_client()?;
+    *SHARED_CLIENT.write().unwrap_or_else(|e| e.into_inner()) = Some(client);
+    Ok(())
+}
+
+/// Returns `true` when `Authorization` header of the request contains bearer `token`. Tokens are
+/// compared in time not depending on the position of the first difference.
+#[cfg(any(feature = "remote", feature = "webhook-server"))]
+pub(crate) fn has_bearer_token(headers: &reqwest::header::HeaderMap, token: &str) -> bool {
+    let constant_time_eq = |a: &[u8], b: &[u8]| {
+        a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+    };
+    headers
+        .get(reqwest::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|value| constant_time_eq(value.as_bytes(), token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_client() {
+        assert!(HttpPoolConfig::default().build_client().is_ok());
+        let config = HttpPoolConfig::default()
+            .with_tcp_keep_alive(Duration::from_secs(30))
+            .with_max_idle_per_host(0)
+            .with_connect_timeout(Duration::from_secs(5));
+        assert!(config.build_client().is_ok());
+    }
+}
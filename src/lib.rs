@@ -59,8 +59,46 @@
 //! cargo run --example simple
 //! ```
 
+// Code generated by `#[toolbox]` macro refers to this crate as `agentai`, also inside of it
+extern crate self as agentai;
+
 pub mod agent;
+pub mod ensemble;
+pub mod event;
+pub mod experiment;
+pub mod http;
+pub mod rag;
+pub mod schema;
+pub mod system_prompt;
 pub mod tool;
+pub mod trace;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "connectors")]
+pub mod connectors;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "hot-reload")]
+pub mod reload;
+
+#[cfg(feature = "remote")]
+pub mod remote;
+
+#[cfg(feature = "scheduler")]
+pub mod schedule;
+
+#[cfg(feature = "tts")]
+pub mod speech;
+
+#[cfg(feature = "sse")]
+pub mod sse;
+
+#[cfg(feature = "webhook-server")]
+pub mod webhook;
 
 // This modules will be enabled only when generating documentation
 #[cfg(doc)]
@@ -71,3 +109,25 @@ pub mod structured_output;
 
 #[allow(unused_imports)]
 pub use agent::*;
+
+// Items used by code generated by `#[toolbox]` macro, they are not part of the public API
+#[doc(hidden)]
+pub mod __private {
+    pub use log;
+    #[cfg(feature = "observability")]
+    pub use tracing;
+
+    /// Maximal number of characters of arguments recorded in the span of the tool call
+    #[cfg(feature = "observability")]
+    const MAX_TRACED_ARGUMENTS_LEN: usize = 256;
+
+    /// Returns arguments of the tool call serialized for the span, truncated when too long.
+    #[cfg(feature = "observability")]
+    pub fn traced_arguments(arguments: &serde_json::Value) -> String {
+        let arguments = arguments.to_string();
+        match arguments.char_indices().nth(MAX_TRACED_ARGUMENTS_LEN) {
+            Some((idx, _)) => format!("{}...", &arguments[..idx]),
+            None => arguments,
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! # Retrieval Augmented Generation
+//!
+//! Documents are split into chunks, which are embedded with [Embedder] and stored in
+//! [VectorStore]. Agents retrieve chunks similar to their queries, to answer with knowledge that
+//! the model wasn't trained on.
+//!
+//! [InMemoryVectorStore] is a simple store kept in memory of the process, other stores (e.g. vector
+//! databases) can be used by implementing [VectorStore] trait.
+//!
+//! Knowledge bases change over time, with `rag-indexer` feature [Indexer] keeps the store fresh
+//! in the background, indexing documents from a directory or sent by the application.
+//! [RetrievalToolBox] lets agents search the store, every run sees a consistent
+//! [snapshot](VectorStore::snapshot) of it, even while the indexer updates it.
+//!
+//! ```rust,no_run
+//! use agentai::rag::{Document, InMemoryVectorStore, VectorStore};
+//! use agentai::Embedder;
+//!
+//! # async fn example(embedder: impl Embedder) -> anyhow::Result<()> {
+//! let store = InMemoryVectorStore::new();
+//! let document = Document::new("handbook", "Employees have 26 days of paid leave per year.");
+//! let chunks = document.embed(&embedder, 1000).await?;
+//! store.upsert(&document.id, chunks).await?;
+//!
+//! let query = embedder.embed("How many days off do I have?").await?;
+//! for result in store.search(&query, 3).await? {
+//!     println!("{} ({:.2}): {}", result.document_id, result.score, result.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "rag-indexer")]
+mod indexer;
+
+mod retrieval;
+
+#[cfg(feature = "rag-indexer")]
+pub use indexer::{Indexer, IndexerBuilder, IndexerEvent};
+pub use retrieval::RetrievalToolBox;
+
+use crate::agent::{cosine_similarity, split_text, Embedder};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Default maximal number of characters of a single chunk
+pub const DEFAULT_CHUNK_LEN: usize = 1_000;
+
+/// Document added to the knowledge base.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Document {
+    /// Identifier of the document, e.g. path of the file, adding document with the same identifier
+    /// replaces it
+    pub id: String,
+    /// Content of the document
+    pub text: String,
+}
+
+impl Document {
+    /// Creates document with the identifier and the content.
+    pub fn new(id: &str, text: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// Splits the document into chunks of at most `chunk_len` characters and embeds them.
+    pub async fn embed(&self, embedder: &dyn Embedder, chunk_len: usize) -> Result<Vec<Chunk>> {
+        let mut chunks = vec![];
+        for (index, text) in self.split(chunk_len) {
+            chunks.push(Chunk {
+                index,
+                text: text.to_string(),
+                embedding: embedder.embed(text).await?,
+            });
+        }
+        Ok(chunks)
+    }
+
+    /// Returns positions and texts of chunks of the document, blank chunks are skipped.
+    pub(crate) fn split(&self, chunk_len: usize) -> impl Iterator<Item = (usize, &str)> {
+        split_text(&self.text, chunk_len)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, text)| !text.trim().is_empty())
+    }
+}
+
+/// Part of the document, together with its embedding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// Position of the chunk in the document
+    pub index: usize,
+    /// Text of the chunk
+    pub text: String,
+    /// Embedding of the text
+    pub embedding: Vec<f32>,
+}
+
+/// Chunk found by [VectorStore::search].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    /// Identifier of the document containing the chunk
+    pub document_id: String,
+    /// Position of the chunk in the document
+    pub chunk: usize,
+    /// Text of the chunk
+    pub text: String,
+    /// Similarity to the query, from -1 to 1
+    pub score: f32,
+}
+
+/// Storage of embedded chunks of documents.
+#[async_trait::async_trait]
+pub trait VectorStore: Send + Sync {
+    /// Replaces chunks of the document.
+    async fn upsert(&self, document_id: &str, chunks: Vec<Chunk>) -> Result<()>;
+
+    /// Removes all chunks of the document.
+    async fn remove(&self, document_id: &str) -> Result<()>;
+
+    /// Returns at most `limit` chunks most similar to the embedding of the query, best first.
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<SearchResult>>;
+
+    /// Returns read-only view of the store as it is now, not affected by later updates.
+    ///
+    /// [RetrievalToolBox] searches the snapshot taken at the beginning of the run, so results
+    /// don't shift while documents are indexed in the middle of the run. By default
+    /// snapshots are not supported and searches see concurrent updates.
+    fn snapshot(&self) -> Option<Arc<dyn VectorStore>> {
+        None
+    }
+}
+
+type Documents = Arc<HashMap<String, Vec<Chunk>>>;
+
+#[derive(Debug, Default)]
+struct StoreState {
+    /// Incremented by every update
+    version: u64,
+    /// Shared with snapshots, copied on the first update after a snapshot was taken
+    documents: Documents,
+}
+
+/// Vector store kept in memory, searched exhaustively. Clones share the same data.
+///
+/// Snapshots are cheap, they share data with the store until it is updated.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryVectorStore {
+    state: Arc<RwLock<StoreState>>,
+}
+
+impl InMemoryVectorStore {
+    /// Creates empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns number of stored documents.
+    pub fn len(&self) -> usize {
+        self.read().documents.len()
+    }
+
+    /// Returns `true` when no document is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns number of updates made to the store.
+    pub fn version(&self) -> u64 {
+        self.read().version
+    }
+
+    /// Returns read-only view of the store as it is now, see [VectorStore::snapshot].
+    pub fn snapshot(&self) -> InMemorySnapshot {
+        let state = self.read();
+        InMemorySnapshot {
+            version: state.version,
+            documents: state.documents.clone(),
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, StoreState> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, update: impl FnOnce(&mut HashMap<String, Vec<Chunk>>)) {
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        update(Arc::make_mut(&mut state.documents));
+        state.version += 1;
+    }
+}
+
+#[async_trait::async_trait]
+impl VectorStore for InMemoryVectorStore {
+    async fn upsert(&self, document_id: &str, chunks: Vec<Chunk>) -> Result<()> {
+        self.update(|documents| {
+            documents.insert(document_id.to_string(), chunks);
+        });
+        Ok(())
+    }
+
+    async fn remove(&self, document_id: &str) -> Result<()> {
+        self.update(|documents| {
+            documents.remove(document_id);
+        });
+        Ok(())
+    }
+
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<SearchResult>> {
+        let documents = self.read().documents.clone();
+        Ok(search_documents(&documents, embedding, limit))
+    }
+
+    fn snapshot(&self) -> Option<Arc<dyn VectorStore>> {
+        Some(Arc::new(InMemoryVectorStore::snapshot(self)))
+    }
+}
+
+/// Read-only view of [InMemoryVectorStore] at some version, updates of the store are not visible
+/// in it.
+#[derive(Debug, Clone)]
+pub struct InMemorySnapshot {
+    version: u64,
+    documents: Documents,
+}
+
+impl InMemorySnapshot {
+    /// Returns version of the store the snapshot was taken at.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns number of documents in the snapshot.
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Returns `true` when the snapshot has no document.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait::async_trait]
+impl VectorStore for InMemorySnapshot {
+    async fn upsert(&self, _document_id: &str, _chunks: Vec<Chunk>) -> Result<()> {
+        Err(anyhow!("Snapshot of the vector store is read-only"))
+    }
+
+    async fn remove(&self, _document_id: &str) -> Result<()> {
+        Err(anyhow!("Snapshot of the vector store is read-only"))
+    }
+
+    async fn search(&self, embedding: &[f32], limit: usize) -> Result<Vec<SearchResult>> {
+        Ok(search_documents(&self.documents, embedding, limit))
+    }
+
+    fn snapshot(&self) -> Option<Arc<dyn VectorStore>> {
+        Some(Arc::new(self.clone()))
+    }
+}
+
+fn search_documents(
+    documents: &HashMap<String, Vec<Chunk>>,
+    embedding: &[f32],
+    limit: usize,
+) -> Vec<SearchResult> {
+    let mut results: Vec<SearchResult> = documents
+        .iter()
+        .flat_map(|(document_id, chunks)| {
+            chunks.iter().map(move |chunk| SearchResult {
+                document_id: document_id.clone(),
+                chunk: chunk.index,
+                text: chunk.text.clone(),
+                score: cosine_similarity(embedding, &chunk.embedding),
+            })
+        })
+        .collect();
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    results.truncate(limit);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Embeds text as counts of vowels
+    struct Vowels;
+
+    #[async_trait::async_trait]
+    impl Embedder for Vowels {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok("aeiou"
+                .chars()
+                .map(|vowel| text.chars().filter(|c| *c == vowel).count() as f32)
+                .collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_store() {
+        let store = InMemoryVectorStore::new();
+        for (id, text) in [("a", "banana\n\naaa"), ("o", "moon\n\nfoo")] {
+            let document = Document::new(id, text);
+            let chunks = document.embed(&Vowels, 8).await.unwrap();
+            assert_eq!(chunks.len(), 2);
+            store.upsert(id, chunks).await.unwrap();
+        }
+        let query = Vowels.embed("boo").await.unwrap();
+        let results = store.search(&query, 3).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].document_id, "o");
+        assert_eq!(results[1].document_id, "o");
+
+        store.remove("o").await.unwrap();
+        assert_eq!(store.len(), 1);
+        let results = store.search(&query, 3).await.unwrap();
+        assert!(results.iter().all(|result| result.document_id == "a"));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let store = InMemoryVectorStore::new();
+        let document = Document::new("a", "banana");
+        store
+            .upsert("a", document.embed(&Vowels, 8).await.unwrap())
+            .await
+            .unwrap();
+        let snapshot = VectorStore::snapshot(&store).unwrap();
+
+        let document = Document::new("o", "moon");
+        store
+            .upsert("o", document.embed(&Vowels, 8).await.unwrap())
+            .await
+            .unwrap();
+        store.remove("a").await.unwrap();
+        assert_eq!(store.version(), 3);
+
+        let query = Vowels.embed("boo").await.unwrap();
+        let results = snapshot.search(&query, 3).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].document_id, "a");
+        assert!(snapshot.upsert("o", vec![]).await.is_err());
+        let results = store.search(&query, 3).await.unwrap();
+        assert_eq!(results[0].document_id, "o");
+    }
+}
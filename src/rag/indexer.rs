@@ -0,0 +1,405 @@
+use crate::agent::Embedder;
+use crate::rag::{Chunk, Document, VectorStore, DEFAULT_CHUNK_LEN};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+/// Default interval of checking the watched directory for changes
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Progress of [Indexer], received with [Indexer::subscribe].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum IndexerEvent {
+    /// Document was embedded and stored
+    Indexed {
+        /// Identifier of the document
+        document_id: String,
+        /// Number of stored chunks
+        chunks: usize,
+        /// Number of documents waiting for indexing
+        pending: usize,
+    },
+    /// Document was removed from the store
+    Removed {
+        /// Identifier of the document
+        document_id: String,
+    },
+    /// Document couldn't be indexed, previous version is kept in the store
+    Failed {
+        /// Identifier of the document
+        document_id: String,
+        /// Description of the error
+        error: String,
+    },
+    /// All known documents are indexed
+    Idle,
+}
+
+enum Command {
+    Index(Document),
+    Remove(String),
+}
+
+/// Configuration of [Indexer], created with [Indexer::builder].
+pub struct IndexerBuilder {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    chunk_len: usize,
+    requests_per_minute: Option<u32>,
+    directory: Option<(PathBuf, Duration)>,
+}
+
+impl IndexerBuilder {
+    /// Sets maximal number of characters of a single chunk, default is [DEFAULT_CHUNK_LEN].
+    pub fn with_chunk_len(mut self, chunk_len: usize) -> Self {
+        self.chunk_len = chunk_len;
+        self
+    }
+
+    /// Limits number of embedding requests per minute, e.g. to stay within rate limits of the
+    /// provider. Requests are spread evenly, unlimited by default.
+    pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+        self.requests_per_minute = Some(requests_per_minute.max(1));
+        self
+    }
+
+    /// Watches text files in the directory and its subdirectories. New and modified files are
+    /// indexed, removed files are removed from the store. Identifier of the document is the path
+    /// of the file.
+    pub fn with_directory(self, path: impl AsRef<Path>) -> Self {
+        self.with_directory_interval(path, DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Like [IndexerBuilder::with_directory], checking for changes with given interval.
+    pub fn with_directory_interval(mut self, path: impl AsRef<Path>, interval: Duration) -> Self {
+        self.directory = Some((path.as_ref().to_path_buf(), interval));
+        self
+    }
+
+    /// Starts the indexer in a background task, it has to be called within tokio runtime.
+    pub fn start(self) -> Indexer {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let (events, _) = broadcast::channel(256);
+        let worker = Worker {
+            embedder: self.embedder,
+            store: self.store,
+            chunk_len: self.chunk_len,
+            min_delay: self
+                .requests_per_minute
+                .map(|rpm| Duration::from_secs(60) / rpm),
+            last_request: None,
+            events: events.clone(),
+        };
+        let task = tokio::spawn(worker.run(receiver, self.directory));
+        Indexer {
+            commands,
+            events,
+            task,
+        }
+    }
+}
+
+/// Background task keeping [VectorStore] up to date, look into [module documentation](crate::rag).
+///
+/// Documents are indexed one by one, so agents can search the store while it is updated. Indexing
+/// stops when the indexer is dropped.
+///
+/// ```rust,no_run
+/// use agentai::rag::{Document, InMemoryVectorStore, Indexer, IndexerEvent};
+/// use agentai::Embedder;
+///
+/// # async fn example(embedder: impl Embedder + 'static) -> anyhow::Result<()> {
+/// let store = InMemoryVectorStore::new();
+/// let indexer = Indexer::builder(embedder, store.clone())
+///     .with_directory("./knowledge")
+///     .with_rate_limit(300)
+///     .start();
+/// let mut events = indexer.subscribe();
+/// indexer.index(Document::new("faq", "Orders are shipped within 2 days."));
+/// while let Ok(event) = events.recv().await {
+///     println!("{event:?}");
+///     if event == IndexerEvent::Idle {
+///         break;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Indexer {
+    commands: mpsc::UnboundedSender<Command>,
+    events: broadcast::Sender<IndexerEvent>,
+    task: JoinHandle<()>,
+}
+
+impl Indexer {
+    /// Creates builder of the indexer storing documents embedded with `embedder` in `store`.
+    pub fn builder(
+        embedder: impl Embedder + 'static,
+        store: impl VectorStore + 'static,
+    ) -> IndexerBuilder {
+        IndexerBuilder {
+            embedder: Arc::new(embedder),
+            store: Arc::new(store),
+            chunk_len: DEFAULT_CHUNK_LEN,
+            requests_per_minute: None,
+            directory: None,
+        }
+    }
+
+    /// Queues the document for indexing, replacing previous version with the same identifier.
+    pub fn index(&self, document: Document) {
+        let _ = self.commands.send(Command::Index(document));
+    }
+
+    /// Queues removal of the document from the store.
+    pub fn remove(&self, document_id: &str) {
+        let _ = self.commands.send(Command::Remove(document_id.to_string()));
+    }
+
+    /// Returns sender queuing documents for indexing, e.g. for producers running in other tasks.
+    pub fn sender(&self) -> mpsc::UnboundedSender<Document> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let commands = self.commands.clone();
+        tokio::spawn(async move {
+            while let Some(document) = receiver.recv().await {
+                if commands.send(Command::Index(document)).is_err() {
+                    break;
+                }
+            }
+        });
+        sender
+    }
+
+    /// Subscribes to progress events.
+    pub fn subscribe(&self) -> broadcast::Receiver<IndexerEvent> {
+        self.events.subscribe()
+    }
+}
+
+impl Drop for Indexer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Worker {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    chunk_len: usize,
+    /// Minimal time between embedding requests
+    min_delay: Option<Duration>,
+    last_request: Option<Instant>,
+    events: broadcast::Sender<IndexerEvent>,
+}
+
+impl Worker {
+    async fn run(
+        mut self,
+        mut commands: mpsc::UnboundedReceiver<Command>,
+        directory: Option<(PathBuf, Duration)>,
+    ) {
+        let (directory, poll_interval) = match directory {
+            Some((path, poll_interval)) => (Some(path), poll_interval),
+            None => (None, DEFAULT_POLL_INTERVAL),
+        };
+        let mut ticker = interval(poll_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        // Modification times of indexed files
+        let mut files: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+        let mut queue: Vec<Command> = vec![];
+        loop {
+            if queue.is_empty() {
+                tokio::select! {
+                    command = commands.recv() => match command {
+                        Some(command) => queue.push(command),
+                        None => return,
+                    },
+                    _ = ticker.tick(), if directory.is_some() => {
+                        if let Some(directory) = &directory {
+                            queue.extend(scan(directory, &mut files).await);
+                        }
+                    }
+                }
+            }
+            while let Ok(command) = commands.try_recv() {
+                queue.push(command);
+            }
+            if queue.is_empty() {
+                continue;
+            }
+            // Documents are processed in order of arrival
+            let command = queue.remove(0);
+            self.process(command, queue.len()).await;
+            if queue.is_empty() {
+                self.emit(IndexerEvent::Idle);
+            }
+        }
+    }
+
+    async fn process(&mut self, command: Command, pending: usize) {
+        match command {
+            Command::Index(document) => match self.index(&document).await {
+                Ok(chunks) => self.emit(IndexerEvent::Indexed {
+                    document_id: document.id,
+                    chunks,
+                    pending,
+                }),
+                Err(err) => {
+                    warn!("Failed to index document {}: {err:#}", document.id);
+                    self.emit(IndexerEvent::Failed {
+                        document_id: document.id,
+                        error: format!("{err:#}"),
+                    });
+                }
+            },
+            Command::Remove(document_id) => match self.store.remove(&document_id).await {
+                Ok(()) => self.emit(IndexerEvent::Removed { document_id }),
+                Err(err) => self.emit(IndexerEvent::Failed {
+                    document_id,
+                    error: format!("{err:#}"),
+                }),
+            },
+        }
+    }
+
+    /// Embeds chunks of the document respecting the rate limit, then stores them.
+    async fn index(&mut self, document: &Document) -> Result<usize> {
+        debug!("Indexing document {}", document.id);
+        let mut chunks = vec![];
+        for (index, text) in document.split(self.chunk_len) {
+            if let (Some(min_delay), Some(last_request)) = (self.min_delay, self.last_request) {
+                tokio::time::sleep_until(last_request + min_delay).await;
+            }
+            self.last_request = Some(Instant::now());
+            chunks.push(Chunk {
+                index,
+                text: text.to_string(),
+                embedding: self.embedder.embed(text).await?,
+            });
+        }
+        let count = chunks.len();
+        self.store.upsert(&document.id, chunks).await?;
+        Ok(count)
+    }
+
+    fn emit(&self, event: IndexerEvent) {
+        // Error means that there are no subscribers
+        let _ = self.events.send(event);
+    }
+}
+
+/// Returns commands updating the store with changes of files in the directory.
+async fn scan(directory: &Path, files: &mut HashMap<PathBuf, Option<SystemTime>>) -> Vec<Command> {
+    let mut found = HashMap::new();
+    if let Err(err) = list_files(directory, &mut found).await {
+        warn!("Failed to scan directory {directory:?}: {err:#}");
+        return vec![];
+    }
+    let mut commands = vec![];
+    for (path, modified) in &found {
+        if files.get(path) == Some(modified) && modified.is_some() {
+            continue;
+        }
+        match tokio::fs::read_to_string(path).await {
+            Ok(text) => commands.push(Command::Index(Document::new(
+                &path.to_string_lossy(),
+                &text,
+            ))),
+            // Binary files are skipped
+            Err(err) => debug!("Skipping file {path:?}: {err}"),
+        }
+    }
+    for path in files.keys() {
+        if !found.contains_key(path) {
+            commands.push(Command::Remove(path.to_string_lossy().to_string()));
+        }
+    }
+    *files = found;
+    commands
+}
+
+async fn list_files(
+    directory: &Path,
+    files: &mut HashMap<PathBuf, Option<SystemTime>>,
+) -> Result<()> {
+    let mut directories = vec![directory.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let mut entries = tokio::fs::read_dir(&directory)
+            .await
+            .with_context(|| format!("Unable to read {directory:?}"))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_dir() {
+                directories.push(entry.path());
+            } else if metadata.is_file() {
+                files.insert(entry.path(), metadata.modified().ok());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::InMemoryVectorStore;
+
+    struct Length;
+
+    #[async_trait::async_trait]
+    impl Embedder for Length {
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32, 1.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_indexer() {
+        let store = InMemoryVectorStore::new();
+        let indexer = Indexer::builder(Length, store.clone())
+            .with_chunk_len(10)
+            .with_rate_limit(60_000)
+            .start();
+        let mut events = indexer.subscribe();
+        indexer.index(Document::new("first", "one two three four"));
+        indexer
+            .sender()
+            .send(Document::new("second", "five"))
+            .unwrap();
+
+        let mut indexed = vec![];
+        while indexed.len() < 2 {
+            if let IndexerEvent::Indexed {
+                document_id,
+                chunks,
+                ..
+            } = events.recv().await.unwrap()
+            {
+                indexed.push((document_id, chunks));
+            }
+        }
+        assert_eq!(
+            indexed,
+            [("first".to_string(), 2), ("second".to_string(), 1)]
+        );
+        assert_eq!(store.len(), 2);
+
+        indexer.remove("first");
+        loop {
+            if let IndexerEvent::Removed { document_id } = events.recv().await.unwrap() {
+                assert_eq!(document_id, "first");
+                break;
+            }
+        }
+        assert_eq!(store.len(), 1);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::agent::Embedder;
+use crate::rag::VectorStore;
+use crate::tool::composite::SharedToolBox;
+use crate::tool::{Capability, Tool, ToolBox, ToolError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+const SEARCH_KNOWLEDGE_TOOL: &str = "search_knowledge";
+const DEFAULT_LIMIT: usize = 5;
+
+/// Toolbox searching [VectorStore] for chunks relevant to the query of the model.
+///
+/// At the beginning of every run (see [ToolBox::for_run]) it takes [snapshot](VectorStore::snapshot)
+/// of the store, all searches of the run use it, so the model doesn't get different results for
+/// the same query when the store is updated concurrently. Every run has its own snapshot, also
+/// when the toolbox is shared by concurrent runs. Stores without snapshots are searched directly.
+#[derive(Clone)]
+pub struct RetrievalToolBox {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn VectorStore>,
+    limit: usize,
+}
+
+impl RetrievalToolBox {
+    /// Creates toolbox embedding queries with the embedder and searching the store.
+    pub fn new(embedder: Arc<dyn Embedder>, store: Arc<dyn VectorStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    /// Sets maximal number of chunks returned by a single search, 5 by default.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+fn search_knowledge_tool() -> Tool {
+    Tool {
+        name: SEARCH_KNOWLEDGE_TOOL.to_string(),
+        description: Some(
+            "Searches the knowledge base for passages relevant to the query.".to_string(),
+        ),
+        schema: Some(json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to search for"
+                }
+            },
+            "required": ["query"]
+        })),
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchKnowledgeArguments {
+    query: String,
+}
+
+#[async_trait::async_trait]
+impl ToolBox for RetrievalToolBox {
+    fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+        Ok(vec![search_knowledge_tool()])
+    }
+
+    async fn call_tool(&self, tool_name: String, arguments: Value) -> Result<String, ToolError> {
+        if tool_name != SEARCH_KNOWLEDGE_TOOL {
+            return Err(ToolError::NoToolFound(tool_name));
+        }
+        let args: SearchKnowledgeArguments =
+            serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+                tool: tool_name,
+                error: e.to_string(),
+                arguments: Box::new(arguments),
+                schema: Box::new(search_knowledge_tool().schema.unwrap_or_default()),
+            })?;
+        let embedding = self.embedder.embed(&args.query).await?;
+        let results = self.store.search(&embedding, self.limit).await?;
+        Ok(serde_json::to_string(&results).map_err(anyhow::Error::from)?)
+    }
+
+    /// Returns toolbox searching snapshot of the store.
+    fn for_run(&self) -> Option<SharedToolBox> {
+        let store = self.store.snapshot()?;
+        Some(Arc::new(Self {
+            store,
+            ..self.clone()
+        }))
+    }
+
+    /// Queries are embedded before the search, usually by a remote API.
+    fn tool_capabilities(&self, _tool_name: &str) -> Vec<Capability> {
+        vec![Capability::Network]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rag::{Chunk, InMemoryVectorStore};
+    use anyhow::Result;
+
+    struct Constant;
+
+    #[async_trait::async_trait]
+    impl Embedder for Constant {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Ok(vec![1.0])
+        }
+    }
+
+    fn chunk(text: &str) -> Vec<Chunk> {
+        vec![Chunk {
+            index: 0,
+            text: text.to_string(),
+            embedding: vec![1.0],
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_retrieval_uses_snapshot_of_run() {
+        let store = InMemoryVectorStore::new();
+        store.upsert("a", chunk("old")).await.unwrap();
+        let toolbox: SharedToolBox = Arc::new(RetrievalToolBox::new(
+            Arc::new(Constant),
+            Arc::new(store.clone()),
+        ));
+        let search = |toolbox: SharedToolBox| async move {
+            toolbox
+                .call_tool(SEARCH_KNOWLEDGE_TOOL.to_string(), json!({"query": "?"}))
+                .await
+        };
+
+        // Second run starts while the first one is in progress
+        let first_run = toolbox.for_run().unwrap();
+        store.upsert("a", chunk("new")).await.unwrap();
+        assert!(search(first_run.clone()).await.unwrap().contains("old"));
+        let second_run = toolbox.for_run().unwrap();
+        assert!(search(second_run.clone()).await.unwrap().contains("new"));
+        assert!(search(first_run.clone()).await.unwrap().contains("old"));
+    }
+}
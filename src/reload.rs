@@ -0,0 +1,249 @@
+//! # Hot Reload of Prompts and Configuration
+//!
+//! Iterating on prompts of a live service shouldn't require redeploys. [ConfigWatcher] watches a
+//! file and reloads it when it changes, so system prompts, templates or [AgentConfig] can be edited
+//! at runtime. It is enabled with `hot-reload` feature.
+//!
+//! Files are polled for changes (every [DEFAULT_POLL_INTERVAL] by default), which works the same
+//! way on all platforms and with files mounted from config maps. When the changed file can't be
+//! parsed the error is logged and the previous version is kept, so a typo doesn't break the
+//! service.
+//!
+//! Agents are owned by the caller, so the watcher doesn't modify them. Wait for the change with
+//! [ConfigWatcher::changed] and apply the new version, e.g. with [AgentConfig::apply] before the
+//! next run:
+//!
+//! ```rust,no_run
+//! use agentai::Agent;
+//! use agentai::reload::{AgentConfig, ConfigWatcher};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let mut watcher = ConfigWatcher::<AgentConfig>::json("agent.json").await?;
+//! let mut agent = Agent::new("");
+//! watcher.current().apply(&mut agent);
+//!
+//! while let Some(config) = watcher.changed().await {
+//!     config.apply(&mut agent);
+//!     let model = config.model.as_deref().unwrap_or("gpt-4o-mini");
+//!     let answer: String = agent.run(model, "Summarize today's news", None, None, None).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agent::{Agent, ModelDowngrade};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Default interval of checking files for changes.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings of the agent that can be changed at runtime, loaded e.g. from JSON file.
+///
+/// Settings that are not present in the file are left unchanged by [AgentConfig::apply].
+///
+/// ```json
+/// {
+///     "system": "You are a helpful assistant.",
+///     "model": "gpt-4o",
+///     "allowed_tools": ["web_search"],
+///     "model_downgrade": {"threshold": {"tokens": 100000}, "model": "gpt-4o-mini"}
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[non_exhaustive]
+pub struct AgentConfig {
+    /// System message of the agent
+    pub system: Option<String>,
+    /// Model used for runs, the agent doesn't store it so it has to be passed to the run by caller
+    pub model: Option<String>,
+    /// Names of tools exposed to the model, see [Agent::set_allowed_tools]
+    pub allowed_tools: Option<Vec<String>>,
+    /// Usage budget after which the agent switches to cheaper model
+    pub model_downgrade: Option<ModelDowngrade>,
+}
+
+impl AgentConfig {
+    /// Applies settings present in the configuration to the agent.
+    pub fn apply(&self, agent: &mut Agent) {
+        if let Some(system) = &self.system {
+            agent.set_system(system);
+        }
+        if let Some(allowed_tools) = &self.allowed_tools {
+            agent.set_allowed_tools(Some(allowed_tools.clone()));
+        }
+        if let Some(model_downgrade) = &self.model_downgrade {
+            agent.set_model_downgrade(Some(model_downgrade.clone()));
+        }
+    }
+}
+
+/// Parser of the watched file
+pub type Parser<T> = fn(&str) -> Result<T>;
+
+/// Watches a file and reloads it on change, look into [module documentation](crate::reload) for details.
+///
+/// Watching stops when the watcher is dropped.
+pub struct ConfigWatcher<T> {
+    receiver: watch::Receiver<Arc<T>>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Send + Sync + 'static> ConfigWatcher<T> {
+    /// Loads the file and starts watching it, using `parser` to read its content.
+    ///
+    /// Fails when the file can't be read or parsed initially.
+    pub async fn with_parser(
+        path: impl AsRef<Path>,
+        interval: Duration,
+        parser: Parser<T>,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (content, modified) = read(&path).await?;
+        let value = parser(&content).with_context(|| format!("Unable to parse {path:?}"))?;
+        let (sender, receiver) = watch::channel(Arc::new(value));
+        let task = tokio::spawn(poll(path, interval, parser, content, modified, sender));
+        Ok(Self { receiver, task })
+    }
+
+    /// Returns current version of the file.
+    pub fn current(&self) -> Arc<T> {
+        self.receiver.borrow().clone()
+    }
+
+    /// Waits until the file changes, returning its new version.
+    ///
+    /// Returns `None` when watching stopped.
+    pub async fn changed(&mut self) -> Option<Arc<T>> {
+        self.receiver.changed().await.ok()?;
+        Some(self.receiver.borrow_and_update().clone())
+    }
+}
+
+impl ConfigWatcher<String> {
+    /// Watches text file, e.g. system prompt or prompt template.
+    pub async fn text(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_parser(path, DEFAULT_POLL_INTERVAL, |content| {
+            Ok(content.to_string())
+        })
+        .await
+    }
+}
+
+impl<T: DeserializeOwned + Send + Sync + 'static> ConfigWatcher<T> {
+    /// Watches JSON file, deserializing it into `T`, e.g. [AgentConfig].
+    pub async fn json(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_parser(path, DEFAULT_POLL_INTERVAL, |content| {
+            Ok(serde_json::from_str(content)?)
+        })
+        .await
+    }
+}
+
+impl<T> Drop for ConfigWatcher<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn read(path: &Path) -> Result<(String, Option<SystemTime>)> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Unable to read {path:?}"))?;
+    let modified = tokio::fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    Ok((content, modified))
+}
+
+/// Checks the file periodically, sending new versions until all receivers are dropped.
+async fn poll<T>(
+    path: PathBuf,
+    interval: Duration,
+    parser: Parser<T>,
+    mut content: String,
+    mut modified: Option<SystemTime>,
+    sender: watch::Sender<Arc<T>>,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    while !sender.is_closed() {
+        ticker.tick().await;
+        let current_modified = tokio::fs::metadata(&path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        // Modification time is checked first, to avoid reading the file on every tick
+        if current_modified.is_some() && current_modified == modified {
+            continue;
+        }
+        let (new_content, new_modified) = match read(&path).await {
+            Ok(file) => file,
+            Err(err) => {
+                warn!("{err:#}");
+                continue;
+            }
+        };
+        modified = new_modified;
+        if new_content == content {
+            continue;
+        }
+        match parser(&new_content) {
+            Ok(value) => {
+                debug!("Reloaded {path:?}");
+                content = new_content;
+                sender.send_replace(Arc::new(value));
+            }
+            Err(err) => warn!("Unable to parse {path:?}, keeping previous version: {err:#}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reload() {
+        let path = std::env::temp_dir().join(format!("agentai-reload-{}.json", std::process::id()));
+        tokio::fs::write(&path, r#"{"model": "gpt-4o"}"#)
+            .await
+            .unwrap();
+        let mut watcher = ConfigWatcher::<AgentConfig>::with_parser(
+            &path,
+            Duration::from_millis(10),
+            |content| Ok(serde_json::from_str(content)?),
+        )
+        .await
+        .unwrap();
+        assert_eq!(watcher.current().model.as_deref(), Some("gpt-4o"));
+
+        tokio::fs::write(&path, "{ invalid").await.unwrap();
+        tokio::fs::write(
+            &path,
+            r#"{"model": "gpt-4o-mini", "allowed_tools": ["search"]}"#,
+        )
+        .await
+        .unwrap();
+        let config = watcher.changed().await.unwrap();
+        assert_eq!(config.model.as_deref(), Some("gpt-4o-mini"));
+
+        let mut agent = Agent::new("");
+        AgentConfig {
+            system: Some("Be brief.".to_string()),
+            ..Default::default()
+        }
+        .apply(&mut agent);
+        assert_eq!(agent.history().len(), 1);
+        assert_eq!(agent.history()[0].content.text_as_str(), Some("Be brief."));
+        let _ = tokio::fs::remove_file(&path).await;
+    }
+}
@@ -0,0 +1,227 @@
+//! # Scheduled Reminders
+//!
+//! Assistant agents often promise to come back later, e.g. "I'll check the delivery tomorrow".
+//! [Scheduler] keeps [Reminder]s in a background task and delivers every reminder through a
+//! channel when it is due, so the application can start a follow-up run with the note of the
+//! reminder as a prompt and the payload (e.g. identifier of the chat) to find the conversation.
+//!
+//! Reminders are scheduled by the application with [Scheduler::schedule], or by the model with
+//! `set_reminder` tool of [AgentSelfToolBox](crate::tool::agent_self::AgentSelfToolBox) connected
+//! with [Scheduler::handler]. They are kept only in memory of the process, pending reminders
+//! are lost when the scheduler is dropped.
+//!
+//! Runs can be also triggered by changes of files, e.g. for developer agents reviewing changes as
+//! they are made. [FileTrigger] added with [Scheduler::watch] schedules a reminder with changed
+//! paths whenever watched files change.
+//!
+//! ```rust,no_run
+//! use agentai::schedule::Scheduler;
+//! use agentai::tool::agent_self::{AgentSelfToolBox, Reminder};
+//! use agentai::Agent;
+//! use serde_json::json;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let (scheduler, mut reminders) = Scheduler::start();
+//! let toolbox = AgentSelfToolBox::new().with_reminder_handler(scheduler.handler());
+//! scheduler.schedule(
+//!     Reminder::after(Duration::from_secs(3600), "Ask the user how the meeting went.")
+//!         .with_payload(json!({"chat_id": 42})),
+//! );
+//!
+//! let mut agent = Agent::new("You are personal assistant.");
+//! while let Some(reminder) = reminders.recv().await {
+//!     let answer: String = agent
+//!         .run("gpt-4o", reminder.note.as_str(), Some(Arc::new(toolbox.clone())), None, None)
+//!         .await?;
+//!     println!("{}: {answer}", reminder.payload);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod trigger;
+
+pub use crate::tool::agent_self::Reminder;
+pub use trigger::{FileTrigger, DEFAULT_POLL_INTERVAL};
+
+use log::debug;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+
+/// Identifier of the reminder returned by [Scheduler::schedule], used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReminderId(u64);
+
+#[derive(Default)]
+struct Shared {
+    /// Pending reminders ordered by time when they are due
+    reminders: Mutex<BTreeMap<(SystemTime, ReminderId), Reminder>>,
+    next_id: AtomicU64,
+    /// Wakes the background task when reminders change
+    changed: Notify,
+}
+
+impl Shared {
+    fn schedule(&self, reminder: Reminder) -> ReminderId {
+        let id = ReminderId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        debug!("Reminder {id:?} scheduled: {}", reminder.note);
+        self.reminders().insert((reminder.due, id), reminder);
+        self.changed.notify_one();
+        id
+    }
+
+    fn reminders(&self) -> std::sync::MutexGuard<'_, BTreeMap<(SystemTime, ReminderId), Reminder>> {
+        self.reminders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Background task delivering [Reminder]s when they are due, look into [module documentation](crate::schedule).
+///
+/// Clones of the scheduler (also inside of [Scheduler::handler]) share the same reminders, the task
+/// stops when the last clone is dropped.
+#[derive(Clone)]
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    /// Delivery of reminders and watching of triggers
+    tasks: Arc<Tasks>,
+}
+
+impl Scheduler {
+    /// Starts the scheduler in a background task, it has to be called within tokio runtime.
+    ///
+    /// Returns receiver of due reminders. Reminders becoming due when the receiver is dropped are
+    /// discarded.
+    pub fn start() -> (Self, mpsc::UnboundedReceiver<Reminder>) {
+        let shared = Arc::new(Shared::default());
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let task = tokio::spawn(deliver(shared.clone(), sender));
+        let scheduler = Self {
+            shared,
+            tasks: Arc::new(Tasks(Mutex::new(vec![task]))),
+        };
+        (scheduler, receiver)
+    }
+
+    /// Schedules the reminder, reminders already due are delivered immediately.
+    pub fn schedule(&self, reminder: Reminder) -> ReminderId {
+        self.shared.schedule(reminder)
+    }
+
+    /// Starts watching files of the trigger, runs are scheduled when they change, see [FileTrigger].
+    pub fn watch(&self, trigger: FileTrigger) {
+        let task = tokio::spawn(trigger.watch(self.shared.clone()));
+        self.tasks
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(task);
+    }
+
+    /// Cancels the reminder, returns it when it was not delivered yet.
+    pub fn cancel(&self, id: ReminderId) -> Option<Reminder> {
+        let mut reminders = self.shared.reminders();
+        let key = reminders
+            .keys()
+            .find(|(_, key_id)| *key_id == id)
+            .copied()?;
+        reminders.remove(&key)
+    }
+
+    /// Returns pending reminders, ordered by time when they are due.
+    pub fn pending(&self) -> Vec<(ReminderId, Reminder)> {
+        self.shared
+            .reminders()
+            .iter()
+            .map(|((_, id), reminder)| (*id, reminder.clone()))
+            .collect()
+    }
+
+    /// Returns handler scheduling reminders set by the model, for
+    /// [AgentSelfToolBox::with_reminder_handler](crate::tool::agent_self::AgentSelfToolBox::with_reminder_handler).
+    pub fn handler(&self) -> impl Fn(Reminder) + Send + Sync + 'static {
+        let scheduler = self.clone();
+        move |reminder| {
+            scheduler.schedule(reminder);
+        }
+    }
+}
+
+/// Background tasks aborted when the last clone of the scheduler is dropped
+struct Tasks(Mutex<Vec<JoinHandle<()>>>);
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        for task in self.0.get_mut().unwrap_or_else(|e| e.into_inner()) {
+            task.abort();
+        }
+    }
+}
+
+/// Sends reminders to the receiver when they are due.
+async fn deliver(shared: Arc<Shared>, sender: mpsc::UnboundedSender<Reminder>) {
+    loop {
+        let next_due = shared.reminders().keys().next().map(|(due, _)| *due);
+        match next_due {
+            Some(due) => {
+                let wait = due.duration_since(SystemTime::now()).unwrap_or_default();
+                tokio::select! {
+                    _ = shared.changed.notified() => continue,
+                    _ = tokio::time::sleep(wait) => {}
+                }
+            }
+            None => {
+                shared.changed.notified().await;
+                continue;
+            }
+        }
+        let due = {
+            let mut reminders = shared.reminders();
+            let now = SystemTime::now();
+            let pending = reminders.split_off(&(now, ReminderId(u64::MAX)));
+            std::mem::replace(&mut *reminders, pending)
+        };
+        for ((_, id), reminder) in due {
+            debug!("Reminder {id:?} is due");
+            if sender.send(reminder).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_scheduler() {
+        let (scheduler, mut reminders) = Scheduler::start();
+        let later = scheduler.schedule(Reminder::after(Duration::from_millis(200), "Later"));
+        scheduler.schedule(Reminder::after(Duration::from_millis(50), "Sooner"));
+        let cancelled = scheduler.schedule(Reminder::after(Duration::from_millis(100), "Never"));
+        scheduler.handler()(Reminder::after(Duration::ZERO, "Now"));
+        assert_eq!(scheduler.cancel(cancelled).unwrap().note, "Never");
+        assert!(scheduler.cancel(cancelled).is_none());
+
+        let mut delivered = vec![];
+        for _ in 0..3 {
+            let reminder = tokio::time::timeout(Duration::from_secs(2), reminders.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            delivered.push(reminder.note);
+            if delivered.len() == 2 {
+                assert_eq!(scheduler.pending()[0].0, later);
+            }
+        }
+        assert_eq!(delivered, ["Now", "Sooner", "Later"]);
+        assert!(scheduler.pending().is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::schedule::{Reminder, Shared};
+use log::{debug, warn};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::time::{interval, MissedTickBehavior};
+
+/// Default interval of checking watched files for changes
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Source of runs triggered by changes of files, added to [Scheduler](crate::schedule::Scheduler) with
+/// [Scheduler::watch](crate::schedule::Scheduler::watch).
+///
+/// Watched files and directories (recursively, hidden entries like `.git` are skipped) are polled
+/// for changes. Changes are collected until files stop changing for one poll interval, so saving
+/// many files at once triggers a single run. Then a [Reminder] is scheduled with the prompt
+/// followed by the list of changed paths, its payload contains the paths as
+/// `{"changed": [...], "removed": [...]}`.
+///
+/// ```rust,no_run
+/// use agentai::schedule::{FileTrigger, Scheduler};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let (scheduler, mut runs) = Scheduler::start();
+/// scheduler.watch(FileTrigger::new("Review my changes.").with_path("./src"));
+/// while let Some(run) = runs.recv().await {
+///     println!("{}", run.note);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileTrigger {
+    prompt: String,
+    paths: Vec<PathBuf>,
+    poll_interval: Duration,
+}
+
+impl FileTrigger {
+    /// Creates trigger scheduling runs with the prompt, without watched paths.
+    pub fn new(prompt: &str) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            paths: vec![],
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Watches the file, or the directory with its subdirectories.
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets interval of checking files for changes, default is [DEFAULT_POLL_INTERVAL].
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Polls watched paths and schedules a run when they settle after a change.
+    pub(super) async fn watch(self, shared: Arc<Shared>) {
+        let mut ticker = interval(self.poll_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut files = self.snapshot().await;
+        let mut changed = BTreeSet::new();
+        let mut removed = BTreeSet::new();
+        loop {
+            ticker.tick().await;
+            let current = self.snapshot().await;
+            let mut settled = true;
+            for (path, modified) in &current {
+                if files.get(path) != Some(modified) {
+                    settled = false;
+                    removed.remove(path);
+                    changed.insert(path.clone());
+                }
+            }
+            for path in files.keys() {
+                if !current.contains_key(path) {
+                    settled = false;
+                    changed.remove(path);
+                    removed.insert(path.clone());
+                }
+            }
+            files = current;
+            if !settled || (changed.is_empty() && removed.is_empty()) {
+                continue;
+            }
+            debug!(
+                "Watched files changed: {} changed, {} removed",
+                changed.len(),
+                removed.len()
+            );
+            shared.schedule(
+                self.reminder(&std::mem::take(&mut changed), &std::mem::take(&mut removed)),
+            );
+        }
+    }
+
+    /// Returns reminder of the run, with changed paths in the prompt and the payload.
+    fn reminder(&self, changed: &BTreeSet<PathBuf>, removed: &BTreeSet<PathBuf>) -> Reminder {
+        let changed = changed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let removed = removed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let mut note = self.prompt.clone();
+        if !changed.is_empty() {
+            note.push_str("\n\nChanged files:\n");
+            note.push_str(&bullets(&changed));
+        }
+        if !removed.is_empty() {
+            note.push_str("\n\nRemoved files:\n");
+            note.push_str(&bullets(&removed));
+        }
+        Reminder::after(Duration::ZERO, &note)
+            .with_payload(json!({"changed": changed, "removed": removed}))
+    }
+
+    /// Returns modification times of watched files.
+    async fn snapshot(&self) -> HashMap<PathBuf, SystemTime> {
+        let paths = self.paths.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut files = HashMap::new();
+            for path in &paths {
+                list_files(path, &mut files);
+            }
+            files
+        })
+        .await
+        .unwrap_or_default()
+    }
+}
+
+fn bullets(paths: &[String]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("- {path}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Adds the file, or files in the directory and its subdirectories, skipping hidden entries.
+fn list_files(path: &Path, files: &mut HashMap<PathBuf, SystemTime>) {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        // Watched file can be removed, it is reported as removed
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+        Err(err) => {
+            warn!("Unable to watch {path:?}: {err}");
+            return;
+        }
+    };
+    if metadata.is_file() {
+        files.insert(
+            path.to_path_buf(),
+            metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        );
+    } else if metadata.is_dir() {
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_name().to_string_lossy().starts_with('.') {
+                list_files(&entry.path(), files);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schedule::{FileTrigger, Scheduler};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_file_trigger() {
+        let directory =
+            std::env::temp_dir().join(format!("agentai-trigger-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join(".git")).unwrap();
+        std::fs::write(directory.join("old.rs"), "fn old() {}").unwrap();
+
+        let (scheduler, mut runs) = Scheduler::start();
+        scheduler.watch(
+            FileTrigger::new("Review my changes.")
+                .with_path(&directory)
+                .with_poll_interval(Duration::from_millis(50)),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        std::fs::write(directory.join("new.rs"), "fn new() {}").unwrap();
+        std::fs::write(directory.join(".git").join("index"), "ignored").unwrap();
+        std::fs::remove_file(directory.join("old.rs")).unwrap();
+
+        let run = tokio::time::timeout(Duration::from_secs(5), runs.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        let new = directory.join("new.rs").display().to_string();
+        let old = directory.join("old.rs").display().to_string();
+        assert_eq!(
+            run.note,
+            format!("Review my changes.\n\nChanged files:\n- {new}\n\nRemoved files:\n- {old}")
+        );
+        assert_eq!(run.payload["changed"][0], new.as_str());
+        assert_eq!(run.payload["removed"][0], old.as_str());
+    }
+}
@@ -0,0 +1,656 @@
+//! # JSON Schema utilities
+//!
+//! Helpers for JSON Schemas sent to the model, both for tools parameters and structured output.
+//!
+//! It also provides [toolbox_schema!](crate::toolbox_schema) helper, that can be used in tests to compare
+//! generated tools schemas with golden files, catching regressions from macro or `schemars` upgrades.
+
+use crate::tool::{ToolBox, ToolError};
+use genai::adapter::AdapterKind;
+use schemars::generate::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+use std::sync::RwLock;
+
+mod gbnf;
+
+pub use gbnf::to_gbnf;
+
+static DEFAULT_SCHEMA_OPTIONS: RwLock<SchemaOptions> = RwLock::new(SchemaOptions::new());
+
+/// Version of JSON Schema specification used when generating schemas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaDraft {
+    /// JSON Schema Draft 7
+    Draft07,
+    /// JSON Schema 2019-09
+    Draft2019_09,
+    /// JSON Schema 2020-12, used by default
+    #[default]
+    Draft2020_12,
+    /// OpenAPI 3.0 schema object
+    OpenApi3,
+}
+
+/// Settings of JSON Schema generation for tools parameters and structured output.
+///
+/// Providers have different, often conflicting, requirements for schemas. For example OpenAI strict
+/// mode requires `additionalProperties: false` on all objects, while Gemini doesn't accept references
+/// to subschemas. Options can be set globally with [set_default_schema_options] or per toolbox
+/// with `#[toolbox(schema = ...)]` attribute:
+///
+/// ```no_run
+/// use agentai::schema::SchemaOptions;
+/// # use agentai::tool::{Tool, ToolBox, ToolError, toolbox};
+/// # struct MyToolBox;
+///
+/// #[toolbox(schema = SchemaOptions::new().with_inline_subschemas(true))]
+/// impl MyToolBox {
+///     // ...
+/// #   #[tool]
+/// #   fn tool(&self, value: i32) -> Result<String, ToolError> { Ok(value.to_string()) }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SchemaOptions {
+    /// Version of JSON Schema specification
+    pub draft: SchemaDraft,
+    /// Inline all subschemas instead of using references to `$defs`
+    pub inline_subschemas: bool,
+    /// Set `additionalProperties: false` on all objects that don't define it
+    pub deny_additional_properties: bool,
+    /// Sort properties (and all other keys) of schema alphabetically, otherwise their order
+    /// depends on `preserve_order` feature of `serde_json`, see [canonicalize]
+    pub sort_properties: bool,
+}
+
+impl SchemaOptions {
+    /// Creates default options, same as used by `schemars` (JSON Schema 2020-12 with subschemas references).
+    pub const fn new() -> Self {
+        Self {
+            draft: SchemaDraft::Draft2020_12,
+            inline_subschemas: false,
+            deny_additional_properties: false,
+            sort_properties: false,
+        }
+    }
+
+    /// Sets version of JSON Schema specification.
+    pub fn with_draft(mut self, draft: SchemaDraft) -> Self {
+        self.draft = draft;
+        self
+    }
+
+    /// Sets whether subschemas should be inlined instead of referenced.
+    pub fn with_inline_subschemas(mut self, inline_subschemas: bool) -> Self {
+        self.inline_subschemas = inline_subschemas;
+        self
+    }
+
+    /// Sets whether objects should forbid properties that are not defined in schema.
+    pub fn with_deny_additional_properties(mut self, deny_additional_properties: bool) -> Self {
+        self.deny_additional_properties = deny_additional_properties;
+        self
+    }
+
+    /// Sets whether properties should be sorted alphabetically.
+    pub fn with_sort_properties(mut self, sort_properties: bool) -> Self {
+        self.sort_properties = sort_properties;
+        self
+    }
+
+    /// Returns `schemars` generator configured with these options.
+    pub fn generator(&self) -> SchemaGenerator {
+        let settings = match self.draft {
+            SchemaDraft::Draft07 => SchemaSettings::draft07(),
+            SchemaDraft::Draft2019_09 => SchemaSettings::draft2019_09(),
+            SchemaDraft::Draft2020_12 => SchemaSettings::draft2020_12(),
+            SchemaDraft::OpenApi3 => SchemaSettings::openapi3(),
+        };
+        settings
+            .with(|s| {
+                s.meta_schema = None;
+                s.inline_subschemas = self.inline_subschemas;
+            })
+            .into_generator()
+    }
+
+    /// Generates JSON Schema of type `T`.
+    pub fn schema_for<T: JsonSchema>(&self) -> Value {
+        let mut schema: Value = self.generator().into_root_schema_for::<T>().into();
+        if self.deny_additional_properties {
+            deny_additional_properties(&mut schema);
+        }
+        if self.sort_properties {
+            schema = canonicalize(schema);
+        }
+        schema
+    }
+}
+
+/// Returns global schema options, used by toolboxes that don't provide their own options.
+pub fn default_schema_options() -> SchemaOptions {
+    DEFAULT_SCHEMA_OPTIONS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Sets global schema options, used by toolboxes that don't provide their own options and for
+/// structured output of the [Agent](crate::agent::Agent).
+pub fn set_default_schema_options(options: SchemaOptions) {
+    *DEFAULT_SCHEMA_OPTIONS
+        .write()
+        .unwrap_or_else(|e| e.into_inner()) = options;
+}
+
+fn deny_additional_properties(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if object.contains_key("properties") && !object.contains_key("additionalProperties") {
+                object.insert("additionalProperties".to_string(), Value::Bool(false));
+            }
+            object.values_mut().for_each(deny_additional_properties);
+        }
+        Value::Array(items) => items.iter_mut().for_each(deny_additional_properties),
+        _ => {}
+    }
+}
+
+/// Environment variable that forces [assert_toolbox_schema_snapshot] to overwrite existing snapshots.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "AGENTAI_UPDATE_SNAPSHOTS";
+
+/// Returns JSON value with keys of all objects sorted alphabetically.
+///
+/// Serialization of such value is stable, regardless of the order in which keys were inserted.
+/// This is used to make requests reproducible (e.g. in deterministic mode or for caching). Without
+/// `preserve_order` feature of `serde_json` keys are always sorted, but any crate of the dependency
+/// graph can enable it.
+pub fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(String, Value)> = object.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonicalize(value)))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        value => value,
+    }
+}
+
+/// Keywords not accepted by Gemini, which supports only a subset of OpenAPI 3.0 schema
+const GEMINI_UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "$defs",
+    "definitions",
+    "title",
+    "default",
+    "examples",
+    "additionalProperties",
+    "unevaluatedProperties",
+    "patternProperties",
+    "dependentRequired",
+    "readOnly",
+    "writeOnly",
+    "deprecated",
+    "uniqueItems",
+    "contentEncoding",
+    "contentMediaType",
+];
+
+/// Formats of numbers generated by `schemars` that are not part of JSON Schema specification
+const NUMBER_FORMATS: &[&str] = &[
+    "int8", "int16", "int32", "int64", "uint", "uint8", "uint16", "uint32", "uint64", "float",
+    "double",
+];
+
+/// Adjusts schema to the requirements of the provider used by the model.
+///
+/// Schemas generated by `schemars` use JSON Schema features, that are not accepted by every provider.
+/// This function is applied by the [Agent](crate::agent::Agent) to both tools and structured output
+/// schemas:
+/// - for all providers `$schema` and `title` are removed from the root of the schema,
+/// - for OpenAI non-standard number formats (e.g. `uint32`) are removed,
+/// - for Gemini references are inlined, `const` is converted into `enum`, `oneOf` into `anyOf`,
+///   nullable types are converted into OpenAPI `nullable` and unsupported keywords and formats are removed.
+///
+/// Sanitization can be disabled with [AgentBuilder::with_raw_schemas](crate::agent::AgentBuilder::with_raw_schemas).
+pub fn sanitize(schema: Value, adapter_kind: AdapterKind) -> Value {
+    let mut schema = schema;
+    if let Value::Object(object) = &mut schema {
+        object.remove("$schema");
+        object.remove("title");
+    }
+    match adapter_kind {
+        AdapterKind::Gemini => {
+            let definitions = schema_definitions(&schema);
+            sanitize_gemini(schema, &definitions, 0)
+        }
+        AdapterKind::OpenAI => {
+            strip_number_formats(&mut schema);
+            schema
+        }
+        _ => schema,
+    }
+}
+
+/// Returns definitions of subschemas (from both `$defs` and `definitions`), used to resolve references.
+fn schema_definitions(schema: &Value) -> Map<String, Value> {
+    let mut definitions = Map::new();
+    for key in ["$defs", "definitions"] {
+        if let Some(Value::Object(defs)) = schema.get(key) {
+            definitions.extend(defs.iter().map(|(name, def)| (name.clone(), def.clone())));
+        }
+    }
+    definitions
+}
+
+fn sanitize_gemini(schema: Value, definitions: &Map<String, Value>, depth: usize) -> Value {
+    // Recursive types can't be inlined, stop at some depth and allow any value instead
+    const MAX_DEPTH: usize = 16;
+
+    match schema {
+        Value::Object(mut object) => {
+            if let Some(Value::String(reference)) = object.remove("$ref") {
+                let name = reference.rsplit('/').next().unwrap_or_default();
+                let Some(Value::Object(definition)) = definitions.get(name) else {
+                    return json!({});
+                };
+                if depth >= MAX_DEPTH {
+                    return json!({});
+                }
+                // Keywords next to the reference (e.g. description) take precedence
+                for (key, value) in definition {
+                    object.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+                return sanitize_gemini(Value::Object(object), definitions, depth + 1);
+            }
+
+            for keyword in GEMINI_UNSUPPORTED_KEYWORDS {
+                object.remove(*keyword);
+            }
+            if let Some(value) = object.remove("const") {
+                object.insert("enum".to_string(), json!([value]));
+            }
+            if let Some(one_of) = object.remove("oneOf") {
+                object.insert("anyOf".to_string(), one_of);
+            }
+            // Nullable types are expressed as ["type", "null"]
+            if let Some(Value::Array(types)) = object.get("type") {
+                let is_nullable = types.iter().any(|t| t == "null");
+                let types: Vec<Value> = types.iter().filter(|t| *t != "null").cloned().collect();
+                if types.len() == 1 {
+                    object.insert("type".to_string(), types[0].clone());
+                    if is_nullable {
+                        object.insert("nullable".to_string(), Value::Bool(true));
+                    }
+                }
+            }
+            // Only few formats are supported, depending on the type
+            let supported_formats: &[&str] = match object.get("type").and_then(Value::as_str) {
+                Some("string") => &["enum", "date-time"],
+                Some("integer") => &["int32", "int64"],
+                Some("number") => &["float", "double"],
+                _ => &[],
+            };
+            let format = object.get("format").and_then(Value::as_str);
+            if format.is_some_and(|format| !supported_formats.contains(&format)) {
+                object.remove("format");
+            }
+
+            Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = match key.as_str() {
+                            // Keys of properties are names, not keywords, don't sanitize them
+                            "properties" => match value {
+                                Value::Object(properties) => Value::Object(
+                                    properties
+                                        .into_iter()
+                                        .map(|(name, property)| {
+                                            (name, sanitize_gemini(property, definitions, depth))
+                                        })
+                                        .collect(),
+                                ),
+                                value => value,
+                            },
+                            _ => sanitize_gemini(value, definitions, depth),
+                        };
+                        (key, value)
+                    })
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| sanitize_gemini(item, definitions, depth))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn strip_number_formats(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if object
+                .get("format")
+                .and_then(Value::as_str)
+                .is_some_and(|format| NUMBER_FORMATS.contains(&format))
+            {
+                object.remove("format");
+            }
+            object.values_mut().for_each(strip_number_formats);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_number_formats),
+        _ => {}
+    }
+}
+
+/// Converts schema to satisfy requirements of strict structured outputs (e.g. OpenAI strict mode).
+///
+/// In strict mode provider guarantees that generated output matches the schema, but schema has to
+/// list all properties of objects as required and forbid additional properties. This function:
+/// - sets `additionalProperties: false` on all objects,
+/// - marks all properties as required (in order of properties in the schema),
+/// - makes properties that were not required nullable, so the model can skip them using `null`.
+pub fn strict(schema: Value) -> Value {
+    let Value::Object(object) = schema else {
+        return schema;
+    };
+    let mut object: Map<String, Value> = object
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match (key.as_str(), value) {
+                // Keys of properties are names, not keywords
+                ("properties", Value::Object(properties)) => Value::Object(
+                    properties
+                        .into_iter()
+                        .map(|(name, property)| (name, strict(property)))
+                        .collect(),
+                ),
+                (_, value) => strict_children(value),
+            };
+            (key, value)
+        })
+        .collect();
+
+    let required = required_properties(&object);
+    if let Some(Value::Object(properties)) = object.get_mut("properties") {
+        for (name, property) in properties.iter_mut() {
+            if !required.contains(name) {
+                *property = nullable(property.take());
+            }
+        }
+        let names: Vec<Value> = properties.keys().cloned().map(Value::String).collect();
+        object.insert("required".to_string(), Value::Array(names));
+        object.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+    Value::Object(object)
+}
+
+fn strict_children(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(strict).collect()),
+        value => strict(value),
+    }
+}
+
+fn required_properties(object: &Map<String, Value>) -> Vec<String> {
+    object
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|required| {
+            required
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns schema that accepts also `null`.
+fn nullable(schema: Value) -> Value {
+    let Value::Object(mut object) = schema else {
+        return schema;
+    };
+    let is_simple = !["$ref", "enum", "const", "anyOf", "oneOf"]
+        .iter()
+        .any(|key| object.contains_key(*key));
+    match object.get_mut("type") {
+        Some(Value::String(kind)) if is_simple => {
+            if kind != "null" {
+                let kind = kind.clone();
+                object.insert("type".to_string(), json!([kind, "null"]));
+            }
+            Value::Object(object)
+        }
+        Some(Value::Array(kinds)) if is_simple => {
+            if !kinds.iter().any(|kind| kind == "null") {
+                kinds.push(json!("null"));
+            }
+            Value::Object(object)
+        }
+        _ => json!({"anyOf": [Value::Object(object), {"type": "null"}]}),
+    }
+}
+
+/// Dumps tools definitions of the toolbox as pretty printed JSON.
+///
+/// Output contains name, description and parameters schema of every tool, in order returned by
+/// the toolbox. Keys are sorted, so the output is stable and can be stored as a golden file.
+pub fn toolbox_schema(toolbox: &dyn ToolBox) -> Result<String, ToolError> {
+    let tools: Vec<Value> = toolbox
+        .tools_definitions()?
+        .into_iter()
+        .map(|tool| {
+            json!({
+                "name": tool.name,
+                "description": tool.description,
+                "parameters": tool.schema,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&canonicalize(Value::Array(tools)))
+        .map_err(|e| ToolError::Other(e.into()))
+}
+
+/// Compares tools definitions of the toolbox with the snapshot stored in `path`.
+///
+/// When the snapshot doesn't exist or [UPDATE_SNAPSHOTS_ENV] environment variable is set, snapshot
+/// is written instead. Intended to be used in tests, catching schema regressions caused by changes
+/// in tools or upgrades of `schemars`. Usually used through [toolbox_schema!](crate::toolbox_schema).
+///
+/// # Panics
+/// Panics when schema differs from the snapshot or snapshot can't be read or written.
+pub fn assert_toolbox_schema_snapshot(toolbox: &dyn ToolBox, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let schema = toolbox_schema(toolbox).expect("Unable to get tools definitions");
+    if !path.exists() || std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Unable to create snapshot directory");
+        }
+        std::fs::write(path, schema + "\n").expect("Unable to write snapshot");
+        return;
+    }
+    let snapshot = std::fs::read_to_string(path).expect("Unable to read snapshot");
+    assert!(
+        snapshot.trim_end() == schema,
+        "Tools schema differs from snapshot {}, run with {}=1 to update it\n\nExpected:\n{}\n\nActual:\n{}",
+        path.display(),
+        UPDATE_SNAPSHOTS_ENV,
+        snapshot.trim_end(),
+        schema
+    );
+}
+
+/// Dumps or verifies tools schema of a toolbox, for snapshot testing.
+///
+/// With single argument returns tools definitions as pretty printed JSON (see [schema::toolbox_schema](crate::schema::toolbox_schema)).
+/// With path to golden file, relative to the crate root, asserts that schema matches the snapshot
+/// (see [schema::assert_toolbox_schema_snapshot](crate::schema::assert_toolbox_schema_snapshot)).
+///
+/// ```no_run
+/// # use agentai::tool::websearch::WebSearchToolBox;
+/// #[test]
+/// fn websearch_schema() {
+///     let toolbox = WebSearchToolBox::new("api_key");
+///     agentai::toolbox_schema!(&toolbox, "tests/snapshots/websearch.json");
+/// }
+/// ```
+#[macro_export]
+macro_rules! toolbox_schema {
+    ($toolbox:expr) => {
+        $crate::schema::toolbox_schema($toolbox)
+    };
+    ($toolbox:expr, $path:expr) => {
+        $crate::schema::assert_toolbox_schema_snapshot(
+            $toolbox,
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_canonicalize() {
+        let value = canonicalize(json!({
+            "type": "object",
+            "properties": {"b": {"type": "string"}, "a": {"type": "integer"}},
+            "required": ["b", "a"]
+        }));
+        assert_eq!(
+            value.to_string(),
+            r#"{"properties":{"a":{"type":"integer"},"b":{"type":"string"}},"required":["b","a"],"type":"object"}"#
+        );
+    }
+
+    #[test]
+    fn test_schema_options() {
+        #[derive(JsonSchema)]
+        #[allow(dead_code)]
+        struct Inner {
+            value: i32,
+        }
+        #[derive(JsonSchema)]
+        #[allow(dead_code)]
+        struct Outer {
+            inner: Inner,
+        }
+
+        let schema = SchemaOptions::new()
+            .with_inline_subschemas(true)
+            .with_deny_additional_properties(true)
+            .schema_for::<Outer>();
+        assert!(schema.get("$schema").is_none());
+        assert!(schema.get("$defs").is_none());
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["properties"]["inner"]["additionalProperties"], false);
+    }
+
+    #[test]
+    fn test_sort_properties() {
+        #[derive(JsonSchema)]
+        #[allow(dead_code)]
+        struct Event {
+            title: String,
+            end: String,
+            attendees: Vec<String>,
+        }
+
+        let keys = |schema: &Value| -> Vec<String> {
+            let properties = schema["properties"].as_object().unwrap();
+            properties.keys().cloned().collect()
+        };
+        // Tests run with `preserve_order`, so fields keep the order of declaration
+        let schema = SchemaOptions::new().schema_for::<Event>();
+        assert_eq!(keys(&schema), ["title", "end", "attendees"]);
+        let schema = SchemaOptions::new()
+            .with_sort_properties(true)
+            .schema_for::<Event>();
+        assert_eq!(keys(&schema), ["attendees", "end", "title"]);
+        assert!(schema
+            .to_string()
+            .starts_with(r#"{"properties":{"attendees""#));
+    }
+
+    #[test]
+    fn test_sanitize_gemini() {
+        let schema = sanitize(
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": "Params",
+                "type": "object",
+                "properties": {
+                    "title": {"$ref": "#/$defs/Inner", "description": "Inner value"},
+                    "count": {"type": ["integer", "null"], "format": "uint32", "minimum": 0},
+                    "kind": {"const": "a"}
+                },
+                "additionalProperties": false,
+                "$defs": {
+                    "Inner": {"type": "string", "format": "email", "title": "Inner"}
+                }
+            }),
+            AdapterKind::Gemini,
+        );
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": {"type": "string", "description": "Inner value"},
+                    "count": {"type": "integer", "nullable": true, "minimum": 0},
+                    "kind": {"enum": ["a"]}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let schema = strict(json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer"},
+                "inner": {"$ref": "#/$defs/Inner"}
+            },
+            "required": ["name"]
+        }));
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["properties"]["name"], json!({"type": "string"}));
+        assert_eq!(
+            schema["properties"]["age"],
+            json!({"type": ["integer", "null"]})
+        );
+        assert_eq!(
+            schema["properties"]["inner"],
+            json!({"anyOf": [{"$ref": "#/$defs/Inner"}, {"type": "null"}]})
+        );
+        assert_eq!(schema["required"].as_array().unwrap().len(), 3);
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn test_toolbox_schema() {
+        let toolbox = crate::tool::websearch::WebSearchToolBox::new("api_key");
+        let schema: Value = serde_json::from_str(&toolbox_schema!(&toolbox).unwrap()).unwrap();
+        assert_eq!(schema[0]["name"], "web_search");
+        assert_eq!(schema[0]["parameters"]["required"], json!(["query"]));
+    }
+}
@@ -0,0 +1,185 @@
+//! Compilation of JSON Schema into GBNF grammar, used by llama.cpp to constrain generated tokens.
+
+use crate::schema::strict;
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+
+/// Rules shared by all grammars, describing JSON primitives
+const PRIMITIVES: &str = r#"ws ::= [ \t\n]*
+string ::= "\"" ( [^"\\\x7F\x00-\x1F] | "\\" ( ["\\/bfnrt] | "u" [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] [0-9a-fA-F] ) )* "\"" ws
+number ::= "-"? ( [0-9] | [1-9] [0-9]* ) ( "." [0-9]+ )? ( [eE] [-+]? [0-9]+ )? ws
+integer ::= "-"? ( [0-9] | [1-9] [0-9]* ) ws
+boolean ::= ( "true" | "false" ) ws
+null ::= "null" ws
+value ::= object | array | string | number | boolean | null
+object ::= "{" ws ( string ":" ws value ( "," ws string ":" ws value )* )? "}" ws
+array ::= "[" ws ( value ( "," ws value )* )? "]" ws
+"#;
+
+/// Compiles JSON Schema into [GBNF](https://github.com/ggml-org/llama.cpp/blob/master/grammars/README.md) grammar.
+///
+/// Grammar can be provided to llama.cpp (e.g. as `grammar` field of the `/completion` request), guaranteeing
+/// that generated output can be parsed. Schema is first converted with [strict], so all properties of objects
+/// are generated (in order of properties in the schema), and optional properties are nullable.
+///
+/// Supported keywords: `type`, `properties`, `items`, `enum`, `const`, `anyOf`, `oneOf` and `$ref` to `$defs`.
+/// Other keywords (e.g. `minimum` or `pattern`) are ignored.
+pub fn to_gbnf(schema: &Value) -> Result<String> {
+    let schema = strict(schema.clone());
+    let definitions = ["$defs", "definitions"]
+        .iter()
+        .filter_map(|key| schema.get(*key).and_then(Value::as_object))
+        .fold(Map::new(), |mut definitions, defs| {
+            definitions.extend(defs.clone());
+            definitions
+        });
+    let mut compiler = Compiler {
+        definitions,
+        rules: vec![],
+    };
+    let root = compiler.expression(&schema, "schema")?;
+    let mut grammar = format!("root ::= ws {root}\n");
+    for (name, rule) in compiler.rules {
+        grammar.push_str(&format!("{name} ::= {rule}\n"));
+    }
+    grammar.push_str(PRIMITIVES);
+    Ok(grammar)
+}
+
+struct Compiler {
+    definitions: Map<String, Value>,
+    rules: Vec<(String, String)>,
+}
+
+impl Compiler {
+    /// Returns GBNF expression matching the schema, adding rules for objects and definitions.
+    fn expression(&mut self, schema: &Value, name: &str) -> Result<String> {
+        let Value::Object(object) = schema else {
+            // `true` schema (or unsupported value) accepts anything
+            return Ok("value".to_string());
+        };
+
+        if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+            let def_name = reference.rsplit('/').next().unwrap_or_default();
+            let rule_name = format!("def-{}", rule_name(def_name));
+            if !self.rules.iter().any(|(name, _)| *name == rule_name) {
+                let definition = self
+                    .definitions
+                    .get(def_name)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("Definition {reference} not found"))?;
+                // Reserve the name first, recursive definitions refer to themselves
+                let index = self.rules.len();
+                self.rules.push((rule_name.clone(), String::new()));
+                let rule = self.expression(&definition, &rule_name)?;
+                self.rules[index].1 = rule;
+            }
+            return Ok(rule_name);
+        }
+        if let Some(value) = object.get("const") {
+            return Ok(literal(value));
+        }
+        if let Some(Value::Array(values)) = object.get("enum") {
+            let alternatives: Vec<String> = values.iter().map(literal).collect();
+            return Ok(format!("( {} )", alternatives.join(" | ")));
+        }
+        for key in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(variants)) = object.get(key) {
+                let alternatives = variants
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, variant)| self.expression(variant, &format!("{name}-{idx}")))
+                    .collect::<Result<Vec<_>>>()?;
+                return Ok(format!("( {} )", alternatives.join(" | ")));
+            }
+        }
+
+        match object.get("type") {
+            Some(Value::String(kind)) => self.typed_expression(kind, object, name),
+            Some(Value::Array(kinds)) => {
+                let alternatives = kinds
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .map(|kind| self.typed_expression(kind, object, &format!("{name}-{kind}")))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("( {} )", alternatives.join(" | ")))
+            }
+            _ => Ok("value".to_string()),
+        }
+    }
+
+    fn typed_expression(
+        &mut self,
+        kind: &str,
+        object: &Map<String, Value>,
+        name: &str,
+    ) -> Result<String> {
+        match kind {
+            "string" | "number" | "integer" | "boolean" | "null" => Ok(kind.to_string()),
+            "array" => match object.get("items") {
+                Some(items) => {
+                    let item = self.expression(items, &format!("{name}-item"))?;
+                    Ok(format!(
+                        r#"( "[" ws ( {item} ( "," ws {item} )* )? "]" ws )"#
+                    ))
+                }
+                None => Ok("array".to_string()),
+            },
+            "object" => match object.get("properties").and_then(Value::as_object) {
+                Some(properties) if !properties.is_empty() => {
+                    let members = properties
+                        .iter()
+                        .map(|(property, schema)| {
+                            let value = self
+                                .expression(schema, &format!("{name}-{}", rule_name(property)))?;
+                            Ok(format!(
+                                "{} \":\" ws {value}",
+                                literal(&Value::String(property.clone()))
+                            ))
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let rule_name = rule_name(name);
+                    let rule = format!(r#""{{" ws {} "}}" ws"#, members.join(r#" "," ws "#));
+                    self.rules.push((rule_name.clone(), rule));
+                    Ok(rule_name)
+                }
+                _ => Ok("object".to_string()),
+            },
+            _ => Err(anyhow!("Unsupported type {kind}")),
+        }
+    }
+}
+
+/// Returns GBNF literal matching JSON representation of the value.
+fn literal(value: &Value) -> String {
+    let json = value.to_string();
+    format!("\"{}\" ws", json.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Returns name, that can be used as GBNF rule name.
+fn rule_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_gbnf() {
+        let grammar = to_gbnf(&json!({
+            "type": "object",
+            "properties": {
+                "kind": {"enum": ["a", "b"]}
+            }
+        }))
+        .unwrap();
+        assert!(grammar.starts_with(
+            "root ::= ws schema\nschema ::= \"{\" ws \"\\\"kind\\\"\" ws \":\" ws \
+             ( ( \"\\\"a\\\"\" ws | \"\\\"b\\\"\" ws ) | null ) \"}\" ws\n"
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+//! # Text to Speech
+//!
+//! Voice assistants answer with audio, [TextToSpeech] converts text into [Audio] using
+//! [OpenAI Speech API](https://platform.openai.com/docs/api-reference/audio/createSpeech) (also
+//! servers compatible with it, see [TextToSpeech::with_url]) or
+//! [ElevenLabs](https://elevenlabs.io/docs/api-reference/text-to-speech/convert). It is enabled
+//! with `tts` feature.
+//!
+//! Configured with [AgentBuilder::with_speech](crate::agent::AgentBuilder::with_speech), answers of
+//! the agent are spoken with [Agent::speak](crate::agent::Agent::speak). Markdown formatting is
+//! removed before the text is sent, so emphasis and links are not read aloud:
+//!
+//! ```rust,no_run
+//! use agentai::speech::{AudioFormat, TextToSpeech};
+//! use agentai::Agent;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let speech = TextToSpeech::openai("<OPENAI API KEY>")
+//!     .with_voice("nova")
+//!     .with_format(AudioFormat::Opus);
+//! let mut agent = Agent::builder()
+//!     .with_system("You are a voice assistant, answer in short sentences.")
+//!     .with_speech(speech)
+//!     .build();
+//! let answer: String = agent
+//!     .run("gpt-4o", "What is the tallest mountain?", None, None, None)
+//!     .await?;
+//! let audio = agent.speak(&answer).await?;
+//! audio.save("answer.opus")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::Client;
+use serde_json::json;
+
+const OPENAI_API_URL: &str = "https://api.openai.com/v1";
+const ELEVENLABS_API_URL: &str = "https://api.elevenlabs.io/v1";
+/// Default model of OpenAI Speech API
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini-tts";
+/// Default voice of OpenAI Speech API
+pub const DEFAULT_OPENAI_VOICE: &str = "alloy";
+/// Default model of ElevenLabs API
+pub const DEFAULT_ELEVENLABS_MODEL: &str = "eleven_multilingual_v2";
+
+/// Encoding of generated audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+    /// MP3, supported by all providers
+    #[default]
+    Mp3,
+    /// Opus in Ogg container, good for streaming over the network
+    Opus,
+    /// AAC, not supported by ElevenLabs
+    Aac,
+    /// FLAC, not supported by ElevenLabs
+    Flac,
+    /// WAV, not supported by ElevenLabs
+    Wav,
+    /// Raw 16-bit little-endian samples at 24 kHz, without header
+    Pcm,
+}
+
+impl AudioFormat {
+    /// Returns MIME type of the format.
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "audio/mpeg",
+            AudioFormat::Opus => "audio/ogg",
+            AudioFormat::Aac => "audio/aac",
+            AudioFormat::Flac => "audio/flac",
+            AudioFormat::Wav => "audio/wav",
+            AudioFormat::Pcm => "audio/pcm",
+        }
+    }
+
+    /// Name of the format used by OpenAI Speech API
+    fn openai_name(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Aac => "aac",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Pcm => "pcm",
+        }
+    }
+
+    /// Name of the format used by ElevenLabs API
+    fn elevenlabs_name(&self) -> Option<&'static str> {
+        match self {
+            AudioFormat::Mp3 => Some("mp3_44100_128"),
+            AudioFormat::Opus => Some("opus_48000_128"),
+            AudioFormat::Pcm => Some("pcm_24000"),
+            _ => None,
+        }
+    }
+}
+
+/// Audio generated by [TextToSpeech].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audio {
+    /// Encoded audio
+    pub data: Vec<u8>,
+    /// Encoding of the audio
+    pub format: AudioFormat,
+}
+
+impl Audio {
+    /// Returns base64 encoded `data:` URL of the audio, e.g. for `<audio>` element of web UI.
+    pub fn to_data_url(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.format.mime_type(),
+            STANDARD.encode(&self.data)
+        )
+    }
+
+    /// Saves the audio to the file.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, &self.data)
+            .with_context(|| format!("Unable to save audio to {}", path.display()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Provider {
+    OpenAi,
+    ElevenLabs,
+}
+
+/// Client of text to speech API, look into [module documentation](crate::speech).
+#[derive(Clone)]
+pub struct TextToSpeech {
+    client: Client,
+    provider: Provider,
+    url: String,
+    api_key: String,
+    model: String,
+    voice: String,
+    format: AudioFormat,
+}
+
+impl TextToSpeech {
+    /// Creates client of OpenAI Speech API, with [DEFAULT_OPENAI_MODEL] and [DEFAULT_OPENAI_VOICE].
+    pub fn openai(api_key: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            provider: Provider::OpenAi,
+            url: OPENAI_API_URL.to_string(),
+            api_key: api_key.to_string(),
+            model: DEFAULT_OPENAI_MODEL.to_string(),
+            voice: DEFAULT_OPENAI_VOICE.to_string(),
+            format: AudioFormat::default(),
+        }
+    }
+
+    /// Creates client of ElevenLabs API speaking with the voice, with [DEFAULT_ELEVENLABS_MODEL].
+    pub fn elevenlabs(api_key: &str, voice_id: &str) -> Self {
+        Self {
+            provider: Provider::ElevenLabs,
+            url: ELEVENLABS_API_URL.to_string(),
+            model: DEFAULT_ELEVENLABS_MODEL.to_string(),
+            voice: voice_id.to_string(),
+            ..Self::openai(api_key)
+        }
+    }
+
+    /// Sets base URL of the API, e.g. `http://localhost:8880/v1` for a local server compatible
+    /// with OpenAI Speech API.
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sets the model, e.g. `tts-1-hd` for OpenAI or `eleven_flash_v2_5` for ElevenLabs.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    /// Sets the voice, name of the voice for OpenAI or voice ID for ElevenLabs.
+    pub fn with_voice(mut self, voice: &str) -> Self {
+        self.voice = voice.to_string();
+        self
+    }
+
+    /// Sets format of generated audio, default is [AudioFormat::Mp3].
+    pub fn with_format(mut self, format: AudioFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets HTTP client, by default client shared by built-in components is used (see [crate::http]).
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Converts the text into audio, the text is sent as it is.
+    pub async fn synthesize(&self, text: &str) -> Result<Audio> {
+        if text.trim().is_empty() {
+            return Err(anyhow!("Text to speak is empty"));
+        }
+        let request = match self.provider {
+            Provider::OpenAi => self
+                .client
+                .post(format!("{}/audio/speech", self.url))
+                .bearer_auth(&self.api_key)
+                .json(&json!({
+                    "model": self.model,
+                    "voice": self.voice,
+                    "input": text,
+                    "response_format": self.format.openai_name(),
+                })),
+            Provider::ElevenLabs => {
+                let format = self.format.elevenlabs_name().ok_or_else(|| {
+                    anyhow!("ElevenLabs doesn't support {:?} format", self.format)
+                })?;
+                self.client
+                    .post(format!("{}/text-to-speech/{}", self.url, self.voice))
+                    .query(&[("output_format", format)])
+                    .header("xi-api-key", &self.api_key)
+                    .json(&json!({
+                        "text": text,
+                        "model_id": self.model,
+                    }))
+            }
+        };
+        let response = request
+            .send()
+            .await
+            .context("Text to speech request failed")?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Text to speech API returned {status}: {text}"));
+        }
+        let data = response.bytes().await?.to_vec();
+        Ok(Audio {
+            data,
+            format: self.format,
+        })
+    }
+}
+
+/// Removes Markdown formatting, that would be read aloud, from the text.
+///
+/// Headings, emphasis, inline code and list markers are removed, links are replaced with their
+/// text and fenced code blocks are skipped.
+pub(crate) fn speakable(text: &str) -> String {
+    let mut lines = vec![];
+    let mut in_code = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let line = line.trim_start_matches('#').trim_start();
+        let line = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| line.strip_prefix(marker))
+            .unwrap_or(line);
+        lines.push(strip_links(line).replace(['*', '`'], "").replace("~~", ""));
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Replaces `[text](url)` links with their text.
+fn strip_links(line: &str) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('[') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        // Text of the link ends with the first `]`, followed by URL in parentheses
+        let link = rest
+            .find(']')
+            .filter(|close| rest[close + 1..].starts_with('('))
+            .and_then(|close| Some((close, close + rest[close..].find(')')?)));
+        match link {
+            Some((close, end)) => {
+                result.push_str(&rest[1..close]);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('[');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speakable() {
+        let answer = "## Mount Everest\n\nThe **tallest** mountain is `Everest`, see \
+            [Wikipedia](https://en.wikipedia.org/wiki/Mount_Everest).\n\n- 8849 m\n\
+            ```\nheight = 8849\n```\nDone [x] ~~maybe~~";
+        assert_eq!(
+            speakable(answer),
+            "Mount Everest\n\nThe tallest mountain is Everest, see Wikipedia.\n\n8849 m\nDone [x] maybe"
+        );
+
+        let speech = TextToSpeech::elevenlabs("key", "voice").with_format(AudioFormat::Wav);
+        assert_eq!(speech.url, ELEVENLABS_API_URL);
+        assert_eq!(speech.model, DEFAULT_ELEVENLABS_MODEL);
+        assert!(AudioFormat::Wav.elevenlabs_name().is_none());
+        let audio = Audio {
+            data: b"ID3".to_vec(),
+            format: AudioFormat::Mp3,
+        };
+        assert_eq!(audio.to_data_url(), "data:audio/mpeg;base64,SUQz");
+    }
+}
@@ -0,0 +1,147 @@
+//! # Server-Sent Events
+//!
+//! Helpers streaming [AgentEvent]s to web frontends using [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html).
+//!
+//! Every event is sent as a frame with `event` field set to the `type` of the event and `data` field
+//! containing its JSON representation (look into [AgentEvent] for the schema):
+//!
+//! ```text
+//! event: tool_call
+//! data: {"type":"tool_call","call_id":"call_1","name":"web_search","arguments":{"query":"rust"}}
+//!
+//! ```
+//!
+//! When the run finishes, `error` frame (`{"type":"error","message":"..."}`) is sent if the run failed,
+//! followed by `done` frame, after which the stream ends.
+//!
+//! [channel] returns [SseSender], that provides [EventHandler] for the agent, and [SseStream], that
+//! can be used directly as a body of the response, e.g. in `axum`:
+//!
+//! ```rust,ignore
+//! use axum::{body::Body, http::header, response::Response};
+//!
+//! async fn chat(prompt: String) -> Response {
+//!     let (sender, stream) = agentai::sse::channel();
+//!     let mut agent = Agent::new("You are a useful assistant");
+//!     agent.replace_event_handler(Some(sender.event_handler()));
+//!     // Run agent in background, events are delivered to the stream while it is running
+//!     run_in_background(async move {
+//!         let result = agent.run::<String>("gpt-4o", &prompt, None, None, None).await;
+//!         sender.finish(result.map(|_| ()));
+//!     });
+//!     Response::builder()
+//!         .header(header::CONTENT_TYPE, "text/event-stream")
+//!         .header(header::CACHE_CONTROL, "no-cache")
+//!         .body(Body::from_stream(stream))
+//!         .unwrap()
+//! }
+//! ```
+//!
+//! In `actix-web` use `HttpResponse::Ok().content_type("text/event-stream").streaming(stream.map_ok(Bytes::from))`.
+
+use crate::event::{AgentEvent, EventHandler};
+use futures::Stream;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Formats event as Server-Sent Events frame.
+pub fn event_frame(event: &AgentEvent) -> String {
+    let data = serde_json::to_value(event).unwrap_or(Value::Null);
+    let name = data["type"].as_str().unwrap_or("message").to_string();
+    frame(&name, &data)
+}
+
+/// Formats error as Server-Sent Events frame.
+pub fn error_frame(message: &str) -> String {
+    frame("error", &json!({"type": "error", "message": message}))
+}
+
+/// Formats frame marking the end of the run.
+pub fn done_frame() -> String {
+    frame("done", &json!({"type": "done"}))
+}
+
+pub(crate) fn frame(name: &str, data: &Value) -> String {
+    // Serialized JSON doesn't contain new lines, so single data field is enough
+    format!("event: {name}\ndata: {data}\n\n")
+}
+
+/// Creates connected [SseSender] and [SseStream].
+pub fn channel() -> (SseSender, SseStream) {
+    let (sender, receiver) = unbounded_channel();
+    (SseSender { sender }, SseStream { receiver })
+}
+
+/// Sending side of the Server-Sent Events channel, look into [channel].
+#[derive(Clone)]
+pub struct SseSender {
+    sender: UnboundedSender<String>,
+}
+
+impl SseSender {
+    /// Returns event handler, that forwards all events of the agent to the stream.
+    pub fn event_handler(&self) -> EventHandler {
+        let sender = self.sender.clone();
+        Arc::new(move |event: &AgentEvent| {
+            // Client can disconnect at any time, there is nothing to do in such case
+            let _ = sender.send(event_frame(event));
+        })
+    }
+
+    /// Sends frame formatted with [frame].
+    pub(crate) fn send_frame(&self, frame: String) {
+        let _ = self.sender.send(frame);
+    }
+
+    /// Sends error frame.
+    pub fn send_error(&self, message: &str) {
+        let _ = self.sender.send(error_frame(message));
+    }
+
+    /// Finishes the stream, sending error frame when the run failed, and done frame.
+    ///
+    /// Stream ends when all senders and event handlers are dropped.
+    pub fn finish<E: std::fmt::Display>(self, result: Result<(), E>) {
+        if let Err(err) = result {
+            self.send_error(&err.to_string());
+        }
+        let _ = self.sender.send(done_frame());
+    }
+}
+
+/// Stream of Server-Sent Events frames, look into [channel].
+pub struct SseStream {
+    receiver: UnboundedReceiver<String>,
+}
+
+impl Stream for SseStream {
+    type Item = Result<String, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx).map(|frame| frame.map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_frame() {
+        let frame = event_frame(&AgentEvent::Answer {
+            content: "Hello\nWorld".to_string(),
+        });
+        let data = frame
+            .strip_prefix("event: answer\ndata: ")
+            .and_then(|data| data.strip_suffix("\n\n"))
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(data).unwrap(),
+            json!({"type": "answer", "content": "Hello\nWorld"})
+        );
+    }
+}
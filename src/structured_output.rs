@@ -2,3 +2,46 @@
 //! # Structured Output
 //!
 //! TODO Add information about structured output
+//!
+//! ## Output types
+//!
+//! Type of the answer is chosen with the type parameter of [Agent::run](crate::agent::Agent::run),
+//! any type implementing [AgentOutput](crate::agent::AgentOutput) can be used. It is implemented
+//! for all types implementing `DeserializeOwned` and `JsonSchema`:
+//!
+//! - `String` and other types with schema of a plain string (e.g. `struct Name(String)`) are
+//!   answered with free text, returned without parsing,
+//! - `serde_json::Value` and other types accepting any JSON are requested as JSON without schema
+//!   (JSON mode of the provider), for exploratory use when defining the type up front is premature,
+//! - other types are requested as JSON matching their schema, e.g. structs, enums, numbers or `Vec<u8>`.
+//!
+//! ## Modes
+//!
+//! Providers differ in how (and if) they support structured output. The way schema of the output type
+//! is provided to the model is selected with [StructuredOutputMode](crate::agent::StructuredOutputMode),
+//! using [AgentBuilder::with_structured_output](crate::agent::AgentBuilder::with_structured_output):
+//!
+//! - `Native` (default) -- schema is sent as the response format,
+//! - `Strict` -- schema is converted for strict structured outputs, for providers that constrain decoding
+//!   to the schema (OpenAI, llama.cpp server), guaranteeing parseable output,
+//! - `Prompt` -- schema is included in the instructions, for providers without structured output support.
+//!
+//! When you call llama.cpp directly, schema can be compiled into GBNF grammar with [crate::schema::to_gbnf].
+//!
+//! ## Lists
+//!
+//! Many providers reject schemas with array at the top level. Use [Agent::run_list](crate::agent::Agent::run_list)
+//! to get `Vec<T>`, the model is asked for an object with `items` field that is unwrapped from the answer.
+//!
+//! ## Parsing
+//!
+//! Models often wrap JSON in markdown fences or add a comment around it. By default such answers are
+//! cleaned up before parsing, JSON5 style syntax (comments, trailing commas, single quotes) can be
+//! accepted too, look into [OutputParsing](crate::agent::OutputParsing).
+//!
+//! ## Post-processing
+//!
+//! Parsed answers can be cleaned up before they are converted into the output type, e.g. with
+//! whitespace normalized, relative URLs resolved or numbers clamped into a range. Post-processors
+//! are registered once per agent with [AgentBuilder::with_post_processor](crate::agent::AgentBuilder::with_post_processor),
+//! look into [PostProcessor](crate::agent::PostProcessor).
@@ -0,0 +1,224 @@
+//! # System Prompt Library
+//!
+//! Writing a good system prompt from a blank string is hard, so this module provides building
+//! blocks for it: [Persona] presets describing the role of the agent, and composable
+//! [PromptSection]s with common rules (tool use etiquette, citing sources, refusals, JSON discipline).
+//!
+//! Parts are layered with [SystemPrompt], persona goes first, then sections in order they were
+//! added. Custom text can be mixed with presets, so presets can be used as a starting point.
+//!
+//! Agents render the prompt with [PromptProfile] of the model, e.g. with every part wrapped in
+//! XML tags for Anthropic models, see [AgentBuilder::with_prompt_profile](crate::agent::AgentBuilder::with_prompt_profile).
+//!
+//! ```rust
+//! # use agentai::Agent;
+//! # use agentai::system_prompt::{Persona, PromptSection, SystemPrompt};
+//! let prompt = SystemPrompt::new()
+//!     .with_persona(Persona::Researcher)
+//!     .with_section(PromptSection::ToolUse)
+//!     .with_section(PromptSection::Citations)
+//!     .with_text("Answer in at most three paragraphs.");
+//! let agent = Agent::builder().with_system_prompt(prompt).build();
+//! ```
+
+use crate::agent::PromptProfile;
+use std::fmt::{Display, Formatter};
+
+/// Role of the agent, placed at the beginning of the [SystemPrompt].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Persona {
+    /// General purpose assistant
+    Assistant,
+    /// Gathers information from many sources and verifies it
+    Researcher,
+    /// Writes and reviews source code
+    Programmer,
+    /// Analyses data and draws conclusions
+    Analyst,
+    /// Helps customers with their problems
+    CustomerSupport,
+    /// Persona described by the user
+    Custom(String),
+}
+
+impl Persona {
+    /// Returns text of the persona.
+    pub fn text(&self) -> &str {
+        match self {
+            Persona::Assistant => {
+                "You are a helpful assistant. Answer questions accurately and concisely, \
+                 and ask for clarification when the request is ambiguous."
+            }
+            Persona::Researcher => {
+                "You are a meticulous researcher. Gather information from multiple sources, \
+                 cross-check facts and clearly separate established facts from speculation."
+            }
+            Persona::Programmer => {
+                "You are an experienced software engineer. Write correct, idiomatic and \
+                 well-tested code, and explain trade-offs of your solutions."
+            }
+            Persona::Analyst => {
+                "You are a data analyst. Base your conclusions on the data, state your \
+                 assumptions and quantify uncertainty where possible."
+            }
+            Persona::CustomerSupport => {
+                "You are a friendly customer support agent. Be polite and patient, solve the \
+                 problem of the customer step by step and never promise what you can't deliver."
+            }
+            Persona::Custom(text) => text,
+        }
+    }
+}
+
+/// Reusable part of the [SystemPrompt] with rules for the model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptSection {
+    /// When and how to call tools
+    ToolUse,
+    /// Requirement to cite sources of information
+    Citations,
+    /// How to refuse requests that can't or shouldn't be fulfilled
+    Refusal,
+    /// Rules for answers in JSON format
+    JsonDiscipline,
+    /// Section written by the user
+    Custom(String),
+}
+
+impl PromptSection {
+    /// Returns text of the section.
+    pub fn text(&self) -> &str {
+        match self {
+            PromptSection::ToolUse => {
+                "Use tools when they provide information you don't have or perform actions you \
+                 were asked for. Don't call tools needlessly and never call the same tool with the \
+                 same arguments twice. Don't make up tool results, when a tool fails explain the \
+                 problem instead of guessing."
+            }
+            PromptSection::Citations => {
+                "Support every factual claim with its source. Cite only sources you actually \
+                 used, and say so when no source supports the claim."
+            }
+            PromptSection::Refusal => {
+                "If a request is harmful, illegal or outside of your responsibilities, politely \
+                 refuse and briefly explain why. If you don't know the answer, say so instead of \
+                 making it up."
+            }
+            PromptSection::JsonDiscipline => {
+                "When asked for JSON, respond only with valid JSON matching the requested schema, \
+                 without comments, markdown code blocks or additional text."
+            }
+            PromptSection::Custom(text) => text,
+        }
+    }
+}
+
+/// System prompt composed of a [Persona] and [PromptSection]s, look into [module documentation](crate::system_prompt) for details.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemPrompt {
+    persona: Option<Persona>,
+    sections: Vec<PromptSection>,
+}
+
+impl SystemPrompt {
+    /// Creates empty prompt.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets persona of the agent, replacing the previous one.
+    pub fn with_persona(mut self, persona: Persona) -> Self {
+        self.persona = Some(persona);
+        self
+    }
+
+    /// Adds section to the prompt, sections that were already added are ignored.
+    pub fn with_section(mut self, section: PromptSection) -> Self {
+        if !self.sections.contains(&section) {
+            self.sections.push(section);
+        }
+        self
+    }
+
+    /// Adds custom text to the prompt.
+    pub fn with_text(self, text: &str) -> Self {
+        self.with_section(PromptSection::Custom(text.to_string()))
+    }
+}
+
+impl PromptSection {
+    /// Returns title of the section, used by [PromptProfile]s with headers or tags.
+    fn title(&self) -> &str {
+        match self {
+            PromptSection::ToolUse => "Tool use",
+            PromptSection::Citations => "Citations",
+            PromptSection::Refusal => "Refusal",
+            PromptSection::JsonDiscipline => "JSON",
+            PromptSection::Custom(_) => "Instructions",
+        }
+    }
+}
+
+impl SystemPrompt {
+    /// Renders the prompt with the layout of the profile, see [PromptProfile].
+    ///
+    /// [Display] of the prompt uses [PromptProfile::Plain].
+    pub fn render(&self, profile: PromptProfile) -> String {
+        let persona = self.persona.iter().map(|persona| {
+            let is_preset = !matches!(persona, Persona::Custom(_));
+            ("Role", persona.text(), is_preset)
+        });
+        let sections = self.sections.iter().map(|section| {
+            let is_preset = !matches!(section, PromptSection::Custom(_));
+            (section.title(), section.text(), is_preset)
+        });
+        let parts = persona
+            .chain(sections)
+            .map(|(title, text, is_preset)| match profile {
+                PromptProfile::Terse if is_preset => (title, first_sentence(text)),
+                _ => (title, text),
+            })
+            .collect::<Vec<_>>();
+        profile.render(&parts)
+    }
+}
+
+/// Returns the first sentence of the text.
+fn first_sentence(text: &str) -> &str {
+    text.find(". ").map(|idx| &text[..=idx]).unwrap_or(text)
+}
+
+impl Display for SystemPrompt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(PromptProfile::Plain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_prompt() {
+        let prompt = SystemPrompt::new()
+            .with_section(PromptSection::Refusal)
+            .with_text("Be brief.")
+            .with_section(PromptSection::Refusal)
+            .with_persona(Persona::Custom("You are a pirate.".to_string()));
+        assert_eq!(
+            prompt.to_string(),
+            format!(
+                "You are a pirate.\n\n{}\n\nBe brief.",
+                PromptSection::Refusal.text()
+            )
+        );
+        assert_eq!(SystemPrompt::new().to_string(), "");
+        assert_eq!(
+            SystemPrompt::new()
+                .with_persona(Persona::Assistant)
+                .with_text("Be brief.")
+                .render(PromptProfile::Terse),
+            "You are a helpful assistant.\nBe brief."
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! # Self-Management Tools
+//!
+//! [AgentSelfToolBox] lets the model manage its own memory explicitly, instead of relying on what
+//! fits into the conversation:
+//! - `remember` stores a fact, remembered facts are added to instructions of every following run,
+//! - `forget` removes facts about a topic, e.g. when the user asks for it or a fact is outdated,
+//! - `compact_history` replaces older part of the conversation with its summary, it is handled by
+//!   the [Agent] itself, like [Agent::compact],
+//! - `set_reminder` schedules a follow-up, it is available only when reminders are handled with
+//!   [AgentSelfToolBox::with_reminder_handler].
+//!
+//! Toolbox is opt-in, it has to be passed to runs like any other toolbox. Facts are kept in memory,
+//! or in a JSON file when opened with [AgentSelfToolBox::open]. Clones of the toolbox share the same
+//! facts.
+//!
+//! ```rust,no_run
+//! use agentai::tool::agent_self::AgentSelfToolBox;
+//! use agentai::Agent;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let toolbox = AgentSelfToolBox::open("facts.json")?
+//!     .with_reminder_handler(|reminder| println!("Follow up at {:?}: {}", reminder.due, reminder.note));
+//! let mut agent = Agent::builder()
+//!     .with_system("You are personal assistant, remember preferences of the user.")
+//!     .with_utility_model("gpt-4o-mini")
+//!     .build();
+//! let answer: String = agent
+//!     .run("gpt-4o", "I'm vegetarian, remind me about lunch in 2h.", Some(Arc::new(toolbox)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [Agent]: crate::agent::Agent
+//! [Agent::compact]: crate::agent::Agent::compact
+
+use crate::agent::{compact_history_tool, COMPACT_HISTORY_TOOL};
+use crate::tool::{Capability, Tool, ToolBox, ToolError};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// Follow-up scheduled by the model with `set_reminder` tool, or by the application (see
+/// [Scheduler](crate::schedule::Scheduler) with `scheduler` feature).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reminder {
+    /// Time when the reminder is due
+    pub due: SystemTime,
+    /// What should be done, e.g. prompt of the follow-up run
+    pub note: String,
+    /// Data of the application needed by the follow-up, e.g. identifier of the chat, `null` for
+    /// reminders set by the model
+    pub payload: Value,
+}
+
+impl Reminder {
+    /// Creates reminder due after the delay.
+    ///
+    /// # Panics
+    /// Panics when the due time can't be represented, use [Reminder::checked_after] for delays
+    /// that are not under control of the application.
+    pub fn after(delay: Duration, note: &str) -> Self {
+        Self::checked_after(delay, note).expect("reminder delay overflows system time")
+    }
+
+    /// Creates reminder due after the delay, returns `None` when the due time can't be
+    /// represented.
+    pub fn checked_after(delay: Duration, note: &str) -> Option<Self> {
+        Some(Self {
+            due: SystemTime::now().checked_add(delay)?,
+            note: note.to_string(),
+            payload: Value::Null,
+        })
+    }
+
+    /// Sets data of the application passed to the follow-up.
+    pub fn with_payload(mut self, payload: Value) -> Self {
+        self.payload = payload;
+        self
+    }
+}
+
+/// Callback receiving reminders set by the model, see [AgentSelfToolBox::with_reminder_handler].
+pub type ReminderHandler = Arc<dyn Fn(Reminder) + Send + Sync>;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Facts {
+    facts: Vec<String>,
+}
+
+/// Toolbox letting the agent manage its own memory, look into [module documentation](crate::tool::agent_self).
+#[derive(Clone, Default)]
+pub struct AgentSelfToolBox {
+    facts: Arc<RwLock<Facts>>,
+    path: Option<PathBuf>,
+    reminder_handler: Option<ReminderHandler>,
+}
+
+impl AgentSelfToolBox {
+    /// Creates toolbox with facts kept only in memory of the process.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens facts stored in the JSON file, file is created when the first fact is remembered.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let facts = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Invalid facts file {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Facts::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            facts: Arc::new(RwLock::new(facts)),
+            path: Some(path),
+            reminder_handler: None,
+        })
+    }
+
+    /// Exposes `set_reminder` tool, reminders set by the model are passed to the handler.
+    pub fn with_reminder_handler(
+        mut self,
+        handler: impl Fn(Reminder) + Send + Sync + 'static,
+    ) -> Self {
+        self.reminder_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Returns remembered facts, in the order they were remembered.
+    pub fn facts(&self) -> Vec<String> {
+        let facts = self.facts.read().unwrap_or_else(|e| e.into_inner());
+        facts.facts.clone()
+    }
+
+    /// Remembers the fact, returns `false` when it was already known.
+    pub fn remember(&self, fact: &str) -> Result<bool> {
+        let fact = fact.trim();
+        if fact.is_empty() {
+            return Err(anyhow!("Fact can't be empty"));
+        }
+        self.update(|facts| {
+            if facts.iter().any(|known| known.eq_ignore_ascii_case(fact)) {
+                return false;
+            }
+            facts.push(fact.to_string());
+            true
+        })
+    }
+
+    /// Removes facts mentioning the topic (case insensitive), returns number of removed facts.
+    pub fn forget(&self, topic: &str) -> Result<usize> {
+        let topic = topic.trim().to_lowercase();
+        if topic.is_empty() {
+            return Err(anyhow!("Topic can't be empty"));
+        }
+        self.update(|facts| {
+            let count = facts.len();
+            facts.retain(|fact| !fact.to_lowercase().contains(&topic));
+            count - facts.len()
+        })
+    }
+
+    /// Modifies facts and stores them in the file, when the toolbox was opened from one.
+    fn update<T>(&self, update: impl FnOnce(&mut Vec<String>) -> T) -> Result<T> {
+        let mut facts = self.facts.write().unwrap_or_else(|e| e.into_inner());
+        let result = update(&mut facts.facts);
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_string_pretty(&*facts)?)
+                .with_context(|| format!("Can't write facts file {}", path.display()))?;
+        }
+        Ok(result)
+    }
+
+    /// Deserializes arguments of the tool, errors contain schema of the tool for the model.
+    fn parse_arguments<T: for<'de> Deserialize<'de>>(
+        &self,
+        tool: &str,
+        arguments: Value,
+    ) -> Result<T, ToolError> {
+        serde_json::from_value(arguments.clone()).map_err(|e| ToolError::InvalidArguments {
+            tool: tool.to_string(),
+            error: e.to_string(),
+            arguments: Box::new(arguments),
+            schema: Box::new(
+                self.tools_definitions()
+                    .ok()
+                    .and_then(|tools| tools.into_iter().find(|t| t.name == tool))
+                    .and_then(|t| t.schema)
+                    .unwrap_or_default(),
+            ),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RememberArguments {
+    fact: String,
+}
+
+#[derive(Deserialize)]
+struct ForgetArguments {
+    topic: String,
+}
+
+#[derive(Deserialize)]
+struct SetReminderArguments {
+    when: String,
+    note: String,
+}
+
+/// Parses delay like `30m`, `2 hours` or `1d`, number without unit is in minutes.
+fn parse_delay(when: &str) -> Option<Duration> {
+    let when = when.trim().to_lowercase();
+    let split = when
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(when.len());
+    let (amount, unit) = when.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let seconds = match unit.trim() {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1,
+        "" | "m" | "min" | "mins" | "minute" | "minutes" => 60,
+        "h" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => 24 * 60 * 60,
+        "w" | "week" | "weeks" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(amount.checked_mul(seconds)?))
+}
+
+#[async_trait::async_trait]
+impl ToolBox for AgentSelfToolBox {
+    fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+        let mut tools = vec![
+            Tool {
+                name: "remember".to_string(),
+                description: Some(
+                    "Remembers a fact for following conversations, e.g. preference of the user. \
+                     Remembered facts are always available in your instructions."
+                        .to_string(),
+                ),
+                schema: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "fact": {
+                            "type": "string",
+                            "description": "Short self-contained fact, e.g. `User is vegetarian`"
+                        }
+                    },
+                    "required": ["fact"]
+                })),
+            },
+            Tool {
+                name: "forget".to_string(),
+                description: Some(
+                    "Forgets all remembered facts mentioning the topic, use it when a fact is \
+                     outdated or the user asks to forget it."
+                        .to_string(),
+                ),
+                schema: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "topic": {
+                            "type": "string",
+                            "description": "Word or phrase contained in forgotten facts"
+                        }
+                    },
+                    "required": ["topic"]
+                })),
+            },
+            compact_history_tool(),
+        ];
+        if self.reminder_handler.is_some() {
+            tools.push(Tool {
+                name: "set_reminder".to_string(),
+                description: Some(
+                    "Schedules a follow-up, you will be reminded with the note when it is due."
+                        .to_string(),
+                ),
+                schema: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "when": {
+                            "type": "string",
+                            "description": "Delay until the reminder, e.g. `30m`, `2h` or `1d`"
+                        },
+                        "note": {
+                            "type": "string",
+                            "description": "What should be done when the reminder is due"
+                        }
+                    },
+                    "required": ["when", "note"]
+                })),
+            });
+        }
+        Ok(tools)
+    }
+
+    async fn call_tool(&self, tool_name: String, arguments: Value) -> Result<String, ToolError> {
+        match tool_name.as_str() {
+            "remember" => {
+                let RememberArguments { fact } = self.parse_arguments(&tool_name, arguments)?;
+                match self.remember(&fact)? {
+                    true => Ok("Remembered".to_string()),
+                    false => Ok("Already known".to_string()),
+                }
+            }
+            "forget" => {
+                let ForgetArguments { topic } = self.parse_arguments(&tool_name, arguments)?;
+                match self.forget(&topic)? {
+                    0 => Ok(format!("Nothing is remembered about {topic}")),
+                    count => Ok(format!("Forgot {count} facts")),
+                }
+            }
+            "set_reminder" => {
+                let handler = self
+                    .reminder_handler
+                    .as_ref()
+                    .ok_or_else(|| ToolError::NoToolFound(tool_name.clone()))?;
+                let SetReminderArguments { when, note } =
+                    self.parse_arguments(&tool_name, arguments)?;
+                let reminder = parse_delay(&when)
+                    .and_then(|delay| Reminder::checked_after(delay, &note))
+                    .ok_or_else(|| {
+                        ToolError::Other(anyhow!(
+                            "Invalid delay `{when}`, use e.g. `30m`, `2h` or `1d`"
+                        ))
+                    })?;
+                handler(reminder);
+                Ok(format!("Reminder set, due in {when}"))
+            }
+            // History is owned by the agent, which handles the tool before calling the toolbox
+            COMPACT_HISTORY_TOOL => Err(ToolError::Other(anyhow!(
+                "History can be compacted only by the agent running the toolbox"
+            ))),
+            _ => Err(ToolError::NoToolFound(tool_name)),
+        }
+    }
+
+    fn instructions(&self) -> Option<String> {
+        let facts = self.facts();
+        (!facts.is_empty()).then(|| {
+            let facts = facts
+                .iter()
+                .map(|fact| format!("- {fact}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("Facts you remembered with `remember` tool:\n{facts}")
+        })
+    }
+
+    fn tool_capabilities(&self, tool_name: &str) -> Vec<Capability> {
+        match tool_name {
+            "remember" | "forget" if self.path.is_some() => vec![Capability::WriteFs],
+            _ => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn test_agent_self_toolbox() {
+        let reminders = Arc::new(Mutex::new(vec![]));
+        let sink = reminders.clone();
+        let toolbox = AgentSelfToolBox::new()
+            .with_reminder_handler(move |reminder| sink.lock().unwrap().push(reminder));
+        assert!(toolbox.instructions().is_none());
+
+        let remember =
+            |fact: &str| toolbox.call_tool("remember".to_string(), json!({ "fact": fact }));
+        assert_eq!(remember("User is vegetarian").await.unwrap(), "Remembered");
+        assert_eq!(remember("User lives in Oslo").await.unwrap(), "Remembered");
+        assert_eq!(
+            remember("user is Vegetarian").await.unwrap(),
+            "Already known"
+        );
+        assert_eq!(
+            toolbox.instructions().unwrap(),
+            "Facts you remembered with `remember` tool:\n- User is vegetarian\n- User lives in Oslo"
+        );
+        let result = toolbox
+            .call_tool("forget".to_string(), json!({ "topic": "OSLO" }))
+            .await;
+        assert_eq!(result.unwrap(), "Forgot 1 facts");
+        assert_eq!(toolbox.facts(), ["User is vegetarian"]);
+
+        let result = toolbox
+            .call_tool(
+                "set_reminder".to_string(),
+                json!({ "when": "2 hours", "note": "Ask about lunch" }),
+            )
+            .await;
+        assert!(result.is_ok());
+        let reminder = reminders.lock().unwrap().pop().unwrap();
+        assert_eq!(reminder.note, "Ask about lunch");
+        assert!(reminder.due > SystemTime::now() + Duration::from_secs(7000));
+        assert_eq!(parse_delay("30m"), Some(Duration::from_secs(1800)));
+        assert_eq!(parse_delay("1d"), Some(Duration::from_secs(86400)));
+        assert_eq!(parse_delay("soon"), None);
+        let result = toolbox
+            .call_tool(
+                "set_reminder".to_string(),
+                json!({ "when": "20000000000000 weeks", "note": "Never" }),
+            )
+            .await;
+        assert!(result.unwrap_err().to_string().starts_with("Invalid delay"));
+    }
+}
@@ -0,0 +1,404 @@
+//! # Calendar Tools
+//!
+//! [CalendarToolBox] powers scheduling assistants with `list_events`, `find_free_slots` and
+//! `create_event` tools working with a single calendar. It is enabled with `calendar` feature.
+//!
+//! Calendars are accessed through [CalendarBackend], implemented for:
+//! - [GoogleCalendar]: Google Calendar API, authorized with OAuth 2.0 access token with
+//!   `https://www.googleapis.com/auth/calendar.events` scope,
+//! - [CalDav]: CalDAV servers (Nextcloud, iCloud, Fastmail, Radicale, ...), authorized with
+//!   username and (app) password or with bearer token.
+//!
+//! Obtaining and refreshing OAuth tokens is left to the application. Rejected credentials are
+//! reported as [ToolError::Unauthorized], which stops the run, so the application can refresh
+//! the token and create the toolbox again.
+//!
+//! `create_event` is the only tool modifying the calendar. Like all tools it is subject to the
+//! [approval policy](crate::agent::AgentBuilder::with_approval) of the agent, so the user can
+//! confirm new events:
+//!
+//! ```rust,no_run
+//! use agentai::tool::calendar::{CalendarToolBox, GoogleCalendar};
+//! use agentai::{Agent, Approval};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let calendar = CalendarToolBox::new(GoogleCalendar::new("<ACCESS TOKEN>"))
+//!     .with_utc_offset(chrono::FixedOffset::east_opt(3600).unwrap())
+//!     .with_working_hours(9, 17);
+//! let mut agent = Agent::builder()
+//!     .with_system("You are assistant scheduling meetings of the user.")
+//!     .with_approval(|call| match call.fn_name.as_str() {
+//!         "create_event" => Approval::Deny("Events are created only after confirmation".to_string()),
+//!         _ => Approval::Allow,
+//!     })
+//!     .build();
+//! let answer: String = agent
+//!     .run("gpt-4o", "Find an hour for code review this week.", Some(Arc::new(calendar)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod caldav;
+mod google;
+
+pub use caldav::CalDav;
+pub use google::GoogleCalendar;
+
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
+use anyhow::anyhow;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use std::sync::Arc;
+
+/// Range of `list_events` and `find_free_slots` when the model doesn't provide its end
+const DEFAULT_RANGE_DAYS: i64 = 7;
+
+/// Event in the calendar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// Identifier assigned by the calendar, empty for events which are not created yet
+    pub id: String,
+    /// Title of the event
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    /// End of the event, exclusive
+    pub end: DateTime<Utc>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Calendar used by [CalendarToolBox].
+#[async_trait::async_trait]
+pub trait CalendarBackend: Send + Sync {
+    /// Returns events overlapping with the range, ordered by start. Recurring events are
+    /// expanded into single occurrences.
+    async fn list_events(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<CalendarEvent>, ToolError>;
+
+    /// Creates the event, returns it with identifier assigned by the calendar.
+    async fn create_event(&self, event: CalendarEvent) -> Result<CalendarEvent, ToolError>;
+}
+
+/// Toolbox working with a calendar, look into [module documentation](crate::tool::calendar).
+pub struct CalendarToolBox {
+    backend: Arc<dyn CalendarBackend>,
+    /// Offset of the user's time zone, times are presented in it
+    offset: FixedOffset,
+    working_hours: (NaiveTime, NaiveTime),
+    weekends: bool,
+}
+
+#[toolbox]
+impl CalendarToolBox {
+    /// Creates toolbox for the calendar, times are in UTC and working hours are 9:00 - 17:00 on weekdays.
+    pub fn new(backend: impl CalendarBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            offset: FixedOffset::east_opt(0).unwrap(),
+            working_hours: (hour(9), hour(17)),
+            weekends: false,
+        }
+    }
+
+    /// Sets offset of the user's time zone, used for presented times, times without offset
+    /// provided by the model and working hours.
+    pub fn with_utc_offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets working hours (e.g. `9, 17`), `find_free_slots` returns only slots within them.
+    pub fn with_working_hours(mut self, start: u32, end: u32) -> Self {
+        self.working_hours = (hour(start), hour(end));
+        self
+    }
+
+    /// Lets `find_free_slots` return slots on Saturdays and Sundays.
+    pub fn with_weekends(mut self) -> Self {
+        self.weekends = true;
+        self
+    }
+
+    /// Lists events in the calendar between start and end.
+    #[tool(capabilities = [Capability::Network])]
+    async fn list_events(
+        &self,
+        #[doc = "Start of the range in RFC 3339 format, e.g. 2025-03-01T09:00:00+01:00, current time by default"]
+        start: Option<String>,
+        #[doc = "End of the range in RFC 3339 format, 7 days after start by default"] end: Option<
+            String,
+        >,
+    ) -> Result<String, ToolError> {
+        let (start, end) = self.range(start, end)?;
+        let events = self.backend.list_events(start, end).await?;
+        let mut lines = vec![format!(
+            "Events from {} to {}:",
+            self.format_time(start),
+            self.format_time(end)
+        )];
+        if events.is_empty() {
+            lines.push("No events".to_string());
+        }
+        for event in events {
+            let mut line = format!(
+                "- {} - {}: {}",
+                self.format_time(event.start),
+                self.format_time(event.end),
+                event.summary
+            );
+            if let Some(location) = event.location.filter(|location| !location.is_empty()) {
+                line.push_str(&format!(" ({location})"));
+            }
+            line.push_str(&format!(" [id: {}]", event.id));
+            lines.push(line);
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Finds free slots of the given duration within working hours, between start and end.
+    #[tool(capabilities = [Capability::Network])]
+    async fn find_free_slots(
+        &self,
+        #[doc = "Duration of the slot in minutes"] duration_minutes: u32,
+        #[doc = "Start of the range in RFC 3339 format, current time by default"] start: Option<
+            String,
+        >,
+        #[doc = "End of the range in RFC 3339 format, 7 days after start by default"] end: Option<
+            String,
+        >,
+    ) -> Result<String, ToolError> {
+        let (start, end) = self.range(start, end)?;
+        let duration = Duration::minutes(duration_minutes.max(1).into());
+        let events = self.backend.list_events(start, end).await?;
+        let slots = self.free_slots(start, end, duration, &events);
+        if slots.is_empty() {
+            return Ok("No free slots".to_string());
+        }
+        Ok(slots
+            .into_iter()
+            .map(|(start, end)| {
+                format!("- {} - {}", self.format_time(start), self.format_time(end))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Creates event in the calendar.
+    #[tool(capabilities = [Capability::Network])]
+    async fn create_event(
+        &self,
+        #[doc = "Title of the event"] summary: String,
+        #[doc = "Start of the event in RFC 3339 format"] start: String,
+        #[doc = "End of the event in RFC 3339 format"] end: String,
+        #[doc = "Description of the event"] description: Option<String>,
+        #[doc = "Location of the event"] location: Option<String>,
+    ) -> Result<String, ToolError> {
+        let start = self.parse_time(&start)?;
+        let end = self.parse_time(&end)?;
+        if end <= start {
+            return Err(anyhow!("End of the event must be after its start").into());
+        }
+        let event = self
+            .backend
+            .create_event(CalendarEvent {
+                id: String::new(),
+                summary,
+                start,
+                end,
+                location,
+                description,
+            })
+            .await?;
+        Ok(format!(
+            "Event created: {} - {}: {} [id: {}]",
+            self.format_time(event.start),
+            self.format_time(event.end),
+            event.summary,
+            event.id
+        ))
+    }
+
+    /// Returns range of the tool call, with defaults for missing bounds.
+    fn range(
+        &self,
+        start: Option<String>,
+        end: Option<String>,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>), ToolError> {
+        let start = match start {
+            Some(start) => self.parse_time(&start)?,
+            None => Utc::now(),
+        };
+        let end = match end {
+            Some(end) => self.parse_time(&end)?,
+            None => start + Duration::days(DEFAULT_RANGE_DAYS),
+        };
+        if end <= start {
+            return Err(anyhow!("End of the range must be after its start").into());
+        }
+        Ok((start, end))
+    }
+
+    /// Parses RFC 3339 time, times without offset are in the user's time zone.
+    fn parse_time(&self, value: &str) -> Result<DateTime<Utc>, ToolError> {
+        let value = value.trim();
+        if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+            return Ok(time.with_timezone(&Utc));
+        }
+        ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+            .and_then(|time| self.offset.from_local_datetime(&time).single())
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| {
+                anyhow!("Invalid time '{value}', expected RFC 3339 format, e.g. 2025-03-01T09:00:00+01:00").into()
+            })
+    }
+
+    fn format_time(&self, time: DateTime<Utc>) -> String {
+        time.with_timezone(&self.offset)
+            .format("%a %Y-%m-%dT%H:%M%:z")
+            .to_string()
+    }
+
+    /// Returns gaps between events, of at least `duration`, within working hours.
+    fn free_slots(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        duration: Duration,
+        events: &[CalendarEvent],
+    ) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let mut busy: Vec<_> = events
+            .iter()
+            .map(|event| (event.start, event.end))
+            .collect();
+        busy.sort();
+        let mut slots = vec![];
+        let mut day = start.with_timezone(&self.offset).date_naive();
+        while let Some(day_start) = self
+            .offset
+            .from_local_datetime(&day.and_time(self.working_hours.0))
+            .single()
+        {
+            if day_start >= end {
+                break;
+            }
+            let weekend = matches!(day.weekday(), Weekday::Sat | Weekday::Sun);
+            if !weekend || self.weekends {
+                let day_end = self
+                    .offset
+                    .from_local_datetime(&day.and_time(self.working_hours.1))
+                    .single()
+                    .map_or(end, |day_end| day_end.with_timezone(&Utc).min(end));
+                let mut free_from = day_start.with_timezone(&Utc).max(start);
+                for (busy_start, busy_end) in &busy {
+                    if *busy_end <= free_from || *busy_start >= day_end {
+                        continue;
+                    }
+                    if *busy_start - free_from >= duration {
+                        slots.push((free_from, *busy_start));
+                    }
+                    free_from = free_from.max(*busy_end);
+                }
+                if day_end - free_from >= duration {
+                    slots.push((free_from, day_end));
+                }
+            }
+            let Some(next) = day.succ_opt() else {
+                break;
+            };
+            day = next;
+        }
+        slots
+    }
+}
+
+fn hour(hour: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour.min(23), 0, 0).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fixed(Vec<CalendarEvent>);
+
+    #[async_trait::async_trait]
+    impl CalendarBackend for Fixed {
+        async fn list_events(
+            &self,
+            _start: DateTime<Utc>,
+            _end: DateTime<Utc>,
+        ) -> Result<Vec<CalendarEvent>, ToolError> {
+            Ok(self.0.clone())
+        }
+
+        async fn create_event(&self, event: CalendarEvent) -> Result<CalendarEvent, ToolError> {
+            Ok(CalendarEvent {
+                id: "new".to_string(),
+                ..event
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_free_slots() {
+        let calendar = CalendarToolBox::new(Fixed(vec![]))
+            .with_utc_offset(FixedOffset::east_opt(3600).unwrap())
+            .with_working_hours(9, 17);
+        let event = |start: &str, end: &str| CalendarEvent {
+            id: "1".to_string(),
+            summary: "Standup".to_string(),
+            start: calendar.parse_time(start).unwrap(),
+            end: calendar.parse_time(end).unwrap(),
+            location: None,
+            description: None,
+        };
+        let events = vec![
+            event("2025-03-07T10:00", "2025-03-07T11:00"),
+            event("2025-03-07T10:30", "2025-03-07T12:00"),
+            event("2025-03-07T16:30", "2025-03-07T18:00"),
+        ];
+        let calendar = CalendarToolBox {
+            backend: Arc::new(Fixed(events)),
+            ..calendar
+        };
+
+        // Friday and weekend
+        let slots = calendar
+            .find_free_slots(
+                60,
+                Some("2025-03-07T09:30:00+01:00".to_string()),
+                Some("2025-03-10T10:00:00+01:00".to_string()),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            slots,
+            "- Fri 2025-03-07T12:00+01:00 - Fri 2025-03-07T16:30+01:00\n\
+             - Mon 2025-03-10T09:00+01:00 - Mon 2025-03-10T10:00+01:00"
+        );
+
+        let created = calendar
+            .create_event(
+                "Review".to_string(),
+                "2025-03-07T12:00:00Z".to_string(),
+                "2025-03-07T13:00:00Z".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            created,
+            "Event created: Fri 2025-03-07T13:00+01:00 - Fri 2025-03-07T14:00+01:00: Review [id: new]"
+        );
+        assert!(calendar.parse_time("tomorrow").is_err());
+    }
+}
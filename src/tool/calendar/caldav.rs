@@ -0,0 +1,395 @@
+use crate::tool::calendar::{CalendarBackend, CalendarEvent};
+use crate::tool::{check_response, ToolError};
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use reqwest::{Client, Method, RequestBuilder};
+use std::hash::{BuildHasher, Hasher};
+
+const ICAL_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+enum Auth {
+    None,
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+/// Calendar on CalDAV server, see [CalendarBackend].
+///
+/// Events are listed with `calendar-query` report asking the server to expand recurring events,
+/// so their occurrences are returned with times in UTC. New events are stored as `{uid}.ics`
+/// resources of the calendar collection.
+pub struct CalDav {
+    client: Client,
+    /// URL of the calendar collection, e.g. `https://cloud.example.com/remote.php/dav/calendars/user/personal/`
+    url: String,
+    auth: Auth,
+}
+
+impl CalDav {
+    /// Creates backend for the calendar collection at `url`, without authorization.
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            url: format!("{}/", url.trim_end_matches('/')),
+            auth: Auth::None,
+        }
+    }
+
+    /// Authorizes requests with username and password, most servers require app specific password.
+    pub fn with_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.auth = Auth::Basic {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        self
+    }
+
+    /// Authorizes requests with OAuth 2.0 bearer token.
+    pub fn with_bearer_token(mut self, token: &str) -> Self {
+        self.auth = Auth::Bearer(token.to_string());
+        self
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let request = self.client.request(method, url);
+        match &self.auth {
+            Auth::None => request,
+            Auth::Basic { username, password } => request.basic_auth(username, Some(password)),
+            Auth::Bearer(token) => request.bearer_auth(token),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CalendarBackend for CalDav {
+    async fn list_events(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<CalendarEvent>, ToolError> {
+        let start = start.format(ICAL_TIME_FORMAT);
+        let end = end.format(ICAL_TIME_FORMAT);
+        let query = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data><C:expand start="{start}" end="{end}"/></C:calendar-data>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT"><C:time-range start="{start}" end="{end}"/></C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#
+        );
+        let report = Method::from_bytes(b"REPORT").expect("Invalid HTTP method");
+        let response = self
+            .request(report, &self.url)
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml; charset=utf-8")
+            .body(query)
+            .send()
+            .await
+            .context("CalDAV request failed")?;
+        let body = check_response(response, "CalDAV server")
+            .await?
+            .text()
+            .await
+            .map_err(anyhow::Error::new)?;
+        let mut events: Vec<_> = calendar_data(&body)
+            .iter()
+            .flat_map(|data| parse_events(data))
+            .collect();
+        events.sort_by_key(|event| event.start);
+        Ok(events)
+    }
+
+    async fn create_event(&self, event: CalendarEvent) -> Result<CalendarEvent, ToolError> {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write(event.summary.as_bytes());
+        let uid = format!(
+            "{:016x}-{}@agentai",
+            hasher.finish(),
+            event.start.timestamp()
+        );
+        let response = self
+            .request(Method::PUT, &format!("{}{uid}.ics", self.url))
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            // Existing resource is never overwritten
+            .header("If-None-Match", "*")
+            .body(to_ical(&uid, &event))
+            .send()
+            .await
+            .context("CalDAV request failed")?;
+        check_response(response, "CalDAV server").await?;
+        Ok(CalendarEvent { id: uid, ..event })
+    }
+}
+
+/// Returns iCalendar data of all resources in `multistatus` response.
+fn calendar_data(xml: &str) -> Vec<String> {
+    let mut result = vec![];
+    let mut rest = xml;
+    while let Some(tag_start) = rest.find('<') {
+        rest = &rest[tag_start + 1..];
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+        // Elements can use any namespace prefix, e.g. `<C:calendar-data>` or `<cal:calendar-data>`
+        let name = tag.split_whitespace().next().unwrap_or_default();
+        if name.starts_with('/')
+            || name.rsplit(':').next() != Some("calendar-data")
+            || tag.ends_with('/')
+        {
+            continue;
+        }
+        let Some(content_end) = rest.find("</") else {
+            break;
+        };
+        let content = &rest[..content_end];
+        result.push(match content.trim().strip_prefix("<![CDATA[") {
+            Some(cdata) => cdata.trim_end().trim_end_matches("]]>").to_string(),
+            None => unescape_xml(content),
+        });
+        rest = &rest[content_end..];
+    }
+    result
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&amp;", "&")
+}
+
+/// Parses events of iCalendar data, properties of nested components (e.g. alarms) are ignored.
+fn parse_events(data: &str) -> Vec<CalendarEvent> {
+    let unfolded = data
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut events = vec![];
+    let mut event: Option<Properties> = None;
+    let mut nested = 0;
+    for line in unfolded.lines() {
+        match line {
+            "BEGIN:VEVENT" => event = Some(Properties::default()),
+            "END:VEVENT" => {
+                if let Some(event) = event.take().and_then(Properties::into_event) {
+                    events.push(event);
+                }
+            }
+            _ if event.is_some() && line.starts_with("BEGIN:") => nested += 1,
+            _ if event.is_some() && line.starts_with("END:") => nested -= 1,
+            _ if nested == 0 => {
+                if let Some(event) = &mut event {
+                    event.add(line);
+                }
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+#[derive(Default)]
+struct Properties {
+    uid: Option<String>,
+    summary: Option<String>,
+    start: Option<(DateTime<Utc>, bool)>,
+    end: Option<DateTime<Utc>>,
+    duration: Option<Duration>,
+    location: Option<String>,
+    description: Option<String>,
+}
+
+impl Properties {
+    fn add(&mut self, line: &str) {
+        let Some((name, value)) = line.split_once(':') else {
+            return;
+        };
+        // Parameters are not needed, e.g. `DTSTART;VALUE=DATE:20250301`
+        let name = name.split(';').next().unwrap_or(name);
+        match name {
+            "UID" => self.uid = Some(value.to_string()),
+            "SUMMARY" => self.summary = Some(unescape_text(value)),
+            "LOCATION" => self.location = Some(unescape_text(value)),
+            "DESCRIPTION" => self.description = Some(unescape_text(value)),
+            "DTSTART" => self.start = parse_time(value).map(|time| (time, value.len() == 8)),
+            "DTEND" => self.end = parse_time(value),
+            "DURATION" => self.duration = parse_duration(value),
+            _ => {}
+        }
+    }
+
+    fn into_event(self) -> Option<CalendarEvent> {
+        let (start, all_day) = self.start?;
+        let end = self.end.unwrap_or_else(|| {
+            start
+                + self.duration.unwrap_or(if all_day {
+                    Duration::days(1)
+                } else {
+                    Duration::zero()
+                })
+        });
+        Some(CalendarEvent {
+            id: self.uid.unwrap_or_default(),
+            summary: self.summary.unwrap_or_else(|| "(no title)".to_string()),
+            start,
+            end,
+            location: self.location,
+            description: self.description,
+        })
+    }
+}
+
+/// Parses date or date-time value, times without `Z` suffix are taken as UTC, as expanded
+/// events are returned in UTC.
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(|date| date.and_time(Default::default()).and_utc());
+    }
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Parses duration like `PT1H30M` or `P1D`, negative durations are not supported.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('P')?;
+    let mut duration = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let amount: i64 = number.parse().ok()?;
+                number.clear();
+                duration += match c {
+                    'W' => Duration::weeks(amount),
+                    'D' => Duration::days(amount),
+                    'H' => Duration::hours(amount),
+                    'M' => Duration::minutes(amount),
+                    _ => Duration::seconds(amount),
+                };
+            }
+            _ => return None,
+        }
+    }
+    Some(duration)
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => result.push('\n'),
+            Some(escaped) => result.push(escaped),
+            None => {}
+        }
+    }
+    result
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn to_ical(uid: &str, event: &CalendarEvent) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//agentai//calendar//EN".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{uid}"),
+        format!("DTSTAMP:{}", Utc::now().format(ICAL_TIME_FORMAT)),
+        format!("DTSTART:{}", event.start.format(ICAL_TIME_FORMAT)),
+        format!("DTEND:{}", event.end.format(ICAL_TIME_FORMAT)),
+        format!("SUMMARY:{}", escape_text(&event.summary)),
+    ];
+    if let Some(location) = &event.location {
+        lines.push(format!("LOCATION:{}", escape_text(location)));
+    }
+    if let Some(description) = &event.description {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caldav_response() {
+        let response = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:" xmlns:cal="urn:ietf:params:xml:ns:caldav">
+  <d:response>
+    <d:href>/calendars/user/personal/standup.ics</d:href>
+    <d:propstat><d:prop><cal:calendar-data>BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:standup
+SUMMARY:Standup\, daily
+DTSTART:20250307T090000Z
+DURATION:PT15M
+BEGIN:VALARM
+SUMMARY:Reminder
+END:VALARM
+LOCATION:Room &amp; Board
+END:VEVENT
+END:VCALENDAR
+</cal:calendar-data></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/calendars/user/personal/holiday.ics</d:href>
+    <d:propstat><d:prop><cal:calendar-data><![CDATA[BEGIN:VCALENDAR
+BEGIN:VEVENT
+UID:holiday
+SUMMARY:Holiday
+DTSTART;VALUE=DATE:20250306
+END:VEVENT
+END:VCALENDAR]]></cal:calendar-data></d:prop></d:propstat>
+  </d:response>
+</d:multistatus>"#;
+        let events: Vec<_> = calendar_data(response)
+            .iter()
+            .flat_map(|data| parse_events(data))
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].summary, "Standup, daily");
+        assert_eq!(events[0].location.as_deref(), Some("Room & Board"));
+        assert_eq!(events[0].end - events[0].start, Duration::minutes(15));
+        assert_eq!(events[1].id, "holiday");
+        assert_eq!(events[1].end - events[1].start, Duration::days(1));
+
+        let ical = to_ical("new@agentai", &events[0]);
+        let created = parse_events(&ical).remove(0);
+        assert_eq!(
+            CalendarEvent {
+                id: "standup".to_string(),
+                ..created
+            },
+            events[0]
+        );
+    }
+}
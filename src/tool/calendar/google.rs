@@ -0,0 +1,197 @@
+use crate::tool::calendar::{CalendarBackend, CalendarEvent};
+use crate::tool::{check_response, ToolError};
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+
+const GOOGLE_CALENDAR_API_URL: &str = "https://www.googleapis.com/calendar/v3/calendars";
+/// Google Calendar API returns at most 2500 events per page
+const MAX_RESULTS: &str = "2500";
+
+/// Calendar in Google Calendar, see [CalendarBackend].
+pub struct GoogleCalendar {
+    client: Client,
+    access_token: String,
+    calendar_id: String,
+}
+
+impl GoogleCalendar {
+    /// Creates backend for the primary calendar of the user authorized with OAuth 2.0 `access_token`.
+    pub fn new(access_token: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            access_token: access_token.to_string(),
+            calendar_id: "primary".to_string(),
+        }
+    }
+
+    /// Uses other calendar of the user, identified e.g. by email address.
+    pub fn with_calendar_id(mut self, calendar_id: &str) -> Self {
+        self.calendar_id = calendar_id.to_string();
+        self
+    }
+
+    fn events_url(&self) -> Url {
+        let mut url = Url::parse(GOOGLE_CALENDAR_API_URL).expect("Invalid Google Calendar API URL");
+        url.path_segments_mut()
+            .expect("Google Calendar API URL is not a base")
+            .extend([self.calendar_id.as_str(), "events"]);
+        url
+    }
+}
+
+#[async_trait::async_trait]
+impl CalendarBackend for GoogleCalendar {
+    async fn list_events(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<CalendarEvent>, ToolError> {
+        let time_min = start.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let time_max = end.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let mut events = vec![];
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut query = vec![
+                ("timeMin", time_min.as_str()),
+                ("timeMax", time_max.as_str()),
+                ("singleEvents", "true"),
+                ("orderBy", "startTime"),
+                ("maxResults", MAX_RESULTS),
+            ];
+            if let Some(page_token) = &page_token {
+                query.push(("pageToken", page_token.as_str()));
+            }
+            let response = self
+                .client
+                .get(self.events_url())
+                .bearer_auth(&self.access_token)
+                .query(&query)
+                .send()
+                .await
+                .context("Google Calendar request failed")?;
+            let page: Value = check_response(response, "Google Calendar")
+                .await?
+                .json()
+                .await
+                .map_err(anyhow::Error::new)?;
+            let items = page["items"].as_array().cloned().unwrap_or_default();
+            // Cancelled occurrences of recurring events are returned too
+            events.extend(
+                items
+                    .iter()
+                    .filter(|item| item["status"] != "cancelled")
+                    .filter_map(parse_event),
+            );
+            match page["nextPageToken"].as_str() {
+                Some(next) => page_token = Some(next.to_string()),
+                None => return Ok(events),
+            }
+        }
+    }
+
+    async fn create_event(&self, event: CalendarEvent) -> Result<CalendarEvent, ToolError> {
+        let mut body = json!({
+            "summary": event.summary,
+            "start": {"dateTime": event.start.to_rfc3339_opts(SecondsFormat::Secs, true)},
+            "end": {"dateTime": event.end.to_rfc3339_opts(SecondsFormat::Secs, true)},
+        });
+        if let Some(location) = &event.location {
+            body["location"] = json!(location);
+        }
+        if let Some(description) = &event.description {
+            body["description"] = json!(description);
+        }
+        let response = self
+            .client
+            .post(self.events_url())
+            .bearer_auth(&self.access_token)
+            .json(&body)
+            .send()
+            .await
+            .context("Google Calendar request failed")?;
+        let created: Value = check_response(response, "Google Calendar")
+            .await?
+            .json()
+            .await
+            .map_err(anyhow::Error::new)?;
+        parse_event(&created)
+            .ok_or_else(|| anyhow!("Unexpected response of Google Calendar: {created}").into())
+    }
+}
+
+/// Converts event resource of Google Calendar API, all-day events start and end at midnight UTC.
+fn parse_event(item: &Value) -> Option<CalendarEvent> {
+    let time = |value: &Value| -> Option<DateTime<Utc>> {
+        match value["dateTime"].as_str() {
+            Some(time) => DateTime::parse_from_rfc3339(time)
+                .ok()
+                .map(|time| time.with_timezone(&Utc)),
+            None => NaiveDate::parse_from_str(value["date"].as_str()?, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(Default::default()).and_utc()),
+        }
+    };
+    Some(CalendarEvent {
+        id: item["id"].as_str()?.to_string(),
+        summary: item["summary"].as_str().unwrap_or("(no title)").to_string(),
+        start: time(&item["start"])?,
+        end: time(&item["end"])?,
+        location: item["location"].as_str().map(str::to_string),
+        description: item["description"].as_str().map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event() {
+        let event = parse_event(&json!({
+            "id": "abc",
+            "summary": "Standup",
+            "start": {"dateTime": "2025-03-10T09:00:00+01:00"},
+            "end": {"dateTime": "2025-03-10T09:15:00+01:00"},
+            "location": "Room 1"
+        }))
+        .unwrap();
+        assert_eq!(event.start.to_rfc3339(), "2025-03-10T08:00:00+00:00");
+        assert_eq!(event.end.to_rfc3339(), "2025-03-10T08:15:00+00:00");
+        assert_eq!(event.location.as_deref(), Some("Room 1"));
+        assert_eq!(event.description, None);
+
+        let all_day = parse_event(&json!({
+            "id": "def",
+            "start": {"date": "2025-03-11"},
+            "end": {"date": "2025-03-12"}
+        }))
+        .unwrap();
+        assert_eq!(all_day.summary, "(no title)");
+        assert_eq!(all_day.start.to_rfc3339(), "2025-03-11T00:00:00+00:00");
+
+        // Events without id or with invalid time are skipped
+        assert!(parse_event(&json!({"start": {"date": "2025-03-11"}})).is_none());
+        assert!(parse_event(&json!({
+            "id": "ghi",
+            "start": {"dateTime": "tomorrow"},
+            "end": {"dateTime": "2025-03-10T09:15:00Z"}
+        }))
+        .is_none());
+    }
+
+    #[test]
+    fn test_events_url_escapes_calendar_id() {
+        let calendar = GoogleCalendar::new("token")
+            .with_calendar_id("en.polish#holiday@group.v.calendar.google.com");
+        assert_eq!(
+            calendar.events_url().as_str(),
+            "https://www.googleapis.com/calendar/v3/calendars/en.polish%23holiday@group.v.calendar.google.com/events"
+        );
+        assert_eq!(
+            GoogleCalendar::new("token").events_url().path(),
+            "/calendar/v3/calendars/primary/events"
+        );
+    }
+}
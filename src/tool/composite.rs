@@ -0,0 +1,497 @@
+//! # Composite Tool Box
+//!
+//! [CompositeToolBox] merges many toolboxes into one, prefixing names of their tools with the name
+//! of the toolbox, e.g. tool `search` from toolbox registered as `web` is exposed as `web_search`.
+//! Composite toolboxes can be nested, also indirectly, when a tool runs another agent (agent as
+//! a tool) using composite toolbox.
+//!
+//! Nesting is guarded against mistakes that are easy to make in such setups:
+//! - depth of nesting is limited (see [CompositeToolBox::with_max_depth]), exceeding it results in
+//!   [ToolError::MaxDepthExceeded],
+//! - delegation loops, where call of a tool ends up calling the same composite toolbox again (e.g.
+//!   two agents delegating to each other), are stopped with [ToolError::CycleDetected],
+//! - prefixes and resulting tool names have to be unique, otherwise [ToolError::AmbiguousToolName]
+//!   is returned.
+//!
+//! Failure of a nested toolbox doesn't break the others. When a toolbox fails to provide its tools
+//! definitions (e.g. MCP server disconnected), its tools are skipped and it is reported as degraded
+//! by [ToolBox::health_check], until it provides definitions again. Errors of tool calls are
+//! returned only for the failed call. Errors caused by wrong setup ([ToolError::MaxDepthExceeded],
+//! [ToolError::CycleDetected] and [ToolError::AmbiguousToolName]) are not isolated.
+//!
+//! Calls are tracked using task local storage, so delegation is detected when nested agents are run
+//! in the same task (are awaited by the tool). Tool calls of agent runs, that are executed in
+//! separate tasks, inherit the path of the run. Agents started in separate tasks by the tool (e.g.
+//! with `tokio::spawn`) are not tracked.
+//!
+//! ```rust,no_run
+//! # use agentai::tool::composite::CompositeToolBox;
+//! # use agentai::tool::websearch::WebSearchToolBox;
+//! # use std::sync::Arc;
+//! let toolbox = CompositeToolBox::new("coordinator")
+//!     .with_toolbox("web", Arc::new(WebSearchToolBox::new("api-key")))
+//!     .with_max_depth(4);
+//! ```
+
+use crate::tool::{Capability, Tool, ToolBox, ToolBoxHealth, ToolError, ToolFuture};
+use futures::future::join_all;
+use log::warn;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Default maximal number of composite toolboxes a call can pass through.
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// Toolbox shared by composite toolboxes and agents.
+pub type SharedToolBox = Arc<dyn ToolBox + Send + Sync>;
+
+/// Composite toolboxes on the path of the current call
+#[derive(Clone, Default)]
+struct Path {
+    /// Toolboxes as `(id, name)`, outermost first
+    toolboxes: Vec<(u64, String)>,
+    /// The strictest depth limit of toolboxes on the path, outer limits apply to nested toolboxes
+    max_depth: Option<usize>,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// Composite toolboxes executing the current tool call, outermost first
+    static CALL_PATH: Path;
+}
+
+/// Returns the future executed with the call path of the current task, so delegation is tracked
+/// also when the future is spawned as a separate task.
+pub(crate) fn with_call_path<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let path = CALL_PATH.try_with(Path::clone).ok();
+    async move {
+        match path {
+            Some(path) => CALL_PATH.scope(path, future).await,
+            None => future.await,
+        }
+    }
+}
+
+thread_local! {
+    /// Composite toolboxes collecting the definitions, outermost first. Collecting definitions is
+    /// synchronous, so it never leaves the thread.
+    static DEFINITIONS_PATH: RefCell<Path> = RefCell::new(Path::default());
+}
+
+/// Toolbox merging many toolboxes, look into [module documentation](crate::tool::composite) for details.
+pub struct CompositeToolBox {
+    id: u64,
+    name: String,
+    max_depth: usize,
+    toolboxes: Vec<(String, SharedToolBox)>,
+    /// Toolboxes that failed to provide definitions, by prefix, with the error
+    degraded: Mutex<BTreeMap<String, String>>,
+}
+
+impl CompositeToolBox {
+    /// Creates empty composite toolbox, the name is used only in error messages.
+    pub fn new(name: &str) -> Self {
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            toolboxes: vec![],
+            degraded: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Adds toolbox, its tools are exposed with names prefixed by `prefix` and `_`.
+    ///
+    /// Prefix can't be empty or contain `_`, otherwise names of tools would be ambiguous.
+    pub fn with_toolbox(mut self, prefix: &str, toolbox: SharedToolBox) -> Self {
+        self.toolboxes.push((prefix.to_string(), toolbox));
+        self
+    }
+
+    /// Sets maximal number of composite toolboxes a call can pass through, including this one.
+    /// The limit applies also to toolboxes nested in this one. Default is [DEFAULT_MAX_DEPTH].
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns path extended by this toolbox, checking for cycles and depth.
+    fn enter(&self, path: &Path) -> Result<Path, ToolError> {
+        if path.toolboxes.iter().any(|(id, _)| *id == self.id) {
+            let cycle = path
+                .toolboxes
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .chain([self.name.as_str()])
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(ToolError::CycleDetected(cycle));
+        }
+        let max_depth = path
+            .max_depth
+            .map_or(self.max_depth, |max| max.min(self.max_depth));
+        if path.toolboxes.len() >= max_depth {
+            return Err(ToolError::MaxDepthExceeded(max_depth));
+        }
+        let mut path = path.clone();
+        path.toolboxes.push((self.id, self.name.clone()));
+        path.max_depth = Some(max_depth);
+        Ok(path)
+    }
+
+    /// Returns prefixes of toolboxes that failed to provide definitions, with their errors.
+    pub fn degraded_toolboxes(&self) -> Vec<(String, String)> {
+        self.degraded
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(prefix, error)| (prefix.clone(), error.clone()))
+            .collect()
+    }
+
+    /// Finds toolbox serving the tool, returns it together with name of the tool in the toolbox.
+    fn route<'a>(&self, tool_name: &'a str) -> Option<(&SharedToolBox, &'a str)> {
+        let (prefix, name) = tool_name.split_once('_')?;
+        self.toolboxes
+            .iter()
+            .find(|(p, _)| p == prefix)
+            .map(|(_, toolbox)| (toolbox, name))
+    }
+
+    fn definitions(&self) -> Result<Vec<Tool>, ToolError> {
+        let mut prefixes = HashSet::new();
+        let mut names = HashSet::new();
+        let mut tools = vec![];
+        for (prefix, toolbox) in &self.toolboxes {
+            if prefix.is_empty() || prefix.contains('_') || !prefixes.insert(prefix) {
+                return Err(ToolError::AmbiguousToolName(format!("{prefix}_*")));
+            }
+            let definitions = match toolbox.tools_definitions() {
+                Ok(definitions) => {
+                    self.degraded.lock().unwrap().remove(prefix);
+                    definitions
+                }
+                Err(
+                    err @ (ToolError::MaxDepthExceeded(_)
+                    | ToolError::CycleDetected(_)
+                    | ToolError::AmbiguousToolName(_)),
+                ) => return Err(err),
+                Err(err) => {
+                    warn!("Skipping tools of `{prefix}` in `{}`: {err}", self.name);
+                    self.degraded
+                        .lock()
+                        .unwrap()
+                        .insert(prefix.clone(), err.to_string());
+                    continue;
+                }
+            };
+            for mut tool in definitions {
+                tool.name = format!("{prefix}_{}", tool.name);
+                if !names.insert(tool.name.clone()) {
+                    return Err(ToolError::AmbiguousToolName(tool.name));
+                }
+                tools.push(tool);
+            }
+        }
+        Ok(tools)
+    }
+}
+
+#[async_trait::async_trait]
+impl ToolBox for CompositeToolBox {
+    fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+        let path = DEFINITIONS_PATH.with_borrow(|path| self.enter(path))?;
+        let outer = DEFINITIONS_PATH.replace(path);
+        let result = self.definitions();
+        DEFINITIONS_PATH.set(outer);
+        result
+    }
+
+    async fn call_tool(&self, tool_name: String, arguments: Value) -> Result<String, ToolError> {
+        let path = CALL_PATH
+            .try_with(|path| self.enter(path))
+            .unwrap_or_else(|_| self.enter(&Path::default()))?;
+        let (toolbox, name) = self
+            .route(&tool_name)
+            .ok_or_else(|| ToolError::NoToolFound(tool_name.clone()))?;
+        CALL_PATH
+            .scope(path, toolbox.call_tool(name.to_string(), arguments))
+            .await
+    }
+
+    /// Joins instructions of all nested toolboxes.
+    fn instructions(&self) -> Option<String> {
+        join_instructions(self.toolboxes.iter().map(|(_, toolbox)| toolbox))
+    }
+
+    /// Returns composite of run views of nested toolboxes, when any of them has one.
+    fn for_run(&self) -> Option<SharedToolBox> {
+        let views: Vec<_> = self
+            .toolboxes
+            .iter()
+            .map(|(_, toolbox)| toolbox.for_run())
+            .collect();
+        if views.iter().all(Option::is_none) {
+            return None;
+        }
+        let toolboxes = self
+            .toolboxes
+            .iter()
+            .zip(views)
+            .map(|((prefix, toolbox), view)| (prefix.clone(), view.unwrap_or(toolbox.clone())))
+            .collect();
+        Some(Arc::new(Self {
+            // Same toolbox for detection of cycles
+            id: self.id,
+            name: self.name.clone(),
+            max_depth: self.max_depth,
+            toolboxes,
+            degraded: Mutex::new(self.degraded.lock().unwrap().clone()),
+        }))
+    }
+
+    /// Refreshes all nested toolboxes, toolboxes that failed are marked as degraded.
+    fn refresh_definitions(&self) -> ToolFuture<'_, Result<(), ToolError>> {
+        Box::pin(async move {
+            let results = join_all(
+                self.toolboxes
+                    .iter()
+                    .map(|(_, toolbox)| toolbox.refresh_definitions()),
+            )
+            .await;
+            let mut degraded = self.degraded.lock().unwrap();
+            for ((prefix, _), result) in self.toolboxes.iter().zip(results) {
+                if let Err(err) = result {
+                    warn!(
+                        "Unable to refresh tools of `{prefix}` in `{}`: {err}",
+                        self.name
+                    );
+                    degraded.insert(prefix.clone(), err.to_string());
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn tool_capabilities(&self, tool_name: &str) -> Vec<Capability> {
+        self.route(tool_name)
+            .map(|(toolbox, name)| toolbox.tool_capabilities(name))
+            .unwrap_or_else(|| vec![Capability::Unknown])
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Composite toolbox is healthy when all nested toolboxes are healthy, it is unhealthy only
+    /// when none of them is usable.
+    async fn health_check(&self) -> ToolBoxHealth {
+        let checks = join_all(
+            self.toolboxes
+                .iter()
+                .map(|(_, toolbox)| toolbox.health_check()),
+        )
+        .await;
+        let degraded = self.degraded.lock().unwrap().clone();
+        let checks = self
+            .toolboxes
+            .iter()
+            .zip(checks)
+            .map(
+                |((prefix, _), health)| match (health, degraded.get(prefix)) {
+                    // Toolbox may consider itself healthy, while its definitions are missing
+                    (ToolBoxHealth::Healthy, Some(error)) => ToolBoxHealth::Degraded(error.clone()),
+                    (health, _) => health,
+                },
+            )
+            .collect::<Vec<_>>();
+        let problems = self
+            .toolboxes
+            .iter()
+            .zip(&checks)
+            .filter_map(|((prefix, _), health)| match health {
+                ToolBoxHealth::Healthy => None,
+                ToolBoxHealth::Degraded(reason) | ToolBoxHealth::Unhealthy(reason) => {
+                    Some(format!("{prefix}: {reason}"))
+                }
+            })
+            .collect::<Vec<_>>();
+        let unusable = checks
+            .iter()
+            .filter(|health| matches!(health, ToolBoxHealth::Unhealthy(_)))
+            .count();
+        if problems.is_empty() {
+            ToolBoxHealth::Healthy
+        } else if unusable == checks.len() {
+            ToolBoxHealth::Unhealthy(problems.join("; "))
+        } else {
+            ToolBoxHealth::Degraded(problems.join("; "))
+        }
+    }
+}
+
+/// Joins instructions of toolboxes, `None` when none of them has instructions.
+pub(crate) fn join_instructions<'a>(
+    toolboxes: impl Iterator<Item = &'a SharedToolBox>,
+) -> Option<String> {
+    let instructions = toolboxes
+        .filter_map(|toolbox| toolbox.instructions())
+        .collect::<Vec<_>>();
+    (!instructions.is_empty()).then(|| instructions.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+
+    /// Toolbox with a single `echo` tool, that can delegate to other toolbox like agent as a tool
+    #[derive(Default)]
+    struct Delegating {
+        delegate: OnceLock<SharedToolBox>,
+    }
+
+    #[async_trait::async_trait]
+    impl ToolBox for Delegating {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Ok(vec![Tool::new("echo")])
+        }
+
+        async fn call_tool(
+            &self,
+            tool_name: String,
+            arguments: Value,
+        ) -> Result<String, ToolError> {
+            match self.delegate.get() {
+                Some(delegate) => {
+                    delegate
+                        .call_tool("inner_echo".to_string(), arguments)
+                        .await
+                }
+                None => Ok(format!("{tool_name}: {arguments}")),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nested_calls() {
+        let inner =
+            CompositeToolBox::new("inner").with_toolbox("leaf", Arc::new(Delegating::default()));
+        let outer = CompositeToolBox::new("outer").with_toolbox("inner", Arc::new(inner));
+        let names: Vec<_> = outer
+            .tools_definitions()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["inner_leaf_echo"]);
+        let result = outer
+            .call_tool("inner_leaf_echo".to_string(), Value::Null)
+            .await;
+        assert_eq!(result.unwrap(), "echo: null");
+
+        let outer = outer.with_max_depth(1);
+        assert!(matches!(
+            outer.tools_definitions(),
+            Err(ToolError::MaxDepthExceeded(1))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cycle_detection() {
+        let delegating = Arc::new(Delegating::default());
+        let composite: SharedToolBox =
+            Arc::new(CompositeToolBox::new("agent").with_toolbox("inner", delegating.clone()));
+        let _ = delegating.delegate.set(composite.clone());
+
+        let result = composite
+            .call_tool("inner_echo".to_string(), Value::Null)
+            .await;
+        assert!(
+            matches!(result, Err(ToolError::CycleDetected(cycle)) if cycle == "agent -> agent")
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_names() {
+        let composite = CompositeToolBox::new("agent")
+            .with_toolbox("web", Arc::new(Delegating::default()))
+            .with_toolbox("web", Arc::new(Delegating::default()));
+        assert!(matches!(
+            composite.tools_definitions(),
+            Err(ToolError::AmbiguousToolName(_))
+        ));
+    }
+
+    struct Offline;
+
+    #[async_trait::async_trait]
+    impl ToolBox for Offline {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Ok(vec![])
+        }
+
+        async fn call_tool(&self, tool_name: String, _: Value) -> Result<String, ToolError> {
+            Err(ToolError::NoToolFound(tool_name))
+        }
+
+        async fn health_check(&self) -> ToolBoxHealth {
+            ToolBoxHealth::Unhealthy("server unreachable".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_introspection() {
+        let composite = CompositeToolBox::new("agent")
+            .with_toolbox("web", Arc::new(Delegating::default()))
+            .with_toolbox("db", Arc::new(Offline));
+        assert_eq!(Delegating::default().name(), "Delegating");
+        let description = composite.describe().unwrap();
+        assert_eq!(description.name, "agent");
+        assert_eq!(description.tools[0].name, "web_echo");
+        assert_eq!(
+            composite.health_check().await,
+            ToolBoxHealth::Degraded("db: server unreachable".to_string())
+        );
+    }
+
+    struct Disconnected;
+
+    #[async_trait::async_trait]
+    impl ToolBox for Disconnected {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Err(ToolError::Other(anyhow::anyhow!("connection closed")))
+        }
+
+        async fn call_tool(&self, tool_name: String, _: Value) -> Result<String, ToolError> {
+            Err(ToolError::NoToolFound(tool_name))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failure_isolation() {
+        let composite = CompositeToolBox::new("agent")
+            .with_toolbox("web", Arc::new(Delegating::default()))
+            .with_toolbox("mcp", Arc::new(Disconnected));
+        let names: Vec<_> = composite
+            .tools_definitions()
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, vec!["web_echo"]);
+        assert_eq!(
+            composite.degraded_toolboxes(),
+            vec![("mcp".to_string(), "connection closed".to_string())]
+        );
+        assert_eq!(
+            composite.health_check().await,
+            ToolBoxHealth::Degraded("mcp: connection closed".to_string())
+        );
+    }
+}
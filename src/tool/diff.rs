@@ -0,0 +1,85 @@
+//! # Changes of Tools
+//!
+//! Toolboxes with dynamic tools, like [McpToolBox](crate::tool::mcp::McpToolBox), can provide
+//! different tools after [ToolBox::refresh_definitions](crate::tool::ToolBox::refresh_definitions),
+//! e.g. when MCP server was restarted with a new version. [ToolsDiff] describes what changed.
+//!
+//! [Agent](crate::agent::Agent) compares tools of its toolbox at the start of every run and emits
+//! [AgentEvent::ToolsChanged](crate::event::AgentEvent::ToolsChanged) when they differ from the
+//! previous run. The model can be informed about the change too, see
+//! [AgentBuilder::with_tools_change_notes](crate::agent::AgentBuilder::with_tools_change_notes).
+
+use crate::tool::Tool;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Difference between two sets of tools definitions, names of tools are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ToolsDiff {
+    /// Tools that were not available before
+    pub added: Vec<String>,
+    /// Tools that are no longer available
+    pub removed: Vec<String>,
+    /// Tools with changed description or schema
+    pub changed: Vec<String>,
+}
+
+impl ToolsDiff {
+    /// Compares definitions of tools, tools are matched by their names.
+    pub fn between(old: &[Tool], new: &[Tool]) -> Self {
+        let old = old
+            .iter()
+            .map(|tool| (&tool.name, tool))
+            .collect::<BTreeMap<_, _>>();
+        let new = new
+            .iter()
+            .map(|tool| (&tool.name, tool))
+            .collect::<BTreeMap<_, _>>();
+        let mut diff = Self::default();
+        for (name, tool) in &new {
+            match old.get(name) {
+                None => diff.added.push(name.to_string()),
+                Some(old) if old.description != tool.description || old.schema != tool.schema => {
+                    diff.changed.push(name.to_string())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|name| !new.contains_key(*name))
+            .map(|name| name.to_string())
+            .collect();
+        diff
+    }
+
+    /// Returns `true` when tools didn't change.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_tools_diff() {
+        let old = vec![
+            Tool::new("search"),
+            Tool::new("fetch"),
+            Tool::new("read").with_schema(json!({"type": "object"})),
+        ];
+        let new = vec![
+            Tool::new("read").with_schema(json!({"type": "object", "required": ["path"]})),
+            Tool::new("search"),
+            Tool::new("write"),
+        ];
+        let diff = ToolsDiff::between(&old, &new);
+        assert_eq!(diff.added, ["write"]);
+        assert_eq!(diff.removed, ["fetch"]);
+        assert_eq!(diff.changed, ["read"]);
+        assert!(ToolsDiff::between(&new, &new).is_empty());
+    }
+}
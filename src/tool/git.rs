@@ -0,0 +1,441 @@
+//! # Git Tools
+//!
+//! [GitToolBox] gives the agent access to a single git repository, e.g. for code review or
+//! changelog agents. It provides `git_status`, `git_diff`, `git_log`, `git_blame` and `git_commit`
+//! tools, implemented with [git2](https://docs.rs/git2) without spawning `git` processes. It is
+//! enabled with `git` feature.
+//!
+//! Tools work only with the configured repository: paths are relative to its working directory
+//! and paths leaving it (absolute paths, `..`, symbolic links pointing outside of it) or pointing
+//! into `.git` are rejected.
+//!
+//! `git_commit` is the only tool modifying the repository. It requires [Capability::WriteFs], so it
+//! is not exposed with read only [sandbox profiles](crate::tool::sandbox::SandboxProfile). Agents
+//! don't ask for approval of tool calls by default, to review commits before they are made set
+//! [approval policy](crate::agent::AgentBuilder::with_approval) of the agent:
+//!
+//! ```rust,no_run
+//! use agentai::tool::git::GitToolBox;
+//! use agentai::{Agent, Approval};
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let git = GitToolBox::open(".")?;
+//! let mut agent = Agent::builder()
+//!     .with_system("You are code reviewer, commit only fixes of typos.")
+//!     .with_approval(|call| match call.fn_name.as_str() {
+//!         "git_commit" => Approval::Deny("Commits need review of a human".to_string()),
+//!         _ => Approval::Allow,
+//!     })
+//!     .build();
+//! let review: String = agent
+//!     .run("gpt-4o", "Review my uncommitted changes.", Some(Arc::new(git)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
+use crate::trace::civil_date;
+use anyhow::{anyhow, Context, Result};
+use git2::{BlameOptions, DiffFormat, DiffOptions, Repository, Status, StatusOptions};
+use std::path::{Component, Path, PathBuf};
+
+/// Number of commits returned by `git_log` when the model doesn't ask for other number
+const DEFAULT_LOG_LEN: usize = 20;
+
+/// Toolbox working with a single git repository, look into [module documentation](crate::tool::git).
+#[derive(Debug, Clone)]
+pub struct GitToolBox {
+    /// Working directory of the repository
+    workdir: PathBuf,
+}
+
+#[toolbox]
+impl GitToolBox {
+    /// Opens the repository containing the path (the path or any of its parents).
+    ///
+    /// Bare repositories are not supported, tools work with files of the working directory.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let repository = Repository::discover(path)
+            .with_context(|| format!("No git repository found at {}", path.display()))?;
+        let workdir = repository
+            .workdir()
+            .ok_or_else(|| anyhow!("Repository at {} is bare", path.display()))?
+            .to_path_buf();
+        Ok(Self { workdir })
+    }
+
+    /// Returns working directory of the repository.
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// Repository is opened for every call, `Repository` can't be shared between threads.
+    fn repository(&self) -> Result<Repository, ToolError> {
+        Ok(Repository::open(&self.workdir)
+            .with_context(|| format!("Unable to open repository {}", self.workdir.display()))?)
+    }
+
+    /// Checks that the path stays inside of the working directory, returns it relative to it.
+    fn relative_path(&self, path: &str) -> Result<PathBuf, ToolError> {
+        let relative = Path::new(path.trim());
+        let inside = relative.components().all(|component| match component {
+            // File systems of Windows and macOS are case insensitive
+            Component::Normal(name) => !name.eq_ignore_ascii_case(".git"),
+            Component::CurDir => true,
+            _ => false,
+        });
+        if !inside || relative.as_os_str().is_empty() {
+            return Err(anyhow!(
+                "Path `{path}` is outside of the repository, use path relative to its root"
+            )
+            .into());
+        }
+        Ok(relative
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect())
+    }
+
+    /// Reads the file of the working directory, symbolic links can't lead outside of it.
+    fn read_file(&self, relative: &Path) -> Result<String, ToolError> {
+        let path = self.workdir.join(relative);
+        let workdir = self
+            .workdir
+            .canonicalize()
+            .with_context(|| format!("Unable to read {}", self.workdir.display()))?;
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("Unable to read {}", relative.display()))?;
+        if !canonical.starts_with(&workdir) {
+            return Err(anyhow!(
+                "Path `{}` is outside of the repository, use path relative to its root",
+                relative.display()
+            )
+            .into());
+        }
+        Ok(std::fs::read_to_string(&canonical)
+            .with_context(|| format!("Unable to read {}", relative.display()))?)
+    }
+
+    /// Returns files changed in the working directory and the index (staging area), one per line
+    /// with status codes like `git status --short`.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn git_status(&self) -> Result<String, ToolError> {
+        let repository = self.repository()?;
+        let mut options = StatusOptions::new();
+        options.include_untracked(true).recurse_untracked_dirs(true);
+        let statuses = repository
+            .statuses(Some(&mut options))
+            .context("Unable to read status")?;
+        let lines: Vec<String> = statuses
+            .iter()
+            .filter_map(|entry| {
+                let path = entry.path()?.to_string();
+                Some(format!("{} {path}", short_status(entry.status())))
+            })
+            .collect();
+        if lines.is_empty() {
+            return Ok("Nothing to commit, working tree clean".to_string());
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Returns changes as unified diff. By default changes of the working directory not yet
+    /// staged, with `staged` changes staged for the next commit.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn git_diff(
+        &self,
+        #[doc = "Show changes staged for the next commit instead of unstaged changes"]
+        staged: Option<bool>,
+        #[doc = "Limit the diff to the file or directory, relative to the repository root"]
+        path: Option<String>,
+    ) -> Result<String, ToolError> {
+        let repository = self.repository()?;
+        let mut options = DiffOptions::new();
+        if let Some(path) = &path {
+            options.pathspec(self.relative_path(path)?);
+        }
+        let diff = if staged.unwrap_or(false) {
+            let head = match repository.head() {
+                Ok(head) => Some(head.peel_to_tree().context("Unable to read HEAD")?),
+                // Everything is new in repository without commits
+                Err(_) => None,
+            };
+            repository.diff_tree_to_index(head.as_ref(), None, Some(&mut options))
+        } else {
+            repository.diff_index_to_workdir(None, Some(&mut options))
+        }
+        .context("Unable to compute diff")?;
+        let mut patch = String::new();
+        diff.print(DiffFormat::Patch, |_, _, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                patch.push(line.origin());
+            }
+            patch.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })
+        .context("Unable to format diff")?;
+        if patch.is_empty() {
+            return Ok("No changes".to_string());
+        }
+        Ok(patch)
+    }
+
+    /// Returns recent commits of the current branch, newest first, with short id, date, author
+    /// and summary.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn git_log(
+        &self,
+        #[doc = "Maximal number of commits, 20 by default"] max_count: Option<usize>,
+    ) -> Result<String, ToolError> {
+        let repository = self.repository()?;
+        let mut revwalk = repository.revwalk().context("Unable to read history")?;
+        if revwalk.push_head().is_err() {
+            return Ok("No commits yet".to_string());
+        }
+        let mut lines = vec![];
+        for oid in revwalk.take(max_count.unwrap_or(DEFAULT_LOG_LEN)) {
+            let commit = repository
+                .find_commit(oid.context("Unable to read history")?)
+                .context("Unable to read commit")?;
+            let author = commit.author();
+            lines.push(format!(
+                "{} {} {}: {}",
+                short_id(&commit.id().to_string()),
+                format_date(commit.time().seconds()),
+                author.name().unwrap_or("unknown"),
+                commit.summary().unwrap_or_default()
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Returns lines of the file in the working directory with short id of the commit and author
+    /// that last changed each line, lines changed since the last commit are marked as uncommitted.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn git_blame(
+        &self,
+        #[doc = "Path of the file, relative to the repository root"] path: String,
+        #[doc = "First returned line, starting at 1"] start_line: Option<usize>,
+        #[doc = "Last returned line"] end_line: Option<usize>,
+    ) -> Result<String, ToolError> {
+        let repository = self.repository()?;
+        let relative = self.relative_path(&path)?;
+        let mut options = BlameOptions::new();
+        let content = self.read_file(&relative)?;
+        // Blame of the committed file is applied to the working copy, so line numbers match
+        // the content returned to the model
+        let blame = repository
+            .blame_file(&relative, Some(&mut options))
+            .and_then(|blame| blame.blame_buffer(content.as_bytes()))
+            .with_context(|| format!("Unable to blame {path}"))?;
+        let start_line = start_line.unwrap_or(1).max(1);
+        let end_line = end_line.unwrap_or(usize::MAX);
+        let mut lines = vec![];
+        for (index, text) in content.lines().enumerate() {
+            let line = index + 1;
+            if line < start_line || line > end_line {
+                continue;
+            }
+            let origin = match blame.get_line(line) {
+                // Lines changed in the working copy have no commit (and no signature)
+                Some(hunk) if !hunk.final_commit_id().is_zero() => format!(
+                    "{} {}",
+                    short_id(&hunk.final_commit_id().to_string()),
+                    hunk.final_signature().name().unwrap_or("unknown")
+                ),
+                _ => "uncommitted".to_string(),
+            };
+            lines.push(format!("{origin} {line}: {text}"));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Stages the files and commits them to the current branch, returns short id of the commit.
+    /// Files that were removed are removed from the repository.
+    #[tool(blocking, capabilities = [Capability::WriteFs])]
+    fn git_commit(
+        &self,
+        #[doc = "Commit message, summary on the first line"] message: String,
+        #[doc = "Paths of the committed files, relative to the repository root"] paths: Vec<String>,
+    ) -> Result<String, ToolError> {
+        if message.trim().is_empty() {
+            return Err(anyhow!("Commit message can't be empty").into());
+        }
+        if paths.is_empty() {
+            return Err(anyhow!("No files to commit").into());
+        }
+        let repository = self.repository()?;
+        let mut index = repository.index().context("Unable to read index")?;
+        for path in &paths {
+            let relative = self.relative_path(path)?;
+            if self.workdir.join(&relative).exists() {
+                index.add_path(&relative)
+            } else {
+                index.remove_path(&relative)
+            }
+            .with_context(|| format!("Unable to stage {path}"))?;
+        }
+        index.write().context("Unable to write index")?;
+        let tree_id = index.write_tree().context("Unable to write tree")?;
+        let tree = repository
+            .find_tree(tree_id)
+            .context("Unable to read tree")?;
+        let signature = repository
+            .signature()
+            .context("Author is not configured, set user.name and user.email")?;
+        let parent = match repository.head() {
+            Ok(head) => Some(head.peel_to_commit().context("Unable to read HEAD")?),
+            Err(_) => None,
+        };
+        let commit_id = repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message.trim(),
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .context("Unable to commit")?;
+        Ok(format!("Committed {}", short_id(&commit_id.to_string())))
+    }
+}
+
+/// Returns two letter status code, first for the index, second for the working directory.
+fn short_status(status: Status) -> String {
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else {
+        ' '
+    };
+    let workdir = if status.is_wt_new() {
+        '?'
+    } else if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else {
+        ' '
+    };
+    match (index, workdir) {
+        (' ', '?') => "??".to_string(),
+        _ => format!("{index}{workdir}"),
+    }
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(7)]
+}
+
+/// Formats UNIX timestamp as `YYYY-MM-DD` (UTC).
+fn format_date(seconds: i64) -> String {
+    let (year, month, day) = civil_date(seconds.div_euclid(86_400));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_toolbox() {
+        let git = GitToolBox {
+            workdir: PathBuf::from("/repo"),
+        };
+        assert_eq!(
+            git.relative_path("./src/lib.rs").unwrap(),
+            PathBuf::from("src/lib.rs")
+        );
+        assert!(git.relative_path("../secret").is_err());
+        assert!(git.relative_path("/etc/passwd").is_err());
+        assert!(git.relative_path(".git/config").is_err());
+        assert!(git.relative_path("sub/.GIT/config").is_err());
+        assert!(git.relative_path("").is_err());
+
+        assert_eq!(short_status(Status::WT_NEW), "??");
+        assert_eq!(
+            short_status(Status::INDEX_MODIFIED | Status::WT_MODIFIED),
+            "MM"
+        );
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(1_709_164_800), "2024-02-29");
+        assert_eq!(format_date(-86_400), "1969-12-31");
+    }
+
+    #[test]
+    fn test_git_commit_capabilities() {
+        let git = GitToolBox {
+            workdir: PathBuf::from("/repo"),
+        };
+        assert_eq!(git.tool_capabilities("git_commit"), [Capability::WriteFs]);
+        assert_eq!(git.tool_capabilities("git_blame"), [Capability::ReadFs]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_file_symlink() {
+        let root = std::env::temp_dir().join(format!("agentai-git-link-{}", std::process::id()));
+        let workdir = root.join("repo");
+        std::fs::create_dir_all(workdir.join("src")).unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+        std::fs::write(workdir.join("src/lib.rs"), "code").unwrap();
+        std::os::unix::fs::symlink(root.join("secret.txt"), workdir.join("leak.txt")).unwrap();
+        std::os::unix::fs::symlink("src/lib.rs", workdir.join("inside.rs")).unwrap();
+
+        let git = GitToolBox { workdir };
+        let inside = git.read_file(Path::new("inside.rs"));
+        let leak = git.read_file(Path::new("leak.txt"));
+        let missing = git.read_file(Path::new("missing.txt"));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(inside.unwrap(), "code");
+        assert!(leak
+            .unwrap_err()
+            .to_string()
+            .contains("outside of the repository"));
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_git_blame_working_copy() {
+        let workdir = std::env::temp_dir().join(format!("agentai-git-{}", std::process::id()));
+        let repository = Repository::init(&workdir).unwrap();
+        std::fs::write(workdir.join("notes.txt"), "first\nsecond\n").unwrap();
+        let mut index = repository.index().unwrap();
+        index.add_path(Path::new("notes.txt")).unwrap();
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = git2::Signature::now("Alice", "alice@example.com").unwrap();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Add notes",
+                &tree,
+                &[],
+            )
+            .unwrap();
+        std::fs::write(workdir.join("notes.txt"), "inserted\nfirst\nsecond\n").unwrap();
+
+        let git = GitToolBox::open(&workdir).unwrap();
+        let blame = git.git_blame("notes.txt".to_string(), None, None);
+        std::fs::remove_dir_all(&workdir).unwrap();
+        let blame = blame.unwrap();
+        let lines: Vec<_> = blame.lines().collect();
+        assert_eq!(lines[0], "uncommitted 1: inserted");
+        assert!(lines[1].ends_with(" Alice 2: first"));
+        assert!(lines[2].ends_with(" Alice 3: second"));
+    }
+}
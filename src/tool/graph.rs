@@ -0,0 +1,262 @@
+//! # Knowledge Graph Memory
+//!
+//! **Experimental**, API of this module may change in future releases.
+//!
+//! [GraphMemoryToolBox] stores facts as relations between entities, e.g. `Alice -- works at --> Acme`.
+//! Unlike memories searched by similarity of embeddings, relations can be followed from an entity
+//! to everything that is known about it, which works better for relationship-heavy domains like
+//! organizations, projects or family trees.
+//!
+//! Relations are added by the model with `remember_relation` tool, or extracted from the conversation
+//! with [GraphMemoryToolBox::remember_session]. The model queries the memory with `find_relations`
+//! and `find_entities` tools.
+//!
+//! Graph is kept in memory, or in a JSON file when opened with [GraphMemoryToolBox::open]. Clones of
+//! the toolbox share the same graph, so a single memory can be used by many agents.
+//!
+//! ```rust,no_run
+//! use agentai::tool::graph::GraphMemoryToolBox;
+//! use agentai::Agent;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let memory = GraphMemoryToolBox::open("memory.json")?;
+//! let mut agent = Agent::new("You are helpful assistant, remember facts about the user.");
+//! let answer: String = agent
+//!     .run("gpt-4o", "Who is my manager?", Some(Arc::new(memory.clone())), None, None)
+//!     .await?;
+//! // Facts mentioned in the conversation are added to the graph
+//! memory.remember_session(&mut agent, "gpt-4o-mini").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agent::Agent;
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// Relation between two entities, stored in [GraphMemoryToolBox].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Relation {
+    /// Name of the entity the relation starts from, e.g. `Alice`
+    pub subject: String,
+    /// Short description of the relation, e.g. `works at`
+    pub relation: String,
+    /// Name of the related entity, e.g. `Acme`
+    pub object: String,
+}
+
+impl Relation {
+    /// Creates relation, whitespace around names is removed.
+    pub fn new(subject: &str, relation: &str, object: &str) -> Self {
+        Self {
+            subject: subject.trim().to_string(),
+            relation: relation.trim().to_string(),
+            object: object.trim().to_string(),
+        }
+    }
+
+    /// Returns `true` when the entity is the subject or the object, names are case insensitive.
+    pub fn involves(&self, entity: &str) -> bool {
+        self.subject.eq_ignore_ascii_case(entity) || self.object.eq_ignore_ascii_case(entity)
+    }
+
+    fn same_as(&self, other: &Relation) -> bool {
+        self.subject.eq_ignore_ascii_case(&other.subject)
+            && self.relation.eq_ignore_ascii_case(&other.relation)
+            && self.object.eq_ignore_ascii_case(&other.object)
+    }
+}
+
+/// Relations extracted from the conversation
+#[derive(Deserialize, JsonSchema)]
+struct ExtractedRelations {
+    /// Facts stated in the conversation as relations between people, organizations, places,
+    /// projects or other named entities. Use the same name for the same entity.
+    relations: Vec<Relation>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Graph {
+    relations: Vec<Relation>,
+}
+
+/// Toolbox giving the agent access to knowledge graph memory, look into [module documentation](crate::tool::graph).
+#[derive(Clone, Default)]
+pub struct GraphMemoryToolBox {
+    graph: Arc<RwLock<Graph>>,
+    path: Option<PathBuf>,
+}
+
+#[toolbox]
+impl GraphMemoryToolBox {
+    /// Creates memory kept only in memory of the process.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens memory stored in the JSON file, file is created when the first relation is added.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let graph = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .with_context(|| format!("Invalid graph memory file {}", path.display()))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Graph::default(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            graph: Arc::new(RwLock::new(graph)),
+            path: Some(path),
+        })
+    }
+
+    /// Adds the relation, returns `false` when it was already known.
+    pub fn add(&self, relation: Relation) -> Result<bool> {
+        let mut graph = self.graph.write().unwrap_or_else(|e| e.into_inner());
+        if graph.relations.iter().any(|known| known.same_as(&relation)) {
+            return Ok(false);
+        }
+        graph.relations.push(relation);
+        if let Some(path) = &self.path {
+            std::fs::write(path, serde_json::to_string_pretty(&*graph)?)
+                .with_context(|| format!("Can't write graph memory file {}", path.display()))?;
+        }
+        Ok(true)
+    }
+
+    /// Returns relations of the entity, where it is the subject or the object.
+    pub fn relations_of(&self, entity: &str) -> Vec<Relation> {
+        let graph = self.graph.read().unwrap_or_else(|e| e.into_inner());
+        graph
+            .relations
+            .iter()
+            .filter(|relation| relation.involves(entity.trim()))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns names of all known entities, sorted and without duplicates.
+    pub fn entities(&self) -> Vec<String> {
+        let graph = self.graph.read().unwrap_or_else(|e| e.into_inner());
+        let mut entities: Vec<String> = graph
+            .relations
+            .iter()
+            .flat_map(|relation| [relation.subject.clone(), relation.object.clone()])
+            .collect();
+        entities.sort_by_key(|entity| entity.to_lowercase());
+        entities.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        entities
+    }
+
+    /// Extracts relations from the conversation stored in the history of the agent and adds them
+    /// to the graph, returns number of new relations.
+    ///
+    /// Extraction is done with [Agent::extract], history of the agent is not modified.
+    pub async fn remember_session(&self, agent: &mut Agent, model: &str) -> Result<usize> {
+        let transcript = agent.transcript();
+        if transcript.is_empty() {
+            return Ok(0);
+        }
+        let extracted: ExtractedRelations = agent.extract(model, &transcript).await?;
+        let mut added = 0;
+        for relation in extracted.relations {
+            let relation = Relation::new(&relation.subject, &relation.relation, &relation.object);
+            if relation.subject.is_empty() || relation.object.is_empty() {
+                continue;
+            }
+            if self.add(relation)? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Returns all known facts about the entity, as relations with other entities. Use it to
+    /// recall information about people, organizations, places or projects.
+    #[tool(capabilities = [])]
+    fn find_relations(
+        &self,
+        #[doc = "Name of the entity, e.g. person or organization"] entity: String,
+    ) -> Result<String, ToolError> {
+        let relations = self.relations_of(&entity);
+        if relations.is_empty() {
+            return Ok(format!("Nothing is known about {entity}"));
+        }
+        Ok(relations
+            .iter()
+            .map(|r| format!("{} -- {} --> {}", r.subject, r.relation, r.object))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// Returns names of known entities containing the query, use it when exact name of the entity
+    /// is not known.
+    #[tool(capabilities = [])]
+    fn find_entities(
+        &self,
+        #[doc = "Part of the name of the entity"] query: String,
+    ) -> Result<String, ToolError> {
+        let query = query.trim().to_lowercase();
+        let entities: Vec<String> = self
+            .entities()
+            .into_iter()
+            .filter(|entity| entity.to_lowercase().contains(&query))
+            .collect();
+        if entities.is_empty() {
+            return Ok("No matching entities".to_string());
+        }
+        Ok(entities.join("\n"))
+    }
+
+    /// Remembers fact as relation between two entities, e.g. subject `Alice`, relation `works at`
+    /// and object `Acme`.
+    #[tool(capabilities = [Capability::WriteFs])]
+    fn remember_relation(
+        &self,
+        #[doc = "Name of the entity the relation starts from"] subject: String,
+        #[doc = "Short description of the relation"] relation: String,
+        #[doc = "Name of the related entity"] object: String,
+    ) -> Result<String, ToolError> {
+        let relation = Relation::new(&subject, &relation, &object);
+        if relation.subject.is_empty() || relation.object.is_empty() {
+            return Err(ToolError::Other(anyhow::anyhow!(
+                "Subject and object can't be empty"
+            )));
+        }
+        match self.add(relation)? {
+            true => Ok("Remembered".to_string()),
+            false => Ok("Already known".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_memory() {
+        let memory = GraphMemoryToolBox::new();
+        assert!(memory
+            .add(Relation::new("Alice", "works at", "Acme"))
+            .unwrap());
+        assert!(memory
+            .add(Relation::new("Bob", "manages", "Alice"))
+            .unwrap());
+        assert!(!memory
+            .add(Relation::new("alice", "Works at", "ACME"))
+            .unwrap());
+
+        assert_eq!(memory.relations_of("ALICE").len(), 2);
+        assert_eq!(memory.relations_of("Acme").len(), 1);
+        assert_eq!(memory.entities(), ["Acme", "Alice", "Bob"]);
+        assert_eq!(
+            memory.find_relations("Bob".to_string()).unwrap(),
+            "Bob -- manages --> Alice"
+        );
+    }
+}
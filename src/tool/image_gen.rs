@@ -0,0 +1,351 @@
+//! # Image Generation Tools
+//!
+//! [ImageGenToolBox] lets creative agents produce images with `generate_image` tool, using
+//! [OpenAI Images](https://platform.openai.com/docs/api-reference/images) or
+//! [Stability AI](https://platform.stability.ai/docs/api-reference) API. It is enabled with
+//! `image-gen` feature.
+//!
+//! Generated images are returned as artifacts in one of two forms:
+//! - saved as PNG files in the directory set with [ImageGenToolBox::with_output_dir], the model
+//!   receives their paths. Saving files requires [Capability::WriteFs], so the tool is not exposed
+//!   with read only [sandbox profiles](crate::tool::sandbox::SandboxProfile),
+//! - kept in memory, when no directory is set. The model receives only identifier of the image,
+//!   applications take PNG data from [GeneratedImages] returned by [ImageGenToolBox::images],
+//!   e.g. when they receive [AgentEvent::ToolResult](crate::event::AgentEvent::ToolResult) event.
+//!
+//! ```rust,no_run
+//! use agentai::tool::image_gen::ImageGenToolBox;
+//! use agentai::Agent;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let images = ImageGenToolBox::openai("<OPENAI API KEY>").with_output_dir("./images");
+//! let mut agent = Agent::new("You are illustrator of children's books.");
+//! let answer: String = agent
+//!     .run("gpt-4o", "Draw a fox reading a book under a tree.", Some(Arc::new(images)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tool::{check_response, toolbox, Capability, Tool, ToolBox, ToolError};
+use anyhow::{anyhow, Context};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use reqwest::multipart::Form;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const OPENAI_IMAGES_URL: &str = "https://api.openai.com/v1/images/generations";
+const STABILITY_API_URL: &str = "https://api.stability.ai/v2beta/stable-image/generate";
+/// Default model of OpenAI Images API
+pub const DEFAULT_OPENAI_MODEL: &str = "gpt-image-1";
+/// Default model (endpoint) of Stability AI API
+pub const DEFAULT_STABILITY_MODEL: &str = "core";
+/// Aspect ratios accepted by `generate_image` tool
+const ASPECT_RATIOS: [&str; 5] = ["1:1", "16:9", "9:16", "3:2", "2:3"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Provider {
+    OpenAi,
+    Stability,
+}
+
+/// PNG images generated without output directory, by their identifiers.
+///
+/// Clones share the images, they are kept until they are taken.
+#[derive(Clone, Debug, Default)]
+pub struct GeneratedImages {
+    images: Arc<Mutex<StoredImages>>,
+}
+
+#[derive(Debug, Default)]
+struct StoredImages {
+    next_id: usize,
+    /// Images by their numbers, identifiers are `image-{number}`
+    images: BTreeMap<usize, Vec<u8>>,
+}
+
+impl GeneratedImages {
+    /// Stores the image, returns its identifier.
+    fn push(&self, image: Vec<u8>) -> String {
+        let mut stored = self.images.lock().unwrap_or_else(|e| e.into_inner());
+        stored.next_id += 1;
+        let number = stored.next_id;
+        stored.images.insert(number, image);
+        format!("image-{number}")
+    }
+
+    /// Returns identifiers of images that were not taken yet, oldest first.
+    pub fn ids(&self) -> Vec<String> {
+        let stored = self.images.lock().unwrap_or_else(|e| e.into_inner());
+        stored
+            .images
+            .keys()
+            .map(|number| format!("image-{number}"))
+            .collect()
+    }
+
+    /// Removes the image and returns its PNG data.
+    pub fn take(&self, id: &str) -> Option<Vec<u8>> {
+        let number = id.strip_prefix("image-")?.parse().ok()?;
+        self.images
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .images
+            .remove(&number)
+    }
+}
+
+/// Toolbox generating images, look into [module documentation](crate::tool::image_gen).
+pub struct ImageGenToolBox {
+    client: Client,
+    provider: Provider,
+    api_key: String,
+    model: String,
+    /// Directory where images are saved, they are kept in `images` when `None`
+    output_dir: Option<PathBuf>,
+    images: GeneratedImages,
+}
+
+#[toolbox]
+impl ImageGenToolBox {
+    /// Creates toolbox generating images with OpenAI Images API, with [DEFAULT_OPENAI_MODEL].
+    pub fn openai(api_key: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            provider: Provider::OpenAi,
+            api_key: api_key.to_string(),
+            model: DEFAULT_OPENAI_MODEL.to_string(),
+            output_dir: None,
+            images: GeneratedImages::default(),
+        }
+    }
+
+    /// Creates toolbox generating images with Stability AI API, with [DEFAULT_STABILITY_MODEL].
+    pub fn stability(api_key: &str) -> Self {
+        Self {
+            provider: Provider::Stability,
+            model: DEFAULT_STABILITY_MODEL.to_string(),
+            ..Self::openai(api_key)
+        }
+    }
+
+    /// Sets the model, e.g. `dall-e-3` for OpenAI, or `ultra` and `sd3` for Stability AI.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    /// Saves images as PNG files in the directory, it is created when it doesn't exist.
+    pub fn with_output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// Returns images generated without output directory, the handle can be kept after the
+    /// toolbox is passed to the agent.
+    pub fn images(&self) -> GeneratedImages {
+        self.images.clone()
+    }
+
+    /// Generates image described by the prompt, returns path of the saved image or its identifier.
+    #[tool(capabilities = self.capabilities())]
+    async fn generate_image(
+        &self,
+        #[doc = "Detailed description of the image"] prompt: String,
+        #[doc = "Aspect ratio of the image: 1:1 (default), 16:9, 9:16, 3:2 or 2:3"]
+        aspect_ratio: Option<String>,
+    ) -> Result<String, ToolError> {
+        let aspect_ratio = aspect_ratio.unwrap_or_else(|| "1:1".to_string());
+        if !ASPECT_RATIOS.contains(&aspect_ratio.as_str()) {
+            return Err(anyhow!(
+                "Unsupported aspect ratio {aspect_ratio}, expected one of {}",
+                ASPECT_RATIOS.join(", ")
+            )
+            .into());
+        }
+        let image = match self.provider {
+            Provider::OpenAi => self.generate_openai(&prompt, &aspect_ratio).await?,
+            Provider::Stability => self.generate_stability(&prompt, &aspect_ratio).await?,
+        };
+        let image = STANDARD
+            .decode(image.trim())
+            .context("Image is not valid base64")?;
+        match &self.output_dir {
+            Some(output_dir) => {
+                let path = save_image(output_dir, &image)?;
+                Ok(format!("Image saved to {}", path.display()))
+            }
+            None => Ok(format!("Image generated as `{}`", self.images.push(image))),
+        }
+    }
+
+    /// Capabilities of `generate_image`, saving images requires access to the file system.
+    fn capabilities(&self) -> Vec<Capability> {
+        match self.output_dir {
+            Some(_) => vec![Capability::Network, Capability::WriteFs],
+            None => vec![Capability::Network],
+        }
+    }
+
+    /// Returns base64 encoded PNG image generated by OpenAI.
+    async fn generate_openai(&self, prompt: &str, aspect_ratio: &str) -> Result<String, ToolError> {
+        let dall_e = self.model.starts_with("dall-e");
+        let size = match (aspect_ratio, dall_e) {
+            ("1:1", _) => "1024x1024",
+            ("16:9" | "3:2", false) => "1536x1024",
+            ("9:16" | "2:3", false) => "1024x1536",
+            ("16:9" | "3:2", true) => "1792x1024",
+            _ => "1024x1792",
+        };
+        let mut body = json!({
+            "model": self.model,
+            "prompt": prompt,
+            "size": size,
+            "n": 1,
+        });
+        // GPT image models always return base64, DALL-E returns URLs by default
+        if dall_e {
+            body["response_format"] = json!("b64_json");
+        }
+        let response = self
+            .client
+            .post(OPENAI_IMAGES_URL)
+            .bearer_auth(&self.api_key)
+            .json(&body)
+            .send()
+            .await
+            .context("OpenAI Images request failed")?;
+        let response: Value = check_response(response, "OpenAI Images API")
+            .await?
+            .json()
+            .await
+            .map_err(anyhow::Error::new)?;
+        response["data"][0]["b64_json"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("OpenAI Images API returned no image: {response}").into())
+    }
+
+    /// Returns base64 encoded PNG image generated by Stability AI.
+    async fn generate_stability(
+        &self,
+        prompt: &str,
+        aspect_ratio: &str,
+    ) -> Result<String, ToolError> {
+        let form = Form::new()
+            .text("prompt", prompt.to_string())
+            .text("aspect_ratio", aspect_ratio.to_string())
+            .text("output_format", "png");
+        let response = self
+            .client
+            .post(format!("{STABILITY_API_URL}/{}", self.model))
+            .bearer_auth(&self.api_key)
+            .header("Accept", "application/json")
+            .multipart(form)
+            .send()
+            .await
+            .context("Stability AI request failed")?;
+        let response: Value = check_response(response, "Stability AI API")
+            .await?
+            .json()
+            .await
+            .map_err(anyhow::Error::new)?;
+        if response["finish_reason"] == "CONTENT_FILTERED" {
+            return Err(anyhow!("Image was rejected by the content filter of Stability AI").into());
+        }
+        response["image"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Stability AI API returned no image: {response}").into())
+    }
+}
+
+/// Saves the image as a new file in the directory, existing files are never overwritten.
+fn save_image(output_dir: &Path, image: &[u8]) -> Result<PathBuf, ToolError> {
+    std::fs::create_dir_all(output_dir)
+        .with_context(|| format!("Unable to create {}", output_dir.display()))?;
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    // Images generated in the same millisecond (or by other processes) get the next free number
+    let mut number = 0;
+    loop {
+        let path = output_dir.join(format!("image-{millis}-{number}.png"));
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(image)
+                    .with_context(|| format!("Unable to save {}", path.display()))?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => number += 1,
+            Err(err) => {
+                return Err(anyhow::Error::new(err)
+                    .context(format!("Unable to save {}", path.display()))
+                    .into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_image_gen_toolbox() {
+        let images = ImageGenToolBox::stability("key").with_model("ultra");
+        assert_eq!(images.model, "ultra");
+        assert_eq!(
+            images.tool_capabilities("generate_image"),
+            [Capability::Network]
+        );
+        let images = images.with_output_dir("images");
+        assert_eq!(
+            images.tool_capabilities("generate_image"),
+            [Capability::Network, Capability::WriteFs]
+        );
+        let error = images
+            .generate_image("A fox".to_string(), Some("4:3".to_string()))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Unsupported aspect ratio 4:3"));
+    }
+
+    #[test]
+    fn test_save_image_unique() {
+        let output_dir =
+            std::env::temp_dir().join(format!("agentai-images-{}", std::process::id()));
+        let first = save_image(&output_dir, b"first").unwrap();
+        let second = save_image(&output_dir, b"second").unwrap();
+        let first_content = std::fs::read(&first).unwrap();
+        let second_content = std::fs::read(&second).unwrap();
+        std::fs::remove_dir_all(&output_dir).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first_content, b"first");
+        assert_eq!(second_content, b"second");
+    }
+
+    #[test]
+    fn test_generated_images() {
+        let toolbox = ImageGenToolBox::openai("key");
+        let images = toolbox.images();
+        let first = toolbox.images.push(b"first".to_vec());
+        let second = toolbox.images.push(b"second".to_vec());
+        assert_eq!(images.ids(), [first.clone(), second.clone()]);
+        assert_eq!(images.take(&first).as_deref(), Some(&b"first"[..]));
+        assert_eq!(images.take(&first), None);
+        assert_eq!(images.ids(), [second]);
+    }
+}
@@ -16,21 +16,55 @@
 //!     for the [`ToolBox` trait](crate::tool::ToolBox).
 //!
 //! Ready-to-use `ToolBox` implementations are available:
+//! - [crate::tool::agent_self]: Lets the agent remember facts, compact its history and set reminders.
+//! - [crate::tool::composite]: Merges many toolboxes into one.
 //! - [crate::tool::buildin]: Provides a set of useful built-in tools.
+//! - [crate::tool::calendar]: Events and free slots of Google or CalDAV calendars (requires the `calendar` feature).
+//! - [crate::tool::git]: Status, diff, log, blame and commit of a git repository (requires the `git` feature).
+//! - [crate::tool::graph]: Knowledge graph memory the agent can query (experimental, requires the `macros` feature).
+//! - [crate::tool::image_gen]: Image generation with OpenAI Images or Stability AI (requires the `image-gen` feature).
 //! - [crate::tool::mcp]: A `ToolBox` for interacting with the MCP Client. (Requires the `mcp-client` feature).
+//! - [crate::tool::ocr]: Text of images and PDF documents recognized with Tesseract or vision models (requires
+//!   the `ocr` feature).
+//! - [crate::tool::slack]: Posting messages to Slack channels and reading their history (requires the `slack` feature).
+//! - [crate::tool::tabular]: Schema, filtered preview and SQL aggregations of CSV and Parquet files (requires
+//!   the `tabular` feature).
 //!
 //! For examples demonstrating how to use tools and toolboxes, look into the `examples` folder.
 //! Examples related to tools typically start with the `tools_*` prefix, e.g., [crate::examples::tools_mcp].
 //!
 //! For example demonstrating how to implement `ToolBox` trait using `#[toolbox]` macro, look into [crate::examples::tools_custom] example.
 
-pub mod websearch;
-
+pub mod agent_self;
+#[cfg(feature = "calendar")]
+pub mod calendar;
+pub mod composite;
+pub mod diff;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "macros")]
+pub mod graph;
+#[cfg(feature = "image-gen")]
+pub mod image_gen;
 #[cfg(feature = "mcp-client")]
 pub mod mcp;
+#[cfg(feature = "ocr")]
+pub mod ocr;
+pub mod repair;
+pub mod sandbox;
+#[cfg(feature = "slack")]
+pub mod slack;
+#[cfg(feature = "tabular")]
+pub mod tabular;
+#[cfg(feature = "macros")]
+pub mod websearch;
 
-use thiserror::{Error};
 use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+pub use sandbox::Capability;
 
 // Re-export Tool structure, it is being used by ToolBoxes
 /// Represents a tool definition that can be exposed to an agent.
@@ -47,8 +81,12 @@ use serde_json::Value;
 pub use genai::chat::Tool;
 
 // Re-export tool and toolbox macros, they are used to generate auto implementation of
+#[cfg(feature = "macros")]
 pub use agentai_macros::toolbox;
 
+/// Boxed future returned by [ToolBox::refresh_definitions].
+pub type ToolFuture<'a, T> = futures::future::BoxFuture<'a, T>;
+
 /// Manages a collection of callable `Tool` instances.
 ///
 /// Implementors of `ToolBox` provide a way to group related tools and expose them to the
@@ -72,6 +110,15 @@ pub trait ToolBox {
     /// This method is typically invoked internally by the [`Agent`](crate::agent::Agent) structure to discover the available tools and their parameters.
     fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError>;
 
+    /// Returns definitions of all tools as a shared slice, used by the [`Agent`](crate::agent::Agent).
+    ///
+    /// Toolboxes keeping many or large definitions (e.g. MCP servers) can override it to return
+    /// the same slice every time, without cloning the JSON schemas of the tools. By default it
+    /// wraps [tools_definitions](ToolBox::tools_definitions).
+    fn shared_tools_definitions(&self) -> Result<Arc<[Tool]>, ToolError> {
+        self.tools_definitions().map(Arc::from)
+    }
+
     /// Calls a specific tool by its name with the given parameters.
     ///
     /// This method is the entry point for executing a tool's functionality. It is typically invoked internally by the [`Agent`](crate::agent::Agent) structure
@@ -95,6 +142,126 @@ pub trait ToolBox {
     /// A `Result` containing the tool's output as a `String` on success,
     /// or a `ToolError` if the tool call fails or the tool is not found.
     async fn call_tool(&self, tool_name: String, arguments: Value) -> Result<String, ToolError>;
+
+    /// Fetches tools definitions again, for toolboxes that discover their tools dynamically (e.g.
+    /// MCP servers or tool registries).
+    ///
+    /// [tools_definitions](ToolBox::tools_definitions) is synchronous, so such toolboxes should
+    /// fetch definitions here and return the fetched ones from it. The [`Agent`](crate::agent::Agent)
+    /// calls this method once at the beginning of every run, failures are handled like failures of
+    /// `tools_definitions`, see [ToolDefinitionsFailure](crate::agent::ToolDefinitionsFailure).
+    /// By default it does nothing.
+    ///
+    /// Unlike other async methods it returns boxed future, so it can be called on any `dyn ToolBox`:
+    ///
+    /// ```rust,ignore
+    /// fn refresh_definitions(&self) -> ToolFuture<'_, Result<(), ToolError>> {
+    ///     Box::pin(async move {
+    ///         let tools = self.registry.fetch_tools().await?;
+    ///         *self.tools.write().unwrap() = tools;
+    ///         Ok(())
+    ///     })
+    /// }
+    /// ```
+    fn refresh_definitions(&self) -> ToolFuture<'_, Result<(), ToolError>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Returns view of the toolbox used by a single run, for toolboxes keeping state of the run.
+    ///
+    /// Toolboxes are shared by concurrent runs, so state that must stay the same during the run
+    /// (e.g. snapshot of searched data) can't be stored in the toolbox itself. The
+    /// [`Agent`](crate::agent::Agent) calls this method at the beginning of every run, after
+    /// [refresh_definitions](ToolBox::refresh_definitions), and uses the returned toolbox until the
+    /// run ends. By default it returns `None` and the toolbox itself is used.
+    fn for_run(&self) -> Option<composite::SharedToolBox> {
+        None
+    }
+
+    /// Returns instructions about using tools of the toolbox, e.g. instructions provided by MCP servers.
+    ///
+    /// The [`Agent`](crate::agent::Agent) adds them to the system prompt of every run using the
+    /// toolbox. By default there are no instructions.
+    fn instructions(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns capabilities required by the tool, e.g. access to the network.
+    ///
+    /// The [`Agent`](crate::agent::Agent) uses them to expose only tools allowed by its
+    /// [sandbox profile](crate::tool::sandbox::SandboxProfile). By default capabilities are not
+    /// declared and tools require [Capability::Unknown], so they are exposed only in full trust
+    /// sandbox. Tools that don't need any capabilities should return empty list.
+    /// With the `#[toolbox]` macro capabilities are declared with `#[tool(capabilities = [...])]`.
+    fn tool_capabilities(&self, _tool_name: &str) -> Vec<Capability> {
+        vec![Capability::Unknown]
+    }
+
+    /// Returns name of the toolbox, used to report what is available, e.g. by [describe](ToolBox::describe).
+    ///
+    /// By default it is the name of the type implementing the trait, without module path.
+    fn name(&self) -> &str {
+        let type_name = std::any::type_name::<Self>();
+        let path = type_name.split('<').next().unwrap_or(type_name);
+        path.rsplit("::").next().unwrap_or(path)
+    }
+
+    /// Returns description of the toolbox with all its tools.
+    ///
+    /// By default it is built from [name](ToolBox::name), [tools_definitions](ToolBox::tools_definitions)
+    /// and [tool_capabilities](ToolBox::tool_capabilities).
+    fn describe(&self) -> Result<ToolBoxDescription, ToolError> {
+        let tools = self
+            .tools_definitions()?
+            .into_iter()
+            .map(|tool| ToolDescription {
+                capabilities: self.tool_capabilities(&tool.name),
+                name: tool.name,
+                description: tool.description,
+            })
+            .collect();
+        Ok(ToolBoxDescription {
+            name: self.name().to_string(),
+            tools,
+        })
+    }
+
+    /// Checks whether the toolbox is able to execute tools, e.g. that the server behind it is
+    /// reachable. By default toolbox is always [ToolBoxHealth::Healthy].
+    async fn health_check(&self) -> ToolBoxHealth {
+        ToolBoxHealth::Healthy
+    }
+}
+
+/// Description of the toolbox returned by [ToolBox::describe].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolBoxDescription {
+    /// Name of the toolbox
+    pub name: String,
+    /// Tools provided by the toolbox
+    pub tools: Vec<ToolDescription>,
+}
+
+/// Description of a single tool, part of [ToolBoxDescription].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolDescription {
+    /// Name of the tool
+    pub name: String,
+    /// Description of the tool shown to the model
+    pub description: Option<String>,
+    /// Capabilities required by the tool
+    pub capabilities: Vec<Capability>,
+}
+
+/// Health of the toolbox returned by [ToolBox::health_check].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolBoxHealth {
+    /// All tools can be executed
+    Healthy,
+    /// Some tools may fail, e.g. one of nested toolboxes is unavailable
+    Degraded(String),
+    /// Tools can't be executed
+    Unhealthy(String),
 }
 
 #[derive(Error, Debug)]
@@ -117,9 +284,303 @@ pub enum ToolError {
     /// This is a general error variant that can encapsulate various runtime issues
     /// encountered while the tool's logic is running.
     #[error("Tool execution failed")]
-	ExecutionError,
+    ExecutionError,
+    /// Indicates that arguments provided by the model don't match the schema of the tool.
+    /// The message contains the deserialization error, received arguments and expected schema,
+    /// so the model can correct the call.
+    #[error("Invalid arguments for tool '{tool}': {error}\nReceived arguments: {arguments}\nExpected schema: {schema}")]
+    InvalidArguments {
+        /// Name of the tool
+        tool: String,
+        /// Description of the deserialization error
+        error: String,
+        /// Arguments received from the model
+        arguments: Box<Value>,
+        /// Schema of the tool arguments, `null` when unknown
+        schema: Box<Value>,
+    },
+    /// Indicates that the tool is not authorized to access the resource, e.g. credentials are
+    /// missing or expired. The model can't fix it, so the [Agent](crate::agent::Agent) stops the
+    /// run and returns this error to the caller.
+    #[error("Tool is not authorized: {0}")]
+    Unauthorized(String),
+    /// Indicates that the service used by the tool limits the rate of requests. The model is
+    /// informed when it can try again, so it can continue with other tasks in the meantime.
+    #[error("{}", rate_limited_message(.retry_after))]
+    RateLimited {
+        /// Time after which the call can be retried, when known
+        retry_after: Option<Duration>,
+    },
+    /// Indicates that the tool didn't finish in allowed time. The model can try again, e.g. with
+    /// arguments narrowing the task.
+    #[error("Tool execution timed out")]
+    Timeout,
+    /// Indicates that a call passed through more nested toolboxes than allowed,
+    /// see [CompositeToolBox](crate::tool::composite::CompositeToolBox).
+    #[error("Toolbox nesting exceeds maximal depth of {0}")]
+    MaxDepthExceeded(usize),
+    /// Indicates that a call was delegated back to a toolbox that is already executing it,
+    /// the message contains names of toolboxes on the path of the call.
+    #[error("Delegation cycle detected: {0}")]
+    CycleDetected(String),
+    /// Indicates that a tool name, or a prefix of nested toolbox, is not unique.
+    #[error("Ambiguous tool name '{0}'")]
+    AmbiguousToolName(String),
+    /// Indicates that MCP server failed to execute the tool, see [McpToolBox](crate::tool::mcp::McpToolBox).
+    /// It is returned both for protocol errors, which have JSON-RPC error `code`, and for results
+    /// marked by the server as errors, in which case `message` contains the result.
+    #[error("MCP server '{server}' failed to call '{tool}'{}: {message}", code.map(|code| format!(" (code {code})")).unwrap_or_default())]
+    Mcp {
+        /// Name of the server, the prefix of tool names
+        server: String,
+        /// Name of the tool in the server
+        tool: String,
+        /// JSON-RPC error code, `None` when the call failed without protocol error
+        code: Option<i32>,
+        /// Error text provided by the server
+        message: String,
+    },
+    /// Indicates that the tool panicked. Agents execute tools in separate tasks, so the panic is
+    /// caught and, depending on [ToolPanic](crate::agent::ToolPanic), reported to the model or
+    /// returned to the caller.
+    #[error("Tool panicked: {message}")]
+    Panicked {
+        /// Message of the panic, when it was raised with a string
+        message: String,
+    },
     /// Represents any other underlying error that occurred, wrapped from the `anyhow::Error` type.
     /// This allows for propagating errors from dependencies or other parts of the system.
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
+
+fn rate_limited_message(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(retry_after) => format!(
+            "Tool is rate limited, retry after {} seconds",
+            retry_after.as_secs().max(1)
+        ),
+        None => "Tool is rate limited, retry later".to_string(),
+    }
+}
+
+/// Executes synchronous or CPU heavy tool on the blocking thread pool of the Tokio runtime.
+///
+/// It keeps the async runtime responsive while the tool is running, so other tool calls and
+/// runs of agents can continue. Tools of the [`#[toolbox]`](crate::tool::toolbox) macro use it
+/// when marked with `#[tool(blocking)]`, manual [ToolBox] implementations can call it from
+/// [ToolBox::call_tool] directly:
+///
+/// ```rust
+/// use agentai::tool::{run_blocking, ToolError};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ToolError> {
+/// let digest = run_blocking(|| {
+///     // Synchronous code, e.g. hashing of a large file
+///     Ok("d41d8cd98f00b204e9800998ecf8427e".to_string())
+/// })
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run_blocking<F>(f: F) -> Result<String, ToolError>
+where
+    F: FnOnce() -> Result<String, ToolError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| match err.try_into_panic() {
+            Ok(payload) => ToolError::Panicked {
+                message: panic_message(payload),
+            },
+            Err(err) => anyhow::anyhow!("Blocking tool failed: {err}").into(),
+        })?
+}
+
+/// Returns the response when it is successful, rejected credentials and rate limits are
+/// reported with dedicated [ToolError] variants.
+#[cfg(any(feature = "calendar", feature = "image-gen"))]
+pub(crate) async fn check_response(
+    response: reqwest::Response,
+    service: &str,
+) -> Result<reqwest::Response, ToolError> {
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Err(
+            ToolError::Unauthorized(format!("{service} rejected the credentials")),
+        ),
+        reqwest::StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs);
+            Err(ToolError::RateLimited { retry_after })
+        }
+        status => {
+            let body = response.text().await.unwrap_or_default();
+            Err(anyhow::anyhow!("{service} returned {status}: {body}").into())
+        }
+    }
+}
+
+/// Returns message of the panic, when it was raised with a string.
+pub(crate) fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+    }
+}
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Messages logged at warning level or above
+    static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct WarningRecorder;
+
+    impl log::Log for WarningRecorder {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[derive(Clone)]
+    struct Failing;
+
+    #[toolbox]
+    impl Failing {
+        /// Always fails
+        #[tool(capabilities = [])]
+        fn fail(&self) -> Result<String, ToolError> {
+            Err(ToolError::ExecutionError)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_error_reported() {
+        let _ = log::set_logger(&WarningRecorder);
+        log::set_max_level(log::LevelFilter::Warn);
+        let result = Failing.call_tool("fail".to_string(), json!({})).await;
+        assert!(matches!(result, Err(ToolError::ExecutionError)));
+        // With `observability` feature errors are recorded in `tracing` spans instead
+        if !cfg!(feature = "observability") {
+            let warnings = WARNINGS.lock().unwrap();
+            assert!(warnings
+                .iter()
+                .any(|warning| warning.contains("Tool execution error for 'fail'")));
+        }
+    }
+
+    #[derive(Clone)]
+    struct Declared;
+
+    #[toolbox]
+    impl Declared {
+        /// Writes a file
+        #[tool(capabilities = [])]
+        fn write(&self) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+
+        /// Reads a file
+        #[tool(capabilities = [])]
+        fn read(&self) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+
+        /// Appends to a file
+        #[tool(capabilities = [])]
+        fn append(&self) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+    }
+
+    #[derive(Clone)]
+    struct Sorted;
+
+    #[toolbox(order = "alphabetical")]
+    impl Sorted {
+        /// Writes a file
+        #[tool(capabilities = [])]
+        fn write(&self) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+
+        /// Appends to a file
+        #[tool(capabilities = [])]
+        fn append(&self) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+    }
+
+    fn tool_names(toolbox: &dyn ToolBox) -> Vec<String> {
+        toolbox
+            .tools_definitions()
+            .unwrap()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_tools_keep_declaration_order() {
+        assert_eq!(tool_names(&Declared), ["write", "read", "append"]);
+    }
+
+    #[test]
+    fn test_tools_sorted_alphabetically() {
+        assert_eq!(tool_names(&Sorted), ["append", "write"]);
+    }
+
+    #[derive(Clone)]
+    struct Threads;
+
+    #[toolbox]
+    impl Threads {
+        /// Returns id of the thread executing the tool
+        #[tool(blocking, capabilities = [])]
+        fn thread(&self) -> Result<String, ToolError> {
+            Ok(format!("{:?}", std::thread::current().id()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_blocking() {
+        let result = run_blocking(|| Ok("done".to_string())).await;
+        assert_eq!(result.unwrap(), "done");
+        let result = run_blocking(|| Err(ToolError::ExecutionError)).await;
+        assert!(matches!(result, Err(ToolError::ExecutionError)));
+        let result = run_blocking(|| panic!("hash table full")).await;
+        assert!(matches!(
+            result,
+            Err(ToolError::Panicked { message }) if message == "hash table full"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_blocking_tool_leaves_runtime_thread() {
+        let thread = Threads
+            .call_tool("thread".to_string(), json!({}))
+            .await
+            .unwrap();
+        assert_ne!(thread, format!("{:?}", std::thread::current().id()));
+    }
+}
@@ -0,0 +1,412 @@
+//! # OCR Tools
+//!
+//! [OcrToolBox] lets document-processing agents read scanned documents, photos and screenshots,
+//! also with models that don't accept images. Its `ocr_extract_text` tool returns text of an image
+//! or PDF document, recognized with one of the engines:
+//! - [Tesseract](https://github.com/tesseract-ocr/tesseract), running locally, the `tesseract`
+//!   command has to be installed ([OcrToolBox::tesseract]),
+//! - vision model of any provider supported by [genai], which transcribes the image ([OcrToolBox::vision]).
+//!
+//! PDF documents are handled with `pdftotext` and `pdftoppm` commands of
+//! [Poppler](https://poppler.freedesktop.org). Text of pages with a text layer is extracted
+//! directly, scanned pages are rendered and recognized. At most [DEFAULT_MAX_PAGES] pages are read
+//! by a single call (configurable with [OcrToolBox::with_max_pages]).
+//!
+//! Documents are files on the local file system, or attachments of the conversation registered
+//! with [OcrToolBox::with_document] and referenced by their names. Text can be also extracted
+//! without the agent with [OcrToolBox::extract_text], e.g. to include it in the prompt. It is
+//! enabled with `ocr` feature.
+//!
+//! ```rust,no_run
+//! use agentai::tool::ocr::OcrToolBox;
+//! use agentai::Agent;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let invoice = std::fs::read("invoice.png")?;
+//! let ocr = OcrToolBox::tesseract()
+//!     .with_languages("eng+deu")
+//!     .with_document("invoice", invoice, "image/png");
+//! let mut agent = Agent::new("You are accountant, answer questions about documents.");
+//! let answer: String = agent
+//!     .run("gpt-4o-mini", "What is the total amount of the invoice?", Some(Arc::new(ocr)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
+use anyhow::{anyhow, Context};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use genai::chat::{ChatMessage, ChatRequest, ContentPart, MessageContent};
+use genai::Client;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Default maximal number of PDF pages read by a single call
+pub const DEFAULT_MAX_PAGES: u32 = 20;
+/// Resolution of rendered PDF pages, recommended for Tesseract
+const RENDER_DPI: &str = "300";
+const VISION_PROMPT: &str = "Transcribe all text in the image exactly as it is written, keeping \
+    the reading order and line breaks. Return only the text, or nothing when there is no text.";
+
+/// Engine recognizing text in images
+#[derive(Clone)]
+enum OcrEngine {
+    Tesseract { languages: String },
+    Vision { client: Client, model: String },
+}
+
+/// Document registered with [OcrToolBox::with_document]
+#[derive(Clone)]
+struct Document {
+    data: Arc<[u8]>,
+    content_type: String,
+}
+
+/// Toolbox extracting text from images and PDF documents, look into [module documentation](crate::tool::ocr).
+#[derive(Clone)]
+pub struct OcrToolBox {
+    engine: OcrEngine,
+    documents: BTreeMap<String, Document>,
+    max_pages: u32,
+}
+
+#[toolbox]
+impl OcrToolBox {
+    /// Creates toolbox recognizing text with Tesseract, in English by default.
+    pub fn tesseract() -> Self {
+        Self {
+            engine: OcrEngine::Tesseract {
+                languages: "eng".to_string(),
+            },
+            documents: BTreeMap::new(),
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+
+    /// Creates toolbox transcribing images with the vision model, e.g. `gpt-4o-mini`.
+    pub fn vision(model: &str) -> Self {
+        Self {
+            engine: OcrEngine::Vision {
+                client: crate::agent::default_client(),
+                model: model.to_string(),
+            },
+            ..Self::tesseract()
+        }
+    }
+
+    /// Sets languages of Tesseract, e.g. `eng+deu`, their trained data has to be installed.
+    ///
+    /// Vision models recognize languages on their own, the setting is ignored.
+    pub fn with_languages(mut self, languages: &str) -> Self {
+        if let OcrEngine::Tesseract { languages: current } = &mut self.engine {
+            *current = languages.to_string();
+        }
+        self
+    }
+
+    /// Sets GenAI client used by the vision model, e.g. with custom credentials.
+    pub fn with_client(mut self, client: Client) -> Self {
+        if let OcrEngine::Vision {
+            client: current, ..
+        } = &mut self.engine
+        {
+            *current = client;
+        }
+        self
+    }
+
+    /// Registers document, e.g. an attachment of the conversation, the model references it by the name.
+    pub fn with_document(mut self, name: &str, data: Vec<u8>, content_type: &str) -> Self {
+        self.documents.insert(
+            name.to_string(),
+            Document {
+                data: data.into(),
+                content_type: content_type.to_string(),
+            },
+        );
+        self
+    }
+
+    /// Sets maximal number of PDF pages read by a single call, default is [DEFAULT_MAX_PAGES].
+    pub fn with_max_pages(mut self, max_pages: u32) -> Self {
+        self.max_pages = max_pages.max(1);
+        self
+    }
+
+    /// Extracts text from the image (PNG, JPEG, TIFF, ...) or PDF document with OCR.
+    #[tool(capabilities = self.capabilities())]
+    async fn ocr_extract_text(
+        &self,
+        #[doc = "Path of the file, or name of the attached document"] source: String,
+        #[doc = "First page of PDF document to read, starting from 1"] first_page: Option<u32>,
+        #[doc = "Last page of PDF document to read"] last_page: Option<u32>,
+    ) -> Result<String, ToolError> {
+        let (data, content_type) = match self.documents.get(&source) {
+            Some(document) => (document.data.to_vec(), document.content_type.clone()),
+            None => {
+                let path = PathBuf::from(&source);
+                let content_type = content_type(&path).ok_or_else(|| {
+                    let documents = self.documents.keys().cloned().collect::<Vec<_>>();
+                    anyhow!(
+                        "Unknown document {source}, expected path of an image or PDF, or one of: {}",
+                        documents.join(", ")
+                    )
+                })?;
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("Unable to read {}", path.display()))?;
+                (data, content_type.to_string())
+            }
+        };
+        let text = self
+            .extract_text(data, &content_type, first_page, last_page)
+            .await?;
+        match text.trim() {
+            "" => Ok("No text was recognized".to_string()),
+            text => Ok(text.to_string()),
+        }
+    }
+
+    /// Extracts text from the image or PDF document, `first_page` and `last_page` select pages of PDF.
+    pub async fn extract_text(
+        &self,
+        data: Vec<u8>,
+        content_type: &str,
+        first_page: Option<u32>,
+        last_page: Option<u32>,
+    ) -> Result<String, ToolError> {
+        if content_type != "application/pdf" {
+            return self.recognize(data, content_type).await;
+        }
+        let max_pages = self.max_pages;
+        let pages = tokio::task::spawn_blocking(move || {
+            read_pdf(&data, first_page.unwrap_or(1), last_page, max_pages)
+        })
+        .await
+        .map_err(|err| anyhow!("Reading of PDF failed: {err}"))??;
+        let mut result = vec![];
+        for (page, content) in pages {
+            let text = match content {
+                PdfPage::Text(text) => text,
+                PdfPage::Scan(image) => self.recognize(image, "image/png").await?,
+            };
+            result.push(format!("--- Page {page} ---\n{}", text.trim()));
+        }
+        Ok(result.join("\n\n"))
+    }
+
+    /// Capabilities of `ocr_extract_text`, PDF documents are always handled by local commands.
+    fn capabilities(&self) -> Vec<Capability> {
+        match self.engine {
+            OcrEngine::Tesseract { .. } => vec![Capability::ReadFs, Capability::Process],
+            OcrEngine::Vision { .. } => {
+                vec![Capability::ReadFs, Capability::Process, Capability::Network]
+            }
+        }
+    }
+
+    /// Recognizes text of the image with the engine.
+    async fn recognize(&self, image: Vec<u8>, content_type: &str) -> Result<String, ToolError> {
+        match &self.engine {
+            OcrEngine::Tesseract { languages } => {
+                let languages = languages.clone();
+                crate::tool::run_blocking(move || {
+                    let output = run_command(
+                        "tesseract",
+                        &["stdin", "stdout", "-l", &languages],
+                        Some(&image),
+                    )?;
+                    Ok(String::from_utf8_lossy(&output).to_string())
+                })
+                .await
+            }
+            OcrEngine::Vision { client, model } => {
+                let parts = vec![
+                    ContentPart::from_text(VISION_PROMPT),
+                    ContentPart::from_image_base64(content_type, STANDARD.encode(&image)),
+                ];
+                let chat_req = ChatRequest::new(vec![ChatMessage::user(parts)]);
+                let response = client
+                    .exec_chat(model, chat_req, None)
+                    .await
+                    .context("Vision model request failed")?;
+                Ok(response
+                    .content
+                    .into_iter()
+                    .find_map(|content| match content {
+                        MessageContent::Text(text) => Some(text),
+                        _ => None,
+                    })
+                    .unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Content of PDF page
+enum PdfPage {
+    /// Text extracted from the text layer
+    Text(String),
+    /// Rendered PNG image of the page without text layer
+    Scan(Vec<u8>),
+}
+
+/// Returns content of pages from `first` to `last`, at most `max_pages` pages are read.
+fn read_pdf(
+    data: &[u8],
+    first: u32,
+    last: Option<u32>,
+    max_pages: u32,
+) -> Result<Vec<(u32, PdfPage)>, ToolError> {
+    // Directory with random name, readable only by the current user, removed when dropped
+    let directory = tempfile::Builder::new()
+        .prefix("agentai-ocr-")
+        .tempdir()
+        .context("Unable to create temporary directory")?;
+    let path = directory.path().join("document.pdf");
+    std::fs::write(&path, data).context("Unable to save PDF document")?;
+    let info = run_command("pdfinfo", &[path_str(&path)?], None)?;
+    let pages = String::from_utf8_lossy(&info)
+        .lines()
+        .find_map(|line| line.strip_prefix("Pages:"))
+        .and_then(|pages| pages.trim().parse::<u32>().ok())
+        .ok_or_else(|| anyhow!("Unable to read number of pages of PDF document"))?;
+    let first = first.max(1);
+    let last = last
+        .unwrap_or(pages)
+        .min(pages)
+        .min(first.saturating_add(max_pages - 1));
+    if first > last {
+        return Err(anyhow!("Document has {pages} pages, page {first} doesn't exist").into());
+    }
+    let mut result = vec![];
+    for page in first..=last {
+        let number = page.to_string();
+        let range = ["-f", &number, "-l", &number];
+        let mut args = range.to_vec();
+        args.extend(["-layout", path_str(&path)?, "-"]);
+        let text = String::from_utf8_lossy(&run_command("pdftotext", &args, None)?).to_string();
+        if !text.trim().is_empty() {
+            result.push((page, PdfPage::Text(text)));
+            continue;
+        }
+        let image = directory.path().join(format!("page-{page}"));
+        let mut args = range.to_vec();
+        args.extend([
+            "-png",
+            "-singlefile",
+            "-r",
+            RENDER_DPI,
+            path_str(&path)?,
+            path_str(&image)?,
+        ]);
+        run_command("pdftoppm", &args, None)?;
+        let image = std::fs::read(image.with_extension("png"))
+            .with_context(|| format!("Unable to render page {page} of PDF document"))?;
+        result.push((page, PdfPage::Scan(image)));
+    }
+    Ok(result)
+}
+
+/// Runs the command, returning its standard output.
+fn run_command(program: &str, args: &[&str], stdin: Option<&[u8]>) -> Result<Vec<u8>, ToolError> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Unable to run {program}, is it installed?"))?;
+    if let Some(input) = stdin {
+        let mut pipe = child.stdin.take().context("Unable to write to stdin")?;
+        pipe.write_all(input)
+            .with_context(|| format!("Unable to pass input to {program}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("{program} failed"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{program} failed: {}", stderr.trim()).into());
+    }
+    Ok(output.stdout)
+}
+
+fn path_str(path: &Path) -> Result<&str, ToolError> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("Invalid path {}", path.display()).into())
+}
+
+/// Returns content type of supported document, recognized from the extension of the path.
+fn content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "pdf" => Some("application/pdf"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "bmp" => Some("image/bmp"),
+        "tif" | "tiff" => Some("image/tiff"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ocr_toolbox() {
+        assert_eq!(
+            content_type(Path::new("scans/page.TIFF")),
+            Some("image/tiff")
+        );
+        assert_eq!(content_type(Path::new("notes.txt")), None);
+
+        let ocr = OcrToolBox::tesseract().with_document("invoice", vec![], "image/png");
+        assert_eq!(
+            ocr.tool_capabilities("ocr_extract_text"),
+            [Capability::ReadFs, Capability::Process]
+        );
+        let error = ocr
+            .ocr_extract_text("notes.txt".to_string(), None, None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("one of: invoice"));
+
+        let ocr = OcrToolBox::vision("gpt-4o-mini").with_languages("deu");
+        assert!(ocr
+            .tool_capabilities("ocr_extract_text")
+            .contains(&Capability::Network));
+    }
+
+    #[test]
+    fn test_read_pdf_cleanup() {
+        let temporary = || {
+            std::fs::read_dir(std::env::temp_dir())
+                .unwrap()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_string_lossy()
+                        .starts_with("agentai-ocr-")
+                })
+                .count()
+        };
+        let before = temporary();
+        // Fails whether poppler is installed or not, the document is not PDF
+        assert!(read_pdf(b"not a PDF document", 1, None, 10).is_err());
+        assert_eq!(temporary(), before);
+    }
+}
@@ -0,0 +1,170 @@
+//! # Tool Arguments Repair
+//!
+//! Models sometimes provide arguments that don't exactly match the schema of the tool, e.g. number
+//! as a string (`"5"` instead of `5`) or single value instead of an array. Such arguments are
+//! rejected by `serde`, even if the intent is clear. [coerce_arguments] fixes most common mistakes,
+//! using the schema of the tool as a guide.
+//!
+//! When arguments can't be repaired, toolboxes should return [ToolError::InvalidArguments](crate::tool::ToolError::InvalidArguments),
+//! the message of this error contains everything the model needs to correct the call.
+
+use serde_json::{Map, Value};
+
+/// Converts arguments to types expected by the schema, where conversion is unambiguous.
+///
+/// Following coercions are applied, recursively for nested objects and arrays:
+/// - string containing a number into `integer` or `number`,
+/// - `"true"` or `"false"` into `boolean`,
+/// - string containing JSON into `object` or `array`,
+/// - single value into `array` with one item.
+///
+/// Values that can't be converted are left untouched.
+pub fn coerce_arguments(arguments: Value, schema: &Value) -> Value {
+    let definitions = ["$defs", "definitions"]
+        .iter()
+        .filter_map(|key| schema.get(*key).and_then(Value::as_object))
+        .fold(Map::new(), |mut definitions, defs| {
+            definitions.extend(defs.clone());
+            definitions
+        });
+    coerce(arguments, schema, &definitions)
+}
+
+fn coerce(value: Value, schema: &Value, definitions: &Map<String, Value>) -> Value {
+    let schema = resolve(schema, definitions);
+    let Some(expected) = expected_type(schema) else {
+        return value;
+    };
+
+    match (expected, value) {
+        ("integer", Value::String(text)) => match text.trim().parse::<i64>() {
+            Ok(number) => Value::from(number),
+            Err(_) => Value::String(text),
+        },
+        ("number", Value::String(text)) => match text.trim().parse::<f64>() {
+            Ok(number) => serde_json::Number::from_f64(number)
+                .map(Value::Number)
+                .unwrap_or(Value::String(text)),
+            Err(_) => Value::String(text),
+        },
+        ("boolean", Value::String(text)) => match text.trim() {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => Value::String(text),
+        },
+        ("object" | "array", Value::String(text))
+            if matches!(
+                serde_json::from_str(&text),
+                Ok(Value::Object(_) | Value::Array(_))
+            ) =>
+        {
+            let parsed = serde_json::from_str(&text).unwrap_or(Value::String(text));
+            coerce(parsed, schema, definitions)
+        }
+        ("object", Value::Object(object)) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            Value::Object(
+                object
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = match properties.and_then(|p| p.get(&name)) {
+                            Some(property) => coerce(value, property, definitions),
+                            None => value,
+                        };
+                        (name, value)
+                    })
+                    .collect(),
+            )
+        }
+        ("array", Value::Array(items)) => match schema.get("items") {
+            Some(item_schema) => Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| coerce(item, item_schema, definitions))
+                    .collect(),
+            ),
+            None => Value::Array(items),
+        },
+        ("array", Value::Null) => Value::Null,
+        ("array", value) => {
+            let item = match schema.get("items") {
+                Some(item_schema) => coerce(value, item_schema, definitions),
+                None => value,
+            };
+            Value::Array(vec![item])
+        }
+        (_, value) => value,
+    }
+}
+
+/// Follows `$ref` to the definition of subschema.
+fn resolve<'a>(schema: &'a Value, definitions: &'a Map<String, Value>) -> &'a Value {
+    match schema.get("$ref").and_then(Value::as_str) {
+        Some(reference) => reference
+            .rsplit('/')
+            .next()
+            .and_then(|name| definitions.get(name))
+            .unwrap_or(schema),
+        None => schema,
+    }
+}
+
+/// Returns type expected by the schema, ignoring `null` of optional values.
+fn expected_type(schema: &Value) -> Option<&str> {
+    match schema.get("type")? {
+        Value::String(expected) => Some(expected),
+        Value::Array(types) => {
+            let mut types = types
+                .iter()
+                .filter_map(Value::as_str)
+                .filter(|t| *t != "null");
+            let expected = types.next()?;
+            // With many possible types conversion is ambiguous
+            types.next().is_none().then_some(expected)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_coerce_arguments() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "count": {"type": "integer"},
+                "ratio": {"type": ["number", "null"]},
+                "enabled": {"type": "boolean"},
+                "tags": {"type": "array", "items": {"type": "string"}},
+                "inner": {"$ref": "#/$defs/Inner"},
+                "name": {"type": "string"}
+            },
+            "$defs": {
+                "Inner": {"type": "object", "properties": {"ids": {"type": "array", "items": {"type": "integer"}}}}
+            }
+        });
+        let arguments = json!({
+            "count": "5",
+            "ratio": "0.5",
+            "enabled": "true",
+            "tags": "rust",
+            "inner": "{\"ids\": \"7\"}",
+            "name": "12"
+        });
+        assert_eq!(
+            coerce_arguments(arguments, &schema),
+            json!({
+                "count": 5,
+                "ratio": 0.5,
+                "enabled": true,
+                "tags": ["rust"],
+                "inner": {"ids": [7]},
+                "name": "12"
+            })
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! # Sandbox Profiles
+//!
+//! Tools can interact with the environment in a way that is not always desired, e.g. modify files
+//! or access the network. Toolboxes declare [Capability] required by each of their tools
+//! (see [ToolBox::tool_capabilities](crate::tool::ToolBox::tool_capabilities)), and the agent exposes
+//! only tools allowed by its [SandboxProfile]. Calls to other tools are rejected. Tools that don't
+//! declare their capabilities require [Capability::Unknown], so restricted profiles reject them.
+//!
+//! This makes it easy to run the same set of toolboxes in "safe demo" and "trusted ops" modes:
+//!
+//! ```rust
+//! use agentai::Agent;
+//! use agentai::tool::sandbox::SandboxProfile;
+//!
+//! let agent = Agent::builder()
+//!     .with_sandbox(SandboxProfile::ReadOnlyFs)
+//!     .build();
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Capability that tool requires to work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Reads files from the local file system
+    ReadFs,
+    /// Creates, modifies or removes files from the local file system
+    WriteFs,
+    /// Accesses the network, e.g. calls external APIs
+    Network,
+    /// Spawns processes, these can do anything user is allowed to
+    Process,
+    /// Capabilities of the tool are not declared, so it may do anything. Such tools are allowed
+    /// only by [SandboxProfile::FullTrust].
+    Unknown,
+}
+
+/// Named set of capabilities allowed for tools used by the agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxProfile {
+    /// Tools can only read files, they can't modify files, access the network or spawn processes
+    ReadOnlyFs,
+    /// Tools can read files and access the network, but can't modify files or spawn processes
+    ReadOnlyFsWithNetwork,
+    /// Tools can access the local file system, but can't access the network or spawn processes
+    NetworkRestricted,
+    /// All tools are allowed, this is the default profile
+    #[default]
+    FullTrust,
+}
+
+impl SandboxProfile {
+    /// Returns `true` when capability is allowed by this profile.
+    pub fn allows(&self, capability: Capability) -> bool {
+        match self {
+            SandboxProfile::ReadOnlyFs => matches!(capability, Capability::ReadFs),
+            SandboxProfile::ReadOnlyFsWithNetwork => {
+                matches!(capability, Capability::ReadFs | Capability::Network)
+            }
+            SandboxProfile::NetworkRestricted => {
+                matches!(capability, Capability::ReadFs | Capability::WriteFs)
+            }
+            SandboxProfile::FullTrust => true,
+        }
+    }
+
+    /// Returns `true` when all capabilities are allowed by this profile.
+    pub fn allows_all(&self, capabilities: &[Capability]) -> bool {
+        capabilities
+            .iter()
+            .all(|capability| self.allows(*capability))
+    }
+}
+
+impl Display for SandboxProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxProfile::ReadOnlyFs => write!(f, "read-only file system"),
+            SandboxProfile::ReadOnlyFsWithNetwork => {
+                write!(f, "read-only file system with network")
+            }
+            SandboxProfile::NetworkRestricted => write!(f, "network restricted"),
+            SandboxProfile::FullTrust => write!(f, "full trust"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tool::{Tool, ToolBox, ToolError};
+    use serde_json::Value;
+
+    /// Toolbox that doesn't declare capabilities of its tools
+    struct Undeclared;
+
+    #[async_trait::async_trait]
+    impl ToolBox for Undeclared {
+        fn tools_definitions(&self) -> Result<Vec<Tool>, ToolError> {
+            Ok(vec![Tool::new("run")])
+        }
+
+        async fn call_tool(
+            &self,
+            _tool_name: String,
+            _arguments: Value,
+        ) -> Result<String, ToolError> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn test_undeclared_capabilities() {
+        let capabilities = Undeclared.tool_capabilities("run");
+        assert_eq!(capabilities, [Capability::Unknown]);
+        assert!(!SandboxProfile::ReadOnlyFs.allows_all(&capabilities));
+        assert!(!SandboxProfile::NetworkRestricted.allows_all(&capabilities));
+        assert!(SandboxProfile::FullTrust.allows_all(&capabilities));
+        assert!(SandboxProfile::ReadOnlyFs.allows_all(&[]));
+    }
+
+    #[test]
+    fn test_profiles() {
+        use Capability::*;
+        let allowed = |profile: SandboxProfile| {
+            [ReadFs, WriteFs, Network, Process, Unknown]
+                .into_iter()
+                .filter(|capability| profile.allows(*capability))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(allowed(SandboxProfile::ReadOnlyFs), [ReadFs]);
+        assert_eq!(
+            allowed(SandboxProfile::ReadOnlyFsWithNetwork),
+            [ReadFs, Network]
+        );
+        assert_eq!(
+            allowed(SandboxProfile::NetworkRestricted),
+            [ReadFs, WriteFs]
+        );
+        assert_eq!(allowed(SandboxProfile::FullTrust).len(), 5);
+        assert!(!SandboxProfile::ReadOnlyFs.allows_all(&[ReadFs, Network]));
+        assert_eq!(
+            serde_json::to_string(&SandboxProfile::ReadOnlyFsWithNetwork).unwrap(),
+            r#""read_only_fs_with_network""#
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! # Slack Tools
+//!
+//! [SlackToolBox] lets the agent post messages to Slack channels and read their history with
+//! `slack_post_message` and `slack_channel_history` tools, using the Slack Web API. It is enabled
+//! with `slack` feature, together with [SlackConnector](crate::connectors::slack::SlackConnector)
+//! serving agents in Slack.
+//!
+//! The bot token needs `chat:write` scope for posting and `channels:history` (`groups:history`,
+//! `im:history` for private channels and direct messages) for reading. The toolbox can be further
+//! scoped to selected channels with [SlackToolBox::with_channels], calls with other channels are
+//! rejected before they reach Slack.
+//!
+//! ```rust,no_run
+//! use agentai::tool::slack::SlackToolBox;
+//! use agentai::Agent;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let slack = SlackToolBox::new("<BOT TOKEN>").with_channels(&["C0123456789"]);
+//! let mut agent = Agent::new("You are release manager, announce releases in #releases (C0123456789).");
+//! let answer: String = agent
+//!     .run("gpt-4o", "Announce release 1.2.0", Some(Arc::new(slack)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
+use anyhow::{anyhow, Context, Result};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+
+const SLACK_API_URL: &str = "https://slack.com/api";
+/// Number of messages returned by `slack_channel_history` when the model doesn't ask for other number
+const DEFAULT_HISTORY_LEN: u32 = 20;
+/// Maximal number of messages returned by Slack in a single request
+const MAX_HISTORY_LEN: u32 = 200;
+
+/// Toolbox posting to and reading from Slack channels, look into [module documentation](crate::tool::slack).
+pub struct SlackToolBox {
+    client: Client,
+    token: String,
+    /// Channels the tools can work with, all channels visible to the bot when empty
+    channels: Vec<String>,
+}
+
+#[toolbox]
+impl SlackToolBox {
+    /// Creates toolbox using the bot token (`xoxb-...`).
+    pub fn new(token: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            token: token.to_string(),
+            channels: vec![],
+        }
+    }
+
+    /// Limits tools to the channels (by id, e.g. `C0123456789`).
+    pub fn with_channels(mut self, channels: &[&str]) -> Self {
+        self.channels = channels.iter().map(|channel| channel.to_string()).collect();
+        self
+    }
+
+    /// Checks that the channel is in scope of the toolbox.
+    fn check_channel(&self, channel: &str) -> Result<(), ToolError> {
+        if self.channels.is_empty() || self.channels.iter().any(|allowed| allowed == channel) {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "Channel {channel} is not allowed, allowed channels: {}",
+            self.channels.join(", ")
+        )
+        .into())
+    }
+
+    /// Posts the message to the Slack channel, returns timestamp identifying the message.
+    #[tool(capabilities = [Capability::Network])]
+    async fn slack_post_message(
+        &self,
+        #[doc = "Id of the channel, e.g. C0123456789"] channel: String,
+        #[doc = "Text of the message, Slack mrkdwn formatting is supported"] text: String,
+        #[doc = "Timestamp of the parent message, to reply in its thread"] thread_ts: Option<
+            String,
+        >,
+    ) -> Result<String, ToolError> {
+        self.check_channel(&channel)?;
+        let response = call_api(
+            &self.client,
+            &self.token,
+            "chat.postMessage",
+            &PostMessage {
+                channel: &channel,
+                text: &text,
+                thread_ts: thread_ts.as_deref(),
+            },
+        )
+        .await?;
+        let ts = response["ts"].as_str().unwrap_or_default();
+        Ok(format!("Message posted, ts: {ts}"))
+    }
+
+    /// Returns recent messages of the Slack channel, oldest first, with timestamp and user id.
+    #[tool(capabilities = [Capability::Network])]
+    async fn slack_channel_history(
+        &self,
+        #[doc = "Id of the channel, e.g. C0123456789"] channel: String,
+        #[doc = "Maximal number of messages, 20 by default"] limit: Option<u32>,
+    ) -> Result<String, ToolError> {
+        self.check_channel(&channel)?;
+        let limit = limit
+            .unwrap_or(DEFAULT_HISTORY_LEN)
+            .clamp(1, MAX_HISTORY_LEN);
+        let response = call_api(
+            &self.client,
+            &self.token,
+            "conversations.history",
+            &[("channel", channel.as_str()), ("limit", &limit.to_string())],
+        )
+        .await?;
+        let messages = response["messages"].as_array().cloned().unwrap_or_default();
+        if messages.is_empty() {
+            return Ok("No messages".to_string());
+        }
+        // Slack returns the newest messages first
+        Ok(messages
+            .iter()
+            .rev()
+            .map(|message| {
+                let author = message["user"]
+                    .as_str()
+                    .or(message["bot_id"].as_str())
+                    .unwrap_or("unknown");
+                format!(
+                    "[{}] {author}: {}",
+                    message["ts"].as_str().unwrap_or_default(),
+                    message["text"].as_str().unwrap_or_default()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct PostMessage<'a> {
+    pub(crate) channel: &'a str,
+    pub(crate) text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) thread_ts: Option<&'a str>,
+}
+
+/// Calls method of Slack Web API with form encoded arguments, returns the response.
+///
+/// Slack reports errors with `"ok": false` in successful HTTP responses, they are returned as errors.
+pub(crate) async fn call_api(
+    client: &Client,
+    token: &str,
+    method: &str,
+    arguments: &impl Serialize,
+) -> Result<Value> {
+    let response: Value = client
+        .post(format!("{SLACK_API_URL}/{method}"))
+        .bearer_auth(token)
+        .form(arguments)
+        .send()
+        .await
+        .with_context(|| format!("Slack API {method} failed"))?
+        .error_for_status()?
+        .json()
+        .await?;
+    if response["ok"].as_bool() != Some(true) {
+        let error = response["error"].as_str().unwrap_or("unknown error");
+        return Err(anyhow!("Slack API {method} failed: {error}"));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slack_channels() {
+        let slack = SlackToolBox::new("xoxb-token");
+        assert!(slack.check_channel("C1").is_ok());
+        let slack = slack.with_channels(&["C1", "C2"]);
+        assert!(slack.check_channel("C2").is_ok());
+        assert!(slack.check_channel("C3").is_err());
+        let names: Vec<String> = slack
+            .tools_definitions()
+            .unwrap()
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        assert_eq!(names, ["slack_post_message", "slack_channel_history"]);
+    }
+}
@@ -0,0 +1,429 @@
+//! # Tabular Data Tools
+//!
+//! [TabularToolBox] lets data-question agents work with CSV and Parquet files without pasting them
+//! into the context. Files are loaded lazily with [polars](https://docs.rs/polars), the agent
+//! inspects their schema with `tabular_schema`, looks at filtered rows with `tabular_preview` and
+//! answers questions with SQL aggregations in `tabular_query`. It is enabled with `tabular` feature.
+//!
+//! Every file is a table, which name is the file stem (or the name provided with
+//! [TabularToolBox::with_table]). Tools return at most [DEFAULT_MAX_ROWS] rows (configurable with
+//! [TabularToolBox::with_max_rows]) formatted as CSV, the model is told when rows were omitted.
+//! Queries can only read these tables, table functions reading other files (e.g. `read_csv`) are
+//! rejected.
+//!
+//! ```rust,no_run
+//! use agentai::tool::tabular::TabularToolBox;
+//! use agentai::Agent;
+//! use std::sync::Arc;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let tables = TabularToolBox::new()
+//!     .with_file("data/sales.csv")?
+//!     .with_file("data/customers.parquet")?;
+//! let mut agent = Agent::new("You are data analyst, answer questions using the tables.");
+//! let answer: String = agent
+//!     .run("gpt-4o", "Which region had the highest revenue in 2024?", Some(Arc::new(tables)), None, None)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
+use anyhow::{anyhow, Context, Result};
+use polars::prelude::*;
+use polars::sql::SQLContext;
+use sqlparser::ast::{Expr, ObjectName, Query, Statement, TableFactor, Visit, Visitor};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::{Parser, ParserOptions};
+use sqlparser::tokenizer::Token;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+/// Default maximal number of rows returned by tools
+pub const DEFAULT_MAX_ROWS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Csv { separator: u8 },
+    Parquet,
+}
+
+#[derive(Debug, Clone)]
+struct Table {
+    path: PathBuf,
+    format: Format,
+}
+
+impl Table {
+    /// Returns lazy scan of the file, it is read only when the query is executed.
+    fn scan(&self) -> Result<LazyFrame> {
+        let path = PlPath::Local(self.path.as_path().into());
+        let frame = match self.format {
+            Format::Csv { separator } => LazyCsvReader::new(path)
+                .with_has_header(true)
+                .with_separator(separator)
+                .finish(),
+            Format::Parquet => LazyFrame::scan_parquet(path, ScanArgsParquet::default()),
+        };
+        frame.with_context(|| format!("Unable to read {}", self.path.display()))
+    }
+}
+
+/// Toolbox querying CSV and Parquet files, look into [module documentation](crate::tool::tabular).
+#[derive(Debug, Clone)]
+pub struct TabularToolBox {
+    tables: BTreeMap<String, Table>,
+    max_rows: usize,
+}
+
+impl Default for TabularToolBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[toolbox]
+impl TabularToolBox {
+    /// Creates toolbox without tables.
+    pub fn new() -> Self {
+        Self {
+            tables: BTreeMap::new(),
+            max_rows: DEFAULT_MAX_ROWS,
+        }
+    }
+
+    /// Adds the file as a table named after the file stem, e.g. `sales` for `data/sales.csv`.
+    ///
+    /// Format is recognized from the extension: `csv`, `tsv` or `parquet`.
+    pub fn with_file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Invalid file path: {}", path.display()))?;
+        self.with_table(&name, path)
+    }
+
+    /// Adds the file as a table with the name, format is recognized from the extension.
+    pub fn with_table(mut self, name: &str, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let format = match extension.as_deref() {
+            Some("csv") => Format::Csv { separator: b',' },
+            Some("tsv") => Format::Csv { separator: b'\t' },
+            Some("parquet") => Format::Parquet,
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported file {}, expected .csv, .tsv or .parquet",
+                    path.display()
+                ))
+            }
+        };
+        if !path.is_file() {
+            return Err(anyhow!("File {} doesn't exist", path.display()));
+        }
+        self.tables.insert(
+            name.to_string(),
+            Table {
+                path: path.to_path_buf(),
+                format,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Sets maximal number of rows returned by tools, default is [DEFAULT_MAX_ROWS].
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows.max(1);
+        self
+    }
+
+    /// Returns tables with their columns, data types and number of rows.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn tabular_schema(
+        &self,
+        #[doc = "Name of the table, all tables by default"] table: Option<String>,
+    ) -> Result<String, ToolError> {
+        let names = match table {
+            Some(table) => {
+                self.table(&table)?;
+                vec![table]
+            }
+            None => self.tables.keys().cloned().collect(),
+        };
+        let mut result = vec![];
+        for name in names {
+            let mut frame = self.table(&name)?.scan()?;
+            let schema = frame.collect_schema().map_err(anyhow::Error::new)?;
+            let rows = frame
+                .select([len()])
+                .collect()
+                .map_err(anyhow::Error::new)?
+                .column("len")
+                .ok()
+                .and_then(|column| column.get(0).ok())
+                .map(|rows| rows.to_string())
+                .unwrap_or_default();
+            result.push(format!("Table {name} ({rows} rows):"));
+            for (column, dtype) in schema.iter() {
+                result.push(format!("- {column}: {dtype}"));
+            }
+        }
+        Ok(result.join("\n"))
+    }
+
+    /// Returns rows of the table as CSV, optionally only selected columns and rows matching the filter.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn tabular_preview(
+        &self,
+        #[doc = "Name of the table"] table: String,
+        #[doc = "Columns to return, all columns by default"] columns: Option<Vec<String>>,
+        #[doc = "SQL condition rows have to match, e.g. region = 'EU' AND amount > 100"]
+        filter: Option<String>,
+        #[doc = "Maximal number of rows"] limit: Option<usize>,
+    ) -> Result<String, ToolError> {
+        self.table(&table)?;
+        let columns = match columns {
+            Some(columns) if !columns.is_empty() => columns
+                .iter()
+                .map(|column| quote_identifier(column))
+                .collect::<Vec<_>>()
+                .join(", "),
+            _ => "*".to_string(),
+        };
+        let mut query = format!("SELECT {columns} FROM {}", quote_identifier(&table));
+        if let Some(filter) = filter.filter(|filter| !filter.trim().is_empty()) {
+            query.push_str(&format!(" WHERE {}", parse_condition(&filter)?));
+        }
+        let limit = limit.unwrap_or(self.max_rows).clamp(1, self.max_rows);
+        self.execute(&query, limit)
+    }
+
+    /// Runs SQL query over the tables and returns result as CSV. Use aggregations (COUNT, SUM,
+    /// AVG, MIN, MAX with GROUP BY) to answer questions instead of reading all rows.
+    #[tool(blocking, capabilities = [Capability::ReadFs])]
+    fn tabular_query(
+        &self,
+        #[doc = "SQL query, tables are referenced by their names"] sql: String,
+    ) -> Result<String, ToolError> {
+        self.execute(&sql, self.max_rows)
+    }
+
+    fn table(&self, name: &str) -> Result<&Table, ToolError> {
+        self.tables.get(name).ok_or_else(|| {
+            let tables = self.tables.keys().cloned().collect::<Vec<_>>().join(", ");
+            anyhow!("Unknown table {name}, available tables: {tables}").into()
+        })
+    }
+
+    /// Executes the query and formats at most `limit` rows of the result as CSV.
+    fn execute(&self, query: &str, limit: usize) -> Result<String, ToolError> {
+        self.check_query(query)?;
+        let mut context = SQLContext::new();
+        for (name, table) in &self.tables {
+            context.register(name, table.scan()?);
+        }
+        let frame = context
+            .execute(query)
+            .map_err(|err| anyhow!("Invalid query: {err}"))?;
+        // One more row is read to find out whether rows were omitted
+        let mut result = frame
+            .limit(limit as IdxSize + 1)
+            .collect()
+            .map_err(|err| anyhow!("Query failed: {err}"))?;
+        let omitted = result.height() > limit;
+        if omitted {
+            result = result.head(Some(limit));
+        }
+        let mut csv = vec![];
+        CsvWriter::new(&mut csv)
+            .finish(&mut result)
+            .map_err(anyhow::Error::new)?;
+        let mut csv = String::from_utf8_lossy(&csv).trim_end().to_string();
+        if result.height() == 0 {
+            csv.push_str("\n(no rows)");
+        }
+        if omitted {
+            csv.push_str(&format!(
+                "\n(only first {limit} rows are shown, use filters or aggregations to narrow the result)"
+            ));
+        }
+        Ok(csv)
+    }
+
+    /// Checks that the query is a single `SELECT` reading only the tables of the toolbox. Table
+    /// functions of polars (e.g. `read_csv`) would read any file.
+    fn check_query(&self, query: &str) -> Result<(), ToolError> {
+        let statements = parser(query)
+            .and_then(|mut parser| parser.parse_statements())
+            .map_err(|err| anyhow!("Invalid query: {err}"))?;
+        let [statement @ Statement::Query(_)] = statements.as_slice() else {
+            return Err(anyhow!("Only a single SELECT query is allowed").into());
+        };
+        let mut checker = RelationChecker {
+            tables: &self.tables,
+            queries: HashSet::new(),
+        };
+        match statement.visit(&mut checker) {
+            ControlFlow::Break(message) => Err(anyhow!(message).into()),
+            ControlFlow::Continue(()) => Ok(()),
+        }
+    }
+}
+
+/// Rejects table functions and relations, that are neither tables nor common table expressions.
+struct RelationChecker<'a> {
+    tables: &'a BTreeMap<String, Table>,
+    /// Names of common table expressions (`WITH name AS (...)`)
+    queries: HashSet<String>,
+}
+
+impl Visitor for RelationChecker<'_> {
+    type Break = String;
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<Self::Break> {
+        for cte in query.with.iter().flat_map(|with| &with.cte_tables) {
+            self.queries.insert(cte.alias.name.value.clone());
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &TableFactor) -> ControlFlow<Self::Break> {
+        match table_factor {
+            TableFactor::Table {
+                name,
+                args: Some(_),
+                ..
+            } => ControlFlow::Break(format!(
+                "Table function {name} is not allowed, tables are referenced by their names"
+            )),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ObjectName) -> ControlFlow<Self::Break> {
+        match relation.0.as_slice() {
+            [name]
+                if self.tables.contains_key(&name.value) || self.queries.contains(&name.value) =>
+            {
+                ControlFlow::Continue(())
+            }
+            _ => {
+                let tables = self.tables.keys().cloned().collect::<Vec<_>>().join(", ");
+                ControlFlow::Break(format!(
+                    "Unknown table {relation}, available tables: {tables}"
+                ))
+            }
+        }
+    }
+}
+
+/// Creates parser of the SQL dialect used by polars.
+fn parser(sql: &str) -> Result<Parser<'static>, sqlparser::parser::ParserError> {
+    Parser::new(&GenericDialect)
+        .with_options(ParserOptions::new().with_trailing_commas(true))
+        .try_with_sql(sql)
+}
+
+/// Parses the condition of rows, so nothing else can be appended to the query.
+fn parse_condition(condition: &str) -> Result<Expr, ToolError> {
+    let condition = parser(condition).and_then(|mut parser| {
+        let condition = parser.parse_expr()?;
+        parser.expect_token(&Token::EOF)?;
+        Ok(condition)
+    });
+    Ok(condition.map_err(|err| anyhow!("Invalid filter: {err}"))?)
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tabular_toolbox() {
+        let directory =
+            std::env::temp_dir().join(format!("agentai-tabular-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("sales.csv");
+        std::fs::write(
+            &path,
+            "region,product,amount\nEU,apple,10\nEU,pear,20\nUS,apple,5\nUS,plum,7\n",
+        )
+        .unwrap();
+        let tables = TabularToolBox::new()
+            .with_file(&path)
+            .unwrap()
+            .with_max_rows(2);
+        assert!(TabularToolBox::new()
+            .with_file(directory.join("notes.txt"))
+            .is_err());
+
+        let schema = tables.tabular_schema(None).unwrap();
+        assert_eq!(
+            schema,
+            "Table sales (4 rows):\n- region: str\n- product: str\n- amount: i64"
+        );
+
+        let preview = tables
+            .tabular_preview(
+                "sales".to_string(),
+                Some(vec!["product".to_string()]),
+                Some("region = 'EU'".to_string()),
+                None,
+            )
+            .unwrap();
+        assert_eq!(preview, "product\napple\npear");
+
+        let query = tables
+            .tabular_query(
+                "SELECT region, SUM(amount) AS total FROM sales GROUP BY region ORDER BY total DESC"
+                    .to_string(),
+            )
+            .unwrap();
+        assert_eq!(query, "region,total\nEU,30\nUS,12");
+
+        let truncated = tables
+            .tabular_query("SELECT * FROM sales".to_string())
+            .unwrap();
+        assert!(truncated.ends_with(
+            "(only first 2 rows are shown, use filters or aggregations to narrow the result)"
+        ));
+        assert!(tables
+            .tabular_preview("orders".to_string(), None, None, None)
+            .is_err());
+
+        let with = tables
+            .tabular_query(
+                "WITH eu AS (SELECT * FROM sales WHERE region = 'EU') SELECT COUNT(*) AS n FROM eu"
+                    .to_string(),
+            )
+            .unwrap();
+        assert_eq!(with, "n\n2");
+        let read_file = tables
+            .tabular_query(format!("SELECT * FROM read_csv('{}')", path.display()))
+            .unwrap_err();
+        assert_eq!(
+            read_file.to_string(),
+            "Table function read_csv is not allowed, tables are referenced by their names"
+        );
+        assert!(tables
+            .tabular_query("SELECT * FROM sales WHERE amount IN (SELECT * FROM orders)".to_string())
+            .is_err());
+        assert!(tables
+            .tabular_preview(
+                "sales".to_string(),
+                None,
+                Some("1 = 1 UNION SELECT * FROM read_parquet('data.parquet')".to_string()),
+                None,
+            )
+            .is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,7 +1,9 @@
-use crate::tool::{Tool, ToolBox, ToolError, toolbox};
+use crate::tool::{toolbox, Capability, Tool, ToolBox, ToolError};
 use anyhow::Context;
-use reqwest::Client;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
 use serde_json::Value;
+use std::time::Duration;
 
 const BRAVE_API_URL: &str = "https://api.search.brave.com/res/v1/web/search";
 
@@ -25,35 +27,58 @@ pub struct WebSearchToolBox {
 impl WebSearchToolBox {
     pub fn new(api_key: &str) -> Self {
         Self {
-            client: Client::default(),
+            client: crate::http::shared_client(),
             api_key: api_key.to_string(),
         }
     }
 
     /// A tool that performs web searches using a specified query parameter to retrieve relevant
     /// results from a search engine. As the result you will receive list of websites with description
-    #[tool]
+    #[tool(capabilities = [Capability::Network])]
     async fn web_search(
         &self,
         #[doc = "The search terms or keywords to be used by the search engine for retrieving relevant results"]
-        query: String
+        query: String,
     ) -> Result<String, ToolError> {
-        let params = [("q", query.as_str()), ("count", "5"), ("result_filter", "web")];
+        let params = [
+            ("q", query.as_str()),
+            ("count", "5"),
+            ("result_filter", "web"),
+        ];
         let response = self
             .client
             .get(BRAVE_API_URL)
             .query(&params)
             .header("X-Subscription-Token", self.api_key.clone())
             .send()
-            .await.map_err(|e| anyhow::Error::new(e))?;
+            .await
+            .map_err(anyhow::Error::new)?;
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                return Err(ToolError::Unauthorized(
+                    "Brave Search API rejected the API key".to_string(),
+                ))
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.trim().parse().ok())
+                    .map(Duration::from_secs);
+                return Err(ToolError::RateLimited { retry_after });
+            }
+            _ => {}
+        }
 
-        let json: Value = response.json().await.map_err(|e| anyhow::Error::new(e))?;
+        let json: Value = response.json().await.map_err(anyhow::Error::new)?;
 
         let mut results: Vec<String> = vec![];
 
-        let response = json["web"]["results"].as_array().ok_or(ToolError::ExecutionError)?;
-        for item in response
-        {
+        let response = json["web"]["results"]
+            .as_array()
+            .ok_or(ToolError::ExecutionError)?;
+        for item in response {
             let title = item["title"]
                 .as_str()
                 .context("web title is not a string")?;
@@ -67,5 +92,5 @@ impl WebSearchToolBox {
         }
 
         Ok(results.join("\n\n"))
-	}
+    }
 }
@@ -0,0 +1,688 @@
+//! # Run Traces
+//!
+//! [TraceRecorder] records what happened during runs of the agent, so they can be inspected
+//! step by step in existing LLM observability UIs, without writing a custom importer. Trace
+//! consists of spans:
+//! - agent span covering the whole run, from the first iteration to the final answer,
+//! - LLM span for every request sent to the model, with all input messages, answer of the model,
+//!   model name and token usage,
+//! - tool span for every tool call, with arguments and result.
+//!
+//! Recorder is both [ChatMiddleware] (recording requests) and source of event handler (recording
+//! the run and tool calls), so it has to be installed as both:
+//!
+//! ```rust,no_run
+//! use agentai::trace::TraceRecorder;
+//! use agentai::Agent;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let recorder = TraceRecorder::new();
+//! let mut agent = Agent::builder()
+//!     .with_middleware(recorder.clone())
+//!     .with_event_handler(recorder.event_handler())
+//!     .build();
+//! let answer: String = agent.run("gpt-4o", "Why is the sky blue?", None, None, None).await?;
+//!
+//! // Spans in OpenInference format, e.g. for Arize Phoenix
+//! let spans = recorder.to_openinference();
+//! // Batch for Langfuse ingestion API (`POST /api/public/ingestion`)
+//! let batch = recorder.to_langfuse();
+//! // Batch for LangSmith runs API (`POST /runs/batch`)
+//! let runs = recorder.to_langsmith("my-project");
+//! std::fs::write("trace.json", serde_json::to_string_pretty(&batch)?)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! All runs recorded by the recorder belong to the same trace, use [TraceRecorder::clear] to start
+//! a new one.
+//!
+//! With `trace-export` feature traces can be pushed to Langfuse or LangSmith automatically, see
+//! [AgentBuilder::with_trace_exporter](crate::agent::AgentBuilder::with_trace_exporter).
+
+#[cfg(feature = "trace-export")]
+mod export;
+
+#[cfg(feature = "trace-export")]
+pub use export::{LangSmithExporter, LangfuseExporter, TraceExporter};
+
+use crate::agent::{ChatMiddleware, RequestContext};
+use crate::event::AgentEvent;
+use anyhow::Result;
+use genai::chat::{
+    ChatMessage, ChatOptions, ChatRequest, ChatResponse, ChatRole, ContentPart, MessageContent,
+    Usage,
+};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// Key of [RequestContext::metadata] holding identifier of the LLM span of the request
+const SPAN_ID_KEY: &str = "trace_span_id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanKind {
+    Agent,
+    Llm,
+    Tool,
+}
+
+#[derive(Debug, Clone)]
+struct Span {
+    id: String,
+    parent_id: Option<String>,
+    kind: SpanKind,
+    name: String,
+    /// Microseconds since Unix epoch
+    start: u64,
+    end: Option<u64>,
+    input: Value,
+    output: Value,
+    model: Option<String>,
+    usage: Option<Usage>,
+    is_error: bool,
+}
+
+#[derive(Debug)]
+struct TraceState {
+    trace_id: String,
+    spans: Vec<Span>,
+    /// Index of the agent span of the run in progress
+    run: Option<usize>,
+    /// Indices of tool spans in progress, by identifiers of tool calls
+    tool_calls: HashMap<String, usize>,
+}
+
+impl Default for TraceState {
+    fn default() -> Self {
+        Self {
+            trace_id: format!("{}{}", random_id(), random_id()),
+            spans: vec![],
+            run: None,
+            tool_calls: HashMap::new(),
+        }
+    }
+}
+
+impl TraceState {
+    /// Returns UUID of the span, made of the trace identifier and the span identifier.
+    fn run_uuid(&self, span_id: &str) -> String {
+        let hex = format!("{}{span_id}", &self.trace_id[..16]);
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+
+    fn start_span(&mut self, kind: SpanKind, name: String, input: Value) -> usize {
+        let parent_id = self.run.map(|run| self.spans[run].id.clone());
+        self.spans.push(Span {
+            id: random_id(),
+            parent_id,
+            kind,
+            name,
+            start: now_micros(),
+            end: None,
+            input,
+            output: Value::Null,
+            model: None,
+            usage: None,
+            is_error: false,
+        });
+        self.spans.len() - 1
+    }
+}
+
+/// Records runs of the agent for trace viewers, look into [module documentation](crate::trace).
+///
+/// Clones of the recorder share recorded spans.
+#[derive(Debug, Clone, Default)]
+pub struct TraceRecorder {
+    state: Arc<Mutex<TraceState>>,
+}
+
+impl TraceRecorder {
+    /// Creates recorder of a new trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns event handler recording runs and tool calls, see [AgentBuilder::with_event_handler](crate::agent::AgentBuilder::with_event_handler).
+    pub fn event_handler(&self) -> impl Fn(&AgentEvent) + Send + Sync + 'static {
+        let recorder = self.clone();
+        move |event| recorder.record_event(event)
+    }
+
+    /// Removes recorded spans, following runs are recorded as a new trace.
+    pub fn clear(&self) {
+        *self.lock() = TraceState::default();
+    }
+
+    /// Returns identifier of the trace, 32 hexadecimal characters.
+    pub fn trace_id(&self) -> String {
+        self.lock().trace_id.clone()
+    }
+
+    /// Exports recorded spans as JSON array of spans with [OpenInference](https://github.com/Arize-ai/openinference)
+    /// semantic conventions, messages are exported as `llm.input_messages` and `llm.output_messages`
+    /// attributes.
+    pub fn to_openinference(&self) -> Value {
+        let state = self.lock();
+        let spans = state
+            .spans
+            .iter()
+            .map(|span| {
+                let mut attributes = Map::new();
+                let kind = match span.kind {
+                    SpanKind::Agent => "AGENT",
+                    SpanKind::Llm => "LLM",
+                    SpanKind::Tool => "TOOL",
+                };
+                attributes.insert("openinference.span.kind".into(), kind.into());
+                attributes.insert("input.value".into(), text_value(&span.input).into());
+                attributes.insert("output.value".into(), text_value(&span.output).into());
+                match span.kind {
+                    SpanKind::Agent => {}
+                    SpanKind::Llm => {
+                        attributes.insert("input.mime_type".into(), "application/json".into());
+                        attributes.insert("output.mime_type".into(), "application/json".into());
+                        flatten_messages(&mut attributes, "llm.input_messages", &span.input);
+                        flatten_messages(&mut attributes, "llm.output_messages", &span.output);
+                    }
+                    SpanKind::Tool => {
+                        attributes.insert("tool.name".into(), span.name.clone().into());
+                        attributes.insert("tool.parameters".into(), text_value(&span.input).into());
+                    }
+                }
+                if let Some(model) = &span.model {
+                    attributes.insert("llm.model_name".into(), model.clone().into());
+                }
+                if let Some(usage) = &span.usage {
+                    attributes.insert("llm.token_count.prompt".into(), usage.prompt_tokens.into());
+                    attributes.insert(
+                        "llm.token_count.completion".into(),
+                        usage.completion_tokens.into(),
+                    );
+                    attributes.insert("llm.token_count.total".into(), usage.total_tokens.into());
+                }
+                json!({
+                    "name": span.name,
+                    "context": {"trace_id": state.trace_id, "span_id": span.id},
+                    "parent_id": span.parent_id,
+                    "span_kind": kind,
+                    "start_time": iso_time(span.start),
+                    "end_time": iso_time(span.end.unwrap_or(span.start)),
+                    "status_code": if span.is_error { "ERROR" } else { "OK" },
+                    "attributes": attributes,
+                })
+            })
+            .collect();
+        Value::Array(spans)
+    }
+
+    /// Exports recorded spans as a batch of [Langfuse ingestion API](https://api.reference.langfuse.com/#tag/ingestion),
+    /// LLM spans are exported as generations with messages as input and output.
+    pub fn to_langfuse(&self) -> Value {
+        let state = self.lock();
+        let mut batch = vec![];
+        let first = state.spans.first();
+        let last_run = state
+            .spans
+            .iter()
+            .rev()
+            .find(|span| span.kind == SpanKind::Agent);
+        batch.push(json!({
+            "id": random_id(),
+            "timestamp": iso_time(first.map_or_else(now_micros, |span| span.start)),
+            "type": "trace-create",
+            "body": {
+                "id": state.trace_id,
+                "name": "agent",
+                "timestamp": iso_time(first.map_or_else(now_micros, |span| span.start)),
+                "input": first.map(|span| span.input.clone()),
+                "output": last_run.map(|span| span.output.clone()),
+            },
+        }));
+        for span in &state.spans {
+            let mut body = json!({
+                "id": span.id,
+                "traceId": state.trace_id,
+                "parentObservationId": span.parent_id,
+                "name": span.name,
+                "startTime": iso_time(span.start),
+                "endTime": span.end.map(iso_time),
+                "input": span.input,
+                "output": span.output,
+                "level": if span.is_error { "ERROR" } else { "DEFAULT" },
+            });
+            let kind = match span.kind {
+                SpanKind::Llm => {
+                    body["model"] = json!(span.model);
+                    if let Some(usage) = &span.usage {
+                        body["usage"] = json!({
+                            "input": usage.prompt_tokens,
+                            "output": usage.completion_tokens,
+                            "total": usage.total_tokens,
+                            "unit": "TOKENS",
+                        });
+                    }
+                    "generation-create"
+                }
+                SpanKind::Agent | SpanKind::Tool => "span-create",
+            };
+            batch.push(json!({
+                "id": random_id(),
+                "timestamp": iso_time(span.start),
+                "type": kind,
+                "body": body,
+            }));
+        }
+        json!({ "batch": batch })
+    }
+
+    /// Exports recorded spans as a batch of [LangSmith runs API](https://api.smith.langchain.com/redoc#tag/run/operation/batch_ingest_runs_api_v1_runs_batch_post)
+    /// (`POST /runs/batch`), runs are added to the project.
+    ///
+    /// Every run of the agent is a separate LangSmith trace, identifiers are converted into UUIDs.
+    pub fn to_langsmith(&self, project: &str) -> Value {
+        let state = self.lock();
+        let runs: Vec<Value> = state
+            .spans
+            .iter()
+            .map(|span| {
+                let root = span
+                    .parent_id
+                    .as_ref()
+                    .and_then(|parent| state.spans.iter().find(|root| root.id == *parent))
+                    .unwrap_or(span);
+                let run_id = state.run_uuid(&span.id);
+                let root_id = state.run_uuid(&root.id);
+                let mut dotted_order = format!("{}{root_id}", compact_time(root.start));
+                if root.id != span.id {
+                    dotted_order.push_str(&format!(".{}{run_id}", compact_time(span.start)));
+                }
+                let run_type = match span.kind {
+                    SpanKind::Agent => "chain",
+                    SpanKind::Llm => "llm",
+                    SpanKind::Tool => "tool",
+                };
+                let mut run = json!({
+                    "id": run_id,
+                    "trace_id": root_id,
+                    "parent_run_id": span.parent_id.as_ref().map(|parent| state.run_uuid(parent)),
+                    "dotted_order": dotted_order,
+                    "name": span.name,
+                    "run_type": run_type,
+                    "start_time": iso_time(span.start),
+                    "end_time": span.end.map(iso_time),
+                    "inputs": match span.kind {
+                        SpanKind::Llm => json!({"messages": span.input}),
+                        _ => json!({"input": span.input}),
+                    },
+                    "outputs": match span.kind {
+                        SpanKind::Llm => json!({"messages": span.output}),
+                        _ => json!({"output": span.output}),
+                    },
+                    "session_name": project,
+                    "error": span.is_error.then(|| text_value(&span.output)),
+                });
+                if let Some(model) = &span.model {
+                    run["extra"] = json!({"metadata": {"ls_model_name": model}});
+                }
+                if let Some(usage) = &span.usage {
+                    run["prompt_tokens"] = json!(usage.prompt_tokens);
+                    run["completion_tokens"] = json!(usage.completion_tokens);
+                    run["total_tokens"] = json!(usage.total_tokens);
+                }
+                run
+            })
+            .collect();
+        json!({ "post": runs })
+    }
+
+    /// Returns identifier of the agent span of the last run, as LangSmith run UUID.
+    #[cfg(feature = "trace-export")]
+    pub(crate) fn last_run_uuid(&self) -> Option<String> {
+        let state = self.lock();
+        state
+            .spans
+            .iter()
+            .rev()
+            .find(|span| span.kind == SpanKind::Agent)
+            .map(|span| state.run_uuid(&span.id))
+    }
+
+    /// Marks the run in progress as failed.
+    #[cfg(feature = "trace-export")]
+    pub(crate) fn fail_run(&self, error: &str) {
+        let mut state = self.lock();
+        if let Some(run) = state.run.take() {
+            let span = &mut state.spans[run];
+            span.end = Some(now_micros());
+            span.output = error.into();
+            span.is_error = true;
+        }
+    }
+
+    pub(crate) fn record_event(&self, event: &AgentEvent) {
+        let mut state = self.lock();
+        match event {
+            AgentEvent::IterationStarted { iteration: 0 } => {
+                let run = state.start_span(SpanKind::Agent, "agent run".to_string(), Value::Null);
+                state.run = Some(run);
+            }
+            AgentEvent::ToolCall {
+                call_id,
+                name,
+                arguments,
+            } => {
+                let span = state.start_span(SpanKind::Tool, name.clone(), arguments.clone());
+                state.tool_calls.insert(call_id.clone(), span);
+            }
+            AgentEvent::ToolResult {
+                call_id,
+                content,
+                is_error,
+                ..
+            } => {
+                if let Some(span) = state.tool_calls.remove(call_id) {
+                    let span = &mut state.spans[span];
+                    span.end = Some(now_micros());
+                    span.output = content.clone().into();
+                    span.is_error = *is_error;
+                }
+            }
+            AgentEvent::Answer { content } => {
+                if let Some(run) = state.run.take() {
+                    let span = &mut state.spans[run];
+                    span.end = Some(now_micros());
+                    span.output = content.clone().into();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn start_request(&self, model: &str, request: &ChatRequest) -> String {
+        let mut messages = vec![];
+        if let Some(system) = &request.system {
+            messages.push(json!({"role": "system", "content": system}));
+        }
+        for message in &request.messages {
+            messages.extend(message_values(message));
+        }
+        let mut state = self.lock();
+        // Input of the run is the last message of the user in its first request
+        if let Some(run) = state.run {
+            if state.spans[run].input.is_null() {
+                state.spans[run].input = messages
+                    .iter()
+                    .rev()
+                    .find(|message| message["role"] == "user")
+                    .map(|message| message["content"].clone())
+                    .unwrap_or_default();
+            }
+        }
+        let span = state.start_span(SpanKind::Llm, model.to_string(), Value::Array(messages));
+        state.spans[span].model = Some(model.to_string());
+        state.spans[span].id.clone()
+    }
+
+    fn finish_request(&self, span_id: &str, response: &ChatResponse) {
+        let output = response
+            .content
+            .iter()
+            .flat_map(|content| content_values("assistant", content))
+            .collect();
+        let mut state = self.lock();
+        if let Some(span) = state.spans.iter_mut().find(|span| span.id == span_id) {
+            span.end = Some(now_micros());
+            span.output = Value::Array(output);
+            span.model = Some(response.provider_model_iden.model_name.to_string());
+            span.usage = Some(response.usage.clone());
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TraceState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatMiddleware for TraceRecorder {
+    async fn before_request(
+        &self,
+        context: &mut RequestContext,
+        request: &mut ChatRequest,
+        _options: &mut ChatOptions,
+    ) -> Result<Option<ChatResponse>> {
+        let span_id = self.start_request(&context.model, request);
+        context.metadata.insert(SPAN_ID_KEY.to_string(), span_id);
+        Ok(None)
+    }
+
+    async fn after_response(
+        &self,
+        context: &RequestContext,
+        _request: &ChatRequest,
+        response: &mut ChatResponse,
+    ) -> Result<()> {
+        if let Some(span_id) = context.metadata.get(SPAN_ID_KEY) {
+            self.finish_request(span_id, response);
+        }
+        Ok(())
+    }
+}
+
+/// Converts message into JSON messages, every tool response is a separate message.
+fn message_values(message: &ChatMessage) -> Vec<Value> {
+    let role = match message.role {
+        ChatRole::System => "system",
+        ChatRole::User => "user",
+        ChatRole::Assistant => "assistant",
+        ChatRole::Tool => "tool",
+    };
+    content_values(role, &message.content)
+}
+
+fn content_values(role: &str, content: &MessageContent) -> Vec<Value> {
+    match content {
+        MessageContent::Text(text) => vec![json!({"role": role, "content": text})],
+        MessageContent::Parts(parts) => {
+            let content = parts
+                .iter()
+                .map(|part| match part {
+                    ContentPart::Text(text) => text.as_str(),
+                    _ => "[attachment]",
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            vec![json!({"role": role, "content": content})]
+        }
+        MessageContent::ToolCalls(tool_calls) => {
+            let tool_calls: Vec<Value> = tool_calls
+                .iter()
+                .map(|tool_call| {
+                    json!({
+                        "id": tool_call.call_id,
+                        "name": tool_call.fn_name,
+                        "arguments": tool_call.fn_arguments.to_string(),
+                    })
+                })
+                .collect();
+            vec![json!({"role": role, "content": "", "tool_calls": tool_calls})]
+        }
+        MessageContent::ToolResponses(tool_responses) => tool_responses
+            .iter()
+            .map(|tool_response| {
+                json!({
+                    "role": role,
+                    "content": tool_response.content,
+                    "tool_call_id": tool_response.call_id,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Adds messages as flattened OpenInference attributes, e.g. `llm.input_messages.0.message.role`.
+fn flatten_messages(attributes: &mut Map<String, Value>, prefix: &str, messages: &Value) {
+    let Some(messages) = messages.as_array() else {
+        return;
+    };
+    for (idx, message) in messages.iter().enumerate() {
+        let prefix = format!("{prefix}.{idx}.message");
+        attributes.insert(format!("{prefix}.role"), message["role"].clone());
+        attributes.insert(format!("{prefix}.content"), message["content"].clone());
+        if let Some(call_id) = message.get("tool_call_id") {
+            attributes.insert(format!("{prefix}.tool_call_id"), call_id.clone());
+        }
+        for (call, tool_call) in message["tool_calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            let prefix = format!("{prefix}.tool_calls.{call}.tool_call");
+            attributes.insert(format!("{prefix}.id"), tool_call["id"].clone());
+            attributes.insert(format!("{prefix}.function.name"), tool_call["name"].clone());
+            attributes.insert(
+                format!("{prefix}.function.arguments"),
+                tool_call["arguments"].clone(),
+            );
+        }
+    }
+}
+
+/// Returns string as is, other values serialized into JSON.
+fn text_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Formats time like [iso_time] without separators, used in LangSmith `dotted_order`.
+fn compact_time(micros: u64) -> String {
+    iso_time(micros).replace(['-', ':', '.'], "")
+}
+
+/// Returns random identifier of 16 hexadecimal characters.
+fn random_id() -> String {
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(now_micros());
+    format!("{:016x}", hasher.finish())
+}
+
+/// Returns number of microseconds since Unix epoch.
+fn now_micros() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or_default()
+}
+
+/// Converts number of days since Unix epoch to `(year, month, day)` of the proleptic Gregorian
+/// calendar, see <http://howardhinnant.github.io/date_algorithms.html>.
+pub(crate) fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Formats microseconds since Unix epoch as RFC 3339 time in UTC.
+fn iso_time(micros: u64) -> String {
+    let secs = micros / 1_000_000;
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_date(days as i64);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:06}Z",
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+        micros % 1_000_000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use genai::chat::ToolResponse;
+
+    #[test]
+    fn test_trace_export() {
+        assert_eq!(iso_time(0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(
+            iso_time(1_709_210_096_000_123),
+            "2024-02-29T12:34:56.000123Z"
+        );
+
+        let recorder = TraceRecorder::new();
+        let handler = recorder.event_handler();
+        handler(&AgentEvent::IterationStarted { iteration: 0 });
+        recorder.start_request(
+            "gpt-4o",
+            &ChatRequest::new(vec![
+                ChatMessage::user("What is the weather?"),
+                ToolResponse::new("call_1", "Sunny").into(),
+            ]),
+        );
+        handler(&AgentEvent::ToolCall {
+            call_id: "call_1".to_string(),
+            name: "weather".to_string(),
+            arguments: json!({"city": "Paris"}),
+        });
+        handler(&AgentEvent::ToolResult {
+            call_id: "call_1".to_string(),
+            name: "weather".to_string(),
+            content: "Sunny".to_string(),
+            is_error: false,
+        });
+        handler(&AgentEvent::Answer {
+            content: "It is sunny".to_string(),
+        });
+
+        let spans = recorder.to_openinference();
+        assert_eq!(spans.as_array().unwrap().len(), 3);
+        let (run, llm, tool) = (&spans[0], &spans[1], &spans[2]);
+        assert_eq!(run["span_kind"], "AGENT");
+        assert_eq!(run["attributes"]["input.value"], "What is the weather?");
+        assert_eq!(run["attributes"]["output.value"], "It is sunny");
+        assert_eq!(llm["parent_id"], run["context"]["span_id"]);
+        assert_eq!(
+            llm["attributes"]["llm.input_messages.1.message.tool_call_id"],
+            "call_1"
+        );
+        assert_eq!(tool["attributes"]["tool.parameters"], r#"{"city":"Paris"}"#);
+        assert_eq!(tool["context"]["trace_id"], recorder.trace_id());
+
+        let batch = recorder.to_langfuse();
+        let types: Vec<&str> = batch["batch"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "trace-create",
+                "span-create",
+                "generation-create",
+                "span-create"
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use crate::trace::TraceRecorder;
+use anyhow::{anyhow, Result};
+use log::debug;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Value};
+
+const LANGFUSE_HOST: &str = "https://cloud.langfuse.com";
+const LANGSMITH_ENDPOINT: &str = "https://api.smith.langchain.com";
+
+/// Destination of traces recorded by [TraceRecorder], see [AgentBuilder::with_trace_exporter](crate::agent::AgentBuilder::with_trace_exporter).
+#[async_trait::async_trait]
+pub trait TraceExporter: Send + Sync {
+    /// Pushes spans recorded by the recorder.
+    async fn export(&self, trace: &TraceRecorder) -> Result<()>;
+
+    /// Attaches score to the last run recorded by the recorder, e.g. feedback of the user.
+    async fn score(
+        &self,
+        trace: &TraceRecorder,
+        name: &str,
+        value: f64,
+        comment: Option<&str>,
+    ) -> Result<()>;
+}
+
+/// Exports traces to [Langfuse](https://langfuse.com), authenticated with keys of the project.
+#[derive(Clone)]
+pub struct LangfuseExporter {
+    client: Client,
+    host: String,
+    public_key: String,
+    secret_key: String,
+}
+
+impl LangfuseExporter {
+    /// Creates exporter sending traces to Langfuse Cloud.
+    pub fn new(public_key: &str, secret_key: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            host: LANGFUSE_HOST.to_string(),
+            public_key: public_key.to_string(),
+            secret_key: secret_key.to_string(),
+        }
+    }
+
+    /// Sets URL of Langfuse instance, e.g. self-hosted one or `https://us.cloud.langfuse.com`.
+    pub fn with_host(mut self, host: &str) -> Self {
+        self.host = host.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Creates new version of the text prompt in Langfuse prompt management, e.g. to keep track
+    /// of system prompts used by agents.
+    pub async fn push_prompt(&self, name: &str, prompt: &str, labels: &[&str]) -> Result<()> {
+        let body = json!({
+            "name": name,
+            "type": "text",
+            "prompt": prompt,
+            "labels": labels,
+        });
+        self.send(self.post("/api/public/v2/prompts").json(&body))
+            .await
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        self.client
+            .post(format!("{}{path}", self.host))
+            .basic_auth(&self.public_key, Some(&self.secret_key))
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<()> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Langfuse API returned {status}: {text}"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl TraceExporter for LangfuseExporter {
+    async fn export(&self, trace: &TraceRecorder) -> Result<()> {
+        let batch = trace.to_langfuse();
+        debug!("Exporting trace {} to Langfuse", trace.trace_id());
+        self.send(self.post("/api/public/ingestion").json(&batch))
+            .await
+    }
+
+    async fn score(
+        &self,
+        trace: &TraceRecorder,
+        name: &str,
+        value: f64,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let body = json!({
+            "traceId": trace.trace_id(),
+            "name": name,
+            "value": value,
+            "comment": comment,
+        });
+        self.send(self.post("/api/public/scores").json(&body)).await
+    }
+}
+
+/// Exports traces to [LangSmith](https://smith.langchain.com), authenticated with API key.
+#[derive(Clone)]
+pub struct LangSmithExporter {
+    client: Client,
+    endpoint: String,
+    api_key: String,
+    project: String,
+}
+
+impl LangSmithExporter {
+    /// Creates exporter sending traces to `default` project of LangSmith Cloud.
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            client: crate::http::shared_client(),
+            endpoint: LANGSMITH_ENDPOINT.to_string(),
+            api_key: api_key.to_string(),
+            project: "default".to_string(),
+        }
+    }
+
+    /// Sets name of the project receiving traces.
+    pub fn with_project(mut self, project: &str) -> Self {
+        self.project = project.to_string();
+        self
+    }
+
+    /// Sets URL of LangSmith API, e.g. `https://eu.api.smith.langchain.com` or self-hosted one.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn post(&self, path: &str, body: &Value) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}{path}", self.endpoint))
+            .header("x-api-key", &self.api_key)
+            .json(body)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("LangSmith API returned {status}: {text}"));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl TraceExporter for LangSmithExporter {
+    async fn export(&self, trace: &TraceRecorder) -> Result<()> {
+        let runs = trace.to_langsmith(&self.project);
+        debug!("Exporting trace {} to LangSmith", trace.trace_id());
+        self.post("/runs/batch", &runs).await
+    }
+
+    async fn score(
+        &self,
+        trace: &TraceRecorder,
+        name: &str,
+        value: f64,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let run_id = trace
+            .last_run_uuid()
+            .ok_or_else(|| anyhow!("No run was recorded"))?;
+        let body = json!({
+            "run_id": run_id,
+            "key": name,
+            "score": value,
+            "comment": comment,
+        });
+        self.post("/feedback", &body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::AgentEvent;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// Serves single request with the given status, returns URL of the server and the received request
+    async fn serve_once(status: &'static str, body: &'static str) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 4096];
+            loop {
+                let read = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some((head, received)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length: "))
+                        .and_then(|length| length.parse::<usize>().ok())
+                        .unwrap_or(0);
+                    if received.len() >= length || read == 0 {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, server)
+    }
+
+    fn recorded_run() -> TraceRecorder {
+        let recorder = TraceRecorder::new();
+        let handler = recorder.event_handler();
+        handler(&AgentEvent::IterationStarted { iteration: 0 });
+        handler(&AgentEvent::Answer {
+            content: "It is sunny".to_string(),
+        });
+        recorder
+    }
+
+    #[tokio::test]
+    async fn test_langfuse_export_rejected() {
+        let (url, server) = serve_once("401 Unauthorized", "Invalid credentials").await;
+        let exporter = LangfuseExporter::new("pk-lf-1", "sk-lf-1").with_host(&format!("{url}/"));
+        let err = exporter.export(&recorded_run()).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Langfuse API returned 401 Unauthorized: Invalid credentials"
+        );
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /api/public/ingestion HTTP/1.1"));
+        // Basic auth with public and secret key
+        assert!(request.contains("Basic cGstbGYtMTpzay1sZi0x"));
+        assert!(request.contains(r#""type":"trace-create""#));
+    }
+
+    #[tokio::test]
+    async fn test_langsmith_export() {
+        let (url, server) = serve_once("202 Accepted", "").await;
+        let exporter = LangSmithExporter::new("ls-key")
+            .with_endpoint(&url)
+            .with_project("weather-bot");
+        exporter.export(&recorded_run()).await.unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /runs/batch HTTP/1.1"));
+        assert!(request.to_lowercase().contains("x-api-key: ls-key"));
+        assert!(request.contains("weather-bot"));
+    }
+
+    #[tokio::test]
+    async fn test_langsmith_score_without_runs() {
+        let exporter = LangSmithExporter::new("ls-key").with_endpoint("http://127.0.0.1:9");
+        let err = exporter
+            .score(&TraceRecorder::new(), "helpful", 1.0, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "No run was recorded");
+    }
+}
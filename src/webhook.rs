@@ -0,0 +1,516 @@
+//! # Webhook Trigger Server
+//!
+//! Small HTTP server that starts agent runs from incoming webhooks, e.g. "GitHub issue opened
+//! → triage agent". It is enabled with `webhook-server` feature.
+//!
+//! Each [Webhook] is registered under a name and has its own agent, model and prompt template.
+//! When JSON payload is received on `POST /webhooks/{name}`, the template is rendered with values
+//! from the payload and the agent is run in the background. Response contains random identifier of
+//! the job, that can be used to check its status on `GET /jobs/{id}`. Status route is served only
+//! when a token is set with [WebhookServer::with_status_token], requests have to provide it in
+//! `Authorization: Bearer <token>` header. When [DEFAULT_MAX_QUEUED_JOBS] jobs (configurable with
+//! [WebhookServer::with_max_queued_jobs]) are waiting for a worker, new requests are rejected with
+//! `503 Service Unavailable`.
+//!
+//! ## Prompt Template
+//! Placeholders in the form `{{ path }}` are replaced with values from the payload. Path is a dot
+//! separated list of object keys or array indexes (e.g. `{{ issue.labels.0.name }}`). String values
+//! are inserted as-is, other values as JSON. Placeholder `{{ payload }}` is replaced with the whole payload.
+//!
+//! ## Signature Verification
+//! When [Signature] is configured for the webhook, requests without a valid HMAC signature are
+//! rejected with `401 Unauthorized`.
+//!
+//! ```rust,no_run
+//! use agentai::Agent;
+//! use agentai::webhook::{Signature, Webhook, WebhookServer};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let triage = Webhook::new(
+//!     Agent::new("You are triaging GitHub issues"),
+//!     "gpt-4.1-mini",
+//!     "Suggest labels for the issue titled {{ issue.title }}:\n{{ issue.body }}",
+//! )
+//! .with_signature(Signature::github("<WEBHOOK SECRET>"));
+//!
+//! WebhookServer::new()
+//!     .with_webhook("github", triage)
+//!     .with_status_token("<STATUS TOKEN>")
+//!     .serve("0.0.0.0:8080")
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::agent::Agent;
+use crate::tool::ToolBox;
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::stream::{FuturesUnordered, StreamExt};
+use hmac::{Hmac, Mac};
+use log::{debug, error};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+
+const DEFAULT_MAX_CONCURRENT_JOBS: usize = 4;
+/// Default number of jobs waiting for a worker, see [WebhookServer::with_max_queued_jobs]
+pub const DEFAULT_MAX_QUEUED_JOBS: usize = 100;
+// Number of jobs which status is kept in memory
+const MAX_STORED_JOBS: usize = 1000;
+
+/// HMAC-SHA256 signature verification of the request body.
+pub struct Signature {
+    header: String,
+    prefix: String,
+    secret: Vec<u8>,
+}
+
+impl Signature {
+    /// Verifies hex encoded HMAC-SHA256 signature provided in `header`, optionally preceded with `prefix`.
+    pub fn hmac_sha256(header: &str, prefix: &str, secret: &str) -> Self {
+        Self {
+            header: header.to_string(),
+            prefix: prefix.to_string(),
+            secret: secret.as_bytes().to_vec(),
+        }
+    }
+
+    /// Signature used by GitHub webhooks (`X-Hub-Signature-256: sha256=...`).
+    pub fn github(secret: &str) -> Self {
+        Self::hmac_sha256("X-Hub-Signature-256", "sha256=", secret)
+    }
+
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> bool {
+        let Some(signature) = headers
+            .get(&self.header)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(&self.prefix))
+            .and_then(|value| hex::decode(value.trim()).ok())
+        else {
+            return false;
+        };
+        let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(&self.secret) else {
+            return false;
+        };
+        mac.update(body);
+        mac.verify_slice(&signature).is_ok()
+    }
+}
+
+/// Agent run triggered by the webhook.
+pub struct Webhook {
+    agent: Agent,
+    model: String,
+    template: String,
+    toolbox: Option<Arc<dyn ToolBox + Send + Sync>>,
+    signature: Option<Signature>,
+}
+
+impl Webhook {
+    /// Creates webhook running copy of `agent` with `model`, prompt is rendered from `template`.
+    pub fn new(agent: Agent, model: &str, template: &str) -> Self {
+        Self {
+            agent,
+            model: model.to_string(),
+            template: template.to_string(),
+            toolbox: None,
+            signature: None,
+        }
+    }
+
+    /// Sets toolbox available for the agent.
+    pub fn with_toolbox(mut self, toolbox: Arc<dyn ToolBox + Send + Sync>) -> Self {
+        self.toolbox = Some(toolbox);
+        self
+    }
+
+    /// Requires valid signature for incoming requests.
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+}
+
+/// Status of the job started by the webhook, returned by `GET /jobs/{id}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Job is waiting for a free worker
+    Pending,
+    /// Agent is running
+    Running,
+    /// Agent finished with the answer
+    Completed {
+        /// Answer of the agent
+        result: String,
+    },
+    /// Agent failed
+    Failed {
+        /// Description of the error
+        error: String,
+    },
+}
+
+struct Job {
+    id: String,
+    webhook: Arc<Webhook>,
+    prompt: String,
+}
+
+#[derive(Default)]
+struct Jobs {
+    statuses: HashMap<String, JobStatus>,
+    order: VecDeque<String>,
+}
+
+impl Jobs {
+    fn insert(&mut self, id: &str) {
+        self.statuses.insert(id.to_string(), JobStatus::Pending);
+        self.order.push_back(id.to_string());
+        while self.order.len() > MAX_STORED_JOBS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.statuses.remove(&oldest);
+            }
+        }
+    }
+
+    fn update(&mut self, id: &str, status: JobStatus) {
+        if let Some(current) = self.statuses.get_mut(id) {
+            *current = status;
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        self.statuses.remove(id);
+        self.order.retain(|queued| queued != id);
+    }
+}
+
+struct ServerState {
+    webhooks: HashMap<String, Arc<Webhook>>,
+    jobs: Mutex<Jobs>,
+    queue: Sender<Job>,
+    status_token: Option<String>,
+}
+
+/// HTTP server mapping webhooks to agent runs.
+pub struct WebhookServer {
+    webhooks: HashMap<String, Arc<Webhook>>,
+    max_concurrent_jobs: usize,
+    max_queued_jobs: usize,
+    status_token: Option<String>,
+}
+
+impl Default for WebhookServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebhookServer {
+    /// Creates server without any webhooks.
+    pub fn new() -> Self {
+        Self {
+            webhooks: HashMap::new(),
+            max_concurrent_jobs: DEFAULT_MAX_CONCURRENT_JOBS,
+            max_queued_jobs: DEFAULT_MAX_QUEUED_JOBS,
+            status_token: None,
+        }
+    }
+
+    /// Registers webhook available on `POST /webhooks/{name}`.
+    pub fn with_webhook(mut self, name: &str, webhook: Webhook) -> Self {
+        self.webhooks.insert(name.to_string(), Arc::new(webhook));
+        self
+    }
+
+    /// Limits number of agents running at the same time, remaining jobs are queued.
+    pub fn with_max_concurrent_jobs(mut self, max_concurrent_jobs: usize) -> Self {
+        self.max_concurrent_jobs = max_concurrent_jobs.max(1);
+        self
+    }
+
+    /// Limits number of jobs waiting for a worker, default is [DEFAULT_MAX_QUEUED_JOBS]. Requests
+    /// received when the queue is full are rejected with `503 Service Unavailable`.
+    pub fn with_max_queued_jobs(mut self, max_queued_jobs: usize) -> Self {
+        self.max_queued_jobs = max_queued_jobs.max(1);
+        self
+    }
+
+    /// Serves `GET /jobs/{id}` to requests with `Authorization: Bearer <token>` header. Without
+    /// the token statuses of jobs are not available.
+    pub fn with_status_token(mut self, token: &str) -> Self {
+        self.status_token = Some(token.to_string());
+        self
+    }
+
+    /// Starts listening on `addr` and serves requests until an error occurs.
+    pub async fn serve(self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        let (queue, jobs_rx) = mpsc::channel(self.max_queued_jobs);
+        let state = Arc::new(ServerState {
+            webhooks: self.webhooks,
+            jobs: Mutex::new(Jobs::default()),
+            queue,
+            status_token: self.status_token,
+        });
+
+        let mut app = Router::new().route("/webhooks/{name}", post(receive_webhook));
+        if state.status_token.is_some() {
+            app = app.route("/jobs/{id}", get(job_status));
+        }
+        let app = app.with_state(state.clone());
+
+        // Agent runs are not spawned as tasks, they are driven by the worker next to the server
+        tokio::select! {
+            result = async { axum::serve(listener, app).await } => result?,
+            _ = worker(state, jobs_rx, self.max_concurrent_jobs) => {}
+        }
+        Ok(())
+    }
+}
+
+async fn receive_webhook(
+    State(state): State<Arc<ServerState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Some(webhook) = state.webhooks.get(&name) else {
+        return (StatusCode::NOT_FOUND, "Unknown webhook").into_response();
+    };
+    if let Some(signature) = &webhook.signature {
+        if !signature.verify(&headers, &body) {
+            return (StatusCode::UNAUTHORIZED, "Invalid signature").into_response();
+        }
+    }
+    let payload: Value = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    let id = match job_id() {
+        Ok(id) => id,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+    let prompt = render_template(&webhook.template, &payload);
+    state.jobs.lock().unwrap().insert(&id);
+    let job = Job {
+        id: id.clone(),
+        webhook: webhook.clone(),
+        prompt,
+    };
+    if let Err(err) = state.queue.try_send(job) {
+        state.jobs.lock().unwrap().remove(&id);
+        let message = match err {
+            TrySendError::Full(_) => "Too many queued jobs",
+            TrySendError::Closed(_) => "Worker stopped",
+        };
+        return (StatusCode::SERVICE_UNAVAILABLE, message).into_response();
+    }
+    debug!("Webhook {name} started job {id}");
+
+    (StatusCode::ACCEPTED, Json(json!({ "job_id": id }))).into_response()
+}
+
+async fn job_status(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let authorized = state
+        .status_token
+        .as_ref()
+        .is_some_and(|token| crate::http::has_bearer_token(&headers, token));
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, "Invalid token").into_response();
+    }
+    match state.jobs.lock().unwrap().statuses.get(&id) {
+        Some(status) => Json(status.clone()).into_response(),
+        None => (StatusCode::NOT_FOUND, "Unknown job").into_response(),
+    }
+}
+
+async fn worker(state: Arc<ServerState>, mut jobs_rx: Receiver<Job>, limit: usize) {
+    let mut running = FuturesUnordered::new();
+    loop {
+        tokio::select! {
+            Some(job) = jobs_rx.recv(), if running.len() < limit => {
+                running.push(run_job(&state, job));
+            }
+            Some(()) = running.next(), if !running.is_empty() => {}
+            else => break,
+        }
+    }
+}
+
+async fn run_job(state: &ServerState, job: Job) {
+    state
+        .jobs
+        .lock()
+        .unwrap()
+        .update(&job.id, JobStatus::Running);
+
+    // Every job starts from a fresh copy of the agent
+    let mut agent = job.webhook.agent.clone();
+    let toolbox = job.webhook.toolbox.clone();
+    let status = match agent
+        .run::<String>(&job.webhook.model, &job.prompt, toolbox, None, None)
+        .await
+    {
+        Ok(result) => JobStatus::Completed { result },
+        Err(err) => {
+            error!("Webhook job {} failed: {err}", job.id);
+            JobStatus::Failed {
+                error: err.to_string(),
+            }
+        }
+    };
+    state.jobs.lock().unwrap().update(&job.id, status);
+}
+
+/// Returns random identifier of the job, it can't be guessed from identifiers of other jobs.
+fn job_id() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes)
+        .map_err(|err| anyhow::anyhow!("Unable to generate job id: {err}"))?;
+    Ok(hex::encode(bytes))
+}
+
+/// Replaces `{{ path }}` placeholders in the template with values from the payload.
+fn render_template(template: &str, payload: &Value) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let path = rest[start + 2..start + end].trim();
+        let value = if path == "payload" {
+            Some(payload)
+        } else {
+            path.split('.').try_fold(payload, |value, key| match value {
+                Value::Array(items) => key.parse::<usize>().ok().and_then(|idx| items.get(idx)),
+                _ => value.get(key),
+            })
+        };
+        match value {
+            Some(Value::String(text)) => result.push_str(text),
+            Some(value) => result.push_str(&value.to_string()),
+            None => {}
+        }
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let payload = json!({
+            "issue": {
+                "title": "Crash on start",
+                "number": 42,
+                "labels": [{"name": "bug"}]
+            }
+        });
+        assert_eq!(
+            render_template(
+                "#{{ issue.number }} {{issue.title}} [{{ issue.labels.0.name }}]{{ missing }}",
+                &payload
+            ),
+            "#42 Crash on start [bug]"
+        );
+        assert_eq!(render_template("{{ payload }}", &json!([1])), "[1]");
+        assert_eq!(render_template("open {{ brace", &payload), "open {{ brace");
+    }
+
+    #[test]
+    fn test_signature_verification() {
+        let signature = Signature::github("It's a Secret to Everybody");
+        let body = b"Hello, World!";
+        let mut headers = HeaderMap::new();
+        // Example from GitHub documentation
+        headers.insert(
+            "X-Hub-Signature-256",
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+                .parse()
+                .unwrap(),
+        );
+        assert!(signature.verify(&headers, body));
+        assert!(!signature.verify(&headers, b"Hello, World?"));
+        assert!(!signature.verify(&HeaderMap::new(), body));
+    }
+
+    fn state(queue: Sender<Job>) -> Arc<ServerState> {
+        let webhook = Webhook::new(
+            Agent::new("You are triaging issues"),
+            "gpt-4o",
+            "{{ title }}",
+        );
+        Arc::new(ServerState {
+            webhooks: HashMap::from([("issues".to_string(), Arc::new(webhook))]),
+            jobs: Mutex::new(Jobs::default()),
+            queue,
+            status_token: Some("secret".to_string()),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_full_queue() {
+        let (queue, mut jobs_rx) = mpsc::channel(1);
+        let state = state(queue);
+        let receive = || {
+            receive_webhook(
+                State(state.clone()),
+                Path("issues".to_string()),
+                HeaderMap::new(),
+                Bytes::from_static(br#"{"title": "Crash"}"#),
+            )
+        };
+        assert_eq!(receive().await.status(), StatusCode::ACCEPTED);
+        assert_eq!(receive().await.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // Rejected job is not reported
+        let job = jobs_rx.recv().await.unwrap();
+        assert_eq!(job.prompt, "Crash");
+        assert_eq!(state.jobs.lock().unwrap().statuses.len(), 1);
+        assert_eq!(receive().await.status(), StatusCode::ACCEPTED);
+    }
+
+    #[tokio::test]
+    async fn test_job_status_requires_token() {
+        let (queue, _jobs_rx) = mpsc::channel(1);
+        let state = state(queue);
+        let id = job_id().unwrap();
+        assert_eq!(id.len(), 32);
+        assert_ne!(id, job_id().unwrap());
+        state.jobs.lock().unwrap().insert(&id);
+
+        let status = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("Authorization", token.parse().unwrap());
+            job_status(State(state.clone()), Path(id.clone()), headers)
+        };
+        assert_eq!(
+            status("Bearer other").await.status(),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status("secret").await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(status("Bearer secret").await.status(), StatusCode::OK);
+    }
+}